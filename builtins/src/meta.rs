@@ -61,7 +61,7 @@ pub enum Whitespace {
     Collapse,
 }
 
-pub fn normalized_value(value: &str, whitespace: Whitespace) -> Cow<str> {
+pub fn normalized_value(value: &str, whitespace: Whitespace) -> Cow<'_, str> {
     // TODO: Don't allocate if not necessary
     match whitespace {
         Whitespace::Preserve => Cow::Borrowed(value),
@@ -2,14 +2,14 @@ use std::collections::BTreeSet;
 
 use syn::{
     __private::Span, Arm, Expr, ExprIf, Field, FieldMutability, FieldValue, Fields, Ident, Item,
-    ItemEnum, Member, Token, Type, TypePath, Variant, Visibility, parse_quote,
+    ItemEnum, Member, Token, Type, TypePath, Variant, parse_quote,
 };
 
 use dt_xsd::{
     AttributeUse, ComplexTypeDefinition, ElementDeclaration, Particle, Ref, RefNamed, Schema,
     SchemaComponentTable, SimpleTypeDefinition, Term, TypeDefinition,
     attribute_decl::ScopeVariety,
-    complex_type_def::{self, ContentType},
+    complex_type_def::ContentType,
     components::{IsBuiltinRef, Named},
     constraining_facet::WhiteSpaceValue,
     model_group::Compositor,
@@ -1,6 +1,6 @@
 use dt_xsd::{
     ComplexTypeDefinition, ElementDeclaration, Ref, Schema, SchemaComponentTable,
-    SimpleTypeDefinition,
+    SimpleTypeDefinition, TypeDefinition,
 };
 
 use super::common::{ComponentVisitor, GeneratorContext};
@@ -36,7 +36,24 @@ impl ComponentVisitor for TypescriptVisitor {
     }
 }
 
-pub fn generate(_schema: &Schema, components: &SchemaComponentTable) -> String {
-    let _context = GeneratorContext::new(components);
+pub fn generate(schema: &Schema, components: &SchemaComponentTable) -> String {
+    let mut ctx = GeneratorContext::new(components);
+    let mut visitor = TypescriptVisitor;
+
+    for type_def in schema.type_definitions.iter().copied() {
+        match type_def {
+            TypeDefinition::Complex(complex_type) => {
+                visitor.visit_complex_type(&mut ctx, complex_type);
+            }
+            TypeDefinition::Simple(simple_type) => {
+                visitor.visit_simple_type(&mut ctx, simple_type);
+            }
+        }
+    }
+
+    for element in schema.element_declarations.iter().copied() {
+        visitor.visit_element_declaration(&mut ctx, element);
+    }
+
     todo!()
 }
@@ -1,12 +1,13 @@
 mod cli;
 mod generators;
-mod ist;
 
 use clap::Parser;
 
 use dt_xsd::{
     RootContext, Schema,
+    diagnostics::{DiagnosticSink, Warning},
     import::{Import, ImportError, ImportResolver},
+    include::{IncludeResolver, LocalIncludeResolver},
 };
 
 struct HttpImportResolver;
@@ -40,8 +41,16 @@ impl ImportResolver for HttpImportResolver {
         let xsd = roxmltree::Document::parse_with_options(&text, options).unwrap();
         let schema = xsd.root_element();
         import.validate_imported_schema(schema)?;
-        let schema = Schema::map_from_xml(context, schema).map_err(ImportError::Xsd)?;
-        Ok(schema)
+        Schema::map_from_xml(context, schema, Some(schema_location)).map_err(ImportError::Xsd)
+    }
+}
+
+/// Prints warnings to stderr, in the same style as errors.
+struct StderrDiagnosticSink;
+
+impl DiagnosticSink for StderrDiagnosticSink {
+    fn warning(&mut self, warning: Warning) {
+        eprintln!("warning: {warning}");
     }
 }
 
@@ -52,7 +61,8 @@ fn main() {
     if cli.allow_http_imports {
         import_resolvers.push(Box::new(HttpImportResolver));
     }
-    let xsd = std::fs::read_to_string(cli.input).unwrap();
+    let include_resolvers: [Box<dyn IncludeResolver>; 1] = [Box::new(LocalIncludeResolver)];
+    let xsd = std::fs::read_to_string(&cli.input).unwrap();
     let options = roxmltree::ParsingOptions {
         allow_dtd: cli.allow_dtd,
         ..Default::default()
@@ -60,6 +70,7 @@ fn main() {
     let xsd = roxmltree::Document::parse_with_options(&xsd, options).unwrap();
     let (schema, components) = dt_xsd::read_schema(
         xsd,
+        Some(&cli.input),
         match cli.builtin_overwrite {
             cli::BuiltinOverwriteAction::Deny => dt_xsd::BuiltinOverwriteAction::Deny,
             cli::BuiltinOverwriteAction::Warn => dt_xsd::BuiltinOverwriteAction::Warn,
@@ -70,8 +81,13 @@ fn main() {
             cli::RegisterBuiltins::No => dt_xsd::RegisterBuiltins::No,
         },
         &import_resolvers,
+        &include_resolvers,
+        &mut StderrDiagnosticSink,
     )
-    .unwrap();
+    .unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
     let rst = cli.generator.generate(&schema, &components);
     print!("{rst}");
 }
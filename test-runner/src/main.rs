@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 use dt_xsd::{
     BuiltinOverwriteAction, RegisterBuiltins, Schema,
     import::{ImportError, ImportResolver},
+    include::{Include, IncludeError, IncludeResolver},
 };
 use encoding_rs::{Encoding, UTF_8};
 use generated::{
@@ -11,6 +12,7 @@ use generated::{
 };
 use roxmltree::Document;
 
+#[allow(clippy::enum_variant_names, clippy::redundant_closure)]
 mod generated;
 mod parser;
 
@@ -162,25 +164,31 @@ fn main() {
                     let buf = std::fs::read(&schema_path).unwrap();
                     let (decoded, _, _) = Encoding::decode(UTF_8, &buf);
                     let schema = Document::parse(&decoded).unwrap();
-                    let res = std::panic::catch_unwind(|| {
-                        let import_resolvers: [Box<dyn ImportResolver>; 1] =
-                            [Box::new(LocalImportResolver {
-                                base_path: predefined_schemas_path.clone(),
-                                schema_dir: schema_dir.to_path_buf(),
-                            })];
-                        dt_xsd::read_schema(
-                            schema,
-                            BuiltinOverwriteAction::Deny,
-                            RegisterBuiltins::Yes,
-                            &import_resolvers,
-                        )
-                    });
+                    let import_resolvers: [Box<dyn ImportResolver>; 1] =
+                        [Box::new(LocalImportResolver {
+                            base_path: predefined_schemas_path.clone(),
+                            schema_dir: schema_dir.to_path_buf(),
+                        })];
+                    let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+                        [Box::new(LocalIncludeResolver)];
+                    let res = dt_xsd::read_schema(
+                        schema,
+                        schema_path.to_str(),
+                        BuiltinOverwriteAction::Deny,
+                        RegisterBuiltins::Yes,
+                        &import_resolvers,
+                        &include_resolvers,
+                        &mut (),
+                    );
                     match res {
-                        Err(_) | Ok(Err(_)) => {
+                        Err(_) => {
                             ok = false;
                         }
-                        Ok(Ok(schema)) => {
-                            schemata.push(schema);
+                        Ok((schema, components)) => {
+                            if !dt_xsd::constraints::check_schema(&schema, &components).is_empty() {
+                                ok = false;
+                            }
+                            schemata.push((schema, components));
                         }
                     }
                 }
@@ -246,11 +254,8 @@ fn main() {
                 );
 
                 let res = std::panic::catch_unwind(|| {
-                    dt_xsd::validation::element_locally_valid_element(
-                        &e,
-                        ged.map(|g| g.get(components)),
-                        &components,
-                    )
+                    dt_xsd::validation::element_locally_valid_element(&e, ged, components)
+                        .is_valid()
                 });
 
                 // TODO: treat panic separately
@@ -313,7 +318,7 @@ impl ImportResolver for LocalImportResolver {
             self.base_path.join(location)
         };
 
-        let text = std::fs::read_to_string(path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
         let options = roxmltree::ParsingOptions {
             allow_dtd: true,
             ..Default::default()
@@ -321,7 +326,26 @@ impl ImportResolver for LocalImportResolver {
         let xsd = roxmltree::Document::parse_with_options(&text, options).unwrap();
         let schema = xsd.root_element();
         import.validate_imported_schema(schema)?;
-        let schema = Schema::map_from_xml(context, schema).map_err(ImportError::Xsd)?;
-        Ok(schema)
+        Schema::map_from_xml(context, schema, Some(&path.to_string_lossy()))
+            .map_err(ImportError::Xsd)
+    }
+}
+
+struct LocalIncludeResolver;
+
+impl IncludeResolver for LocalIncludeResolver {
+    fn resolve_include(
+        &self,
+        include: &Include,
+        base_uri: Option<&str>,
+    ) -> Result<String, IncludeError> {
+        // TODO: better path validation?
+        if include.schema_location.contains(':') {
+            return Err(IncludeError::UnsupportedInclude);
+        }
+        let buf = std::fs::read(include.location(base_uri))
+            .map_err(|e| IncludeError::UnspecifiedLoad(Box::new(e)))?;
+        let (decoded, _, _) = Encoding::decode(UTF_8, &buf);
+        Ok(decoded.into_owned())
     }
 }
@@ -7,12 +7,11 @@ const XLINK: &str = "http://www.w3.org/1999/xlink";
 impl Annotation {
     pub fn from_xml(_node: Node) -> Self {
         // TODO this is generated wrong, should be list of Annotation
-        let result = Annotation::Documentation(Documentation {
+        Annotation::Documentation(Documentation {
             wildcard: (),
             source: None,
             lang: None,
-        });
-        result
+        })
     }
 }
 
@@ -230,7 +229,7 @@ impl InstanceTest {
             }
         }
 
-        let result = InstanceTest {
+        InstanceTest {
             annotations,
             instance_document: instance_document.unwrap(),
             expecteds,
@@ -242,8 +241,7 @@ impl InstanceTest {
                 .map(VersionInfo::from_string)
                 .transpose()
                 .unwrap(),
-        };
-        result
+        }
     }
 }
 
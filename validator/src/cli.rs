@@ -15,8 +15,23 @@ pub enum RegisterBuiltins {
     No,
 }
 
+/// The exit status if the input document is valid
+pub const EXIT_VALID: i32 = 0;
+/// The exit status if the input document is invalid
+pub const EXIT_INVALID: i32 = 1;
+/// The exit status if the schema or the input document could not be read, or the schema is
+/// invalid. This is also the status of usage errors reported by clap.
+pub const EXIT_ERROR: i32 = 2;
+
 #[derive(Parser)]
-#[clap(version, about)]
+#[clap(
+    version,
+    about,
+    after_help = "Exit status:\n  \
+        0  the input document is valid\n  \
+        1  the input document is invalid; the violations have been printed\n  \
+        2  an error occurred, e.g. a file could not be read or the schema is invalid"
+)]
 pub struct Cli {
     /// The source file or URL
     #[clap(value_parser)]
@@ -25,6 +40,17 @@ pub struct Cli {
     /// The input file to validate
     pub input: PathBuf,
 
+    /// Print the input document with defaulted attributes added and empty elements filled in
+    /// with the default or fixed values of their declarations. Violations are printed to stderr.
+    #[clap(long, conflicts_with = "stream")]
+    pub augment: bool,
+
+    /// Validate the input document while reading it, without loading it into memory. Violations
+    /// are printed as soon as they are found. Only the subtrees of elements with assertions are
+    /// kept in memory, to evaluate the assertions on them.
+    #[clap(long)]
+    pub stream: bool,
+
     /// Allow a XML Document Type Definition (DTD) to occur
     #[clap(long)]
     pub allow_dtd: bool,
@@ -1,18 +1,50 @@
 mod cli;
 
 use clap::Parser;
+use dt_xsd::{
+    diagnostics::{DiagnosticSink, Warning},
+    include::{IncludeResolver, LocalIncludeResolver},
+};
+
+/// Prints warnings to stderr, in the same style as errors.
+struct StderrDiagnosticSink;
+
+impl DiagnosticSink for StderrDiagnosticSink {
+    fn warning(&mut self, warning: Warning) {
+        eprintln!("warning: {warning}");
+    }
+}
+
+/// Prints `message` to stderr and exits with [`cli::EXIT_ERROR`]
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(cli::EXIT_ERROR);
+}
+
+/// Exits with [`cli::EXIT_VALID`] or [`cli::EXIT_INVALID`]
+fn exit_with_validity(valid: bool) -> ! {
+    std::process::exit(if valid {
+        cli::EXIT_VALID
+    } else {
+        cli::EXIT_INVALID
+    });
+}
 
 fn main() {
     let cli = cli::Cli::parse();
 
-    let xsd = std::fs::read_to_string(cli.schema).unwrap();
+    let include_resolvers: [Box<dyn IncludeResolver>; 1] = [Box::new(LocalIncludeResolver)];
+    let xsd = std::fs::read_to_string(&cli.schema)
+        .unwrap_or_else(|e| fail(format_args!("{}: {e}", cli.schema)));
     let options = roxmltree::ParsingOptions {
         allow_dtd: cli.allow_dtd,
         ..Default::default()
     };
-    let xsd = roxmltree::Document::parse_with_options(&xsd, options).unwrap();
+    let xsd = roxmltree::Document::parse_with_options(&xsd, options)
+        .unwrap_or_else(|e| fail(format_args!("{}:{e}", cli.schema)));
     let (schema, components) = dt_xsd::read_schema(
         xsd,
+        Some(&cli.schema),
         match cli.builtin_overwrite {
             cli::BuiltinOverwriteAction::Deny => dt_xsd::BuiltinOverwriteAction::Deny,
             cli::BuiltinOverwriteAction::Warn => dt_xsd::BuiltinOverwriteAction::Warn,
@@ -23,19 +55,57 @@ fn main() {
             cli::RegisterBuiltins::No => dt_xsd::RegisterBuiltins::No,
         },
         &[],
+        &include_resolvers,
+        &mut StderrDiagnosticSink,
     )
-    .unwrap();
+    .unwrap_or_else(|e| fail(e));
+
+    let violations = dt_xsd::constraints::check_schema(&schema, &components);
+    if !violations.is_empty() {
+        for violation in &violations {
+            eprintln!("{violation}");
+        }
+        std::process::exit(cli::EXIT_ERROR);
+    }
+
+    if cli.stream {
+        let input = std::fs::File::open(&cli.input)
+            .unwrap_or_else(|e| fail(format_args!("{}: {e}", cli.input.display())));
+        let input = std::io::BufReader::new(input);
+        let mut reader = dt_xsd::validation::EventReader::new(input).allow_dtd(cli.allow_dtd);
+        let valid = dt_xsd::validation::validate_stream(&mut reader, &components, |violation| {
+            println!("{}:{violation}", cli.input.display());
+        })
+        .unwrap_or_else(|e| fail(format_args!("{}:{e}", cli.input.display())));
+        println!("Result: {valid:?}");
+        exit_with_validity(valid);
+    }
 
-    let xml_input = std::fs::read_to_string(&cli.input).unwrap();
-    let xml = roxmltree::Document::parse(&xml_input).unwrap();
+    let xml_input = std::fs::read_to_string(&cli.input)
+        .unwrap_or_else(|e| fail(format_args!("{}: {e}", cli.input.display())));
+    let xml = roxmltree::Document::parse(&xml_input)
+        .unwrap_or_else(|e| fail(format_args!("{}:{e}", cli.input.display())));
 
     let e = xml.root_element();
-    let ged =
-        schema.find_element_by_name(e.tag_name().namespace(), e.tag_name().name(), &components);
-    let res = dt_xsd::validation::element_locally_valid_element(
-        &e,
-        ged.map(|g| g.get(&components)),
+    let ged = schema.find_element_by_name(
+        dt_xsd::validation::element_namespace(&e),
+        e.tag_name().name(),
         &components,
     );
-    println!("Result: {:?}", res);
+    let report = dt_xsd::validation::element_locally_valid_element(&e, ged, &components);
+    if cli.augment {
+        for violation in &report.violations {
+            eprintln!("{}:{violation}", cli.input.display());
+        }
+        print!(
+            "{}",
+            dt_xsd::validation::augmented_document(&xml, &report.psvi)
+        );
+        exit_with_validity(report.is_valid());
+    }
+    for violation in &report.violations {
+        println!("{}:{violation}", cli.input.display());
+    }
+    println!("Result: {:?}", report.is_valid());
+    exit_with_validity(report.is_valid());
 }
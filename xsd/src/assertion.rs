@@ -2,7 +2,9 @@ use crate::{
     MappingContext, Ref,
     annotation::Annotation,
     components::{AnnotatedComponent, Component},
+    error::XsdError,
     values::actual_value,
+    xpath::AssertionTest,
     xstypes::{AnyURI, NCName, Sequence, Set},
 };
 use roxmltree::Node;
@@ -12,6 +14,8 @@ use roxmltree::Node;
 pub struct Assertion {
     pub annotations: Sequence<Ref<Annotation>>,
     pub test: XPathExpression,
+    /// The parsed {test}
+    pub test_expression: AssertionTest,
 }
 
 /// Property Record: XPath Expression (§3.13)
@@ -35,44 +39,67 @@ impl Assertion {
         context: &mut MappingContext,
         assert: Node,
         schema: Node,
-    ) -> Ref<Self> {
-        assert_eq!(assert.tag_name().name(), "assert");
+    ) -> Result<Ref<Self>, XsdError> {
+        // <assertion> facets map in the same way as <assert> (pt. 2, §4.3.13.2)
+        assert!(["assert", "assertion"].contains(&assert.tag_name().name()));
 
         // {test}
         //   An XPath Expression property record, as described below, with <assert> as the "host
         //   element" and test as the designated expression [attribute].
-        let test = assert.attribute("test").unwrap();
-        let test = XPathExpression::map_from_xml(test, assert, schema);
+        let test = assert
+            .attribute("test")
+            .ok_or(XsdError::missing_attribute(assert, "test"))?;
+        let test = XPathExpression::map_from_xml(context, test, assert, schema)?;
+        // Schema Component Constraint: Assertion Properties Correct (§3.13.6)
+        // 2 The {test} property of A is an XPath Expression property record whose {expression}
+        //   conforms to the XPath subset of the processor.
+        let test_expression =
+            AssertionTest::parse(&test).map_err(|error| XsdError::InvalidXPath {
+                expression: test.expression.clone(),
+                error,
+            })?;
 
         // {annotations}
         //   The ·annotation mapping· of the <assert> element, as defined in XML Representation of
         //   Annotation Schema Components (§3.15.2).
         let annotations = Annotation::xml_element_annotation_mapping(context, assert);
 
-        context.create(Self { annotations, test })
+        Ok(context.create(Self {
+            annotations,
+            test,
+            test_expression,
+        }))
+    }
+}
+
+impl NamespaceBinding {
+    /// Returns a Namespace Binding for each entry in the [in-scope namespaces] of `element`
+    pub(super) fn in_scope(element: Node) -> Set<Self> {
+        element
+            .namespaces() // `namespaces()` is equivalent to the [in-scope namespaces]
+            .map(|namespace| {
+                NamespaceBinding {
+                    // TODO does None map to the empty namespace?
+                    prefix: namespace.name().unwrap_or_default().to_string(),
+                    namespace: namespace.uri().into(),
+                }
+            })
+            .collect()
     }
 }
 
 impl XPathExpression {
     pub(super) fn map_from_xml(
+        context: &MappingContext,
         designated_attribute: &str,
         host_element: Node,
         schema: Node,
-    ) -> Self {
+    ) -> Result<Self, XsdError> {
         // {namespace bindings}
         //    A set of Namespace Binding property records. Each member corresponds to an entry in
         //    the [in-scope namespaces] of the host element, with {prefix} being the [prefix] and
         //    {namespace} the [namespace name].
-        let namespace_bindings = host_element
-            .namespaces() // `namespaces()` is equivalent to the [in-scope namespaces]
-            .map(|namespace| {
-                NamespaceBinding {
-                    // TODO does None map to the empty namespace?
-                    prefix: namespace.name().unwrap_or_default().to_string(),
-                    namespace: namespace.uri().into(),
-                }
-            })
-            .collect::<Set<_>>();
+        let namespace_bindings = NamespaceBinding::in_scope(host_element);
 
         // {default namespace}
         //   Let D be the ·actual value· of the xpathDefaultNamespace [attribute], if present on
@@ -82,6 +109,7 @@ impl XPathExpression {
             .attribute("xpathDefaultNamespace")
             .or_else(|| schema.attribute("xpathDefaultNamespace"))
             .map(|v| actual_value::<&str>(v, host_element))
+            .transpose()?
             .unwrap_or("##local");
 
         //   Then the value is the appropriate case among the following:
@@ -101,9 +129,7 @@ impl XPathExpression {
                 // 2.1 If the targetNamespace [attribute] is present on the <schema> ancestor, then
                 //     its ·actual value·;
                 // 2.2 otherwise ·absent·;
-                schema
-                    .attribute("targetNamespace")
-                    .map(|v| actual_value::<String>(v, host_element))
+                context.target_namespace(schema)?
             }
             // 3 If D is ##local, then ·absent·;
             "##local" => None,
@@ -116,14 +142,14 @@ impl XPathExpression {
         // TODO compute according to https://www.w3.org/TR/xmlbase/
         let base_uri = None;
 
-        let expression = actual_value::<String>(designated_attribute, host_element);
+        let expression = actual_value::<String>(designated_attribute, host_element)?;
 
-        Self {
+        Ok(Self {
             namespace_bindings,
             default_namespace,
             base_uri,
             expression,
-        }
+        })
     }
 }
 
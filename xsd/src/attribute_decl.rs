@@ -44,24 +44,26 @@ pub use shared::ValueConstraint;
 pub use shared::ValueConstraintVariety;
 
 impl NamedXml for AttributeDeclaration {
-    fn get_name_from_xml(attribute: Node, schema: Node) -> QName {
+    fn get_name_from_xml(
+        context: &MappingContext,
+        attribute: Node,
+        schema: Node,
+    ) -> Result<QName, XsdError> {
         assert_eq!(attribute.tag_name().name(), Self::TAG_NAME);
 
         // {name}
         //   The ·actual value· of the name [attribute]
         let name = attribute
             .attribute("name")
-            .map(|v| actual_value::<String>(v, attribute))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(attribute, "name"))
+            .and_then(|v| actual_value::<String>(v, attribute))?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the parent <schema> element
         //   information item, or ·absent· if there is none.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<String>(v, attribute));
+        let target_namespace = context.target_namespace(schema)?;
 
-        QName::with_optional_namespace(target_namespace, name)
+        Ok(QName::with_optional_namespace(target_namespace, name))
     }
 }
 
@@ -69,6 +71,36 @@ impl AttributeDeclaration {
     // TODO validate §3.2.3
     pub const TAG_NAME: &'static str = "attribute";
 
+    /// Resolves ·xs:anySimpleType·, the default type definition, which also replaces unresolved
+    /// type definitions when recovering from errors
+    fn any_simple_type(
+        context: &mut MappingContext,
+    ) -> Result<Ref<SimpleTypeDefinition>, XsdError> {
+        let any_simple_type: TypeDefinition = context
+            .resolve(&XS_ANY_SIMPLE_TYPE_NAME)
+            .ok_or(XsdError::UnresolvedBuiltin(&XS_ANY_SIMPLE_TYPE_NAME))?;
+        any_simple_type
+            .simple()
+            .ok_or(XsdError::UnresolvedBuiltin(&XS_ANY_SIMPLE_TYPE_NAME))
+    }
+
+    /// Creates a global attribute declaration of ·xs:anySimpleType· named `name`, which replaces
+    /// an unresolved attribute declaration reference when recovering from errors
+    fn placeholder(context: &mut MappingContext, name: QName) -> Result<Ref<Self>, XsdError> {
+        let type_definition = Self::any_simple_type(context)?;
+        let (target_namespace, name) = name.into_parts();
+        Ok(context.create(Self {
+            annotations: Sequence::new(),
+            name,
+            target_namespace,
+            type_definition,
+            scope: Scope::new_global(),
+            value_constraint: None,
+            inheritable: false,
+            is_builtin: false,
+        }))
+    }
+
     pub(super) fn map_from_xml_global(
         context: &mut MappingContext,
         attribute: Node,
@@ -77,7 +109,8 @@ impl AttributeDeclaration {
     ) -> Result<Ref<Self>, XsdError> {
         assert_eq!(attribute.tag_name().name(), Self::TAG_NAME);
 
-        let (target_namespace, name) = Self::get_name_from_xml(attribute, schema).into_parts();
+        let (target_namespace, name) =
+            Self::get_name_from_xml(context, attribute, schema)?.into_parts();
 
         // {type definition}
         //   The simple type definition corresponding to the <simpleType> element information item
@@ -94,6 +127,7 @@ impl AttributeDeclaration {
                     None,
                     Some(SimpleContext::Attribute(tlref)),
                 )
+                .map_err(|e| context.locate(e, simple_type, SimpleTypeDefinition::DISPLAY_NAME))
             })
             .transpose()?;
 
@@ -101,16 +135,15 @@ impl AttributeDeclaration {
             simple_type_def
         } else if let Some(type_) = attribute
             .attribute("type")
-            .map(|v| actual_value::<QName>(v, attribute))
+            .map(|v| context.qname(v, attribute))
+            .transpose()?
         {
-            context
+            let result = context
                 .resolve(&type_)
-                .ok_or_else(|| XsdError::UnresolvedReference(type_))?
+                .ok_or(XsdError::UnresolvedTypeReference(type_));
+            context.recover(result, attribute, Self::DISPLAY_NAME, Self::any_simple_type)?
         } else {
-            let any_simple_type: TypeDefinition = context
-                .resolve(&XS_ANY_SIMPLE_TYPE_NAME)
-                .ok_or_else(|| XsdError::UnresolvedBuiltin(&XS_ANY_SIMPLE_TYPE_NAME))?;
-            any_simple_type.simple().unwrap()
+            Self::any_simple_type(context)?
         };
 
         // {scope}
@@ -137,10 +170,8 @@ impl AttributeDeclaration {
                         .map(|fixed| (fixed, ValueConstraintVariety::Fixed))
                 });
 
-            attrib.map(|(value, variety)| ValueConstraint {
-                variety,
-                value: actual_value::<String>(value, attribute),
-                lexical_form: normalized_value(value).to_string(),
+            attrib.map(|(value, variety)| {
+                ValueConstraint::new(variety, normalized_value(value), attribute)
             })
         };
 
@@ -149,6 +180,7 @@ impl AttributeDeclaration {
         let inheritable = attribute
             .attribute("inheritable")
             .map(|v| actual_value::<bool>(v, attribute))
+            .transpose()?
             .unwrap_or(false);
 
         // {annotations}
@@ -205,8 +237,14 @@ impl AttributeDeclaration {
             // {attribute declaration}
             //   The (top-level) attribute declaration ·resolved· to by the ·actual value· of the
             //   ref [attribute]
-            let ref_ = actual_value::<QName>(ref_, attribute);
-            let attribute_declaration: Ref<AttributeDeclaration> = context.resolve(&ref_).unwrap(); // TODO
+            let ref_ = context.qname(ref_, attribute)?;
+            let result = context
+                .resolve(&ref_)
+                .ok_or_else(|| XsdError::UnresolvedReference(ref_.clone()));
+            let attribute_declaration =
+                context.recover(result, attribute, AttributeUse::DISPLAY_NAME, |context| {
+                    AttributeDeclaration::placeholder(context, ref_)
+                })?;
 
             // {value constraint}
             //   If there is a default or a fixed [attribute], then a Value Constraint as follows,
@@ -229,10 +267,8 @@ impl AttributeDeclaration {
                             .map(|fixed| (fixed, ValueConstraintVariety::Fixed))
                     });
 
-                attrib.map(|(value, variety)| ValueConstraint {
-                    variety,
-                    value: actual_value::<String>(value, attribute),
-                    lexical_form: normalized_value(value).to_string(),
+                attrib.map(|(value, variety)| {
+                    ValueConstraint::new(variety, normalized_value(value), attribute)
                 })
             };
 
@@ -241,7 +277,7 @@ impl AttributeDeclaration {
             //   {attribute declaration}.{inheritable}.
             let inheritable = attribute
                 .attribute("inheritable")
-                .map(|v| Ok(actual_value::<bool>(v, attribute)))
+                .map(|v| actual_value::<bool>(v, attribute))
                 .unwrap_or_else(|| {
                     context
                         .request(attribute_declaration)
@@ -265,8 +301,8 @@ impl AttributeDeclaration {
             //   The ·actual value· of the name [attribute]
             let name = attribute
                 .attribute("name")
-                .map(|v| actual_value::<String>(v, attribute))
-                .unwrap();
+                .ok_or_else(|| XsdError::missing_attribute(attribute, "name"))
+                .and_then(|v| actual_value::<String>(v, attribute))?;
 
             // {target namespace}
             //   The appropriate case among the following:
@@ -279,15 +315,13 @@ impl AttributeDeclaration {
             //   3 otherwise ·absent·.
             let target_namespace =
                 if let Some(target_namespace) = attribute.attribute("targetNamespace") {
-                    Some(actual_value::<String>(target_namespace, attribute))
+                    Some(actual_value::<String>(target_namespace, attribute)?)
                 } else {
                     let form = attribute
                         .attribute("form")
                         .or_else(|| schema.attribute("attributeFormDefault"));
                     if form == Some("qualified") {
-                        schema
-                            .attribute("targetNamespace")
-                            .map(|v| actual_value::<String>(v, attribute))
+                        context.target_namespace(schema)?
                     } else {
                         None
                     }
@@ -309,6 +343,7 @@ impl AttributeDeclaration {
                         None,
                         Some(SimpleContext::Attribute(self_ref)),
                     )
+                    .map_err(|e| context.locate(e, simple_type, SimpleTypeDefinition::DISPLAY_NAME))
                 })
                 .transpose()?;
 
@@ -316,13 +351,15 @@ impl AttributeDeclaration {
                 simple_type_def
             } else if let Some(type_) = attribute
                 .attribute("type")
-                .map(|v| actual_value::<QName>(v, attribute))
+                .map(|v| context.qname(v, attribute))
+                .transpose()?
             {
-                context.resolve(&type_).unwrap() // TODO
+                let result = context
+                    .resolve(&type_)
+                    .ok_or(XsdError::UnresolvedTypeReference(type_));
+                context.recover(result, attribute, Self::DISPLAY_NAME, Self::any_simple_type)?
             } else {
-                let any_simple_type: TypeDefinition =
-                    context.resolve(&XS_ANY_SIMPLE_TYPE_NAME).unwrap(); // TODO
-                any_simple_type.simple().unwrap()
+                Self::any_simple_type(context)?
             };
 
             // {scope} A Scope as follows:
@@ -343,6 +380,7 @@ impl AttributeDeclaration {
             let inheritable = attribute
                 .attribute("inheritable")
                 .map(|v| actual_value::<bool>(v, attribute))
+                .transpose()?
                 .unwrap_or(false);
 
             let attribute_declaration = context.insert(
@@ -385,10 +423,8 @@ impl AttributeDeclaration {
                             .map(|fixed| (fixed, ValueConstraintVariety::Fixed))
                     });
 
-                attrib.map(|(value, variety)| ValueConstraint {
-                    variety,
-                    value: actual_value::<String>(value, attribute),
-                    lexical_form: normalized_value(value).to_string(),
+                attrib.map(|(value, variety)| {
+                    ValueConstraint::new(variety, normalized_value(value), attribute)
                 })
             };
 
@@ -23,22 +23,24 @@ pub struct AttributeGroupDefinition {
 }
 
 impl NamedXml for AttributeGroupDefinition {
-    fn get_name_from_xml(attribute_group: Node, schema: Node) -> QName {
+    fn get_name_from_xml(
+        context: &MappingContext,
+        attribute_group: Node,
+        schema: Node,
+    ) -> Result<QName, XsdError> {
         // {name}
         //   The ·actual value· of the name [attribute]
         let name = attribute_group
             .attribute("name")
-            .map(|v| actual_value::<NCName>(v, attribute_group))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(attribute_group, "name"))
+            .and_then(|v| actual_value::<NCName>(v, attribute_group))?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the <schema> ancestor element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<AnyURI>(v, attribute_group));
+        let target_namespace = context.target_namespace(schema)?;
 
-        QName::with_optional_namespace(target_namespace, name)
+        Ok(QName::with_optional_namespace(target_namespace, name))
     }
 }
 
@@ -54,7 +56,7 @@ impl AttributeGroupDefinition {
         let attrib_group_ref = attrib_group_ref.unwrap_or_else(|| context.reserve());
 
         let (target_namespace, name) =
-            Self::get_name_from_xml(attribute_group, schema).into_parts();
+            Self::get_name_from_xml(context, attribute_group, schema)?.into_parts();
 
         // {attribute uses}
         //     The union of the set of attribute uses corresponding to the <attribute> [children],
@@ -71,7 +73,8 @@ impl AttributeGroupDefinition {
                 attribute,
                 schema,
                 attribute_decl::ScopeParent::AttributeGroup(attrib_group_ref),
-            )?;
+            )
+            .map_err(|e| context.locate(e, attribute, AttributeDeclaration::DISPLAY_NAME))?;
             if let Some(attribute_use) = attribute_use {
                 attribute_uses.push(attribute_use);
             }
@@ -2,7 +2,7 @@ use crate::{
     Ref,
     annotation::Annotation,
     attribute_decl::AttributeDeclaration,
-    components::{AnnotatedComponent, Component},
+    components::{AnnotatedComponent, Component, ComponentTable},
     shared,
     xstypes::Sequence,
 };
@@ -20,6 +20,22 @@ pub struct AttributeUse {
 /// Property Record: Value Constraint (§3.5)
 pub use shared::ValueConstraint;
 
+impl AttributeUse {
+    /// The effective value constraint: {value constraint} if present, otherwise the {value
+    /// constraint} of the {attribute declaration}
+    pub fn effective_value_constraint<'a>(
+        &'a self,
+        components: &'a impl ComponentTable,
+    ) -> Option<&'a ValueConstraint> {
+        self.value_constraint.as_ref().or_else(|| {
+            self.attribute_declaration
+                .get(components)
+                .value_constraint
+                .as_ref()
+        })
+    }
+}
+
 impl Component for AttributeUse {
     const DISPLAY_NAME: &'static str = "AttributeUse";
 }
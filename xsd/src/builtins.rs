@@ -32,8 +32,11 @@ pub const XS_ANY_ATOMIC_TYPE_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "a
 pub const XS_QNAME_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "QName");
 pub const XS_ANY_URI_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "anyURI");
 pub const XS_BOOLEAN_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "boolean");
+#[cfg(test)]
 pub const XS_DECIMAL_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "decimal");
 pub const XS_STRING_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "string");
+/// The `xs:error` qualified name
+pub const XS_ERROR_NAME: QName = QName::with_ns_const(XS_NAMESPACE, "error");
 
 pub(super) fn register_builtins(context: &mut RootContext) {
     register_xs_any_type(context);
@@ -177,7 +180,7 @@ fn register_xs_error(context: &mut RootContext) {
         .expect("xs:anySimpleType should be registered before xs:error");
 
     let xs_error = context.create(SimpleTypeDefinition {
-        name: Some("error".into()),
+        name: Some(XS_ERROR_NAME.local_name().into()),
         target_namespace: Some(XS_NAMESPACE.into()),
         final_: [
             simple_type_def::DerivationMethod::Extension,
@@ -1013,7 +1016,9 @@ mod tests {
 
     #[test]
     fn registers_builtins_without_crashing() {
-        let mut root_context = RootContext::new(BuiltinOverwriteAction::Deny, &[]);
+        let mut warnings = Vec::new();
+        let mut root_context =
+            RootContext::new(BuiltinOverwriteAction::Deny, &[], &[], &mut warnings);
 
         register_builtins(&mut root_context);
 
@@ -15,7 +15,7 @@ use crate::{
     shared::TypeDefinition,
     simple_type_def::SimpleTypeDefinition,
     values::{ActualValue, actual_value},
-    wildcard::{self, DisallowedNameSet, Wildcard},
+    wildcard::Wildcard,
     xstypes::{AnyURI, NCName, QName, Sequence, Set},
 };
 use roxmltree::Node;
@@ -56,11 +56,14 @@ pub enum DerivationMethod {
 }
 
 impl ActualValue<'_> for DerivationMethod {
-    fn convert(src: &str, _parent: Node) -> Self {
+    fn convert(src: &str, _parent: Node) -> Result<Self, XsdError> {
         match src {
-            "extension" => Self::Extension,
-            "restriction" => Self::Restriction,
-            _ => panic!("Invalid value for derivation method"),
+            "extension" => Ok(Self::Extension),
+            "restriction" => Ok(Self::Restriction),
+            _ => Err(XsdError::InvalidAttributeValue {
+                value: src.to_string(),
+                expected: "derivation method",
+            }),
         }
     }
 }
@@ -173,21 +176,24 @@ pub enum OpenContentMode {
 impl ComplexTypeDefinition {
     pub const TAG_NAME: &'static str = "complexType";
 
-    pub(super) fn name_from_xml(complex_type: Node, schema: Node) -> Option<QName> {
+    pub(super) fn name_from_xml(
+        context: &MappingContext,
+        complex_type: Node,
+        schema: Node,
+    ) -> Result<Option<QName>, XsdError> {
         // {name}
         //   The ·actual value· of the name [attribute] if present, otherwise ·absent·.
         let name = complex_type
             .attribute("name")
-            .map(|v| actual_value::<String>(v, complex_type));
+            .map(|v| actual_value::<String>(v, complex_type))
+            .transpose()?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the <schema> ancestor element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<String>(v, complex_type));
+        let target_namespace = context.target_namespace(schema)?;
 
-        name.map(|name| QName::with_optional_namespace(target_namespace, name))
+        Ok(name.map(|name| QName::with_optional_namespace(target_namespace, name)))
     }
 
     pub(super) fn map_from_xml(
@@ -258,11 +264,7 @@ impl ComplexTypeDefinition {
             // {base type definition}
             //   The type definition ·resolved· to by the ·actual value· of the base [attribute] on
             //   the <restriction> [...] element appearing as a child of <simpleContent>
-            let base_type_definition: TypeDefinition = restriction
-                .attribute("base")
-                .map(|base| actual_value::<QName>(base, restriction))
-                .map(|n| context.resolve(&n).unwrap()) // TODO
-                .unwrap();
+            let base_type_definition = Self::resolve_base(context, restriction)?;
 
             // {derivation method}
             //   If the <restriction> alternative is chosen, then restriction [...].
@@ -289,27 +291,27 @@ impl ComplexTypeDefinition {
                             //   definition} of the {content type} of the {base type definition} a
                             //   simple type definition as follows:
                             // TODO
-                            todo!()
+                            return Err(XsdError::Unsupported(
+                                "simple content restriction of a complex type with simple content",
+                            ));
                         }
                         ContentType::Mixed { particle, .. }
                             if particle
                                 .get(context.components())
                                 .is_emptiable(context.components()) =>
                         {
-                            todo!()
+                            return Err(XsdError::Unsupported(
+                                "simple content restriction of a mixed complex type",
+                            ));
                         }
                         _ => {
                             // 5 otherwise ·xs:anySimpleType·.
-                            let any_simple_type: TypeDefinition =
-                                context.resolve(&XS_ANY_SIMPLE_TYPE_NAME).unwrap(); // TODO
-                            any_simple_type.simple().unwrap()
+                            Self::any_simple_type(context)?
                         }
                     }
                 } else {
                     // 5 otherwise ·xs:anySimpleType·.
-                    let any_simple_type: TypeDefinition =
-                        context.resolve(&XS_ANY_SIMPLE_TYPE_NAME).unwrap(); // TODO
-                    any_simple_type.simple().unwrap()
+                    Self::any_simple_type(context)?
                 };
             let content_type = ContentType::Simple {
                 // {simple_type_definition}  [from above]
@@ -325,11 +327,7 @@ impl ComplexTypeDefinition {
             // {base type definition}
             //   The type definition ·resolved· to by the ·actual value· of the base [attribute] on
             //   the [...] <extension> element appearing as a child of <simpleContent>
-            let base_type_definition = extension
-                .attribute("base")
-                .map(|base| actual_value::<QName>(base, extension))
-                .map(|n| context.resolve(&n).unwrap()) // TODO
-                .unwrap();
+            let base_type_definition = Self::resolve_base(context, extension)?;
 
             // {derivation method}
             //   If [...] the <extension> alternative is chosen, extension.
@@ -352,9 +350,7 @@ impl ComplexTypeDefinition {
                             simple_type_definition
                         } else {
                             // 5 otherwise ·xs:anySimpleType·
-                            let any_simple_type: TypeDefinition =
-                                context.resolve(&XS_ANY_SIMPLE_TYPE_NAME).unwrap(); // TODO
-                            any_simple_type.simple().unwrap()
+                            Self::any_simple_type(context)?
                         }
                     }
                     TypeDefinition::Simple(base_type_definition) => {
@@ -368,10 +364,13 @@ impl ComplexTypeDefinition {
 
             (base_type_definition, derivation_method, content_type)
         } else {
-            panic!("Invalid simple content")
+            return Err(XsdError::invalid_content(
+                simple_content,
+                "expected <restriction> or <extension>",
+            ));
         };
 
-        let common = Self::map_common(context, complex_type, schema, ancestor_element);
+        let common = Self::map_common(context, complex_type, schema, ancestor_element)?;
 
         let attribute_uses = Self::map_attribute_uses_property(
             context,
@@ -383,7 +382,22 @@ impl ComplexTypeDefinition {
             base_type_definition,
         )?;
 
-        // TODO attribute wildcard
+        let attribute_wildcard = Self::map_attribute_wildcard_property(
+            context,
+            complex_type,
+            Some(simple_content),
+            schema,
+            derivation_method,
+            base_type_definition,
+        )?;
+
+        let assertions = Self::map_assertions_property(
+            context,
+            complex_type,
+            Some(simple_content),
+            schema,
+            base_type_definition,
+        )?;
 
         context.insert(
             complex_type_ref,
@@ -392,6 +406,8 @@ impl ComplexTypeDefinition {
                 derivation_method: Some(derivation_method),
                 content_type,
                 attribute_uses,
+                attribute_wildcard,
+                assertions,
                 ..common
             },
         );
@@ -409,15 +425,13 @@ impl ComplexTypeDefinition {
         let content = complex_content
             .children()
             .find(|c| ["restriction", "extension"].contains(&c.tag_name().name()))
-            .unwrap();
+            .ok_or_else(|| {
+                XsdError::invalid_content(complex_content, "expected <restriction> or <extension>")
+            })?;
 
         // {base type definition}
         //   The type definition ·resolved· to by the ·actual value· of the base [attribute]
-        let base_type_definition = content
-            .attribute("base")
-            .map(|base| actual_value::<QName>(base, content))
-            .map(|n| context.resolve(&n).unwrap()) // TODO
-            .unwrap();
+        let base_type_definition = Self::resolve_base(context, content)?;
 
         // {derivation method}
         //   If the <restriction> alternative is chosen, then restriction, otherwise (the
@@ -438,7 +452,7 @@ impl ComplexTypeDefinition {
             base_type_definition,
         )?;
 
-        let common = Self::map_common(context, complex_type, schema, ancestor_element);
+        let common = Self::map_common(context, complex_type, schema, ancestor_element)?;
 
         let attribute_uses = Self::map_attribute_uses_property(
             context,
@@ -450,7 +464,22 @@ impl ComplexTypeDefinition {
             base_type_definition,
         )?;
 
-        // TODO attribute wildcard
+        let attribute_wildcard = Self::map_attribute_wildcard_property(
+            context,
+            complex_type,
+            Some(complex_content),
+            schema,
+            derivation_method,
+            base_type_definition,
+        )?;
+
+        let assertions = Self::map_assertions_property(
+            context,
+            complex_type,
+            Some(complex_content),
+            schema,
+            base_type_definition,
+        )?;
 
         context.insert(
             complex_type_ref,
@@ -459,6 +488,8 @@ impl ComplexTypeDefinition {
                 derivation_method: Some(derivation_method),
                 content_type,
                 attribute_uses,
+                attribute_wildcard,
+                assertions,
                 ..common
             },
         );
@@ -473,7 +504,9 @@ impl ComplexTypeDefinition {
         ancestor_element: Option<Ref<ElementDeclaration>>,
     ) -> Result<(), XsdError> {
         // {base type definition} ·xs:anyType·
-        let base_type_definition = context.resolve(&XS_ANY_TYPE_NAME).unwrap(); // TODO
+        let base_type_definition = context
+            .resolve(&XS_ANY_TYPE_NAME)
+            .ok_or(XsdError::UnresolvedReference(XS_ANY_TYPE_NAME))?;
 
         // {derivation method}    restriction
         let derivation_method = DerivationMethod::Restriction;
@@ -488,7 +521,7 @@ impl ComplexTypeDefinition {
             base_type_definition,
         )?;
 
-        let common = Self::map_common(context, complex_type, schema, ancestor_element);
+        let common = Self::map_common(context, complex_type, schema, ancestor_element)?;
 
         let attribute_uses = Self::map_attribute_uses_property(
             context,
@@ -500,7 +533,22 @@ impl ComplexTypeDefinition {
             base_type_definition,
         )?;
 
-        // TODO attribute wildcard
+        let attribute_wildcard = Self::map_attribute_wildcard_property(
+            context,
+            complex_type,
+            None,
+            schema,
+            derivation_method,
+            base_type_definition,
+        )?;
+
+        let assertions = Self::map_assertions_property(
+            context,
+            complex_type,
+            None,
+            schema,
+            base_type_definition,
+        )?;
 
         context.insert(
             complex_type_ref,
@@ -509,6 +557,8 @@ impl ComplexTypeDefinition {
                 derivation_method: Some(derivation_method),
                 content_type,
                 attribute_uses,
+                attribute_wildcard,
+                assertions,
                 ..common
             },
         );
@@ -520,29 +570,57 @@ impl ComplexTypeDefinition {
         complex_type: Node,
         schema: Node,
         ancestor_element: Option<Ref<ElementDeclaration>>,
-    ) -> Self {
+    ) -> Result<Self, XsdError> {
         // {name}
         //   The ·actual value· of the name [attribute] if present, otherwise ·absent·.
         let name = complex_type
             .attribute("name")
-            .map(|v| actual_value::<String>(v, complex_type));
+            .map(|v| actual_value::<String>(v, complex_type))
+            .transpose()?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the <schema> ancestor element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<String>(v, complex_type));
+        let target_namespace = mapping_context.target_namespace(schema)?;
 
         // {abstract}
         //   The ·actual value· of the abstract [attribute], if present, otherwise false.
         let abstract_ = complex_type
             .attribute("abstract")
             .map(|v| actual_value::<bool>(v, complex_type))
+            .transpose()?
             .unwrap_or(false);
 
-        // TODO impl, doc
-        let prohibited_substitutions = Set::new();
+        // {prohibited substitutions}
+        //   A set corresponding to the ·actual value· of the block [attribute], if present,
+        //   otherwise to the ·actual value· of the blockDefault [attribute] of the ancestor
+        //   <schema> element information item, if present, otherwise on the empty string. Call
+        //   this the EBV (for effective block value). Then the value of this property is the
+        //   appropriate case among the following:
+        //   1 If the EBV is the empty string, then the empty set;
+        //   2 If the EBV is #all, then {extension, restriction};
+        //   3 otherwise a set with members drawn from the set above, each being present or
+        //     absent depending on whether the ·actual value· (which is a list) contains an
+        //     equivalently named item.
+        // NOTE: blockDefault may contain substitution, which is irrelevant here
+        let effective_block_value = complex_type
+            .attribute("block")
+            .or_else(|| schema.attribute("blockDefault"))
+            .unwrap_or_default();
+        let effective_block_value =
+            actual_value::<Vec<String>>(effective_block_value, complex_type)?;
+        let prohibited_substitutions = [
+            ("extension", DerivationMethod::Extension),
+            ("restriction", DerivationMethod::Restriction),
+        ]
+        .into_iter()
+        .filter(|(name, _)| {
+            effective_block_value
+                .iter()
+                .any(|v| v == "#all" || v == name)
+        })
+        .map(|(_, method)| method)
+        .collect();
 
         // TODO same
         let final_ = Set::new();
@@ -555,21 +633,11 @@ impl ComplexTypeDefinition {
         let context = if complex_type.has_attribute("name") {
             None
         } else {
-            let ancestor_element = ancestor_element.expect(
-                "Expected an unnamed complex type definition to have an ancestor <element>",
-            );
+            let ancestor_element = ancestor_element
+                .ok_or_else(|| XsdError::missing_attribute(complex_type, "name"))?;
             Some(Context::Element(ancestor_element))
         };
 
-        // {assertions}
-        //   A sequence whose members are Assertions drawn from the following sources, in order:
-        //   1 The {assertions} of the {base type definition}.
-        //   2 Assertions corresponding to all the <assert> element information items among the
-        //     [children] of <complexType>, <restriction> and <extension>, if any, in document
-        //     order.
-        // TODO
-        let assertions = Sequence::new();
-
         // {annotations}
         //   The ·annotation mapping· of the set of elements containing the <complexType>, the
         //   <openContent> [child], if present, the <attributeGroup> [children], if present, the
@@ -594,7 +662,11 @@ impl ComplexTypeDefinition {
         let annotations =
             Annotation::xml_element_set_annotation_mapping(mapping_context, &annot_elements);
 
-        Self {
+        let any_type = mapping_context
+            .resolve(&XS_ANY_TYPE_NAME)
+            .ok_or(XsdError::UnresolvedReference(XS_ANY_TYPE_NAME))?;
+
+        Ok(Self {
             annotations,
             name,
             target_namespace,
@@ -602,17 +674,56 @@ impl ComplexTypeDefinition {
             context,
             abstract_,
             prohibited_substitutions,
-            assertions,
 
             // Populated in the specific mapping implementations
             // TODO restructure
-            base_type_definition: mapping_context.resolve(&XS_ANY_TYPE_NAME).unwrap(), // TODO !!
+            assertions: Sequence::new(),
+            base_type_definition: any_type,
             derivation_method: None,
             content_type: ContentType::Empty,
             attribute_uses: Set::new(),
             attribute_wildcard: None,
             is_builtin: false,
-        }
+        })
+    }
+
+    /// Resolves the type definition referenced by the base [attribute] of a `<restriction>` or
+    /// `<extension>` element. When recovering from errors, an unresolved base is replaced by
+    /// ·xs:anySimpleType· within `<simpleContent>`, and by ·xs:anyType· otherwise.
+    fn resolve_base(
+        context: &mut MappingContext,
+        content: Node,
+    ) -> Result<TypeDefinition, XsdError> {
+        let base = content
+            .attribute("base")
+            .ok_or_else(|| XsdError::missing_attribute(content, "base"))?;
+        let base = context.qname(base, content)?;
+        let result = context
+            .resolve(&base)
+            .ok_or(XsdError::UnresolvedTypeReference(base));
+        context.recover(result, content, Self::DISPLAY_NAME, |context| {
+            let in_simple_content = content
+                .parent_element()
+                .is_some_and(|p| p.tag_name().name() == "simpleContent");
+            if in_simple_content {
+                Self::any_simple_type(context).map(TypeDefinition::Simple)
+            } else {
+                context
+                    .resolve(&XS_ANY_TYPE_NAME)
+                    .ok_or(XsdError::UnresolvedReference(XS_ANY_TYPE_NAME))
+            }
+        })
+    }
+
+    fn any_simple_type(
+        context: &mut MappingContext,
+    ) -> Result<Ref<SimpleTypeDefinition>, XsdError> {
+        let any_simple_type: TypeDefinition = context
+            .resolve(&XS_ANY_SIMPLE_TYPE_NAME)
+            .ok_or(XsdError::UnresolvedBuiltin(&XS_ANY_SIMPLE_TYPE_NAME))?;
+        any_simple_type
+            .simple()
+            .ok_or(XsdError::UnresolvedBuiltin(&XS_ANY_SIMPLE_TYPE_NAME))
     }
 
     /// Maps the {attribute uses} property
@@ -647,28 +758,28 @@ impl ComplexTypeDefinition {
             && complex_type
                 .attribute("defaultAttributesApply")
                 .map(|v| actual_value::<bool>(v, complex_type))
+                .transpose()?
                 != Some(false)
         {
-            todo!("default attributes")
+            Err(XsdError::Unsupported("defaultAttributes"))
         } else {
             // Then the value is a union of sets of attribute uses as follows
             let mut attribute_uses = Set::new();
 
             // 1 The set of attribute uses corresponding to the <attribute> [children], if any.
-            attribute_uses.extend(
-                children_node
-                    .children()
-                    .filter(|c| c.tag_name().name() == "attribute")
-                    .filter_map(|attribute| {
-                        AttributeDeclaration::map_from_xml_local(
-                            context,
-                            attribute,
-                            schema,
-                            attribute_decl::ScopeParent::ComplexType(complex_type_ref),
-                        )
-                        .unwrap() // TODO
-                    }),
-            );
+            for attribute in children_node
+                .children()
+                .filter(|c| c.tag_name().name() == "attribute")
+            {
+                let attribute_use = AttributeDeclaration::map_from_xml_local(
+                    context,
+                    attribute,
+                    schema,
+                    attribute_decl::ScopeParent::ComplexType(complex_type_ref),
+                )
+                .map_err(|e| context.locate(e, attribute, AttributeDeclaration::DISPLAY_NAME))?;
+                attribute_uses.extend(attribute_use);
+            }
 
             // 2 The {attribute uses} of the attribute groups ·resolved· to by the ·actual value·s
             //   of the ref [attribute] of the <attributeGroup> [children], if any.
@@ -676,12 +787,20 @@ impl ComplexTypeDefinition {
                 .children()
                 .filter(|c| c.tag_name().name() == "attributeGroup")
             {
-                let ref_ = attribute_group.attribute("ref").unwrap();
-                let ref_ = actual_value::<QName>(ref_, complex_type);
-                let group = context
+                let ref_ = attribute_group
+                    .attribute("ref")
+                    .ok_or_else(|| XsdError::missing_attribute(attribute_group, "ref"))?;
+                let ref_ = context.qname(ref_, complex_type)?;
+                let result = context
                     .resolve::<Ref<AttributeGroupDefinition>>(&ref_)
-                    .unwrap(); // TODO
-                attribute_uses.extend(context.request(group)?.attribute_uses.iter())
+                    .ok_or(XsdError::UnresolvedGroupReference(ref_))
+                    .map(Some);
+                // Unresolved attribute groups are left out when recovering from errors
+                let group =
+                    context.recover(result, attribute_group, Self::DISPLAY_NAME, |_| Ok(None))?;
+                if let Some(group) = group {
+                    attribute_uses.extend(context.request(group)?.attribute_uses.iter())
+                }
             }
 
             // 3 The attribute uses "inherited" from the {base type definition} T, as described by
@@ -714,6 +833,115 @@ impl ComplexTypeDefinition {
             Ok(attribute_uses)
         }
     }
+
+    /// Maps the {assertions} property
+    fn map_assertions_property(
+        context: &mut MappingContext,
+        complex_type: Node,
+        content_node: Option<Node>,
+        schema: Node,
+        base_type_definition: TypeDefinition,
+    ) -> Result<Sequence<Ref<Assertion>>, XsdError> {
+        // A sequence whose members are Assertions drawn from the following sources, in order:
+        // 1 The {assertions} of the {base type definition}.
+        let mut assertions = match base_type_definition {
+            TypeDefinition::Complex(base_type_definition) => {
+                context.request(base_type_definition)?.assertions.clone()
+            }
+            TypeDefinition::Simple(_) => Sequence::new(),
+        };
+
+        // 2 Assertions corresponding to all the <assert> element information items among the
+        //   [children] of <complexType>, <restriction> and <extension>, if any, in document
+        //   order.
+        let derivation = content_node.and_then(|content_node| {
+            content_node
+                .children()
+                .find(|child| ["extension", "restriction"].contains(&child.tag_name().name()))
+        });
+        for parent in std::iter::once(complex_type).chain(derivation) {
+            for assert in parent
+                .children()
+                .filter(|c| c.tag_name().name() == "assert")
+            {
+                assertions.push(Assertion::map_from_xml(context, assert, schema)?);
+            }
+        }
+
+        Ok(assertions)
+    }
+
+    /// Maps the {attribute wildcard} property
+    fn map_attribute_wildcard_property(
+        context: &mut MappingContext,
+        complex_type: Node,
+        content_node: Option<Node>,
+        schema: Node,
+        derivation_method: DerivationMethod,
+        base_type_definition: TypeDefinition,
+    ) -> Result<Option<Ref<Wildcard>>, XsdError> {
+        // As for {attribute uses}, "the [children]" are those of the <extension> or
+        // <restriction> element, if present, otherwise those of the <complexType> itself.
+        let children_node = content_node
+            .and_then(|content_node| {
+                content_node
+                    .children()
+                    .find(|child| ["extension", "restriction"].contains(&child.tag_name().name()))
+            })
+            .unwrap_or(complex_type);
+
+        // 1 Let the <anyAttribute> [child] of the <complexType>, <extension> or <restriction>
+        //   element, if any, be the ·local wildcard·.
+        // 2 Let the ·complete wildcard· be the ·local wildcard· intersected with the {attribute
+        //   wildcard}s of the attribute groups referenced by the <attributeGroup> [children], as
+        //   defined in Attribute Wildcard Intersection (§3.10.6.4).
+        // TODO: attribute group wildcards are not mapped yet, so the ·complete wildcard· is the
+        //   ·local wildcard·.
+        let complete_wildcard = children_node
+            .children()
+            .find(|c| c.tag_name().name() == "anyAttribute")
+            .map(|any_attribute| Wildcard::map_from_xml_any(context, any_attribute, schema))
+            .transpose()?;
+
+        // 3 The value is then determined by the appropriate case among the following:
+        //  3.1 If the {derivation method} is restriction, then the ·complete wildcard·;
+        //  3.2 If the {derivation method} is extension, then
+        //   3.2.1 let the ·base wildcard· be defined as the appropriate case among the following:
+        //    3.2.1.1 If the {base type definition} is a complex type definition with an
+        //      {attribute wildcard}, then that {attribute wildcard}.
+        //    3.2.1.2 otherwise ·absent·.
+        //   3.2.2 The value is then determined by the appropriate case among the following:
+        //    3.2.2.1 If the ·base wildcard· is non-·absent·, then the appropriate case among the
+        //      following:
+        //     3.2.2.1.1 If the ·complete wildcard· is ·absent·, then the ·base wildcard·.
+        //     3.2.2.1.2 otherwise a wildcard whose {process contents} and {annotations} are
+        //       those of the ·complete wildcard·, and whose {namespace constraint} is the
+        //       wildcard union of the {namespace constraint} of the ·complete wildcard· and of
+        //       the ·base wildcard·, as defined in Attribute Wildcard Union (§3.10.6.3).
+        //    3.2.2.2 otherwise (the ·base wildcard· is ·absent·) the ·complete wildcard·
+        let base_wildcard = match (derivation_method, base_type_definition) {
+            (DerivationMethod::Extension, TypeDefinition::Complex(base_type_definition)) => {
+                context.request(base_type_definition)?.attribute_wildcard
+            }
+            _ => None,
+        };
+        Ok(match (complete_wildcard, base_wildcard) {
+            (Some(complete_wildcard), Some(base_wildcard)) => {
+                let components = context.components();
+                let base_wildcard = base_wildcard.get(components);
+                let complete_wildcard = complete_wildcard.get(components);
+                let wildcard = Wildcard {
+                    namespace_constraint: complete_wildcard
+                        .namespace_constraint
+                        .union(&base_wildcard.namespace_constraint),
+                    process_contents: complete_wildcard.process_contents.clone(),
+                    annotations: complete_wildcard.annotations.clone(),
+                };
+                Some(context.create(wildcard))
+            }
+            (complete_wildcard, base_wildcard) => complete_wildcard.or(base_wildcard),
+        })
+    }
 }
 
 impl ContentType {
@@ -739,15 +967,18 @@ impl ContentType {
                     .any(|c| c.tag_name().name() != Annotation::TAG_NAME)
         });
         // 2.1.3 There is among the [children] a <choice> element whose minOccurs [attribute] has the ·actual value· 0 and which has no [children] of its own except for <annotation>;
-        let cond_3 = children_elem.children().any(|c| {
-            c.tag_name().name() == "choice"
-                && c.attribute("minOccurs")
-                    .map(|v| actual_value::<u64>(v, complex_type))
-                    == Some(0)
-                && !c
-                    .children()
-                    .any(|c| c.tag_name().name() != Annotation::TAG_NAME)
-        });
+        let cond_3 = children_elem.children().try_fold(false, |acc, c| {
+            Ok::<_, XsdError>(
+                acc || c.tag_name().name() == "choice"
+                    && c.attribute("minOccurs")
+                        .map(|v| actual_value::<u64>(v, complex_type))
+                        .transpose()?
+                        == Some(0)
+                    && !c
+                        .children()
+                        .any(|c| c.tag_name().name() != Annotation::TAG_NAME),
+            )
+        })?;
         // 2.1.4 The <group>, <all>, <choice> or <sequence> element among the [children] has a maxOccurs [attribute] with an ·actual value· of 0;
         let cond_4 = children_elem
             .children()
@@ -756,9 +987,11 @@ impl ContentType {
                 c.attribute("maxOccurs")
                     .filter(|m| *m != "unbounded")
                     .map(|v| actual_value::<u64>(v, complex_type))
-                    == Some(0)
+                    .transpose()
             })
-            == Some(true);
+            .transpose()?
+            .flatten()
+            == Some(0);
         if cond_1 || cond_2 || cond_3 || cond_4 {
             // then empty
             Ok(None)
@@ -797,7 +1030,12 @@ impl ContentType {
             complex_content
                 .children()
                 .find(|c| ["extension", "restriction"].contains(&c.tag_name().name()))
-                .unwrap()
+                .ok_or_else(|| {
+                    XsdError::invalid_content(
+                        complex_content,
+                        "expected <restriction> or <extension>",
+                    )
+                })?
         } else {
             // If no <complexContent> is present, then the [children] of the <complexType> source
             // declaration itself are meant
@@ -808,10 +1046,10 @@ impl ContentType {
         let effective_mixed =
             if let Some(mixed) = complex_content.and_then(|cc| cc.attribute("mixed")) {
                 // 1.1 If the mixed [attribute] is present on <complexContent>, then its ·actual value·;
-                actual_value::<bool>(mixed, complex_type)
+                actual_value::<bool>(mixed, complex_type)?
             } else if let Some(mixed) = complex_type.attribute("mixed") {
                 // 1.2 If the mixed [attribute] is present on <complexType>, then its ·actual value·;
-                actual_value::<bool>(mixed, complex_type)
+                actual_value::<bool>(mixed, complex_type)?
             } else {
                 // 1.3 otherwise false.
                 false
@@ -891,6 +1129,7 @@ impl ContentType {
                     && default_open_content
                         .attribute("appliesToEmpty")
                         .map(|v| actual_value::<bool>(v, complex_type))
+                        .transpose()?
                         == Some(true))
             {
                 // then the <defaultOpenContent> [child] of the <schema>.
@@ -906,11 +1145,7 @@ impl ContentType {
 
         // 6 Then the value of the property is the appropriate case among the following:
         if wildcard_element
-            .map(|e| {
-                e.attribute("mode")
-                    .map(|v| actual_value::<&str>(v, complex_type))
-                    == Some("none")
-            })
+            .map(|e| e.attribute("mode") == Some("none"))
             .unwrap_or(true)
         {
             // 6.1 If the ·wildcard element· is ·absent· or is present and has mode = 'none', then
@@ -960,11 +1195,15 @@ impl ContentType {
                 //   present, otherwise interleave.
                 let mode = wildcard_element
                     .attribute("mode")
-                    .and_then(|v| match v {
-                        "interleave" => Some(OpenContentMode::Interleave),
-                        "suffix" => Some(OpenContentMode::Suffix),
-                        _ => unreachable!(),
+                    .map(|v| match v {
+                        "interleave" => Ok(OpenContentMode::Interleave),
+                        "suffix" => Ok(OpenContentMode::Suffix),
+                        _ => Err(XsdError::InvalidAttributeValue {
+                            value: v.to_string(),
+                            expected: "open content mode",
+                        }),
                     })
+                    .transpose()?
                     .unwrap_or(OpenContentMode::Interleave);
 
                 // {wildcard}
@@ -974,20 +1213,29 @@ impl ContentType {
                 //   those of W, and whose {namespace constraint} is the wildcard union of the
                 //   {namespace constraint} of W and of {open content}.{wildcard} of the ·explicit
                 //   content type·, as defined in Attribute Wildcard Union (§3.10.6.3).
-                // TODO
-                let _w = wildcard_element
+                let w = wildcard_element
                     .children()
                     .find(|c| c.tag_name().name() == "any")
-                    .unwrap();
-                let wildcard = context.create(Wildcard {
-                    namespace_constraint: wildcard::NamespaceConstraint {
-                        variety: wildcard::NamespaceConstraintVariety::Any,
-                        namespaces: Set::new(),
-                        disallowed_names: DisallowedNameSet::default(),
-                    },
-                    process_contents: wildcard::ProcessContents::Strict,
-                    annotations: Sequence::new(),
-                });
+                    .ok_or_else(|| XsdError::invalid_content(wildcard_element, "expected <any>"))?;
+                let w = Wildcard::map_from_xml_any(context, w, schema)?;
+                let wildcard = match explicit_content_type.open_content() {
+                    None => w,
+                    Some(explicit_open_content) => {
+                        let components = context.components();
+                        let w = w.get(components);
+                        let wildcard = Wildcard {
+                            namespace_constraint: w.namespace_constraint.union(
+                                &explicit_open_content
+                                    .wildcard
+                                    .get(components)
+                                    .namespace_constraint,
+                            ),
+                            process_contents: w.process_contents.clone(),
+                            annotations: w.annotations.clone(),
+                        };
+                        context.create(wildcard)
+                    }
+                };
 
                 Some(OpenContent { mode, wildcard })
             };
@@ -1254,9 +1502,11 @@ mod tests {
         "#;
         let schema_doc = Document::parse(SCHEMA).unwrap();
         let schema = schema_doc.root_element();
-        let mut root_context = RootContext::new(BuiltinOverwriteAction::Deny, &[]);
+        let mut warnings = Vec::new();
+        let mut root_context =
+            RootContext::new(BuiltinOverwriteAction::Deny, &[], &[], &mut warnings);
         builtins::register_builtins(&mut root_context);
-        let mut context = MappingContext::new(&mut root_context, schema);
+        let mut context = MappingContext::new(&mut root_context, None, Vec::new());
 
         let base_node = schema
             .children()
@@ -1273,11 +1523,15 @@ mod tests {
             .unwrap();
 
         let base = context.reserve::<ComplexTypeDefinition>();
-        context.register_with_name(QName::without_namespace("base"), base);
+        context
+            .register_with_name(QName::without_namespace("base"), base)
+            .unwrap();
         context.top_level_refs.insert(base_node, base);
 
         let extension = context.reserve::<ComplexTypeDefinition>();
-        context.register_with_name(QName::without_namespace("extension"), extension);
+        context
+            .register_with_name(QName::without_namespace("extension"), extension)
+            .unwrap();
         context.top_level_refs.insert(extension_node, extension);
 
         let particle = ContentType::compute_explicit_content(
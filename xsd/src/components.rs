@@ -1,8 +1,12 @@
 use crate::{
     Annotation, Assertion, AttributeDeclaration, AttributeGroupDefinition, AttributeUse,
     BuiltinOverwriteAction, ComplexTypeDefinition, ConstrainingFacet, ElementDeclaration,
-    IdentityConstraintDefinition, ModelGroup, ModelGroupDefinition, NotationDeclaration, Particle,
-    SimpleTypeDefinition, TypeAlternative, TypeDefinition, Wildcard, xstypes::QName,
+    IdentityConstraintDefinition, MappingContext, ModelGroup, ModelGroupDefinition,
+    NotationDeclaration, Particle, SimpleTypeDefinition, TypeAlternative, TypeDefinition, Wildcard,
+    diagnostics::{DiagnosticSink, Warning},
+    element_decl::ScopeVariety,
+    error::XsdError,
+    xstypes::QName,
 };
 use roxmltree::Node;
 use std::{
@@ -12,6 +16,7 @@ use std::{
     hash::Hash,
     marker::PhantomData,
     num::{NonZeroU32, NonZeroUsize},
+    sync::OnceLock,
 };
 
 /// Trait implemented by all concrete schema components.
@@ -284,6 +289,7 @@ impl ConstructionComponentTable {
             simple_type_definitions: Self::convert_container(self.simple_type_definitions)?,
             type_alternatives: Self::convert_container(self.type_alternatives)?,
             wildcards: Self::convert_container(self.wildcards)?,
+            global_names: OnceLock::new(),
         })
     }
 
@@ -321,6 +327,20 @@ pub struct SchemaComponentTable {
     pub simple_type_definitions: Box<[SimpleTypeDefinition]>,
     pub type_alternatives: Box<[TypeAlternative]>,
     pub wildcards: Box<[Wildcard]>,
+
+    /// The index returned by [`Self::global_names()`], built on first use
+    global_names: OnceLock<GlobalNames>,
+}
+
+/// The type definitions and global attribute and element declarations of a
+/// [`SchemaComponentTable`], indexed by their expanded names. Where several components share a
+/// name, the one created first is indexed; simple type definitions take precedence over complex
+/// ones.
+#[derive(Default)]
+pub(crate) struct GlobalNames {
+    pub(crate) type_definitions: LookupTable<TypeDefinition>,
+    pub(crate) attribute_declarations: LookupTable<Ref<AttributeDeclaration>>,
+    pub(crate) element_declarations: LookupTable<Ref<ElementDeclaration>>,
 }
 
 impl ComponentTable for SchemaComponentTable {
@@ -336,6 +356,60 @@ impl ComponentTable for SchemaComponentTable {
     }
 }
 
+impl SchemaComponentTable {
+    /// Returns references to all components of type `R` in this table, in order of creation
+    pub fn refs<R>(&self) -> impl Iterator<Item = Ref<R>> + use<R>
+    where
+        R: Component,
+        ComponentTraits: HasArenaContainer<R>,
+    {
+        let len = ComponentTraits::get_container_from_schema_component_table(self).len();
+        let len = u32::try_from(len).expect("ID did not fit into 32-bit integer");
+        (1..=len).map(|id| Ref::from_inner(NonZeroU32::new(id).unwrap()))
+    }
+
+    /// Returns the named components looked up during validation, indexed by their expanded names.
+    /// The index is built once per table, when it is first needed.
+    pub(crate) fn global_names(&self) -> &GlobalNames {
+        self.global_names.get_or_init(|| {
+            let mut names = GlobalNames::default();
+            for t in self.refs::<SimpleTypeDefinition>() {
+                if let Some(name) = t.name(self) {
+                    names
+                        .type_definitions
+                        .entry(name)
+                        .or_insert(TypeDefinition::Simple(t));
+                }
+            }
+            for t in self.refs::<ComplexTypeDefinition>() {
+                if let Some(name) = t.name(self) {
+                    names
+                        .type_definitions
+                        .entry(name)
+                        .or_insert(TypeDefinition::Complex(t));
+                }
+            }
+            for d in self.refs::<AttributeDeclaration>() {
+                let declaration = d.get(self);
+                if declaration.scope.variety() == ScopeVariety::Global
+                    && let Some(name) = declaration.name()
+                {
+                    names.attribute_declarations.entry(name).or_insert(d);
+                }
+            }
+            for d in self.refs::<ElementDeclaration>() {
+                let declaration = d.get(self);
+                if declaration.scope.variety() == ScopeVariety::Global
+                    && let Some(name) = declaration.name()
+                {
+                    names.element_declarations.entry(name).or_insert(d);
+                }
+            }
+            names
+        })
+    }
+}
+
 macro_rules! has_arena_container_impl {
     ($type_name:ty, $field_name:ident) => {
         impl HasArenaContainer<$type_name> for ComponentTraits {
@@ -542,7 +616,9 @@ impl ComponentResolver {
         name: QName,
         value: R,
         table: &impl ComponentTable,
-    ) where
+        diagnostic_sink: &mut dyn DiagnosticSink,
+    ) -> Result<(), XsdError>
+    where
         R: Copy,
         LookupTables: Lookup<R>,
     {
@@ -554,23 +630,26 @@ impl ComponentResolver {
             if prev.is_builtin(table) {
                 match self.builtin_overwrite {
                     BuiltinOverwriteAction::Deny => {
-                        panic!("Tried to overwrite built-in component: {name}");
+                        return Err(XsdError::BuiltinOverwrite(name));
                     }
                     BuiltinOverwriteAction::Warn => {
-                        eprintln!("WARN: Overwriting built-in component {name}");
+                        diagnostic_sink.warning(Warning::BuiltinOverwrite(name));
                     }
                     BuiltinOverwriteAction::Allow => {}
                 }
             } else {
-                panic!(
-                    "Duplicate component: {name} ({})",
-                    std::any::type_name::<R>()
-                ); // TODO propagate
+                return Err(XsdError::DuplicateComponent(name));
             }
         }
+        Ok(())
     }
 
-    pub(super) fn register<R>(&mut self, value: R, table: &impl ComponentTable)
+    pub(super) fn register<R>(
+        &mut self,
+        value: R,
+        table: &impl ComponentTable,
+        diagnostic_sink: &mut dyn DiagnosticSink,
+    ) -> Result<(), XsdError>
     where
         R: RefNamed + Copy,
         LookupTables: Lookup<R>,
@@ -578,11 +657,15 @@ impl ComponentResolver {
         let name = value
             .name(table)
             .expect("Tried to register unnamed component");
-        self.register_with_name(name, value, table)
+        self.register_with_name(name, value, table, diagnostic_sink)
     }
 }
 
 /// (Top-level) Components whose name is always available from XML
 pub(super) trait NamedXml: Component {
-    fn get_name_from_xml(this_node: Node, schema_node: Node) -> QName;
+    fn get_name_from_xml(
+        context: &MappingContext,
+        this_node: Node,
+        schema_node: Node,
+    ) -> Result<QName, XsdError>;
 }
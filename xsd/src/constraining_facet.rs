@@ -1,6 +1,7 @@
 use crate::{
     Annotation, Assertion, MappingContext, Ref,
     components::{Component, ComponentTable},
+    error::XsdError,
     values::{ActualValue, actual_value},
     xstypes::{Sequence, Set},
 };
@@ -134,12 +135,17 @@ impl ConstrainingFacet {
     /// This function maps a list of facet elements to a list of [`ConstrainingFacet`]s.
     /// As there are elements (`<enumeration>`, `<pattern>`, `<assertion>`) where multiple
     /// occurrences are mapped to a single facet, the function needs knowledge of all the elements.
-    /// Returns `None` in case one of the constraining facets is not supported by the processor
+    /// Returns an error in case one of the elements is not a constraining facet known to the
+    /// processor.
+    ///
+    /// `base_assertions` are the Assertions of the assertions facet of the {base type definition}
+    /// of the owner, if any.
     pub(super) fn map_from_xml(
         context: &mut MappingContext,
         facets: &[Node],
+        base_assertions: &[Ref<Assertion>],
         schema: Node,
-    ) -> Option<Vec<Ref<Self>>> {
+    ) -> Result<Vec<Ref<Self>>, XsdError> {
         // First, create separate groups for facets with potentially multiple elements
         let mut patterns = Vec::new();
         let mut enumerations = Vec::new();
@@ -163,15 +169,15 @@ impl ConstrainingFacet {
             let r = if patterns.len() == 1 {
                 // If there is only one <pattern> among the [children] of a <restriction>, then the
                 // actual value of its value [attribute]
-                actual_value::<String>(patterns[0].attribute("value").unwrap(), patterns[0])
+                actual_value::<String>(Self::value_attribute(patterns[0])?, patterns[0])?
             } else {
                 // otherwise the concatenation of the actual values of all the <pattern>
                 // [children]'s value [attributes], in order, separated by '|', so forming a single
                 // regular expression with multiple ·branches·.
                 patterns
                     .iter()
-                    .map(|&p| actual_value::<&str>(p.attribute("value").unwrap(), p))
-                    .collect::<Vec<_>>()
+                    .map(|&p| actual_value::<&str>(Self::value_attribute(p)?, p))
+                    .collect::<Result<Vec<_>, _>>()?
                     .join("|")
             };
 
@@ -199,8 +205,8 @@ impl ConstrainingFacet {
             //   [attributes], interpreted as instances of the {base type definition}.
             let value = enumerations
                 .iter()
-                .map(|&e| e.attribute("value").unwrap().to_string())
-                .collect::<Vec<_>>();
+                .map(|&e| Self::value_attribute(e).map(String::from))
+                .collect::<Result<Vec<_>, _>>()?;
 
             // A (possibly empty) sequence of Annotation components, one for each <annotation>
             // among the [children] of the <enumeration>s among the [children] of a <restriction>,
@@ -220,11 +226,10 @@ impl ConstrainingFacet {
             //   {facets}, then the Assertions which appear in the {value} of that assertions facet.
             // 2 Assertions corresponding to the <assertion> element information items among the
             //   [children] of <restriction>, if any, in document order.
-            // TODO base type assertions
-            let value = assertions
-                .into_iter()
-                .map(|assert| Assertion::map_from_xml(context, assert, schema))
-                .collect::<Vec<_>>();
+            let mut value = base_assertions.to_vec();
+            for assertion in assertions {
+                value.push(Assertion::map_from_xml(context, assertion, schema)?);
+            }
 
             // {annotations} The empty sequence.
             let annotations = Sequence::new();
@@ -239,7 +244,7 @@ impl ConstrainingFacet {
             let facet = match facet.tag_name().name() {
                 "length" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::Length(Length {
                         value,
@@ -249,7 +254,7 @@ impl ConstrainingFacet {
                 }
                 "minLength" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::MinLength(Length {
                         value,
@@ -259,7 +264,7 @@ impl ConstrainingFacet {
                 }
                 "maxLength" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::MaxLength(Length {
                         value,
@@ -269,7 +274,7 @@ impl ConstrainingFacet {
                 }
                 "whiteSpace" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::WhiteSpace(WhiteSpace {
                         value,
@@ -279,7 +284,7 @@ impl ConstrainingFacet {
                 }
                 "maxInclusive" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::MaxInclusive(MinMax {
                         value,
@@ -289,7 +294,7 @@ impl ConstrainingFacet {
                 }
                 "maxExclusive" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::MaxExclusive(MinMax {
                         value,
@@ -299,7 +304,7 @@ impl ConstrainingFacet {
                 }
                 "minExclusive" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::MinExclusive(MinMax {
                         value,
@@ -309,7 +314,7 @@ impl ConstrainingFacet {
                 }
                 "minInclusive" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::MinInclusive(MinMax {
                         value,
@@ -319,7 +324,7 @@ impl ConstrainingFacet {
                 }
                 "totalDigits" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::TotalDigits(TotalDigits {
                         value,
@@ -329,7 +334,7 @@ impl ConstrainingFacet {
                 }
                 "fractionDigits" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::FractionDigits(FractionDigits {
                         value,
@@ -339,7 +344,7 @@ impl ConstrainingFacet {
                 }
                 "explicitTimezone" => {
                     let (value, fixed, annotations) =
-                        Self::map_value_fixed_annotations(context, facet);
+                        Self::map_value_fixed_annotations(context, facet)?;
 
                     context.create(Self::ExplicitTimezone(ExplicitTimezone {
                         value,
@@ -347,12 +352,23 @@ impl ConstrainingFacet {
                         annotations,
                     }))
                 }
-                _ => return None,
+                _ => {
+                    return Err(XsdError::invalid_content(
+                        facet,
+                        "unknown constraining facet",
+                    ));
+                }
             };
             facets.push(facet);
         }
 
-        Some(facets)
+        Ok(facets)
+    }
+
+    fn value_attribute<'a>(facet: Node<'a, '_>) -> Result<&'a str, XsdError> {
+        facet
+            .attribute("value")
+            .ok_or(XsdError::missing_attribute(facet, "value"))
     }
 
     /// Shared code for mapping facets which have the `value` and `fixed` attribute (with default of
@@ -360,21 +376,22 @@ impl ConstrainingFacet {
     fn map_value_fixed_annotations<'a, V: ActualValue<'a>>(
         context: &mut MappingContext,
         facet: Node<'a, '_>,
-    ) -> (V, bool, Vec<Ref<Annotation>>) {
+    ) -> Result<(V, bool, Vec<Ref<Annotation>>), XsdError> {
         // {value} The actual value of the value [attribute]
-        let value = actual_value::<V>(facet.attribute("value").unwrap(), facet);
+        let value = actual_value::<V>(Self::value_attribute(facet)?, facet)?;
 
         // {fixed}
         //   The actual value of the fixed [attribute], if present, otherwise false
         let fixed = facet
             .attribute("fixed")
             .map(|v| actual_value::<bool>(v, facet))
+            .transpose()?
             .unwrap_or(false);
 
         // {annotations} The annotation mapping of the <...> element [...]
         let annotations = Annotation::xml_element_annotation_mapping(context, facet);
 
-        (value, fixed, annotations)
+        Ok((value, fixed, annotations))
     }
 
     pub fn annotations(&self) -> &[Ref<Annotation>] {
@@ -473,23 +490,29 @@ impl WhiteSpace {
 }
 
 impl ActualValue<'_> for WhiteSpaceValue {
-    fn convert(src: &'_ str, _parent: Node) -> Self {
+    fn convert(src: &'_ str, _parent: Node) -> Result<Self, XsdError> {
         match src {
-            "preserve" => Self::Preserve,
-            "replace" => Self::Replace,
-            "collapse" => Self::Collapse,
-            _ => panic!("Invalid value for whiteSpace value"),
+            "preserve" => Ok(Self::Preserve),
+            "replace" => Ok(Self::Replace),
+            "collapse" => Ok(Self::Collapse),
+            _ => Err(XsdError::InvalidAttributeValue {
+                value: src.to_string(),
+                expected: "whiteSpace value",
+            }),
         }
     }
 }
 
 impl ActualValue<'_> for ExplicitTimezoneValue {
-    fn convert(src: &'_ str, _parent: Node) -> Self {
+    fn convert(src: &'_ str, _parent: Node) -> Result<Self, XsdError> {
         match src {
-            "required" => Self::Required,
-            "prohibited" => Self::Prohibited,
-            "optional" => Self::Optional,
-            _ => panic!("Invalid value for whiteSpace value"),
+            "required" => Ok(Self::Required),
+            "prohibited" => Ok(Self::Prohibited),
+            "optional" => Ok(Self::Optional),
+            _ => Err(XsdError::InvalidAttributeValue {
+                value: src.to_string(),
+                expected: "explicitTimezone value",
+            }),
         }
     }
 }
@@ -0,0 +1,2000 @@
+//! Schema Component Constraints (pt. 1, §3.x.6 and pt. 2, §4.3), which a schema must satisfy in
+//! addition to being correctly represented in XML. They are checked by [`check_schema()`] after a
+//! schema has been mapped.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    ComplexTypeDefinition, ElementDeclaration, Ref, Schema, SchemaComponentTable,
+    SimpleTypeDefinition, Term, TypeDefinition, Wildcard,
+    assertion::NamespaceBinding,
+    attribute_use::AttributeUse,
+    builtins::{XS_ANY_SIMPLE_TYPE_NAME, XS_ANY_TYPE_NAME, XS_ERROR_NAME},
+    complex_type_def::{ContentType, DerivationMethod},
+    components::{IsBuiltinRef, Named, RefNamed},
+    constraining_facet::{ConstrainingFacet, ConstrainingFacets, WhiteSpaceValue},
+    element_decl::SubstitutionMethod,
+    model_group::Compositor,
+    particle::{MaxOccurs, Particle},
+    shared::{ScopeVariety, ValueConstraintVariety},
+    simple_type_def::{self, Variety},
+    state_machine::{Transition, create_state_machine, verify_upa_satisfied},
+    validation::{Primitive, Value, compile_pattern, string_valid},
+    wildcard::{NamespaceConstraintVariety, ProcessContents},
+    xstypes::QName,
+};
+
+/// A violation of a Schema Component Constraint
+#[derive(Clone, Debug)]
+pub struct ConstraintViolation {
+    /// The name of the violated constraint as given in the specification, e.g. `cos-ct-extends`
+    pub constraint: &'static str,
+    /// The kind of the component violating the constraint, e.g. `ComplexTypeDefinition`
+    pub component: &'static str,
+    /// The name of the component violating the constraint, if it has one
+    pub name: Option<QName>,
+    pub reason: String,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} violated by {}", self.constraint, self.component)?;
+        if let Some(name) = &self.name {
+            write!(f, " {name}")?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Checks the Schema Component Constraints of all components of `schema`, returning the violated
+/// ones. The schema is valid if the result is empty.
+///
+/// Currently covered are the constraints on schemas (sch-props-correct), complex type definitions
+/// (ct-props-correct, cos-ct-extends, derivation-ok-restriction, including particle restriction),
+/// simple type definitions (st-props-correct, cos-st-restricts, cos-applicable-facets and the
+/// facet restriction constraints of pt. 2), element declarations (e-props-correct), particles
+/// (p-props-correct, cos-all-limited, cos-element-consistent) and content models (cos-nonambig).
+pub fn check_schema(
+    schema: &Schema,
+    components: &SchemaComponentTable,
+) -> Vec<ConstraintViolation> {
+    let mut checker = Checker {
+        components,
+        violations: Vec::new(),
+    };
+    checker.check_schema_properties(schema);
+    for simple_type in components.refs::<SimpleTypeDefinition>() {
+        if !simple_type.get(components).is_builtin {
+            checker.check_simple_type(simple_type);
+        }
+    }
+    for complex_type in components.refs::<ComplexTypeDefinition>() {
+        if !complex_type.get(components).is_builtin {
+            checker.check_complex_type(complex_type);
+        }
+    }
+    for element_decl in components.refs::<ElementDeclaration>() {
+        checker.check_element_declaration(element_decl);
+    }
+    for particle in components.refs::<Particle>() {
+        checker.check_particle(particle.get(components));
+    }
+    checker.violations
+}
+
+/// Schema Component Constraint: Type Derivation OK (Complex) (§3.4.6.5) and Type Derivation OK
+/// (Simple) (§3.16.6.3): whether `d` is validly derived from `b`, given the derivation methods in
+/// `blocking`. For simple type definitions, only restriction is considered for blocking.
+pub(crate) fn type_derivation_ok(
+    d: TypeDefinition,
+    b: TypeDefinition,
+    blocking: &[DerivationMethod],
+    components: &SchemaComponentTable,
+) -> bool {
+    let mut current = d;
+    loop {
+        if Checker::same_type(current, b) {
+            return true;
+        }
+        // cos-st-derived-ok 2.2.4: B's {variety} is union and D is validly derived from a type
+        // definition in B's {member type definitions}, given that B has no pattern or
+        // enumeration facets
+        if let (TypeDefinition::Simple(_), TypeDefinition::Simple(union)) = (current, b) {
+            let union = union.get(components);
+            if union.variety == Some(Variety::Union)
+                && union.facets.patterns(components).is_none()
+                && union.facets.enumerations(components).is_none()
+                && union.member_type_definitions.iter().flatten().any(|&m| {
+                    type_derivation_ok(current, TypeDefinition::Simple(m), blocking, components)
+                })
+            {
+                return true;
+            }
+        }
+        // anyType is its own base type definition
+        if current.is_builtin(components) && current.name(components) == Some(XS_ANY_TYPE_NAME) {
+            return false;
+        }
+        let derivation_method = match current {
+            TypeDefinition::Complex(c) => c.get(components).derivation_method,
+            TypeDefinition::Simple(_) => Some(DerivationMethod::Restriction),
+        };
+        if derivation_method.is_some_and(|m| blocking.contains(&m)) {
+            return false;
+        }
+        current = current.base_type_definition(components);
+    }
+}
+
+/// Whether `t` is ·validly substitutable· for `s`, subject to the blocking keywords `blocking`:
+/// `t` is validly derived from `s` (Type Derivation OK (Complex) (§3.4.6.5), Type Derivation OK
+/// (Simple) (§3.16.6.3)), given the union of `blocking` and the {prohibited substitutions} of `s`
+/// and of all intermediate types between `t` and `s`.
+pub(crate) fn validly_substitutable(
+    t: TypeDefinition,
+    s: TypeDefinition,
+    blocking: &[DerivationMethod],
+    components: &SchemaComponentTable,
+) -> bool {
+    let mut blocking = blocking.to_vec();
+    let mut current = t;
+    loop {
+        if current != t
+            && let TypeDefinition::Complex(c) = current
+        {
+            blocking.extend(c.get(components).prohibited_substitutions.iter().copied());
+        }
+        let base = current.base_type_definition(components);
+        if current == s || base == current {
+            break;
+        }
+        current = base;
+    }
+    type_derivation_ok(t, s, &blocking, components)
+}
+
+struct Checker<'a> {
+    components: &'a SchemaComponentTable,
+    violations: Vec<ConstraintViolation>,
+}
+
+/// The component violating a constraint, see [`Checker::report()`]
+trait Subject {
+    fn describe(&self, components: &SchemaComponentTable) -> (&'static str, Option<QName>);
+}
+
+impl<R: Named> Subject for Ref<R>
+where
+    crate::components::ComponentTraits: crate::components::HasArenaContainer<R>,
+{
+    fn describe(&self, components: &SchemaComponentTable) -> (&'static str, Option<QName>) {
+        (R::DISPLAY_NAME, self.name(components))
+    }
+}
+
+impl Subject for Particle {
+    fn describe(&self, _components: &SchemaComponentTable) -> (&'static str, Option<QName>) {
+        (
+            <Particle as crate::components::Component>::DISPLAY_NAME,
+            None,
+        )
+    }
+}
+
+impl Subject for Schema {
+    fn describe(&self, _components: &SchemaComponentTable) -> (&'static str, Option<QName>) {
+        ("Schema", None)
+    }
+}
+
+/// The failure of a constraint which is checked as part of another one, e.g. the restriction of
+/// an element declaration within a content type
+type Failure = (&'static str, String);
+
+impl Checker<'_> {
+    fn report(&mut self, subject: &impl Subject, constraint: &'static str, reason: String) {
+        let (component, name) = subject.describe(self.components);
+        self.violations.push(ConstraintViolation {
+            constraint,
+            component,
+            name,
+            reason,
+        });
+    }
+
+    fn report_failure(&mut self, subject: &impl Subject, (constraint, reason): Failure) {
+        self.report(subject, constraint, reason);
+    }
+
+    // ===== Schemas =====
+
+    /// Schema Component Constraint: Schema Properties Correct (§3.17.6.1)
+    fn check_schema_properties(&mut self, schema: &Schema) {
+        // 2 None of the {type definitions}, {element declarations}, {attribute group
+        //   definitions}, {model group definitions}, {notation declarations}, or
+        //   {identity-constraint definitions} contains two or more schema components with the same
+        //   expanded name.
+        let c = self.components;
+        let names = [
+            schema.type_definitions.iter().map(|t| t.name(c)).collect(),
+            schema
+                .element_declarations
+                .iter()
+                .map(|e| e.name(c))
+                .collect(),
+            schema
+                .attribute_declarations
+                .iter()
+                .map(|a| a.name(c))
+                .collect(),
+            schema
+                .attribute_group_definitions
+                .iter()
+                .map(|a| a.name(c))
+                .collect(),
+            schema
+                .model_group_definitions
+                .iter()
+                .map(|m| m.name(c))
+                .collect(),
+            schema
+                .notation_declarations
+                .iter()
+                .map(|n| n.name(c))
+                .collect(),
+            schema
+                .identity_constraint_definitions
+                .iter()
+                .map(|i| i.name(c))
+                .collect::<Vec<_>>(),
+        ];
+        for names in names {
+            let mut seen = HashMap::new();
+            for name in names.into_iter().flatten() {
+                let count = seen.entry(name.clone()).or_insert(0);
+                *count += 1;
+                if *count == 2 {
+                    self.report(
+                        schema,
+                        "sch-props-correct",
+                        format!("there are multiple components named {name}"),
+                    );
+                }
+            }
+        }
+    }
+
+    // ===== Type definitions =====
+
+    fn is_any_type(&self, t: TypeDefinition) -> bool {
+        t.is_builtin(self.components) && t.name(self.components) == Some(XS_ANY_TYPE_NAME)
+    }
+
+    fn is_any_simple_type(&self, t: TypeDefinition) -> bool {
+        t.is_builtin(self.components)
+            && t.name(self.components).as_ref() == Some(&XS_ANY_SIMPLE_TYPE_NAME)
+    }
+
+    fn same_type(a: TypeDefinition, b: TypeDefinition) -> bool {
+        match (a, b) {
+            (TypeDefinition::Simple(a), TypeDefinition::Simple(b)) => a == b,
+            (TypeDefinition::Complex(a), TypeDefinition::Complex(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn type_derivation_ok(
+        &self,
+        d: TypeDefinition,
+        b: TypeDefinition,
+        blocking: &[DerivationMethod],
+    ) -> bool {
+        type_derivation_ok(d, b, blocking, self.components)
+    }
+
+    /// Checks the facet-independent constraints on simple type definitions, and delegates to
+    /// [`check_facets()`](Self::check_facets)
+    fn check_simple_type(&mut self, simple_type: Ref<SimpleTypeDefinition>) {
+        let c = self.components;
+        let t = simple_type.get(c);
+        let TypeDefinition::Simple(base) = t.base_type_definition else {
+            // Only the (built-in) xs:anySimpleType has a complex base type definition
+            self.report(
+                &simple_type,
+                "st-props-correct",
+                "the base type definition is a complex type definition".into(),
+            );
+            return;
+        };
+        let base_type = base.get(c);
+
+        if !self.is_any_simple_type(t.base_type_definition) {
+            // Schema Component Constraint: Simple Type Definition Properties Correct (§3.16.6.1)
+            // 4 D.{base type definition}.{final} does not contain restriction.
+            if base_type
+                .final_
+                .iter()
+                .any(|m| matches!(m, simple_type_def::DerivationMethod::Restriction))
+            {
+                self.report(
+                    &simple_type,
+                    "st-props-correct",
+                    "the base type definition is final for restriction".into(),
+                );
+            }
+            if base_type.variety != t.variety {
+                self.report(
+                    &simple_type,
+                    "cos-st-restricts",
+                    "a restriction must have the same variety as its base type definition".into(),
+                );
+            }
+            self.check_facets(simple_type, base);
+            return;
+        }
+
+        // Schema Component Constraint: Derivation Valid (Restriction, Simple) (§3.16.6.2), for
+        // lists and unions constructed from xs:anySimpleType
+        match t.variety {
+            Some(Variety::List) => {
+                let Some(item_type) = t.item_type_definition else {
+                    return;
+                };
+                let item = item_type.get(c);
+                // 2.1 D.{item type definition}.{variety} is atomic or union, with no list
+                //   members
+                let has_list_members = item.variety == Some(Variety::List)
+                    || item
+                        .member_type_definitions
+                        .iter()
+                        .flatten()
+                        .any(|m| m.get(c).variety == Some(Variety::List));
+                if has_list_members {
+                    self.report(
+                        &simple_type,
+                        "cos-st-restricts",
+                        "the item type definition of a list must not be or contain a list".into(),
+                    );
+                }
+                // 2.2.1 D.{item type definition}.{final} does not contain list.
+                if item
+                    .final_
+                    .iter()
+                    .any(|m| matches!(m, simple_type_def::DerivationMethod::List))
+                {
+                    self.report(
+                        &simple_type,
+                        "cos-st-restricts",
+                        "the item type definition is final for list".into(),
+                    );
+                }
+            }
+            Some(Variety::Union) => {
+                // Schema Representation Constraint: Union Type Definition Representation OK
+                //   (§3.16.3)
+                //   Either the memberTypes [attribute] of the <union> element must be non-empty or
+                //   there must be at least one simpleType [child].
+                if t.member_type_definitions.iter().flatten().next().is_none() {
+                    self.report(
+                        &simple_type,
+                        "src-union-memberTypes-or-simpleTypes",
+                        "a union must have at least one member type definition".into(),
+                    );
+                }
+                // 3.3.1.1 All of the {member type definitions} have a {final} which does not
+                //   contain union.
+                for member in t.member_type_definitions.iter().flatten() {
+                    if member
+                        .get(c)
+                        .final_
+                        .iter()
+                        .any(|m| matches!(m, simple_type_def::DerivationMethod::Union))
+                    {
+                        self.report(
+                            &simple_type,
+                            "cos-st-restricts",
+                            format!(
+                                "the member type definition {} is final for union",
+                                Self::type_name(member.name(c))
+                            ),
+                        );
+                    }
+                }
+            }
+            Some(Variety::Atomic) | None => {}
+        }
+        self.check_facets(simple_type, base);
+    }
+
+    fn type_name(name: Option<QName>) -> String {
+        name.map_or_else(|| "(anonymous)".into(), |name| name.to_string())
+    }
+
+    /// The local name of the primitive type definition of `t`, if it is atomic
+    fn primitive_name(&self, t: &SimpleTypeDefinition) -> Option<String> {
+        if t.is_primitive() {
+            return t.name.clone();
+        }
+        t.primitive_type_definition
+            .and_then(|p| p.get(self.components).name.clone())
+    }
+
+    /// The names of the constraining facets applicable to `t` (pt. 2, §4.1.5)
+    fn applicable_facets(&self, t: &SimpleTypeDefinition) -> Option<&'static [&'static str]> {
+        const LENGTH: &[&str] = &[
+            "length",
+            "minLength",
+            "maxLength",
+            "pattern",
+            "enumeration",
+            "whiteSpace",
+            "assertions",
+        ];
+        const ORDERED: &[&str] = &[
+            "pattern",
+            "enumeration",
+            "whiteSpace",
+            "maxInclusive",
+            "maxExclusive",
+            "minInclusive",
+            "minExclusive",
+            "assertions",
+        ];
+        const DECIMAL: &[&str] = &[
+            "totalDigits",
+            "fractionDigits",
+            "pattern",
+            "whiteSpace",
+            "enumeration",
+            "maxInclusive",
+            "maxExclusive",
+            "minInclusive",
+            "minExclusive",
+            "assertions",
+        ];
+        const DATE_TIME: &[&str] = &[
+            "pattern",
+            "enumeration",
+            "whiteSpace",
+            "maxInclusive",
+            "maxExclusive",
+            "minInclusive",
+            "minExclusive",
+            "assertions",
+            "explicitTimezone",
+        ];
+        match t.variety? {
+            Variety::List => Some(LENGTH),
+            Variety::Union => Some(&["pattern", "enumeration", "assertions"]),
+            Variety::Atomic => match self.primitive_name(t)?.as_str() {
+                "string" | "anyURI" | "QName" | "NOTATION" | "hexBinary" | "base64Binary" => {
+                    Some(LENGTH)
+                }
+                "boolean" => Some(&["pattern", "whiteSpace", "assertions"]),
+                "float" | "double" | "duration" => Some(ORDERED),
+                "decimal" => Some(DECIMAL),
+                "dateTime" | "time" | "date" | "gYearMonth" | "gYear" | "gMonthDay" | "gDay"
+                | "gMonth" => Some(DATE_TIME),
+                _ => None,
+            },
+        }
+    }
+
+    fn facet_name(facet: &ConstrainingFacet) -> &'static str {
+        match facet {
+            ConstrainingFacet::Length(_) => "length",
+            ConstrainingFacet::MinLength(_) => "minLength",
+            ConstrainingFacet::MaxLength(_) => "maxLength",
+            ConstrainingFacet::Pattern(_) => "pattern",
+            ConstrainingFacet::Enumeration(_) => "enumeration",
+            ConstrainingFacet::WhiteSpace(_) => "whiteSpace",
+            ConstrainingFacet::MaxInclusive(_) => "maxInclusive",
+            ConstrainingFacet::MaxExclusive(_) => "maxExclusive",
+            ConstrainingFacet::MinExclusive(_) => "minExclusive",
+            ConstrainingFacet::MinInclusive(_) => "minInclusive",
+            ConstrainingFacet::TotalDigits(_) => "totalDigits",
+            ConstrainingFacet::FractionDigits(_) => "fractionDigits",
+            ConstrainingFacet::Assertions(_) => "assertions",
+            ConstrainingFacet::ExplicitTimezone(_) => "explicitTimezone",
+        }
+    }
+
+    /// Returns whether the values of `t` depend on namespace bindings, i.e. `t` is or contains a
+    /// type derived from xs:QName or xs:NOTATION
+    fn is_namespace_sensitive(&self, t: &SimpleTypeDefinition) -> bool {
+        let c = self.components;
+        match t.variety {
+            Some(Variety::Atomic) => {
+                matches!(
+                    self.primitive_name(t).as_deref(),
+                    Some("QName" | "NOTATION")
+                )
+            }
+            Some(Variety::List) => t
+                .item_type_definition
+                .is_some_and(|item| self.is_namespace_sensitive(item.get(c))),
+            Some(Variety::Union) => t
+                .member_type_definitions
+                .iter()
+                .flatten()
+                .any(|member| self.is_namespace_sensitive(member.get(c))),
+            None => false,
+        }
+    }
+
+    /// Maps the value of a min/max facet to the value space of the primitive type of `t`, so
+    /// that e.g. decimal bounds are compared exactly. Values which can't be compared are `None`.
+    fn ordered_value(&self, t: &SimpleTypeDefinition, value: &str) -> Option<Value> {
+        let primitive = Primitive::from_name(&self.primitive_name(t)?)?;
+        // The values of ordered datatypes don't depend on namespace bindings
+        Value::parse(primitive, value.trim(), &Vec::<NamespaceBinding>::new())
+    }
+
+    /// Checks cos-applicable-facets and the facet restriction constraints of pt. 2, §4.3, for the
+    /// facets `simple_type` adds to those of its base type definition `base`
+    fn check_facets(
+        &mut self,
+        simple_type: Ref<SimpleTypeDefinition>,
+        base: Ref<SimpleTypeDefinition>,
+    ) {
+        let c = self.components;
+        let t = simple_type.get(c);
+        let base_type = base.get(c);
+        // The {facets} of a restriction are the facets of its base type definition, overlaid with
+        // the facets specified by the restriction itself.
+        let own_facets = t
+            .facets
+            .iter()
+            .filter(|f| !base_type.facets.iter().any(|b| b == *f))
+            .map(|f| f.get(c))
+            .collect::<Vec<_>>();
+        if own_facets.is_empty() {
+            return;
+        }
+
+        // Schema Component Constraint: Applicable Facets (pt. 2, §4.1.5)
+        //   The constraining facets which are allowed to be members of {facets} are dependent on
+        //   {base type definition} [...]
+        if let Some(applicable) = self.applicable_facets(t) {
+            for facet in &own_facets {
+                let name = Self::facet_name(facet);
+                if !applicable.contains(&name) {
+                    self.report(
+                        &simple_type,
+                        "cos-applicable-facets",
+                        format!("the {name} facet is not applicable to this type"),
+                    );
+                }
+            }
+        }
+
+        // The values of the facets must be as described in their property tableaux (clause 1 of
+        // Simple Type Definition Properties Correct (§3.16.6.1)), and enumeration values must be
+        // in the value space of the base type definition (pt. 2, §4.3.5.5).
+        for facet in &own_facets {
+            match facet {
+                ConstrainingFacet::Pattern(pattern) => {
+                    for regex in &pattern.value {
+                        if let Err(error) = compile_pattern(regex) {
+                            self.report(
+                                &simple_type,
+                                "st-props-correct",
+                                format!("invalid pattern {regex:?}: {error}"),
+                            );
+                        }
+                    }
+                }
+                // The namespace bindings in scope of the facet are not known, so QName and
+                // NOTATION values can't be checked
+                ConstrainingFacet::Enumeration(enumeration)
+                    if !self.is_namespace_sensitive(base_type) =>
+                {
+                    for value in &enumeration.value {
+                        let bindings = Vec::<NamespaceBinding>::new();
+                        if let Err(error) = string_valid(value, base_type, &bindings, c) {
+                            self.report(
+                                &simple_type,
+                                "enumeration-valid-restriction",
+                                format!(
+                                    "the enumeration value {value:?} is not valid for the base type: {error}"
+                                ),
+                            );
+                        }
+                    }
+                }
+                ConstrainingFacet::TotalDigits(total_digits) if total_digits.value == 0 => {
+                    self.report(
+                        &simple_type,
+                        "st-props-correct",
+                        "totalDigits must be a positive integer".into(),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let facets = &t.facets;
+        let base_facets = &base_type.facets;
+        let own = |name: &str| own_facets.iter().any(|f| Self::facet_name(f) == name);
+
+        // {fixed} facets of the base type definition must not be changed
+        for facet in &own_facets {
+            let fixed = match base_facets
+                .iter_resolved(c)
+                .find(|b| b.is_of_same_kind_as(facet))
+            {
+                Some(ConstrainingFacet::Length(b))
+                | Some(ConstrainingFacet::MinLength(b))
+                | Some(ConstrainingFacet::MaxLength(b)) => {
+                    b.fixed && !Self::same_length(facet, b.value)
+                }
+                Some(ConstrainingFacet::WhiteSpace(b)) => {
+                    b.fixed
+                        && !matches!(facet, ConstrainingFacet::WhiteSpace(f) if f.value == b.value)
+                }
+                Some(ConstrainingFacet::MaxInclusive(b))
+                | Some(ConstrainingFacet::MaxExclusive(b))
+                | Some(ConstrainingFacet::MinInclusive(b))
+                | Some(ConstrainingFacet::MinExclusive(b)) => {
+                    b.fixed && !Self::same_min_max(facet, &b.value)
+                }
+                Some(ConstrainingFacet::TotalDigits(b)) => {
+                    b.fixed
+                        && !matches!(facet, ConstrainingFacet::TotalDigits(f) if f.value == b.value)
+                }
+                Some(ConstrainingFacet::FractionDigits(b)) => {
+                    b.fixed
+                        && !matches!(facet, ConstrainingFacet::FractionDigits(f) if f.value == b.value)
+                }
+                Some(ConstrainingFacet::ExplicitTimezone(b)) => {
+                    b.fixed
+                        && !matches!(facet, ConstrainingFacet::ExplicitTimezone(f) if f.value == b.value)
+                }
+                _ => false,
+            };
+            if fixed {
+                let name = Self::facet_name(facet);
+                self.report(
+                    &simple_type,
+                    Self::valid_restriction_constraint(name),
+                    format!("the {name} facet is fixed in the base type definition"),
+                );
+            }
+        }
+
+        // length, minLength, maxLength (pt. 2, §4.3.1.4, §4.3.2.4, §4.3.3.4)
+        let length = facets.length(c).map(|f| f.value);
+        let min_length = facets.min_length(c).map(|f| f.value);
+        let max_length = facets.max_length(c).map(|f| f.value);
+        if let (Some(min), Some(max)) = (min_length, max_length)
+            && min > max
+        {
+            self.report(
+                &simple_type,
+                "minLength-less-than-equal-to-maxLength",
+                format!("minLength {min} is greater than maxLength {max}"),
+            );
+        }
+        if let Some(length) = length
+            && (min_length.is_some_and(|min| length < min)
+                || max_length.is_some_and(|max| length > max))
+        {
+            self.report(
+                &simple_type,
+                "length-minLength-maxLength",
+                format!("length {length} is outside of the range allowed by minLength/maxLength"),
+            );
+        }
+        if own("length")
+            && let (Some(derived), Some(base)) = (length, base_facets.length(c).map(|f| f.value))
+            && derived != base
+        {
+            self.report(
+                &simple_type,
+                "length-valid-restriction",
+                format!("length {derived} differs from length {base} of the base type"),
+            );
+        }
+        if own("minLength")
+            && let (Some(derived), Some(base)) =
+                (min_length, base_facets.min_length(c).map(|f| f.value))
+            && derived < base
+        {
+            self.report(
+                &simple_type,
+                "minLength-valid-restriction",
+                format!("minLength {derived} is less than minLength {base} of the base type"),
+            );
+        }
+        if own("maxLength")
+            && let (Some(derived), Some(base)) =
+                (max_length, base_facets.max_length(c).map(|f| f.value))
+            && derived > base
+        {
+            self.report(
+                &simple_type,
+                "maxLength-valid-restriction",
+                format!("maxLength {derived} is greater than maxLength {base} of the base type"),
+            );
+        }
+
+        // totalDigits, fractionDigits (pt. 2, §4.3.11.4, §4.3.12.4)
+        let total_digits = facets.total_digits(c).map(|f| f.value);
+        let fraction_digits = facets.fraction_digits(c).map(|f| f.value);
+        if let (Some(fraction), Some(total)) = (fraction_digits, total_digits)
+            && fraction > total
+        {
+            self.report(
+                &simple_type,
+                "fractionDigits-totalDigits",
+                format!("fractionDigits {fraction} is greater than totalDigits {total}"),
+            );
+        }
+        if own("totalDigits")
+            && let (Some(derived), Some(base)) =
+                (total_digits, base_facets.total_digits(c).map(|f| f.value))
+            && derived > base
+        {
+            self.report(
+                &simple_type,
+                "totalDigits-valid-restriction",
+                format!(
+                    "totalDigits {derived} is greater than totalDigits {base} of the base type"
+                ),
+            );
+        }
+        if own("fractionDigits")
+            && let (Some(derived), Some(base)) = (
+                fraction_digits,
+                base_facets.fraction_digits(c).map(|f| f.value),
+            )
+            && derived > base
+        {
+            self.report(
+                &simple_type,
+                "fractionDigits-valid-restriction",
+                format!(
+                    "fractionDigits {derived} is greater than fractionDigits {base} of the base type"
+                ),
+            );
+        }
+
+        // whiteSpace (pt. 2, §4.3.6.4)
+        if own("whiteSpace")
+            && let (Some(derived), Some(base)) = (
+                facets.white_space(c).map(|f| f.value),
+                base_facets.white_space(c).map(|f| f.value),
+            )
+        {
+            let valid = match base {
+                WhiteSpaceValue::Collapse => derived == WhiteSpaceValue::Collapse,
+                WhiteSpaceValue::Replace => derived != WhiteSpaceValue::Preserve,
+                WhiteSpaceValue::Preserve => true,
+            };
+            if !valid {
+                self.report(
+                    &simple_type,
+                    "whiteSpace-valid-restriction",
+                    format!("whiteSpace {derived:?} is less strict than {base:?} of the base type"),
+                );
+            }
+        }
+
+        self.check_min_max(simple_type, t, base_facets, &own);
+    }
+
+    fn same_length(facet: &ConstrainingFacet, value: u64) -> bool {
+        matches!(
+            facet,
+            ConstrainingFacet::Length(f)
+            | ConstrainingFacet::MinLength(f)
+            | ConstrainingFacet::MaxLength(f) if f.value == value
+        )
+    }
+
+    fn same_min_max(facet: &ConstrainingFacet, value: &str) -> bool {
+        matches!(
+            facet,
+            ConstrainingFacet::MaxInclusive(f)
+            | ConstrainingFacet::MaxExclusive(f)
+            | ConstrainingFacet::MinInclusive(f)
+            | ConstrainingFacet::MinExclusive(f) if f.value == value
+        )
+    }
+
+    fn valid_restriction_constraint(facet: &str) -> &'static str {
+        match facet {
+            "length" => "length-valid-restriction",
+            "minLength" => "minLength-valid-restriction",
+            "maxLength" => "maxLength-valid-restriction",
+            "whiteSpace" => "whiteSpace-valid-restriction",
+            "maxInclusive" => "maxInclusive-valid-restriction",
+            "maxExclusive" => "maxExclusive-valid-restriction",
+            "minInclusive" => "minInclusive-valid-restriction",
+            "minExclusive" => "minExclusive-valid-restriction",
+            "totalDigits" => "totalDigits-valid-restriction",
+            "fractionDigits" => "fractionDigits-valid-restriction",
+            "explicitTimezone" => "timezone-valid-restriction",
+            _ => "cos-applicable-facets",
+        }
+    }
+
+    /// Checks the constraints on the maxInclusive, maxExclusive, minInclusive and minExclusive
+    /// facets (pt. 2, §4.3.7.4 to §4.3.10.4), as far as their values can be compared
+    fn check_min_max(
+        &mut self,
+        simple_type: Ref<SimpleTypeDefinition>,
+        t: &SimpleTypeDefinition,
+        base_facets: &ConstrainingFacets,
+        own: &dyn Fn(&str) -> bool,
+    ) {
+        let c = self.components;
+        let value = |facet: Option<&crate::constraining_facet::MinMax>| {
+            facet.and_then(|f| self.ordered_value(t, &f.value))
+        };
+        let facets = &t.facets;
+        let max_inclusive = value(facets.max_inclusive(c));
+        let max_exclusive = value(facets.max_exclusive(c));
+        let min_inclusive = value(facets.min_inclusive(c));
+        let min_exclusive = value(facets.min_exclusive(c));
+
+        let mut violations: Vec<(&'static str, bool)> = Vec::new();
+        // It is an error for both maxInclusive and maxExclusive (or minInclusive and
+        // minExclusive) to be specified for the same datatype.
+        violations.push((
+            "maxInclusive-maxExclusive",
+            facets.max_inclusive(c).is_some() && facets.max_exclusive(c).is_some(),
+        ));
+        violations.push((
+            "minInclusive-minExclusive",
+            facets.min_inclusive(c).is_some() && facets.min_exclusive(c).is_some(),
+        ));
+        let lt = |a: &Option<Value>, b: &Option<Value>| {
+            a.as_ref()
+                .zip(b.as_ref())
+                .and_then(|(a, b)| a.compare(b))
+                .is_some_and(Ordering::is_lt)
+        };
+        let le = |a: &Option<Value>, b: &Option<Value>| {
+            a.as_ref()
+                .zip(b.as_ref())
+                .and_then(|(a, b)| a.compare(b))
+                .is_some_and(Ordering::is_le)
+        };
+        violations.push((
+            "minInclusive-less-than-equal-to-maxInclusive",
+            lt(&max_inclusive, &min_inclusive),
+        ));
+        violations.push((
+            "minExclusive-less-than-equal-to-maxExclusive",
+            lt(&max_exclusive, &min_exclusive),
+        ));
+        violations.push((
+            "minExclusive-less-than-maxInclusive",
+            le(&max_inclusive, &min_exclusive),
+        ));
+        violations.push((
+            "minInclusive-less-than-maxExclusive",
+            le(&max_exclusive, &min_inclusive),
+        ));
+
+        // The values must be within the range allowed by the base type definition
+        let base_max_inclusive = value(base_facets.max_inclusive(c));
+        let base_max_exclusive = value(base_facets.max_exclusive(c));
+        let base_min_inclusive = value(base_facets.min_inclusive(c));
+        let base_min_exclusive = value(base_facets.min_exclusive(c));
+        for (name, derived) in [
+            ("maxInclusive", &max_inclusive),
+            ("maxExclusive", &max_exclusive),
+            ("minInclusive", &min_inclusive),
+            ("minExclusive", &min_exclusive),
+        ] {
+            if !own(name) {
+                continue;
+            }
+            let exclusive = name.ends_with("Exclusive");
+            let out_of_range = lt(&base_max_inclusive, derived)
+                || if exclusive {
+                    lt(&base_max_exclusive, derived)
+                } else {
+                    le(&base_max_exclusive, derived)
+                }
+                || lt(derived, &base_min_inclusive)
+                || if exclusive {
+                    lt(derived, &base_min_exclusive)
+                } else {
+                    le(derived, &base_min_exclusive)
+                };
+            violations.push((Self::valid_restriction_constraint(name), out_of_range));
+        }
+
+        for (constraint, violated) in violations {
+            if violated {
+                self.report(
+                    &simple_type,
+                    constraint,
+                    "the bounds of the value space are inconsistent".into(),
+                );
+            }
+        }
+    }
+
+    /// Checks ct-props-correct, and cos-ct-extends or derivation-ok-restriction
+    fn check_complex_type(&mut self, complex_type: Ref<ComplexTypeDefinition>) {
+        let c = self.components;
+        let t = complex_type.get(c);
+
+        // Schema Component Constraint: Complex Type Definition Properties Correct (§3.4.6.1)
+        // 2 If the {base type definition} is a simple type definition, the {derivation method}
+        //   is extension.
+        if matches!(t.base_type_definition, TypeDefinition::Simple(_))
+            && t.derivation_method != Some(DerivationMethod::Extension)
+        {
+            self.report(
+                &complex_type,
+                "ct-props-correct",
+                "a complex type with a simple base type definition must be an extension".into(),
+            );
+        }
+        // 4 No two distinct attribute declarations in the {attribute uses} have identical
+        //   {name}s and {target namespace}s.
+        let mut names = HashMap::new();
+        for attribute_use in &t.attribute_uses {
+            let name = self.attribute_use_name(*attribute_use);
+            if names.insert(name.clone(), ()).is_some() {
+                self.report(
+                    &complex_type,
+                    "ct-props-correct",
+                    format!("there are multiple attribute uses named {name}"),
+                );
+            }
+        }
+
+        // Schema Component Constraint: Unique Particle Attribution (§3.8.6.4)
+        //   A content model must not contain two particles which ·compete· with each other [...]
+        if let Some(particle) = t.content_type.particle() {
+            match create_state_machine(particle.get(c), c) {
+                Ok(state_machine) if !verify_upa_satisfied(&state_machine, c) => self.report(
+                    &complex_type,
+                    "cos-nonambig",
+                    "the content model contains particles which compete with each other".into(),
+                ),
+                Ok(_) => {}
+                // Reported as a violation of cos-all-limited by check_particle
+                Err(_) => {}
+            }
+        }
+
+        if self.is_any_type(TypeDefinition::Complex(complex_type)) {
+            return;
+        }
+        match t.derivation_method {
+            Some(DerivationMethod::Extension) => self.check_extension(complex_type),
+            Some(DerivationMethod::Restriction) => self.check_restriction(complex_type),
+            None => {}
+        }
+    }
+
+    fn attribute_use_name(&self, attribute_use: Ref<AttributeUse>) -> QName {
+        let attribute_use = attribute_use.get(self.components);
+        attribute_use
+            .attribute_declaration
+            .name(self.components)
+            .expect("Attribute declarations are always named")
+    }
+
+    /// Schema Component Constraint: Derivation Valid (Extension) (§3.4.6.2)
+    fn check_extension(&mut self, complex_type: Ref<ComplexTypeDefinition>) {
+        const CONSTRAINT: &str = "cos-ct-extends";
+        let c = self.components;
+        let t = complex_type.get(c);
+
+        match t.base_type_definition {
+            TypeDefinition::Complex(base) => {
+                let b = base.get(c);
+                // 1.1 B.{final} does not contain extension.
+                if b.final_.contains(&DerivationMethod::Extension) {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        "the base type definition is final for extension".into(),
+                    );
+                }
+                // 1.2 Its {attribute uses} is a subset of T.{attribute uses}. That is, for every
+                //   attribute use U in B.{attribute uses}, there is an attribute use in
+                //   T.{attribute uses} whose properties, recursively, are identical to those of U.
+                for base_use in &b.attribute_uses {
+                    let name = self.attribute_use_name(*base_use);
+                    if !t
+                        .attribute_uses
+                        .iter()
+                        .any(|u| self.attribute_use_name(*u) == name)
+                    {
+                        self.report(
+                            &complex_type,
+                            CONSTRAINT,
+                            format!("the attribute use {name} of the base type is missing"),
+                        );
+                    }
+                }
+                // 1.3 If B has an {attribute wildcard}, then T also has one, and B.{attribute
+                //   wildcard}.{namespace constraint} is a subset of T.{attribute
+                //   wildcard}.{namespace constraint}, as defined by Wildcard Subset (§3.10.6.2).
+                if let Some(base_wildcard) = b.attribute_wildcard {
+                    let subset = t
+                        .attribute_wildcard
+                        .is_some_and(|w| self.wildcard_subset(base_wildcard, w));
+                    if !subset {
+                        self.report(
+                            &complex_type,
+                            CONSTRAINT,
+                            "the attribute wildcard must be a superset of that of the base type"
+                                .into(),
+                        );
+                    }
+                }
+                // 1.4 One of the following is true:
+                let valid = match (&b.content_type, &t.content_type) {
+                    // 1.4.1 B and T both have {content type}.{variety} simple, and both have the
+                    //   same {content type}.{simple type definition}.
+                    (
+                        ContentType::Simple {
+                            simple_type_definition: b,
+                        },
+                        ContentType::Simple {
+                            simple_type_definition: t,
+                        },
+                    ) => b == t,
+                    // 1.4.2 B and T both have {content type}.{variety} empty.
+                    (ContentType::Empty, ContentType::Empty) => true,
+                    // 1.4.3.1 B.{content type}.{variety} = empty.
+                    (
+                        ContentType::Empty,
+                        ContentType::ElementOnly { .. } | ContentType::Mixed { .. },
+                    ) => true,
+                    // 1.4.3.2.2.1 Both B and T have {content type}.{variety} mixed or both have
+                    //   {content type}.{variety} element-only.
+                    (ContentType::ElementOnly { .. }, ContentType::ElementOnly { .. })
+                    | (ContentType::Mixed { .. }, ContentType::Mixed { .. }) => true,
+                    // An extension of a type with emptiable content may have any content.
+                    (
+                        ContentType::ElementOnly { particle, .. }
+                        | ContentType::Mixed { particle, .. },
+                        _,
+                    ) => {
+                        particle.get(c).is_emptiable(c)
+                            && !matches!(t.content_type, ContentType::Simple { .. })
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        format!(
+                            "{:?} content cannot be extended to {:?} content",
+                            b.content_type.variety(),
+                            t.content_type.variety()
+                        ),
+                    );
+                }
+            }
+            TypeDefinition::Simple(base) => {
+                // 2 If T.{base type definition} is a simple type definition, then all of the
+                //   following are true:
+                // 2.1 T.{content type}.{variety} = simple and T.{content type}.{simple type
+                //   definition} = T.{base type definition}.
+                let valid = matches!(
+                    t.content_type,
+                    ContentType::Simple { simple_type_definition } if simple_type_definition == base
+                );
+                if !valid {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        "the content type must be the simple base type definition".into(),
+                    );
+                }
+                // 2.2 T.{base type definition}.{final} does not contain extension.
+                if base
+                    .get(c)
+                    .final_
+                    .iter()
+                    .any(|m| matches!(m, simple_type_def::DerivationMethod::Extension))
+                {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        "the base type definition is final for extension".into(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Schema Component Constraint: Derivation Valid (Restriction, Complex) (§3.4.6.3)
+    fn check_restriction(&mut self, complex_type: Ref<ComplexTypeDefinition>) {
+        const CONSTRAINT: &str = "derivation-ok-restriction";
+        let c = self.components;
+        let t = complex_type.get(c);
+        let TypeDefinition::Complex(base) = t.base_type_definition else {
+            // Reported as ct-props-correct
+            return;
+        };
+        let b = base.get(c);
+
+        // 1 B is a complex type definition whose {final} does not contain restriction.
+        if b.final_.contains(&DerivationMethod::Restriction) {
+            self.report(
+                &complex_type,
+                CONSTRAINT,
+                "the base type definition is final for restriction".into(),
+            );
+        }
+
+        // 2 For every attribute use R in T.{attribute uses}, one of the following is true:
+        for &attribute_use in &t.attribute_uses {
+            let r = attribute_use.get(c);
+            let name = self.attribute_use_name(attribute_use);
+            let base_use = b
+                .attribute_uses
+                .iter()
+                .find(|&&u| self.attribute_use_name(u) == name);
+            if let Some(&base_use) = base_use {
+                // 2.1 There is an attribute use B in B.{attribute uses} with the same expanded
+                //   name, and all of the following are true:
+                let base_use = base_use.get(c);
+                // 2.1.1 B.{required} = false or R.{required} = true.
+                if base_use.required && !r.required {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        format!("the attribute {name} is required in the base type"),
+                    );
+                }
+                // 2.1.2 R.{attribute declaration}.{type definition} is validly derived from
+                //   B.{attribute declaration}.{type definition}
+                let r_type = r.attribute_declaration.get(c).type_definition;
+                let b_type = base_use.attribute_declaration.get(c).type_definition;
+                if !self.type_derivation_ok(
+                    TypeDefinition::Simple(r_type),
+                    TypeDefinition::Simple(b_type),
+                    &[],
+                ) {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        format!(
+                            "the type of attribute {name} does not restrict that of the base type"
+                        ),
+                    );
+                }
+                // 2.1.3 Let BVC be B's ·effective value constraint· and RVC be R's ·effective
+                //   value constraint·. One of the following is true: BVC is ·absent· or has
+                //   {variety} default; BVC.{variety} = fixed and RVC.{variety} = fixed and
+                //   RVC.{value} is equal or identical to BVC.{value}.
+                let bvc = base_use.effective_value_constraint(c);
+                let rvc = r.effective_value_constraint(c);
+                if let Some(bvc) = bvc
+                    && bvc.variety == ValueConstraintVariety::Fixed
+                    && !rvc.is_some_and(|rvc| {
+                        rvc.variety == ValueConstraintVariety::Fixed && rvc.value == bvc.value
+                    })
+                {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        format!("the attribute {name} has a fixed value in the base type"),
+                    );
+                }
+            } else {
+                // 2.2 B.{attribute wildcard} is present and allows R's expanded name
+                let allowed = b
+                    .attribute_wildcard
+                    .is_some_and(|w| w.get(self.components).allows_name(&name));
+                if !allowed {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        format!("the attribute {name} is not allowed by the base type"),
+                    );
+                }
+            }
+        }
+
+        // 3 For each attribute use U in B.{attribute uses}, if U.{required} = true, then there
+        //   is an attribute use in T.{attribute uses} with the same expanded name.
+        for &base_use in &b.attribute_uses {
+            let name = self.attribute_use_name(base_use);
+            if base_use.get(c).required
+                && !t
+                    .attribute_uses
+                    .iter()
+                    .any(|&u| self.attribute_use_name(u) == name)
+            {
+                self.report(
+                    &complex_type,
+                    CONSTRAINT,
+                    format!("the required attribute {name} of the base type is missing"),
+                );
+            }
+        }
+
+        // 4 If T has an {attribute wildcard}, then B also has one, T's namespace constraint is a
+        //   subset of B's, and T's {process contents} is not weaker than B's.
+        if let Some(wildcard) = t.attribute_wildcard {
+            let valid = b.attribute_wildcard.is_some_and(|base_wildcard| {
+                self.wildcard_subset(wildcard, base_wildcard)
+                    && Self::process_contents_ok(wildcard, base_wildcard, c)
+            });
+            if !valid {
+                self.report(
+                    &complex_type,
+                    CONSTRAINT,
+                    "the attribute wildcard does not restrict that of the base type".into(),
+                );
+            }
+        }
+
+        // 5 One of the following is true:
+        // 5.1 B is ·xs:anyType·.
+        if self.is_any_type(t.base_type_definition) {
+            return;
+        }
+        let emptiable = |particle: Ref<Particle>| particle.get(c).is_emptiable(c);
+        match (&t.content_type, &b.content_type) {
+            // 5.2 All of the following are true: T.{content type}.{variety} = simple, and B's
+            //   content type is simple with T's simple type definition validly derived from it,
+            //   or B is mixed with an emptiable particle.
+            (
+                ContentType::Simple {
+                    simple_type_definition: st,
+                },
+                ContentType::Simple {
+                    simple_type_definition: sb,
+                },
+            ) => {
+                if !self.type_derivation_ok(
+                    TypeDefinition::Simple(*st),
+                    TypeDefinition::Simple(*sb),
+                    &[],
+                ) {
+                    self.report(
+                        &complex_type,
+                        CONSTRAINT,
+                        "the simple content does not restrict that of the base type".into(),
+                    );
+                }
+            }
+            (ContentType::Simple { .. }, ContentType::Mixed { particle, .. })
+                if emptiable(*particle) => {}
+            // 5.3 T.{content type}.{variety} = empty and B.{content type}.{variety} = empty, or
+            //   B's particle is emptiable.
+            (ContentType::Empty, ContentType::Empty) => {}
+            (
+                ContentType::Empty,
+                ContentType::ElementOnly { particle, .. } | ContentType::Mixed { particle, .. },
+            ) if emptiable(*particle) => {}
+            // 5.4 T.{content type}.{variety} = element-only and B.{content type}.{variety} =
+            //   element-only or mixed, or both are mixed, and T's content type ·restricts·
+            //   B's content type as defined in Content type restricts (Complex Content)
+            //   (§3.4.6.4).
+            (
+                ContentType::ElementOnly { particle: pt, .. },
+                ContentType::ElementOnly { particle: pb, .. }
+                | ContentType::Mixed { particle: pb, .. },
+            )
+            | (ContentType::Mixed { particle: pt, .. }, ContentType::Mixed { particle: pb, .. }) => {
+                if let Err(failure) = self.content_type_restricts(pt.get(c), pb.get(c)) {
+                    self.report_failure(&complex_type, failure);
+                }
+            }
+            (t, b) => self.report(
+                &complex_type,
+                CONSTRAINT,
+                format!(
+                    "{:?} content does not restrict {:?} content of the base type",
+                    t.variety(),
+                    b.variety()
+                ),
+            ),
+        }
+    }
+
+    // ===== Wildcards =====
+
+    /// Schema Component Constraint: Wildcard Subset (§3.10.6.2), for the namespace constraints
+    /// of `sub` and `sup`
+    fn wildcard_subset(&self, sub: Ref<Wildcard>, sup: Ref<Wildcard>) -> bool {
+        let sub = &sub.get(self.components).namespace_constraint;
+        let sup = &sup.get(self.components).namespace_constraint;
+        use NamespaceConstraintVariety::{Any, Enumeration, Not};
+        match (&sub.variety, &sup.variety) {
+            // 1 super.{variety} = any.
+            (_, Any) => true,
+            // 2 Both sub and super have {variety} = enumeration, and super.{namespaces} is a
+            //   superset of sub.{namespaces}.
+            (Enumeration, Enumeration) => sub.namespaces.iter().all(|n| sup.namespaces.contains(n)),
+            // 3 sub.{variety} = enumeration, super.{variety} = not, and the {namespaces} of the
+            //   two are disjoint.
+            (Enumeration, Not) => !sub.namespaces.iter().any(|n| sup.namespaces.contains(n)),
+            // 4 Both sub and super have {variety} = not, and super.{namespaces} is a subset of
+            //   sub.{namespaces}.
+            (Not, Not) => sup.namespaces.iter().all(|n| sub.namespaces.contains(n)),
+            _ => false,
+        }
+    }
+
+    /// Whether the {process contents} of `r` is at least as strong as that of `b`
+    fn process_contents_ok(
+        r: Ref<Wildcard>,
+        b: Ref<Wildcard>,
+        components: &SchemaComponentTable,
+    ) -> bool {
+        match b.get(components).process_contents {
+            ProcessContents::Skip => true,
+            ProcessContents::Lax => {
+                !matches!(r.get(components).process_contents, ProcessContents::Skip)
+            }
+            ProcessContents::Strict => {
+                matches!(r.get(components).process_contents, ProcessContents::Strict)
+            }
+        }
+    }
+
+    // ===== Element declarations =====
+
+    /// Schema Component Constraint: Element Declaration Properties Correct (§3.3.6.1)
+    fn check_element_declaration(&mut self, element_decl: Ref<ElementDeclaration>) {
+        const CONSTRAINT: &str = "e-props-correct";
+        let c = self.components;
+        let e = element_decl.get(c);
+
+        // 2 If E has a {value constraint}, then E.{value constraint} is a valid default with
+        //   respect to E.{type definition} as defined in Element Default Valid (Immediate)
+        //   (§3.3.6.2).
+        // The {lexical form} is checked against the effective simple type definition when its
+        // {value} is resolved, see ValueConstraint::resolve_values().
+        if e.value_constraint.is_some()
+            && let TypeDefinition::Complex(t) = e.type_definition
+        {
+            // Element Default Valid (Immediate) 2.1 If T is a complex type definition, its
+            //   {content type} is simple, or mixed with an emptiable particle.
+            let valid = match &t.get(c).content_type {
+                ContentType::Simple { .. } => true,
+                ContentType::Mixed { particle, .. } => particle.get(c).is_emptiable(c),
+                _ => false,
+            };
+            if !valid {
+                self.report(
+                    &element_decl,
+                    CONSTRAINT,
+                    "a value constraint requires simple or emptiable mixed content".into(),
+                );
+            }
+        }
+
+        // 3 If E.{substitution group affiliations} is non-empty, then for each member H of
+        //   E.{substitution group affiliations}, E.{type definition} is validly derived from
+        //   H.{type definition}, given the blocking constraint H.{substitution group exclusions}.
+        for head in &e.substitution_group_affiliations {
+            let h = head.get(c);
+            if !self.type_derivation_ok(
+                e.type_definition,
+                h.type_definition,
+                &h.substitution_group_exclusions,
+            ) {
+                self.report(
+                    &element_decl,
+                    CONSTRAINT,
+                    format!(
+                        "the type definition is not validly derived from that of the substitution \
+                         group head {}",
+                        Self::type_name(head.name(c))
+                    ),
+                );
+            }
+        }
+
+        // 4 There is no circularity in the ·substitution group· of E.
+        let mut pending = e.substitution_group_affiliations.clone();
+        let mut seen = Vec::new();
+        while let Some(head) = pending.pop() {
+            if head == element_decl {
+                self.report(
+                    &element_decl,
+                    CONSTRAINT,
+                    "the substitution group affiliations are circular".into(),
+                );
+                break;
+            }
+            if !seen.contains(&head) {
+                seen.push(head);
+                pending.extend(head.get(c).substitution_group_affiliations.iter().copied());
+            }
+        }
+
+        // 5 If E has a {type table}, then for each Type Alternative A in E.{type
+        //   table}.{alternatives}, and also for E.{type table}.{default type definition}, one of
+        //   the following is true:
+        //   5.1 A.{type definition} is ·xs:error·.
+        //   5.2 A.{type definition} is validly substitutable for E.{type definition}, subject to
+        //     the blocking keywords of E.{disallowed substitutions}.
+        if let Some(type_table) = &e.type_table {
+            let blocking = e
+                .disallowed_substitutions
+                .iter()
+                .filter_map(|s| match s {
+                    SubstitutionMethod::Extension => Some(DerivationMethod::Extension),
+                    SubstitutionMethod::Restriction => Some(DerivationMethod::Restriction),
+                    SubstitutionMethod::Substitution => None,
+                })
+                .collect::<Vec<_>>();
+            for alternative in type_table
+                .alternatives
+                .iter()
+                .chain([&type_table.default_type_definition])
+            {
+                let t = alternative.get(c).type_definition;
+                let is_xs_error = t.is_builtin(c) && t.name(c) == Some(XS_ERROR_NAME);
+                if !is_xs_error && !self.type_derivation_ok(t, e.type_definition, &blocking) {
+                    self.report(
+                        &element_decl,
+                        CONSTRAINT,
+                        format!(
+                            "the type alternative {} is not validly substitutable for the \
+                             declared type definition",
+                            Self::type_name(t.name(c))
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    // ===== Particles =====
+
+    /// Checks p-props-correct, cos-all-limited and cos-element-consistent for the model group of
+    /// `particle`
+    fn check_particle(&mut self, particle: &Particle) {
+        let c = self.components;
+
+        // Schema Component Constraint: Particle Correct (§3.9.6.1)
+        // 2 If {max occurs} is not unbounded, [...] then it is greater than or equal to {min
+        //   occurs}.
+        if particle.max_occurs < MaxOccurs::Count(particle.min_occurs) {
+            self.report(
+                particle,
+                "p-props-correct",
+                format!(
+                    "maxOccurs {:?} is less than minOccurs {}",
+                    particle.max_occurs, particle.min_occurs
+                ),
+            );
+        }
+
+        let Term::ModelGroup(group) = particle.term else {
+            return;
+        };
+        let group = group.get(c);
+
+        // Schema Component Constraint: All Group Limited (§3.8.6.2)
+        //   When a model group has {compositor} all, then [...] 1.2 [it is] the {term} property
+        //   of a particle with {max occurs} = 1 which is part of a pair which constitutes the
+        //   {content type} of a complex type definition, or 2 it appears as a particle in the
+        //   {particles} of another all model group.
+        if group.compositor == Compositor::All && particle.max_occurs != MaxOccurs::Count(1) {
+            self.report(
+                particle,
+                "cos-all-limited",
+                "an all group must have maxOccurs 1".into(),
+            );
+        }
+        for child in &group.particles {
+            let Some(child_group) = child.get(c).term.model_group() else {
+                continue;
+            };
+            let child_compositor = child_group.get(c).compositor;
+            if group.compositor != Compositor::All && child_compositor == Compositor::All {
+                self.report(
+                    particle,
+                    "cos-all-limited",
+                    "an all group must not appear within a sequence or choice".into(),
+                );
+            }
+            if group.compositor == Compositor::All && child_compositor != Compositor::All {
+                self.report(
+                    particle,
+                    "cos-all-limited",
+                    "a sequence or choice must not appear within an all group".into(),
+                );
+            }
+        }
+
+        // Schema Component Constraint: Element Declarations Consistent (§3.8.6.3)
+        //   If the {particles} property contains, either directly, indirectly (that is, within
+        //   the {particles} property of a contained model group, recursively), [...] two or more
+        //   element declarations with the same expanded name, then all their type definitions
+        //   must be the same top-level definition.
+        let mut declarations = HashMap::<QName, TypeDefinition>::new();
+        let mut pending = group.particles.clone();
+        while let Some(child) = pending.pop() {
+            match child.get(c).term {
+                Term::ElementDeclaration(e) => {
+                    let e_decl = e.get(c);
+                    let name = e.name(c).expect("Element declarations are always named");
+                    match declarations.get(&name) {
+                        Some(&t) if !Self::same_type(t, e_decl.type_definition) => {
+                            self.report(
+                                particle,
+                                "cos-element-consistent",
+                                format!(
+                                    "the element declarations named {name} have different types"
+                                ),
+                            );
+                        }
+                        Some(_) => {}
+                        None => {
+                            declarations.insert(name, e_decl.type_definition);
+                        }
+                    }
+                }
+                Term::ModelGroup(g) => pending.extend(g.get(c).particles.iter().copied()),
+                Term::Wildcard(_) => {}
+            }
+        }
+    }
+
+    /// Schema Component Constraint: Content type restricts (Complex Content) (§3.4.6.4), for the
+    /// particles `r` and `b` of the content types. Both content models are run in parallel on
+    /// their state machines, i.e. every state pair reachable by a sequence of elements accepted
+    /// so far by R is visited once.
+    // NOTE: Open content is not taken into account.
+    fn content_type_restricts(&self, r: &Particle, b: &Particle) -> Result<(), Failure> {
+        const CONSTRAINT: &str = "cos-content-act-restrict";
+        let c = self.components;
+        // Content models which can't be turned into a state machine violate cos-all-limited or
+        // cos-nonambig, which is reported for them
+        let (Ok(r_machine), Ok(b_machine)) =
+            (create_state_machine(r, c), create_state_machine(b, c))
+        else {
+            return Ok(());
+        };
+        let start = (r_machine.start_state(), b_machine.start_state());
+        let mut visited = HashSet::from([start.clone()]);
+        let mut pending = vec![start];
+        while let Some((r_state, b_state)) = pending.pop() {
+            // 1 Every sequence of element information items which is ·locally valid· with
+            //   respect to R is also ·locally valid· with respect to B.
+            if r_machine.is_end_state(&r_state) && !b_machine.is_end_state(&b_state) {
+                return Err((
+                    CONSTRAINT,
+                    "a sequence of elements accepted by the content model is rejected by the \
+                     base content model"
+                        .into(),
+                ));
+            }
+            let r_transitions = r_machine.transitions(&r_state);
+            for r_label in &r_transitions {
+                let mut b_next = b_state.clone();
+                match *r_label {
+                    Transition::ElementDeclaration(re) => {
+                        let name = re.name(c).expect("Element declarations are named");
+                        let mut be = None;
+                        b_machine
+                            .step(&mut b_next, |b_label| match *b_label {
+                                Transition::ElementDeclaration(label) => {
+                                    be = self.base_declaration(label, &name);
+                                    be.is_some()
+                                }
+                                Transition::Wildcard(w) => w.get(c).allows_name(&name),
+                            })
+                            .ok_or_else(|| {
+                                (
+                                    CONSTRAINT,
+                                    format!(
+                                        "the element {name} is not allowed by the base content \
+                                         model at this point"
+                                    ),
+                                )
+                            })?;
+                        // 2 An element ·attributed· to an element declaration of R is
+                        //   attributed to a declaration of B which that one restricts, or to a
+                        //   wildcard of B.
+                        if let Some(be) = be {
+                            self.element_restricts(re, be)?;
+                        }
+                    }
+                    Transition::Wildcard(rw) => {
+                        // An element allowed by the wildcard of R must not be ·attributed· to an
+                        // element declaration of B, unless R attributes it to one of its own
+                        // element declarations, which takes precedence over the wildcard.
+                        for b_label in b_machine.transitions(&b_state) {
+                            if let Transition::ElementDeclaration(be) = b_label
+                                && let Some(name) = be.name(c)
+                                && rw.get(c).allows_name(&name)
+                                && !r_transitions.iter().any(|t| {
+                                    matches!(t, Transition::ElementDeclaration(re)
+                                        if re.name(c).as_ref() == Some(&name))
+                                })
+                            {
+                                return Err((
+                                    CONSTRAINT,
+                                    format!(
+                                        "the wildcard allows the element {name}, which is \
+                                         declared in the base content model"
+                                    ),
+                                ));
+                            }
+                        }
+                        // Schema Component Constraint: Wildcard Subset (§3.10.6.2), and the
+                        // {process contents} of R's wildcard is at least as strong
+                        let Some(Transition::Wildcard(bw)) =
+                            b_machine.step(&mut b_next, |b_label| {
+                                matches!(*b_label, Transition::Wildcard(bw)
+                                    if self.wildcard_subset(rw, bw))
+                            })
+                        else {
+                            return Err((
+                                CONSTRAINT,
+                                "the wildcard does not restrict a wildcard of the base content \
+                                 model at this point"
+                                    .into(),
+                            ));
+                        };
+                        if !Self::process_contents_ok(rw, bw, c) {
+                            return Err((
+                                CONSTRAINT,
+                                "the wildcard's processContents is weaker than the base \
+                                 wildcard's"
+                                    .into(),
+                            ));
+                        }
+                    }
+                }
+                let mut r_next = r_state.clone();
+                r_machine.step(&mut r_next, |t| t == r_label);
+                r_machine.canonicalize(&mut r_next);
+                b_machine.canonicalize(&mut b_next);
+                let next = (r_next, b_next);
+                if visited.insert(next.clone()) {
+                    pending.push(next);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the declaration of B which an element named `name` is ·attributed· to if it is
+    /// matched by the element declaration `label` of B: `label` itself, or the ·substituting
+    /// declaration· from its substitution group
+    fn base_declaration(
+        &self,
+        label: Ref<ElementDeclaration>,
+        name: &QName,
+    ) -> Option<Ref<ElementDeclaration>> {
+        let c = self.components;
+        if label.name(c).as_ref() == Some(name) {
+            Some(label)
+        } else if label.get(c).scope.variety() == ScopeVariety::Global
+            && !label
+                .get(c)
+                .disallowed_substitutions
+                .contains(&SubstitutionMethod::Substitution)
+        {
+            ElementDeclaration::substitution_group(label, c)
+                .into_iter()
+                .find(|s| s.name(c).as_ref() == Some(name))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the element declaration `re` of R restricts the declaration `be` of B with the
+    /// same name, see Content type restricts (Complex Content) (§3.4.6.4), clause 2
+    fn element_restricts(
+        &self,
+        re: Ref<ElementDeclaration>,
+        be: Ref<ElementDeclaration>,
+    ) -> Result<(), Failure> {
+        const CONSTRAINT: &str = "cos-content-act-restrict";
+        if re == be {
+            return Ok(());
+        }
+        let (rd, bd) = (re.get(self.components), be.get(self.components));
+        // B's declaration's {nillable} is true, or R's declaration's {nillable} is false.
+        if !bd.nillable && rd.nillable {
+            return Err((
+                CONSTRAINT,
+                format!("the element {} must not be nillable", rd.name),
+            ));
+        }
+        // B's declaration's {value constraint} is absent, or is not fixed, or R's
+        // declaration's {value constraint} is fixed with the same value.
+        if let Some(bvc) = &bd.value_constraint
+            && bvc.variety == ValueConstraintVariety::Fixed
+            && !rd.value_constraint.as_ref().is_some_and(|rvc| {
+                rvc.variety == ValueConstraintVariety::Fixed && rvc.value == bvc.value
+            })
+        {
+            return Err((
+                CONSTRAINT,
+                format!("the element {} has a fixed value in the base", rd.name),
+            ));
+        }
+        // R's declaration's {identity-constraint definitions} is a superset of B's
+        // declaration's {identity-constraint definitions}.
+        if !bd
+            .identity_constraint_definitions
+            .iter()
+            .all(|i| rd.identity_constraint_definitions.contains(i))
+        {
+            return Err((
+                CONSTRAINT,
+                format!(
+                    "the element {} lacks identity constraints of the base",
+                    rd.name
+                ),
+            ));
+        }
+        // R's declaration's {disallowed substitutions} is a superset of B's declaration's
+        // {disallowed substitutions}.
+        if !bd
+            .disallowed_substitutions
+            .iter()
+            .all(|s| rd.disallowed_substitutions.contains(s))
+        {
+            return Err((
+                CONSTRAINT,
+                format!(
+                    "the element {} blocks fewer substitutions than in the base",
+                    rd.name
+                ),
+            ));
+        }
+        // R's {type definition} is validly derived given {extension, list, union} from B's
+        // {type definition}
+        if !self.type_derivation_ok(
+            rd.type_definition,
+            bd.type_definition,
+            &[DerivationMethod::Extension],
+        ) {
+            return Err((
+                CONSTRAINT,
+                format!("the type of element {} does not restrict the base", rd.name),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn violated_constraints(schema: &str) -> Vec<&'static str> {
+        let (schema, components) = crate::testing::read_schema(schema).unwrap();
+        let mut constraints = super::check_schema(&schema, &components)
+            .into_iter()
+            .map(|v| v.constraint)
+            .collect::<Vec<_>>();
+        constraints.sort();
+        constraints
+    }
+
+    #[test]
+    fn valid_derivations() {
+        let constraints = violated_constraints(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:simpleType name="short">
+                <xs:restriction base="xs:string">
+                  <xs:minLength value="1"/>
+                  <xs:maxLength value="10"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="shorter">
+                <xs:restriction base="short">
+                  <xs:maxLength value="5"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:complexType name="base">
+                <xs:sequence>
+                  <xs:element name="a" type="xs:string"/>
+                  <xs:element name="b" type="xs:string" minOccurs="0" maxOccurs="unbounded"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:complexType name="restricted">
+                <xs:complexContent>
+                  <xs:restriction base="base">
+                    <xs:sequence>
+                      <xs:element name="a" type="shorter"/>
+                      <xs:element name="b" type="xs:string" maxOccurs="2"/>
+                    </xs:sequence>
+                  </xs:restriction>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:complexType name="optional">
+                <xs:sequence>
+                  <xs:element name="x" type="xs:string" minOccurs="0"/>
+                  <xs:element name="y" type="xs:string" minOccurs="0"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:complexType name="either">
+                <xs:complexContent>
+                  <xs:restriction base="optional">
+                    <xs:choice>
+                      <xs:element name="x" type="xs:string"/>
+                      <xs:element name="y" type="xs:string"/>
+                    </xs:choice>
+                  </xs:restriction>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:complexType name="extended">
+                <xs:complexContent>
+                  <xs:extension base="base">
+                    <xs:sequence>
+                      <xs:element name="c" type="xs:int"/>
+                    </xs:sequence>
+                  </xs:extension>
+                </xs:complexContent>
+              </xs:complexType>
+            </xs:schema>
+            "#,
+        );
+        assert_eq!(constraints, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn invalid_derivations() {
+        let constraints = violated_constraints(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:simpleType name="short">
+                <xs:restriction base="xs:string">
+                  <xs:maxLength value="10"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="longer">
+                <xs:restriction base="short">
+                  <xs:minLength value="20"/>
+                  <xs:maxLength value="15"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:complexType name="base">
+                <xs:sequence>
+                  <xs:element name="a" type="xs:string"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:complexType name="restricted">
+                <xs:complexContent>
+                  <xs:restriction base="base">
+                    <xs:sequence>
+                      <xs:element name="a" type="xs:string" maxOccurs="2"/>
+                    </xs:sequence>
+                  </xs:restriction>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:simpleType name="small">
+                <xs:restriction base="xs:decimal">
+                  <xs:maxInclusive value="1.00000000000000001"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="notSmaller">
+                <xs:restriction base="small">
+                  <xs:maxInclusive value="1.00000000000000002"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:complexType name="inconsistent">
+                <xs:sequence>
+                  <xs:element name="a" type="xs:string"/>
+                  <xs:element name="a" type="xs:int"/>
+                </xs:sequence>
+              </xs:complexType>
+            </xs:schema>
+            "#,
+        );
+        assert_eq!(
+            constraints,
+            [
+                "cos-content-act-restrict",
+                "cos-element-consistent",
+                "maxInclusive-valid-restriction",
+                "maxLength-valid-restriction",
+                "minLength-less-than-equal-to-maxLength",
+            ]
+        );
+    }
+
+    #[test]
+    fn ambiguous_content_models() {
+        let constraints = violated_constraints(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:group name="sequence">
+                <xs:sequence>
+                  <xs:element name="c"/>
+                </xs:sequence>
+              </xs:group>
+              <xs:complexType name="deterministic">
+                <xs:sequence>
+                  <xs:element name="a" minOccurs="0"/>
+                  <xs:element name="b"/>
+                  <xs:element name="a"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:complexType name="ambiguous">
+                <xs:choice>
+                  <xs:element name="a"/>
+                  <xs:sequence>
+                    <xs:element name="a"/>
+                    <xs:element name="b"/>
+                  </xs:sequence>
+                </xs:choice>
+              </xs:complexType>
+              <xs:complexType name="sequenceInAll">
+                <xs:all>
+                  <xs:element name="a"/>
+                  <xs:group ref="sequence"/>
+                </xs:all>
+              </xs:complexType>
+            </xs:schema>
+            "#,
+        );
+        assert_eq!(constraints, ["cos-all-limited", "cos-nonambig"]);
+    }
+
+    #[test]
+    fn invalid_facet_values() {
+        let violated = |simple_type: &str| {
+            violated_constraints(&format!(
+                r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">{simple_type}</xs:schema>"#
+            ))
+        };
+        assert_eq!(
+            violated(
+                r#"<xs:simpleType name="t">
+                  <xs:restriction base="xs:string"><xs:pattern value="[a-"/></xs:restriction>
+                </xs:simpleType>"#
+            ),
+            ["st-props-correct"]
+        );
+        assert_eq!(
+            violated(
+                r#"<xs:simpleType name="t">
+                  <xs:restriction base="xs:int">
+                    <xs:enumeration value="1"/>
+                    <xs:enumeration value="x"/>
+                  </xs:restriction>
+                </xs:simpleType>"#
+            ),
+            ["enumeration-valid-restriction"]
+        );
+        assert_eq!(
+            violated(
+                r#"<xs:simpleType name="t">
+                  <xs:restriction base="xs:decimal"><xs:totalDigits value="0"/></xs:restriction>
+                </xs:simpleType>"#
+            ),
+            ["st-props-correct"]
+        );
+        assert_eq!(
+            violated(r#"<xs:simpleType name="t"><xs:union/></xs:simpleType>"#),
+            ["src-union-memberTypes-or-simpleTypes"]
+        );
+        // The prefixes of QName enumeration values can't be resolved
+        assert_eq!(
+            violated(
+                r#"<xs:simpleType name="t">
+                  <xs:restriction base="xs:QName">
+                    <xs:enumeration value="a"/>
+                    <xs:enumeration value="xs:b"/>
+                  </xs:restriction>
+                </xs:simpleType>"#
+            ),
+            Vec::<&str>::new()
+        );
+    }
+}
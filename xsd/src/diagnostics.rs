@@ -0,0 +1,100 @@
+use std::fmt;
+
+use crate::{
+    Schema, SchemaComponentTable,
+    error::XsdError,
+    import::{Import, ImportError},
+    include::{Include, IncludeError},
+    xstypes::QName,
+};
+
+/// A noteworthy condition encountered while reading a schema, which (unlike an [`XsdError`]) does
+/// not make the schema invalid.
+#[derive(Debug)]
+pub enum Warning {
+    /// An import resolver failed to load the schema referenced by an `<import>`
+    ImportResolution { import: Import, error: ImportError },
+    /// No import resolver could load the schema referenced by an `<import>`, so it was skipped
+    UnresolvedImport(Import),
+    /// An include resolver failed to load the schema document referenced by an `<include>`,
+    /// `<redefine>` or `<override>`
+    IncludeResolution {
+        include: Include,
+        error: IncludeError,
+    },
+    /// A built-in component was overwritten by a schema component with the same name (see
+    /// [`BuiltinOverwriteAction::Warn`](crate::BuiltinOverwriteAction::Warn))
+    BuiltinOverwrite(QName),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ImportResolution { import, error } => {
+                write!(f, "Error during import resolution of ")?;
+                fmt_import(f, import)?;
+                write!(f, ": {error}")
+            }
+            Self::UnresolvedImport(import) => {
+                write!(f, "Failed to resolve import of ")?;
+                fmt_import(f, import)
+            }
+            Self::IncludeResolution { include, error } => write!(
+                f,
+                "Error during include resolution of {}: {error}",
+                include.schema_location
+            ),
+            Self::BuiltinOverwrite(name) => write!(f, "Overwriting built-in component {name}"),
+        }
+    }
+}
+
+/// Receives the [`Warning`]s emitted while reading a schema, see [`RootContext::new()`]
+///
+/// [`RootContext::new()`]: crate::RootContext::new
+pub trait DiagnosticSink {
+    fn warning(&mut self, warning: Warning);
+}
+
+/// Collects the warnings
+impl DiagnosticSink for Vec<Warning> {
+    fn warning(&mut self, warning: Warning) {
+        self.push(warning);
+    }
+}
+
+/// Discards the warnings
+impl DiagnosticSink for () {
+    fn warning(&mut self, _warning: Warning) {}
+}
+
+fn fmt_import(f: &mut fmt::Formatter<'_>, import: &Import) -> fmt::Result {
+    match &import.namespace {
+        Some(namespace) => write!(f, "namespace {namespace}")?,
+        None => write!(f, "no namespace")?,
+    }
+    if let Some(schema_location) = &import.schema_location {
+        write!(f, " (schemaLocation {schema_location})")?;
+    }
+    Ok(())
+}
+
+/// The result of [`read_schema_with_diagnostics()`](crate::read_schema_with_diagnostics)
+pub struct Diagnostics {
+    /// The schema and its components. This is `None` if an error occurred which could not be
+    /// recovered from. Otherwise, if there are [`errors`](Self::errors), the schema is partial:
+    /// the offending parts are replaced by fallbacks (e.g. `xs:anyType` for an unresolved type
+    /// definition reference).
+    pub schema: Option<(Schema, SchemaComponentTable)>,
+    /// All errors encountered, in the order they were found (which is not necessarily document
+    /// order). The last one is fatal if [`schema`](Self::schema) is `None`.
+    pub errors: Vec<XsdError>,
+    pub warnings: Vec<Warning>,
+}
+
+impl Diagnostics {
+    /// Returns whether the schema was read without any errors
+    pub fn is_valid(&self) -> bool {
+        self.schema.is_some() && self.errors.is_empty()
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
-    MappingContext, Particle, Ref, SimpleTypeDefinition, Term,
+    MappingContext, Particle, Ref, SchemaComponentTable, SimpleTypeDefinition, Term,
     annotation::Annotation,
     builtins::{XS_ANY_TYPE_NAME, XS_STRING_NAME},
-    complex_type_def::{self, ComplexTypeDefinition, ContentType},
-    components::{Component, Named, NamedXml},
+    complex_type_def::{self, ComplexTypeDefinition, ContentType, DerivationMethod},
+    components::{Component, IsBuiltinRef, Named, NamedXml, RefNamed},
+    constraints::{type_derivation_ok, validly_substitutable},
     error::XsdError,
     identity_constraint_def::IdentityConstraintDefinition,
     mapping_context::TopLevelMappable,
@@ -45,12 +46,15 @@ pub enum SubstitutionMethod {
 }
 
 impl ActualValue<'_> for SubstitutionMethod {
-    fn convert(src: &str, _parent: Node) -> Self {
+    fn convert(src: &str, _parent: Node) -> Result<Self, XsdError> {
         match src {
-            "substitution" => Self::Substitution,
-            "extension" => Self::Extension,
-            "restriction" => Self::Restriction,
-            _ => panic!("Invalid value for substitution method"),
+            "substitution" => Ok(Self::Substitution),
+            "extension" => Ok(Self::Extension),
+            "restriction" => Ok(Self::Restriction),
+            _ => Err(XsdError::InvalidAttributeValue {
+                value: src.to_string(),
+                expected: "substitution method",
+            }),
         }
     }
 }
@@ -78,27 +82,37 @@ pub enum ScopeParent {
 pub type ValueConstraint = shared::ValueConstraint;
 
 impl NamedXml for ElementDeclaration {
-    fn get_name_from_xml(element: Node, schema: Node) -> QName {
+    fn get_name_from_xml(
+        context: &MappingContext,
+        element: Node,
+        schema: Node,
+    ) -> Result<QName, XsdError> {
         // {name} The ·actual value· of the name [attribute].
         let name = element
             .attribute("name")
-            .map(|v| actual_value::<String>(v, element))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(element, "name"))
+            .and_then(|v| actual_value::<String>(v, element))?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the parent <schema> element
         //   information item, or ·absent· if there is none.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<String>(v, element));
+        let target_namespace = context.target_namespace(schema)?;
 
-        QName::with_optional_namespace(target_namespace, name)
+        Ok(QName::with_optional_namespace(target_namespace, name))
     }
 }
 
 impl ElementDeclaration {
     pub const TAG_NAME: &'static str = "element";
 
+    /// Resolves ·xs:anyType·, the default type definition, which also replaces unresolved type
+    /// definitions when recovering from errors
+    fn any_type(context: &mut MappingContext) -> Result<TypeDefinition, XsdError> {
+        context
+            .resolve(&XS_ANY_TYPE_NAME)
+            .ok_or(XsdError::UnresolvedReference(XS_ANY_TYPE_NAME))
+    }
+
     fn map_from_xml_common(
         context: &mut MappingContext,
         self_ref: Ref<Self>,
@@ -111,8 +125,8 @@ impl ElementDeclaration {
         // {name} The ·actual value· of the name [attribute].
         let name = element
             .attribute("name")
-            .map(|v| actual_value::<String>(v, element))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(element, "name"))
+            .and_then(|v| actual_value::<String>(v, element))?;
 
         // {type definition}
         //   The first of the following that applies:
@@ -125,56 +139,54 @@ impl ElementDeclaration {
         //     ·resolved· to by the first QName in the ·actual value· of the
         //     substitutionGroup [attribute], if present.
         //   4 ·xs:anyType·.
-        let type_definition = element
+        let substitution_group_head = element
+            .attribute("substitutionGroup")
+            .map(|v| context.qnames(v, element))
+            .transpose()?
+            .and_then(|v| v.first().cloned());
+        let type_definition = if let Some(simple_type) = element
             .children()
             .find(|c| c.tag_name().name() == "simpleType")
-            .map(|simple_type| {
-                let simple_type_def = SimpleTypeDefinition::map_from_xml(
-                    context,
-                    simple_type,
-                    schema,
-                    None,
-                    Some(SimpleContext::Element(self_ref)),
-                )
-                .unwrap(); // TODO
-                TypeDefinition::Simple(simple_type_def)
-            })
-            .or_else(|| {
-                element
-                    .children()
-                    .find(|c| c.tag_name().name() == "complexType")
-                    .map(|complex_type| {
-                        let complex_type_def = ComplexTypeDefinition::map_from_xml(
-                            context,
-                            complex_type,
-                            schema,
-                            Some(self_ref),
-                            None,
-                        )
-                        .unwrap(); // TODO
-                        TypeDefinition::Complex(complex_type_def)
-                    })
-            })
-            .or_else(|| {
-                element
-                    .attribute("type")
-                    .map(|type_| actual_value::<QName>(type_, element))
-                    .map(|type_| context.resolve(&type_).unwrap()) // TODO
-            })
-            .or_else(|| {
-                element
-                    .attribute("substitutionGroup")
-                    .map(|v| actual_value::<Vec<QName>>(v, element))
-                    .and_then(|v| v.first().cloned())
-                    .map(|name| context.resolve::<Ref<ElementDeclaration>>(&name).unwrap()) // TODO
-                    .map(|element_decl| {
-                        context
-                            .request(element_decl)
-                            .unwrap() // TODO
-                            .type_definition
-                    })
-            })
-            .unwrap_or_else(|| context.resolve(&XS_ANY_TYPE_NAME).unwrap()); // TODO
+        {
+            let simple_type_def = SimpleTypeDefinition::map_from_xml(
+                context,
+                simple_type,
+                schema,
+                None,
+                Some(SimpleContext::Element(self_ref)),
+            )
+            .map_err(|e| context.locate(e, simple_type, SimpleTypeDefinition::DISPLAY_NAME))?;
+            TypeDefinition::Simple(simple_type_def)
+        } else if let Some(complex_type) = element
+            .children()
+            .find(|c| c.tag_name().name() == "complexType")
+        {
+            let complex_type_def = ComplexTypeDefinition::map_from_xml(
+                context,
+                complex_type,
+                schema,
+                Some(self_ref),
+                None,
+            )
+            .map_err(|e| context.locate(e, complex_type, ComplexTypeDefinition::DISPLAY_NAME))?;
+            TypeDefinition::Complex(complex_type_def)
+        } else if let Some(type_) = element.attribute("type") {
+            let type_ = context.qname(type_, element)?;
+            let result = context
+                .resolve(&type_)
+                .ok_or(XsdError::UnresolvedTypeReference(type_));
+            context.recover(result, element, Self::DISPLAY_NAME, Self::any_type)?
+        } else if let Some(name) = substitution_group_head {
+            let result = context
+                .resolve::<Ref<ElementDeclaration>>(&name)
+                .ok_or(XsdError::UnresolvedElementReference(name));
+            match context.recover(result.map(Some), element, Self::DISPLAY_NAME, |_| Ok(None))? {
+                Some(element_decl) => context.request(element_decl)?.type_definition,
+                None => Self::any_type(context)?,
+            }
+        } else {
+            Self::any_type(context)?
+        };
 
         // {type table}
         //   A Type Table corresponding to the <alternative> element information items among the
@@ -191,8 +203,8 @@ impl ElementDeclaration {
             let alternatives = alternative_elements
                 .iter()
                 .filter(|a| a.has_attribute("test"))
-                .map(|&a| TypeAlternative::map_from_xml(a, schema))
-                .collect::<Sequence<_>>();
+                .map(|&a| TypeAlternative::map_from_xml(context, a, schema, self_ref))
+                .collect::<Result<Sequence<_>, _>>()?;
 
             // {default type definition}
             //   Depends upon the final <alternative> element among the [children].
@@ -206,7 +218,7 @@ impl ElementDeclaration {
             let default_type_definition = if !final_alternative.has_attribute("test") {
                 // 1 If the <alternative> has no test [attribute], then a Type Alternative
                 //   corresponding to the <alternative>.
-                TypeAlternative::map_from_xml(final_alternative, schema)
+                TypeAlternative::map_from_xml(context, final_alternative, schema, self_ref)?
             } else {
                 // 2 otherwise (the <alternative> has a test) a Type Alternative with the following
                 //   properties:
@@ -218,6 +230,7 @@ impl ElementDeclaration {
                     test: None,
                     type_definition,
                     annotations: Sequence::new(),
+                    test_expression: None,
                 })
             };
 
@@ -234,6 +247,7 @@ impl ElementDeclaration {
         let nillable = element
             .attribute("nillable")
             .map(|v| actual_value::<bool>(v, element))
+            .transpose()?
             .unwrap_or(false);
 
         // {value constraint}
@@ -241,27 +255,8 @@ impl ElementDeclaration {
         //   otherwise ·absent·.
         let value_constraint = if element.has_attribute("default") || element.has_attribute("fixed")
         {
-            // [Definition:]
-            //   Use the name effective simple type definition for the declared {type definition},
-            //   if it is a simple type definition, or, if {type definition}.{content type}
-            //   .{variety} = simple, for {type definition}.{content type}.{simple type definition},
-            //   or else for the built-in string simple type definition).
-            // TODO store as the effective type
-            let _effective_simple_type_definition =
-                if let TypeDefinition::Simple(st) = type_definition {
-                    st
-                } else {
-                    let ct = context.request(type_definition.complex().unwrap())?;
-                    if let ContentType::Simple {
-                        simple_type_definition,
-                    } = ct.content_type
-                    {
-                        simple_type_definition
-                    } else {
-                        context.resolve(&XS_STRING_NAME).unwrap() // TODO
-                    }
-                };
-
+            // {value} is resolved once the effective simple type definition is available, see
+            // ElementDeclaration::effective_simple_type_definition().
             let (variety, value) = if let Some(default) = element.attribute("default") {
                 (ValueConstraintVariety::Default, default)
             } else if let Some(fixed) = element.attribute("fixed") {
@@ -270,11 +265,7 @@ impl ElementDeclaration {
                 unreachable!()
             };
 
-            Some(ValueConstraint {
-                variety,
-                value: value.into(),
-                lexical_form: value.into(),
-            })
+            Some(ValueConstraint::new(variety, value, element))
         } else {
             None
         };
@@ -283,26 +274,49 @@ impl ElementDeclaration {
         //   A set consisting of the identity-constraint-definitions corresponding to all the
         //   <key>, <unique> and <keyref> element information items in the [children], if any,
         //   otherwise the empty set.
-        let identity_constraint_definitions = element
+        //   The identity-constraint definitions are also components of the schema, which were
+        //   registered by name.
+        let mut identity_constraint_definitions = Set::new();
+        for icd in element
             .children()
-            .filter(|c| {
-                [
-                    IdentityConstraintDefinition::KEY_TAG_NAME,
-                    IdentityConstraintDefinition::UNIQUE_TAG_NAME,
-                    IdentityConstraintDefinition::KEYREF_TAG_NAME,
-                ]
-                .contains(&c.tag_name().name())
-            })
-            .map(|icd| IdentityConstraintDefinition::map_from_xml_local(context, icd, schema))
-            .collect::<Result<Vec<_>, _>>()?;
+            .filter(|c| IdentityConstraintDefinition::TAG_NAMES.contains(&c.tag_name().name()))
+        {
+            let icd_ref = IdentityConstraintDefinition::get_name_from_xml(context, icd, schema)
+                .and_then(|name| {
+                    context
+                        .resolve(&name)
+                        .ok_or(XsdError::UnresolvedReference(name))
+                })
+                .and_then(|icd_ref| {
+                    context.request::<IdentityConstraintDefinition>(icd_ref)?;
+                    Ok(icd_ref)
+                })
+                .map_err(|e| context.locate(e, icd, IdentityConstraintDefinition::DISPLAY_NAME))?;
+            identity_constraint_definitions.push(icd_ref);
+        }
 
         // {substitution group affiliations}
         //   A set of the element declarations ·resolved· to by the items in the ·actual value· of
         //   the substitutionGroup [attribute], if present, otherwise the empty set.
         let substitution_group_affiliations = element
             .attribute("substitutionGroup")
-            .map(|v| actual_value::<Vec<QName>>(v, element))
-            .map(|v| v.iter().map(|c| context.resolve(c).unwrap()).collect()) // TODO
+            .map(|v| context.qnames(v, element))
+            .transpose()?
+            .map(|v| {
+                // Unresolved affiliations are left out when recovering from errors
+                v.into_iter()
+                    .filter_map(|c| {
+                        let result = context
+                            .resolve(&c)
+                            .ok_or(XsdError::UnresolvedElementReference(c))
+                            .map(Some);
+                        context
+                            .recover(result, element, Self::DISPLAY_NAME, |_| Ok(None))
+                            .transpose()
+                    })
+                    .collect::<Result<_, _>>()
+            })
+            .transpose()?
             .unwrap_or_default();
 
         // {disallowed substitutions} (see the helper function for explanation)
@@ -316,7 +330,7 @@ impl ElementDeclaration {
             ],
             element,
             schema,
-        );
+        )?;
 
         // As for {disallowed substitutions} above, but using the final and finalDefault
         // [attributes] in place of the block and blockDefault [attributes] and with the relevant
@@ -330,13 +344,14 @@ impl ElementDeclaration {
             ],
             element,
             schema,
-        );
+        )?;
 
         // {abstract}
         //   The ·actual value· of the abstract [attribute], if present, otherwise false.
         let abstract_ = element
             .attribute("abstract")
             .map(|v| actual_value::<bool>(v, element))
+            .transpose()?
             .unwrap_or(false);
 
         // {annotations}
@@ -370,6 +385,28 @@ impl ElementDeclaration {
         })
     }
 
+    /// Creates a global element declaration of ·xs:anyType· named `name`, which replaces an
+    /// unresolved element declaration reference when recovering from errors
+    fn placeholder(context: &mut MappingContext, name: QName) -> Result<Ref<Self>, XsdError> {
+        let type_definition = Self::any_type(context)?;
+        let (target_namespace, name) = name.into_parts();
+        Ok(context.create(Self {
+            annotations: Sequence::new(),
+            name,
+            target_namespace,
+            type_definition,
+            type_table: None,
+            scope: Scope::new_global(),
+            value_constraint: None,
+            nillable: false,
+            identity_constraint_definitions: Set::new(),
+            substitution_group_affiliations: Set::new(),
+            substitution_group_exclusions: Set::new(),
+            disallowed_substitutions: Set::new(),
+            abstract_: false,
+        }))
+    }
+
     /// Maps the [`ElementDeclaration`] from an `<element>` without a `ref` attribute.
     fn map_local_element_decl(
         context: &mut MappingContext,
@@ -380,9 +417,10 @@ impl ElementDeclaration {
         let self_ref = context.reserve();
 
         // {target namespace} The appropriate case among the following:
-        let target_namespace = if let Some(target_namespace) = schema.attribute("targetNamespace") {
+        let target_namespace = if let Some(target_namespace) = element.attribute("targetNamespace")
+        {
             // 1 If targetNamespace is present , then its ·actual value·.
-            Some(actual_value::<String>(target_namespace, element))
+            Some(actual_value::<String>(target_namespace, element)?)
         } else {
             // 2 If targetNamespace is not present and one of the following is true
             // 2.1 form = qualified
@@ -393,9 +431,7 @@ impl ElementDeclaration {
             if form == Some("qualified") {
                 // then the ·actual value· of the targetNamespace [attribute] of the ancestor
                 // <schema> element information item, or ·absent· if there is none.
-                schema
-                    .attribute("targetNamespace")
-                    .map(|v| actual_value::<String>(v, element))
+                context.target_namespace(schema)?
             } else {
                 // 3 otherwise ·absent·.
                 None
@@ -439,8 +475,13 @@ impl ElementDeclaration {
             // {term}
             //   The (top-level) element declaration ·resolved· to by the ·actual value· of the ref
             //   [attribute].
-            let ref_: QName = actual_value(ref_, element);
-            context.resolve(&ref_).unwrap() // TODO
+            let ref_ = context.qname(ref_, element)?;
+            let result = context
+                .resolve(&ref_)
+                .ok_or_else(|| XsdError::UnresolvedElementReference(ref_.clone()));
+            context.recover(result, element, Self::DISPLAY_NAME, |context| {
+                Self::placeholder(context, ref_)
+            })?
         } else {
             // If the <element> element information item has <complexType> or <group> as an
             // ancestor, and the ref [attribute] is absent, and it does not have
@@ -460,6 +501,7 @@ impl ElementDeclaration {
         let min_occurs = element
             .attribute("minOccurs")
             .map(|min_occurs| actual_value::<u64>(min_occurs, element))
+            .transpose()?
             .unwrap_or(1);
 
         // {max occurs}
@@ -469,11 +511,12 @@ impl ElementDeclaration {
             .attribute("maxOccurs")
             .map(|max_occurs| {
                 if max_occurs == "unbounded" {
-                    MaxOccurs::Unbounded
+                    Ok(MaxOccurs::Unbounded)
                 } else {
-                    MaxOccurs::Count(actual_value::<u64>(max_occurs, element))
+                    actual_value::<u64>(max_occurs, element).map(MaxOccurs::Count)
                 }
             })
+            .transpose()?
             .unwrap_or(MaxOccurs::Count(1));
 
         // {annotations}
@@ -495,7 +538,7 @@ impl ElementDeclaration {
         relevant_set: &[T],
         element: Node<'a, 'a>,
         schema: Node<'a, 'a>,
-    ) -> Set<T> {
+    ) -> Result<Set<T>, XsdError> {
         // Comment text is from {disallowed substitutions}, but this applies to {substitution group
         // exclusions} as well
 
@@ -509,7 +552,7 @@ impl ElementDeclaration {
             .unwrap_or_default();
 
         // Then the value of this property is the appropriate case among the following:
-        if effective_value.is_empty() {
+        Ok(if effective_value.is_empty() {
             // 1 If the EBV is the empty string, then the empty set;
             Set::new()
         } else if effective_value == "#all" {
@@ -519,13 +562,143 @@ impl ElementDeclaration {
             // otherwise a set with members drawn from the set above, each being present or absent
             // depending on whether the ·actual value· (which is a list) contains an equivalently
             // named item.
-            let effective_block_value = actual_value::<Vec<T>>(effective_value, element);
+            let effective_block_value = actual_value::<Vec<T>>(effective_value, element)?;
             relevant_set
                 .iter()
                 .filter(|m| effective_block_value.contains(m))
                 .copied()
                 .collect()
+        })
+    }
+}
+
+impl ElementDeclaration {
+    /// [Definition:] Use the name effective simple type definition for the declared {type
+    /// definition}, if it is a simple type definition, or, if {type definition}.{content type}
+    /// .{variety} = simple, for {type definition}.{content type}.{simple type definition}, or else
+    /// for the built-in string simple type definition).
+    ///
+    /// Returns `None` if the latter is needed but the built-ins are not registered.
+    pub fn effective_simple_type_definition(
+        &self,
+        components: &SchemaComponentTable,
+    ) -> Option<Ref<SimpleTypeDefinition>> {
+        match self.type_definition {
+            TypeDefinition::Simple(st) => Some(st),
+            TypeDefinition::Complex(ct) => match ct.get(components).content_type {
+                ContentType::Simple {
+                    simple_type_definition,
+                } => Some(simple_type_definition),
+                _ => components.refs::<SimpleTypeDefinition>().find(|t| {
+                    t.is_builtin(components) && t.name(components) == Some(XS_STRING_NAME)
+                }),
+            },
+        }
+    }
+
+    /// 3.3.6.2 Substitution Group OK (Direct)
+    fn substitutable_directly(
+        m: Ref<Self>,
+        h: Ref<Self>,
+        components: &SchemaComponentTable,
+    ) -> bool {
+        // For an element declaration (call it M) to be substitutable directly for another element
+        // declaration (call it H) all of the following must be true:
+        let (m, h) = (m.get(components), h.get(components));
+        // 1 M.{substitution group affiliations} contains H. (checked by the caller)
+        // 2 M.{type definition} is validly derived from H.{type definition}, given the blocking
+        //   constraint H.{substitution group exclusions}.
+        type_derivation_ok(
+            m.type_definition,
+            h.type_definition,
+            &h.substitution_group_exclusions,
+            components,
+        )
+    }
+
+    /// 3.3.6.3 Substitution Group OK (Transitive): whether the element declaration `m` is
+    /// ·substitutable· for `h`
+    pub fn substitutable(m: Ref<Self>, h: Ref<Self>, components: &SchemaComponentTable) -> bool {
+        // For an element declaration (call it M) to be substitutable for another element
+        // declaration (call it H) one of the following must be true:
+
+        // 1 M and H are the same element declaration.
+        if m == h {
+            return true;
+        }
+
+        // 2 All of the following are true:
+        let head = h.get(components);
+        // 2.1 H.{disallowed substitutions} does not contain substitution.
+        if head
+            .disallowed_substitutions
+            .contains(&SubstitutionMethod::Substitution)
+        {
+            return false;
+        }
+
+        // 2.2 There is a chain of {substitution group affiliations} properties from M to H, that
+        //   is, either M.{substitution group affiliations} contains H, or M.{substitution group
+        //   affiliations} contains a declaration whose {substitution group affiliations} contains
+        //   H, or ...
+        // Each link of the chain must be substitutable directly (§3.3.6.2).
+        let mut pending = vec![m];
+        let mut visited = Vec::new();
+        let mut found = false;
+        while let Some(current) = pending.pop() {
+            if visited.contains(&current) {
+                continue;
+            }
+            visited.push(current);
+            for &affiliation in &current.get(components).substitution_group_affiliations {
+                if Self::substitutable_directly(current, affiliation, components) {
+                    found |= affiliation == h;
+                    pending.push(affiliation);
+                }
+            }
+        }
+        if !found {
+            return false;
         }
+
+        // 2.3 The set of all {derivation method}s involved in the derivation of M.{type
+        //   definition} from H.{type definition} does not intersect with the union of (a)
+        //   H.{disallowed substitutions}, (b) H.{type definition}.{prohibited substitutions}, and
+        //   (c) the {prohibited substitutions} of all intermediate types between M.{type
+        //   definition} and H.{type definition}.
+        let blocking = head
+            .disallowed_substitutions
+            .iter()
+            .filter_map(|s| match s {
+                SubstitutionMethod::Extension => Some(DerivationMethod::Extension),
+                SubstitutionMethod::Restriction => Some(DerivationMethod::Restriction),
+                SubstitutionMethod::Substitution => None,
+            })
+            .collect::<Vec<_>>();
+        validly_substitutable(
+            m.get(components).type_definition,
+            head.type_definition,
+            &blocking,
+            components,
+        )
+    }
+
+    /// Returns the members of the ·substitution group· of the global element declaration `head`
+    /// which may appear in its place in an instance, i.e. those which are ·substitutable· for it
+    /// and not abstract. This includes `head` itself unless it is abstract.
+    pub fn substitution_group(
+        head: Ref<Self>,
+        components: &SchemaComponentTable,
+    ) -> Vec<Ref<Self>> {
+        components
+            .refs::<Self>()
+            .filter(|&m| {
+                let d = m.get(components);
+                d.scope.variety() == ScopeVariety::Global
+                    && !d.abstract_
+                    && Self::substitutable(m, head, components)
+            })
+            .collect()
     }
 }
 
@@ -552,9 +725,7 @@ impl TopLevelMappable for ElementDeclaration {
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the parent <schema> element
         //   information item, or ·absent· if there is none.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<String>(v, element));
+        let target_namespace = context.target_namespace(schema)?;
 
         // {scope} A Scope as follows:
         //   {variety} global
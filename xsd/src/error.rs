@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::xstypes::QName;
+use crate::{include::IncludeError, validation::InvalidValue, xpath::XPathError, xstypes::QName};
 
 #[derive(Debug)]
 pub enum XsdError {
@@ -11,6 +11,137 @@ pub enum XsdError {
     UnresolvedReference(QName),
     UnresolvedBuiltin(&'static QName),
     UnsupportedCircularReference,
+    Include(IncludeError),
+    InvalidRedefinition(QName),
+    InvalidAttributeValue {
+        value: String,
+        expected: &'static str,
+    },
+    MissingAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+    InvalidContent {
+        element: String,
+        reason: &'static str,
+    },
+    ImportNamespaceMismatch {
+        namespace: Option<String>,
+        target_namespace: Option<String>,
+    },
+    UnresolvedTypeReference(QName),
+    UnresolvedGroupReference(QName),
+    UnresolvedElementReference(QName),
+    DuplicateComponent(QName),
+    BuiltinOverwrite(QName),
+    Unsupported(&'static str),
+    InvalidXPath {
+        expression: String,
+        error: XPathError,
+    },
+    /// The {lexical form} of a default or fixed value is not valid with respect to the type of the
+    /// declaration imposing it
+    InvalidValueConstraint {
+        /// The kind of the declaration (e.g. `ElementDeclaration`)
+        component: &'static str,
+        declaration: QName,
+        error: InvalidValue,
+    },
+    /// Wraps another error with the location of the element information item it originates from
+    Located {
+        error: Box<XsdError>,
+        /// The kind of the component enclosing the error location (e.g. `ElementDeclaration`)
+        component: &'static str,
+        location: SourceLocation,
+    },
+}
+
+/// The position of an element information item in a schema document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The URI of the schema document, if known
+    pub document: Option<String>,
+    /// 1-based line number
+    pub line: u32,
+    /// 1-based column number
+    pub column: u32,
+    /// The source line containing the position
+    pub snippet: String,
+}
+
+impl SourceLocation {
+    pub fn of_node(node: roxmltree::Node, document: Option<&str>) -> Self {
+        let input = node.document().input_text();
+        let pos = node.document().text_pos_at(node.range().start);
+        let snippet = input
+            .lines()
+            .nth(pos.row as usize - 1)
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            document: document.map(str::to_string),
+            line: pos.row,
+            column: pos.col,
+            snippet,
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let document = self.document.as_deref().unwrap_or("<schema>");
+        write!(f, "{document}:{}:{}", self.line, self.column)
+    }
+}
+
+impl XsdError {
+    /// Returns the underlying error, stripping any [`Located`](Self::Located) wrappers
+    pub fn kind(&self) -> &XsdError {
+        match self {
+            Self::Located { error, .. } => error.kind(),
+            _ => self,
+        }
+    }
+
+    /// Returns the source location of the error, if known
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            Self::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Attaches a source location to the error, unless it already has one. As errors propagate
+    /// outwards, this keeps the innermost (most precise) location.
+    pub(crate) fn at(
+        self,
+        node: roxmltree::Node,
+        document: Option<&str>,
+        component: &'static str,
+    ) -> Self {
+        if self.location().is_some() {
+            return self;
+        }
+        Self::Located {
+            error: Box::new(self),
+            component,
+            location: SourceLocation::of_node(node, document),
+        }
+    }
+
+    pub(crate) fn missing_attribute(element: roxmltree::Node, attribute: &'static str) -> Self {
+        Self::MissingAttribute {
+            element: element.tag_name().name().to_string(),
+            attribute,
+        }
+    }
+
+    pub(crate) fn invalid_content(element: roxmltree::Node, reason: &'static str) -> Self {
+        Self::InvalidContent {
+            element: element.tag_name().name().to_string(),
+            reason,
+        }
+    }
 }
 
 impl fmt::Display for XsdError {
@@ -37,6 +168,90 @@ impl fmt::Display for XsdError {
             Self::UnsupportedCircularReference => {
                 write!(f, "Unsupported circular reference")
             }
+            Self::Include(error) => {
+                write!(f, "Failed to include schema: {error}")
+            }
+            Self::InvalidRedefinition(name) => {
+                write!(f, "Invalid redefinition of {name}")
+            }
+            Self::InvalidAttributeValue { value, expected } => {
+                write!(f, "Invalid value {value:?}, expected {expected}")
+            }
+            Self::MissingAttribute { element, attribute } => {
+                write!(
+                    f,
+                    "Element <{element}> is missing the {attribute:?} attribute"
+                )
+            }
+            Self::InvalidContent { element, reason } => {
+                write!(f, "Invalid content of <{element}>: {reason}")
+            }
+            Self::ImportNamespaceMismatch {
+                namespace,
+                target_namespace,
+            } => {
+                write!(
+                    f,
+                    "Import namespace {namespace:?} is not allowed with target namespace \
+                    {target_namespace:?}"
+                )
+            }
+            Self::UnresolvedTypeReference(name) => {
+                write!(f, "Unresolved type definition reference {name}")
+            }
+            Self::UnresolvedGroupReference(name) => {
+                write!(f, "Unresolved group reference {name}")
+            }
+            Self::UnresolvedElementReference(name) => {
+                write!(f, "Unresolved element declaration reference {name}")
+            }
+            Self::DuplicateComponent(name) => {
+                write!(f, "Duplicate component {name}")
+            }
+            Self::BuiltinOverwrite(name) => {
+                write!(f, "Tried to overwrite built-in component {name}")
+            }
+            Self::Unsupported(feature) => {
+                write!(f, "Unsupported feature: {feature}")
+            }
+            Self::InvalidXPath { expression, error } => {
+                write!(f, "Invalid XPath expression {expression:?}: {error}")
+            }
+            Self::InvalidValueConstraint {
+                component,
+                declaration,
+                error,
+            } => {
+                write!(
+                    f,
+                    "Invalid value constraint of {component} {declaration}: {error}"
+                )
+            }
+            Self::Located {
+                error,
+                component,
+                location,
+            } => {
+                // Rendered in the style of compiler diagnostics:
+                //   error: <message>
+                //     --> <document>:<line>:<column>
+                //      |
+                //   12 |     <xs:element ... />
+                //      |     ^ in <component>
+                let line = location.line.to_string();
+                let gutter = " ".repeat(line.len());
+                let indent: String = location
+                    .snippet
+                    .chars()
+                    .take(location.column as usize - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                writeln!(f, "error: {error}")?;
+                writeln!(f, "{gutter}--> {location}")?;
+                writeln!(f, "{gutter} |")?;
+                writeln!(f, "{line} | {}", location.snippet)?;
+                write!(f, "{gutter} | {indent}^ in {component}")
+            }
         }
     }
 }
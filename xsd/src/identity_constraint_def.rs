@@ -6,6 +6,7 @@ use crate::{
     error::XsdError,
     mapping_context::{MappingContext, TopLevelMappable},
     values::actual_value,
+    xpath::{IdentityPath, XPathError},
     xstypes::{AnyURI, NCName, QName, Sequence},
 };
 use roxmltree::Node;
@@ -20,6 +21,10 @@ pub struct IdentityConstraintDefinition {
     pub selector: XPathExpression,
     pub fields: Sequence<XPathExpression>,
     pub referenced_key: Option<Ref<IdentityConstraintDefinition>>,
+    /// The parsed {selector}
+    pub selector_path: IdentityPath,
+    /// The parsed {fields}, in order
+    pub field_paths: Sequence<IdentityPath>,
 }
 
 #[derive(Clone, Debug)]
@@ -39,21 +44,23 @@ impl Named for IdentityConstraintDefinition {
 }
 
 impl NamedXml for IdentityConstraintDefinition {
-    fn get_name_from_xml(icd: Node, schema: Node) -> QName {
+    fn get_name_from_xml(
+        context: &MappingContext,
+        icd: Node,
+        schema: Node,
+    ) -> Result<QName, XsdError> {
         // {name} The ·actual value· of the name [attribute]
         let name = icd
             .attribute("name")
-            .map(|v| actual_value::<String>(v, icd))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(icd, "name"))
+            .and_then(|v| actual_value::<String>(v, icd))?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the <schema> ancestor element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<String>(v, schema));
+        let target_namespace = context.target_namespace(schema)?;
 
-        QName::with_optional_namespace(target_namespace, name)
+        Ok(QName::with_optional_namespace(target_namespace, name))
     }
 }
 
@@ -67,22 +74,13 @@ impl IdentityConstraintDefinition {
         Self::UNIQUE_TAG_NAME,
     ];
 
-    pub(super) fn map_from_xml_local(
-        context: &mut MappingContext,
-        icd: Node,
-        schema: Node,
-    ) -> Result<Ref<Self>, XsdError> {
-        let self_ref = context.reserve();
-        Self::map_from_xml(context, icd, schema, self_ref)
-    }
-
     fn map_from_xml(
         context: &mut MappingContext,
         icd: Node,
         schema: Node,
         self_ref: Ref<Self>,
     ) -> Result<Ref<Self>, XsdError> {
-        let (target_namespace, name) = Self::get_name_from_xml(icd, schema).into_parts();
+        let (target_namespace, name) = Self::get_name_from_xml(context, icd, schema)?.into_parts();
 
         // {identity-constraint category}
         //   One of key, keyref or unique, depending on the item.
@@ -90,40 +88,65 @@ impl IdentityConstraintDefinition {
             "key" => IdentityConstraintCategory::Key,
             "keyref" => IdentityConstraintCategory::KeyRef,
             "unique" => IdentityConstraintCategory::Unique,
-            _ => panic!("Invalid ICD element name {:?}", icd.tag_name().name()),
+            _ => {
+                return Err(XsdError::invalid_content(
+                    icd,
+                    "unknown identity constraint",
+                ));
+            }
         };
 
         // {selector}
         //   An XPath Expression property record, as described in section XML Representation of
         //   Assertion Schema Components (§3.13.2), with <selector> as the "host element" and xpath
         //   as the designated expression [attribute].
-        // TODO absence?
-        let selector = icd
+        let selector_element = icd
             .children()
             .find(|c| c.tag_name().name() == "selector")
-            .unwrap();
-        let xpath = selector.attribute("xpath").unwrap();
-        let selector = XPathExpression::map_from_xml(xpath, selector, schema);
+            .ok_or(XsdError::invalid_content(icd, "missing <selector>"))?;
+        let xpath = selector_element
+            .attribute("xpath")
+            .ok_or(XsdError::missing_attribute(selector_element, "xpath"))?;
+        let selector = XPathExpression::map_from_xml(context, xpath, selector_element, schema)?;
+        // Schema Component Constraint: Selector Value OK (§3.11.6.2)
+        // 1 The {selector} satisfies the constraint XPath Valid (§3.13.6.2).
+        // 2 One of the following is true:
+        // 2.1 Its {expression} conforms to the following extended BNF: [...]
+        let selector_path = IdentityPath::parse_selector(&selector)
+            .map_err(|error| Self::invalid_xpath(context, &selector, error, selector_element))?;
 
         // {fields}
         //   A sequence of XPath Expression property records, corresponding to the <field> element
         //   information item [children], in order, following the rules given in XML Representation
         //   of Assertion Schema Components (§3.13.2), with <field> as the "host element" and xpath
         //   as the designated expression [attribute].
-        let fields = icd
+        let field_elements = icd
             .children()
             .filter(|c| c.tag_name().name() == "field")
-            .map(|field| {
-                let xpath = field.attribute("xpath").unwrap();
-                XPathExpression::map_from_xml(xpath, field, schema)
-            })
-            .collect();
+            .collect::<Vec<_>>();
+        let mut fields = Sequence::with_capacity(field_elements.len());
+        let mut field_paths = Sequence::with_capacity(field_elements.len());
+        for &field_element in &field_elements {
+            let xpath = field_element
+                .attribute("xpath")
+                .ok_or(XsdError::missing_attribute(field_element, "xpath"))?;
+            let field = XPathExpression::map_from_xml(context, xpath, field_element, schema)?;
+            // Schema Component Constraint: Fields Value OK (§3.11.6.3)
+            //   Each member of the {fields} conforms to the extended BNF for fields
+            let field_path = IdentityPath::parse_field(&field)
+                .map_err(|error| Self::invalid_xpath(context, &field, error, field_element))?;
+            fields.push(field);
+            field_paths.push(field_path);
+        }
 
         // {referenced key}
         //   If the item is a <keyref>, the identity-constraint definition ·resolved· to by the
         //   ·actual value· of the refer [attribute], otherwise ·absent·.
         let referenced_key = if icd.tag_name().name() == "keyref" {
-            let refer: QName = actual_value(icd.attribute("refer").unwrap(), icd);
+            let refer = icd
+                .attribute("refer")
+                .ok_or(XsdError::missing_attribute(icd, "refer"))?;
+            let refer = context.qname(refer, icd)?;
             Some(
                 context
                     .resolve(&refer)
@@ -137,8 +160,10 @@ impl IdentityConstraintDefinition {
         //   The ·annotation mapping· of the set of elements containing the <key>, <keyref>, or
         //   <unique> element, whichever is present, and the <selector> and <field> [children], if
         //   present, as defined in XML Representation of Annotation Schema Components (§3.15.2).
-        let ae = vec![icd];
-        // TODO selector, field
+        let ae = [icd, selector_element]
+            .into_iter()
+            .chain(field_elements)
+            .collect::<Vec<_>>();
         let annotations = Annotation::xml_element_set_annotation_mapping(context, &ae);
 
         Ok(context.insert(
@@ -151,9 +176,24 @@ impl IdentityConstraintDefinition {
                 selector,
                 fields,
                 referenced_key,
+                selector_path,
+                field_paths,
             },
         ))
     }
+
+    fn invalid_xpath(
+        context: &MappingContext,
+        xpath: &XPathExpression,
+        error: XPathError,
+        host_element: Node,
+    ) -> XsdError {
+        let error = XsdError::InvalidXPath {
+            expression: xpath.expression.clone(),
+            error,
+        };
+        context.locate(error, host_element, Self::DISPLAY_NAME)
+    }
 }
 
 impl Component for IdentityConstraintDefinition {
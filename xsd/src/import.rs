@@ -14,30 +14,37 @@ pub struct Import {
 impl Import {
     pub const TAG_NAME: &'static str = "import";
 
-    pub fn map_from_xml(import: Node, schema: Node) -> Result<Self, XsdError> {
-        let namespace = import
+    /// Maps an `<import>` of a schema document with the given `target_namespace`, which is
+    /// that of the including schema document in case of chameleon includes (Appendix F.1).
+    pub fn map_from_xml(import: Node, target_namespace: Option<&str>) -> Result<Self, XsdError> {
+        let namespace: Option<String> = import
             .attribute("namespace")
-            .map(|ns| actual_value(ns, import));
-        let schema_location = import
+            .map(|ns| actual_value(ns, import))
+            .transpose()?;
+        let schema_location: Option<String> = import
             .attribute("schemaLocation")
-            .map(|sl| actual_value(sl, import));
+            .map(|sl| actual_value(sl, import))
+            .transpose()?;
 
         // § 4.2.6 Schema Representation Constraint: Import Constraints and Semantics
         // 1 The appropriate case among the following must be true:
         if let Some(namespace) = namespace.as_ref() {
             // 1.1 If the namespace [attribute] is present, then its ·actual value· does not match
             //   the ·actual value· of the enclosing <schema>'s targetNamespace [attribute].
-            if let Some(target_namespace) = schema.attribute("targetNamespace") {
-                let target_namespace: &str = actual_value(target_namespace, schema);
-                if namespace == target_namespace {
-                    panic!("TODO: error: import namespace matches target namespace");
-                }
+            if target_namespace == Some(namespace) {
+                return Err(XsdError::ImportNamespaceMismatch {
+                    namespace: Some(namespace.clone()),
+                    target_namespace: Some(namespace.clone()),
+                });
             }
         } else {
             // 1.2 If the namespace [attribute] is not present, then the enclosing <schema> has a
             //   targetNamespace [attribute]
-            if schema.attribute("targetNamespace").is_none() {
-                panic!("TODO: error: no import namespace and schema target namespace is missing");
+            if target_namespace.is_none() {
+                return Err(XsdError::ImportNamespaceMismatch {
+                    namespace: None,
+                    target_namespace: None,
+                });
             }
         }
 
@@ -54,7 +61,9 @@ impl Import {
             //   ·actual value· of the targetNamespace [attribute] of D2.
             let target_namespace = schema
                 .attribute("targetNamespace")
-                .map(|tn| actual_value::<&str>(tn, schema));
+                .map(|tn| actual_value::<&str>(tn, schema))
+                .transpose()
+                .map_err(ImportError::Xsd)?;
             target_namespace == Some(namespace)
         } else {
             // 3.2 If there is no namespace [attribute], then D2 has no targetNamespace [attribute]
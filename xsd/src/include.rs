@@ -0,0 +1,815 @@
+use crate::{
+    MappingContext, error::XsdError, mapping_context::RootContext, values::actual_value,
+    xstypes::QName,
+};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+use typed_arena::Arena;
+
+/// This structure represents the `include` element; it is not a schema component.
+#[derive(Clone, Debug)]
+pub struct Include {
+    pub schema_location: String,
+}
+
+impl Include {
+    pub const TAG_NAME: &'static str = "include";
+
+    pub fn map_from_xml(include: Node) -> Result<Self, XsdError> {
+        let schema_location = include
+            .attribute("schemaLocation")
+            .map(|sl| actual_value(sl, include))
+            .transpose()?
+            .ok_or(XsdError::Include(IncludeError::MissingSchemaLocation))?;
+
+        Ok(Include { schema_location })
+    }
+
+    /// Returns the schemaLocation resolved against `base_uri`, the location of the schema
+    /// document containing the `<include>`. Absolute URIs and paths are returned as they are.
+    pub fn location(&self, base_uri: Option<&str>) -> String {
+        let location = &self.schema_location;
+        let is_absolute = location.contains("://") || location.starts_with(['/', '\\']);
+        match base_uri.and_then(|base| base.rfind(['/', '\\']).map(|i| &base[..=i])) {
+            Some(base_dir) if !is_absolute => format!("{base_dir}{location}"),
+            _ => location.clone(),
+        }
+    }
+
+    /// Checks the included schema document against the target namespace of the including schema
+    /// document, returning whether this is a chameleon include (i.e. the included schema document
+    /// has no targetNamespace, but the includer does).
+    pub fn validate_included_schema(
+        &self,
+        target_namespace: Option<&str>,
+        schema: Node,
+    ) -> Result<bool, IncludeError> {
+        // § 4.2.3 Schema Representation Constraint: Inclusion Constraints and Semantics
+        // 1 If the [children] contain <include>s, then the ·actual value· of the schemaLocation
+        //   [attribute] of each <include> successfully resolves to a <schema> element information
+        //   item.
+        if schema.tag_name().name() != "schema" {
+            return Err(IncludeError::NotASchema);
+        }
+
+        let included_namespace = schema.attribute("targetNamespace");
+
+        // 2 If the [children] contain <include>s, then for each such <include>, the schema
+        //   document D2 identified by the ·actual value· of its schemaLocation [attribute] must
+        //   satisfy one of the following:
+        match (target_namespace, included_namespace) {
+            // 2.1 D2's targetNamespace [attribute] is present and has the same ·actual value· as
+            //   the targetNamespace [attribute] of the <schema> containing the <include>.
+            // 2.2 Neither D2 nor the <schema> containing the <include> has a targetNamespace
+            //   [attribute].
+            (a, b) if a == b => Ok(false),
+            // 2.3 D2 has no targetNamespace [attribute] (but the <schema> containing the <include>
+            //   does).
+            (Some(_), None) => Ok(true),
+            (includer, included) => Err(IncludeError::TargetNamespaceMismatch {
+                includer: includer.map(String::from),
+                included: included.map(String::from),
+            }),
+        }
+    }
+}
+
+/// The ways in which the components of a schema document can be brought into a schema by another
+/// schema document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Composition {
+    /// Assembling a schema for a single target namespace from multiple schema definition
+    /// documents (<include>) (§4.2.3)
+    Include,
+    /// Including modified component definitions (<redefine>) (§4.2.4)
+    Redefine,
+    /// Overriding component definitions (<override>) (§4.2.5)
+    Override,
+}
+
+impl Composition {
+    pub const REDEFINE_TAG_NAME: &'static str = "redefine";
+    pub const OVERRIDE_TAG_NAME: &'static str = "override";
+
+    pub fn from_tag_name(name: &str) -> Option<Self> {
+        match name {
+            Include::TAG_NAME => Some(Self::Include),
+            Self::REDEFINE_TAG_NAME => Some(Self::Redefine),
+            Self::OVERRIDE_TAG_NAME => Some(Self::Override),
+            _ => None,
+        }
+    }
+}
+
+/// A `<redefine>` or `<override>` element applying to an included schema document. It is
+/// identified by the index of the schema document containing it (see
+/// [`collect_included_schemas()`]) and its index among the element [children] of that `<schema>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Modification {
+    composition: Composition,
+    document: usize,
+    element: usize,
+}
+
+impl Modification {
+    fn element<'a, 'input>(
+        &self,
+        schema_documents: &[Node<'a, 'input>],
+    ) -> Option<Node<'a, 'input>> {
+        schema_documents
+            .get(self.document)?
+            .children()
+            .filter(Node::is_element)
+            .nth(self.element)
+    }
+}
+
+/// A schema document brought in via `<include>`, `<redefine>` or `<override>`.
+pub(crate) struct IncludedSchema<'input> {
+    /// The parsed schema document. Chameleon includes are parsed as they are, the chameleon
+    /// inclusion transformation is applied while mapping them (see
+    /// [`MappingContext::target_namespace()`] and [`MappingContext::qname()`]).
+    pub document: Document<'input>,
+    /// The location the schema document was loaded from, i.e. the schemaLocation resolved against
+    /// the location of the including schema document (see [`Include::location()`])
+    pub location: String,
+    /// The `<redefine>` and `<override>` elements applying to the schema document, outermost
+    /// first. Modifications apply to the transitively included schema documents as well.
+    modifications: Vec<Modification>,
+}
+
+/// Collects all schema documents (transitively) included, redefined or overridden by `schema`.
+/// In the returned list, the schema document with index `i` corresponds to the element at index
+/// `i + 1`, index 0 being `schema` itself.
+///
+/// Note that all schema documents reachable this way share the target namespace of `schema`, so
+/// the nested schema documents can be checked against it as well.
+///
+/// Each schema document is parsed once, from its source text allocated in `sources`. The
+/// schemaLocations of `schema` are resolved against `document_uri`, those of the included schema
+/// documents against their own location.
+pub(crate) fn collect_included_schemas<'input>(
+    root_context: &mut RootContext,
+    schema: Node,
+    document_uri: Option<&str>,
+    sources: &'input Arena<String>,
+) -> Result<Vec<IncludedSchema<'input>>, XsdError> {
+    let target_namespace = schema
+        .attribute("targetNamespace")
+        .map(|tn| actual_value::<&str>(tn, schema))
+        .transpose()?;
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+
+    let mut pending = includes_of(schema, 0, document_uri, &[])?;
+    let mut included_schemas = Vec::<IncludedSchema>::new();
+
+    // Guard against circular includes, including ones leading back to `schema` itself. Including
+    // the same schema document twice (with the same modifications) does not contribute any new
+    // components.
+    let mut seen_locations = HashSet::new();
+    let mut seen_sources = HashSet::from([(schema.document().input_text(), vec![])]);
+
+    while let Some((include, base_uri, modifications)) = pending.pop() {
+        let location = include.location(base_uri.as_deref());
+        if !seen_locations.insert((location.clone(), modifications.clone())) {
+            continue;
+        }
+        let Some(source) = root_context.resolve_include(&include, base_uri.as_deref()) else {
+            continue;
+        };
+        if seen_sources.contains(&(source.as_str(), modifications.clone())) {
+            continue;
+        }
+        let source = sources.alloc(source).as_str();
+        seen_sources.insert((source, modifications.clone()));
+
+        let document = Document::parse_with_options(source, options)
+            .map_err(|e| XsdError::Include(IncludeError::Parse(e)))?;
+        let included = document.root_element();
+        // § 4.2.4 Schema Representation Constraint: Redefinition Constraints and Semantics
+        // § 4.2.5 Schema Representation Constraint: Override Constraints and Semantics
+        //   The target namespace constraints of <redefine> and <override> are the same as those
+        //   of <include>.
+        include
+            .validate_included_schema(target_namespace, included)
+            .map_err(XsdError::Include)?;
+
+        pending.extend(includes_of(
+            included,
+            included_schemas.len() + 1,
+            Some(&location),
+            &modifications,
+        )?);
+
+        included_schemas.push(IncludedSchema {
+            document,
+            location,
+            modifications,
+        });
+    }
+
+    Ok(included_schemas)
+}
+
+/// An `<include>`, `<redefine>` or `<override>` element to be resolved, along with the location of
+/// the schema document containing it and the modifications applying to the schema document it
+/// references
+type PendingInclude = (Include, Option<String>, Vec<Modification>);
+
+/// Returns the `<include>`, `<redefine>` and `<override>` elements of `schema`, along with the
+/// location of `schema` they are resolved against and the modifications applying to the schema
+/// document they reference.
+fn includes_of(
+    schema: Node,
+    document: usize,
+    document_uri: Option<&str>,
+    modifications: &[Modification],
+) -> Result<Vec<PendingInclude>, XsdError> {
+    let mut includes = Vec::new();
+    for (element, node) in schema.children().filter(Node::is_element).enumerate() {
+        let Some(composition) = Composition::from_tag_name(node.tag_name().name()) else {
+            continue;
+        };
+        let mut modifications = modifications.to_vec();
+        if composition != Composition::Include {
+            modifications.push(Modification {
+                composition,
+                document,
+                element,
+            });
+        }
+        includes.push((
+            Include::map_from_xml(node)?,
+            document_uri.map(String::from),
+            modifications,
+        ));
+    }
+    // `includes` is used as a stack, so reverse it to process the includes in document order
+    includes.reverse();
+    Ok(includes)
+}
+
+/// The effect of all `<redefine>` and `<override>` elements on the top-level elements of the
+/// schema documents making up a schema.
+#[derive(Default)]
+pub(crate) struct Modifications<'a, 'input> {
+    /// Maps the redefined top-level elements to the [children] of `<redefine>` redefining them
+    pub redefined: HashMap<Node<'a, 'input>, Node<'a, 'input>>,
+    /// Top-level elements which are overridden, and are thus not part of the schema
+    overridden: HashSet<Node<'a, 'input>>,
+    /// The [children] of `<override>` which actually override a top-level element
+    overriding: HashSet<Node<'a, 'input>>,
+}
+
+impl<'a, 'input> Modifications<'a, 'input> {
+    /// `schema_documents` contains the `<schema>` elements of the including schema document and
+    /// all `included_schemas`, in the order of [`collect_included_schemas()`].
+    pub(crate) fn compute(
+        context: &MappingContext,
+        schema_documents: &[Node<'a, 'input>],
+        included_schemas: &[IncludedSchema],
+    ) -> Result<Self, XsdError> {
+        let mut result = Self::default();
+
+        for (schema, included) in schema_documents[1..].iter().zip(included_schemas) {
+            for element in schema.children().filter(Node::is_element) {
+                let Some(name) = element.attribute("name") else {
+                    continue;
+                };
+                // The outermost modification takes precedence, see the note on the override
+                // transformation in Appendix F.2.
+                let replacement = included.modifications.iter().find_map(|modification| {
+                    modification
+                        .element(schema_documents)?
+                        .children()
+                        .find(|c| {
+                            c.tag_name() == element.tag_name() && c.attribute("name") == Some(name)
+                        })
+                        .map(|c| (modification.composition, c))
+                });
+                match replacement {
+                    Some((Composition::Redefine, redefining)) => {
+                        result.redefined.insert(element, redefining);
+                    }
+                    Some((Composition::Override, overriding)) => {
+                        result.overridden.insert(element);
+                        result.overriding.insert(overriding);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for redefine in schema_documents.iter().flat_map(|schema| {
+            schema
+                .children()
+                .filter(|c| c.tag_name().name() == Composition::REDEFINE_TAG_NAME)
+        }) {
+            result.check_redefinitions(context, redefine)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Checks the [children] of `redefine` as per § 4.2.4 Schema Representation Constraint:
+    /// Redefinition Constraints and Semantics.
+    fn check_redefinitions(
+        &self,
+        context: &MappingContext,
+        redefine: Node,
+    ) -> Result<(), XsdError> {
+        let target_namespace = context.target_namespace(redefine.document().root_element())?;
+
+        for redefining in redefine
+            .children()
+            .filter(|c| c.is_element() && c.tag_name().name() != "annotation")
+        {
+            let name = redefining.attribute("name").unwrap_or_default();
+            let qname = QName::with_optional_namespace(target_namespace.as_deref(), name);
+            let invalid = || XsdError::InvalidRedefinition(qname.clone());
+
+            // 2 [...] each <simpleType>, <complexType>, <group> and <attributeGroup> [child] of
+            //   <redefine> must correspond to a top-level definition of the same kind and name in
+            //   the redefined schema document.
+            if !self.redefined.values().any(|&r| r == redefining) {
+                return Err(invalid());
+            }
+
+            match redefining.tag_name().name() {
+                // 5 Within the [children], each <simpleType> must have a <restriction> among its
+                //   [children] and each <complexType> must have a restriction or extension among
+                //   its grand-[children] the ·actual value· of whose base [attribute] must be the
+                //   same as the ·actual value· of its own name attribute plus target namespace;
+                "simpleType" | "complexType" => {
+                    let derivation = redefining
+                        .children()
+                        .filter(Node::is_element)
+                        .flat_map(|c| {
+                            if c.tag_name().name() == "restriction" {
+                                vec![c]
+                            } else {
+                                c.children().filter(Node::is_element).collect()
+                            }
+                        })
+                        .find(|c| ["restriction", "extension"].contains(&c.tag_name().name()));
+                    let base = derivation
+                        .and_then(|d| d.attribute("base").map(|base| (base, d)))
+                        .map(|(base, d)| context.qname(base, d))
+                        .transpose()?;
+                    if base.as_ref() != Some(&qname) {
+                        return Err(invalid());
+                    }
+                }
+                // 6 Within the [children], for each <group> the appropriate case among the
+                //   following must be true:
+                //   6.1 If it has a <group> among its contents at some level the ·actual value· of
+                //     whose ref [attribute] is the same as the ·actual value· of its own name
+                //     attribute plus target namespace [...], then all of the following must be
+                //     true:
+                //     6.1.1 It must have exactly one such group.
+                //     6.1.2 The ·actual value· of both that group's minOccurs and maxOccurs
+                //       [attribute] must be 1 (or ·absent·).
+                // 7 Within the [children], for each <attributeGroup> the appropriate case among
+                //   the following must be true:
+                //   7.1 If it has an <attributeGroup> among its contents the ·actual value· of
+                //     whose ref [attribute] is the same as the ·actual value· of its own name
+                //     attribute plus target namespace, then it must have exactly one such group.
+                tag @ ("group" | "attributeGroup") => {
+                    let mut self_references = Vec::new();
+                    for d in redefining
+                        .descendants()
+                        .filter(|d| d.tag_name().name() == tag)
+                    {
+                        if let Some(ref_) = d.attribute("ref")
+                            && context.qname(ref_, d)? == qname
+                        {
+                            self_references.push(d);
+                        }
+                    }
+                    if self_references.len() > 1 {
+                        return Err(invalid());
+                    }
+                    let occurs_once = |d: &Node| {
+                        ["minOccurs", "maxOccurs"]
+                            .iter()
+                            .all(|a| d.attribute(*a).map(str::trim).unwrap_or("1") == "1")
+                    };
+                    if tag == "group" && !self_references.iter().all(occurs_once) {
+                        return Err(invalid());
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the top-level elements of `schema_documents` which correspond to components, in
+    /// document order. The [children] of `<redefine>` and the [children] of `<override>` which
+    /// override some element are treated as top-level elements, while the elements they
+    /// override are skipped. Redefined elements are kept, as the redefining components refer to
+    /// them.
+    pub(crate) fn top_level_elements(
+        &self,
+        schema_documents: &[Node<'a, 'input>],
+    ) -> Vec<Node<'a, 'input>> {
+        schema_documents
+            .iter()
+            .flat_map(|schema| schema.children().filter(Node::is_element))
+            .flat_map(|element| match element.tag_name().name() {
+                Composition::REDEFINE_TAG_NAME => {
+                    element.children().filter(Node::is_element).collect()
+                }
+                Composition::OVERRIDE_TAG_NAME => element
+                    .children()
+                    .filter(|c| self.overriding.contains(c))
+                    .collect(),
+                _ if self.overridden.contains(&element) => vec![],
+                _ => vec![element],
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IncludeError {
+    #[error("the resolver does not support the include")]
+    UnsupportedInclude,
+    #[error("the <include>, <redefine> or <override> has no schemaLocation")]
+    MissingSchemaLocation,
+    #[error("the included document failed to parse")]
+    Parse(roxmltree::Error),
+    #[error("the included document is not a <schema>")]
+    NotASchema,
+    #[error(
+        "the target namespace of the included schema ({included:?}) does not match the \
+        including schema ({includer:?})"
+    )]
+    TargetNamespaceMismatch {
+        includer: Option<String>,
+        included: Option<String>,
+    },
+    #[error("an unspecified error occurred while loading the schema")]
+    UnspecifiedLoad(Box<dyn std::error::Error>),
+}
+
+pub trait IncludeResolver {
+    /// Loads the schema document referenced by `include`, returning its source text. This is also
+    /// used for the schema documents referenced by `<redefine>` and `<override>`. `base_uri` is
+    /// the location of the schema document containing `include`, if known (see
+    /// [`Include::location()`]).
+    fn resolve_include(
+        &self,
+        include: &Include,
+        base_uri: Option<&str>,
+    ) -> Result<String, IncludeError>;
+}
+
+/// Loads included schema documents from the local file system, resolving relative
+/// schemaLocations against the location of the including schema document.
+pub struct LocalIncludeResolver;
+
+impl IncludeResolver for LocalIncludeResolver {
+    fn resolve_include(
+        &self,
+        include: &Include,
+        base_uri: Option<&str>,
+    ) -> Result<String, IncludeError> {
+        if include.schema_location.contains("://") {
+            return Err(IncludeError::UnsupportedInclude);
+        }
+        std::fs::read_to_string(include.location(base_uri))
+            .map_err(|e| IncludeError::UnspecifiedLoad(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Include, IncludeError, IncludeResolver};
+    use crate::{Term, components::RefNamed, xstypes::QName};
+
+    struct StaticIncludeResolver(&'static str, &'static str);
+
+    impl IncludeResolver for StaticIncludeResolver {
+        fn resolve_include(
+            &self,
+            include: &Include,
+            base_uri: Option<&str>,
+        ) -> Result<String, IncludeError> {
+            if include.location(base_uri) == self.0 {
+                Ok(self.1.to_string())
+            } else {
+                Err(IncludeError::UnsupportedInclude)
+            }
+        }
+    }
+
+    #[test]
+    fn chameleon_include() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"
+                       xmlns:m="urn:main" targetNamespace="urn:main">
+                <xs:include schemaLocation="common.xsd" />
+                <xs:element name="root" type="m:common" />
+            </xs:schema>
+        "#;
+        const COMMON: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:complexType name="common">
+                    <xs:sequence>
+                        <xs:element ref="child" />
+                    </xs:sequence>
+                </xs:complexType>
+                <xs:element name="child" type="xs:string" />
+            </xs:schema>
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+            [Box::new(StaticIncludeResolver("common.xsd", COMMON))];
+        let (schema, components) =
+            crate::testing::read_schema_with_includes(SCHEMA, None, &include_resolvers).unwrap();
+
+        assert_eq!(schema.element_declarations.len(), 2);
+        assert_eq!(schema.type_definitions.len(), 1);
+        let child = schema
+            .find_element_by_name(Some("urn:main"), "child", &components)
+            .expect("chameleon element should adopt the including target namespace");
+        assert_eq!(
+            child.name(&components),
+            Some(QName::with_namespace("urn:main", "child"))
+        );
+    }
+
+    #[test]
+    fn chameleon_include_undeclaring_default_namespace() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"
+                       xmlns:m="urn:main" targetNamespace="urn:main">
+                <xs:include schemaLocation="common.xsd" />
+                <xs:element name="root" type="m:common" />
+            </xs:schema>
+        "#;
+        // QName references without namespace name are only found where the default namespace is
+        // undeclared
+        const COMMON: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:other">
+                <xs:complexType name="common">
+                    <xs:sequence xmlns="">
+                        <xs:element ref="child" />
+                    </xs:sequence>
+                </xs:complexType>
+                <xs:element name="child" type="xs:string" />
+            </xs:schema>
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+            [Box::new(StaticIncludeResolver("common.xsd", COMMON))];
+        let (schema, components) =
+            crate::testing::read_schema_with_includes(SCHEMA, None, &include_resolvers).unwrap();
+
+        let common = schema.type_definitions[0]
+            .complex()
+            .unwrap()
+            .get(&components);
+        let particle = common.content_type.particle().unwrap().get(&components);
+        let Term::ModelGroup(sequence) = particle.term else {
+            panic!("expected a sequence");
+        };
+        let child = sequence.get(&components).particles[0].get(&components);
+        let Term::ElementDeclaration(child) = child.term else {
+            panic!("expected an element declaration");
+        };
+        assert_eq!(
+            child.name(&components),
+            Some(QName::with_namespace("urn:main", "child"))
+        );
+    }
+
+    #[test]
+    fn target_namespace_mismatch() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:main">
+                <xs:include schemaLocation="other.xsd" />
+            </xs:schema>
+        "#;
+        const OTHER: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:other" />
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+            [Box::new(StaticIncludeResolver("other.xsd", OTHER))];
+        let result = crate::testing::read_schema_with_includes(SCHEMA, None, &include_resolvers);
+        assert!(matches!(
+            result.as_ref().map_err(crate::error::XsdError::kind),
+            Err(crate::error::XsdError::Include(
+                IncludeError::TargetNamespaceMismatch { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn error_location_in_included_document() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:include schemaLocation="other.xsd" />
+            </xs:schema>
+        "#;
+        const OTHER: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:element name="root" type="missing" />
+            </xs:schema>
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+            [Box::new(StaticIncludeResolver("other.xsd", OTHER))];
+        let error =
+            crate::testing::read_schema_with_includes(SCHEMA, Some("main.xsd"), &include_resolvers)
+                .err()
+                .unwrap();
+        let location = error.location().unwrap();
+        assert_eq!(location.document.as_deref(), Some("other.xsd"));
+        assert_eq!(location.line, 3);
+    }
+
+    #[test]
+    fn nested_include_relative_to_including_document() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"
+                       xmlns:m="urn:main" targetNamespace="urn:main">
+                <xs:include schemaLocation="sub/cham.xsd" />
+                <xs:element name="root" type="m:outer" />
+            </xs:schema>
+        "#;
+        const CHAM: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:include schemaLocation="inner.xsd" />
+                <xs:complexType name="outer">
+                    <xs:sequence>
+                        <xs:element name="inner" type="inner" />
+                    </xs:sequence>
+                </xs:complexType>
+            </xs:schema>
+        "#;
+        const INNER: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:simpleType name="inner">
+                    <xs:restriction base="xs:string" />
+                </xs:simpleType>
+            </xs:schema>
+        "#;
+        // inner.xsd is included by sub/cham.xsd, so it is found next to it. Otherwise, the type
+        // definition `inner` would not be resolved.
+        let include_resolvers: [Box<dyn IncludeResolver>; 2] = [
+            Box::new(StaticIncludeResolver("schemas/sub/cham.xsd", CHAM)),
+            Box::new(StaticIncludeResolver("schemas/sub/inner.xsd", INNER)),
+        ];
+        let (schema, _) = crate::testing::read_schema_with_includes(
+            SCHEMA,
+            Some("schemas/root.xsd"),
+            &include_resolvers,
+        )
+        .unwrap();
+        assert_eq!(schema.type_definitions.len(), 2);
+    }
+
+    #[test]
+    fn circular_nested_include() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:include schemaLocation="sub/a.xsd" />
+            </xs:schema>
+        "#;
+        const A: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:include schemaLocation="b.xsd" />
+                <xs:element name="a" type="xs:string" />
+            </xs:schema>
+        "#;
+        const B: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:include schemaLocation="a.xsd" />
+                <xs:element name="b" type="xs:string" />
+            </xs:schema>
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 2] = [
+            Box::new(StaticIncludeResolver("sub/a.xsd", A)),
+            Box::new(StaticIncludeResolver("sub/b.xsd", B)),
+        ];
+        let (schema, _) =
+            crate::testing::read_schema_with_includes(SCHEMA, Some("main.xsd"), &include_resolvers)
+                .unwrap();
+        assert_eq!(schema.element_declarations.len(), 2);
+    }
+
+    #[test]
+    fn include_location() {
+        let include = |schema_location: &str| Include {
+            schema_location: schema_location.to_string(),
+        };
+        assert_eq!(include("a.xsd").location(None), "a.xsd");
+        assert_eq!(include("a.xsd").location(Some("main.xsd")), "a.xsd");
+        assert_eq!(
+            include("a.xsd").location(Some("x/sub/b.xsd")),
+            "x/sub/a.xsd"
+        );
+        assert_eq!(include("/a.xsd").location(Some("x/b.xsd")), "/a.xsd");
+        assert_eq!(
+            include("http://example.org/a.xsd").location(Some("x/b.xsd")),
+            "http://example.org/a.xsd"
+        );
+    }
+
+    #[test]
+    fn redefine_refers_to_original() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"
+                       xmlns:m="urn:main" targetNamespace="urn:main">
+                <xs:redefine schemaLocation="base.xsd">
+                    <xs:complexType name="T">
+                        <xs:complexContent>
+                            <xs:extension base="m:T">
+                                <xs:sequence>
+                                    <xs:element name="extra" type="xs:string" />
+                                </xs:sequence>
+                            </xs:extension>
+                        </xs:complexContent>
+                    </xs:complexType>
+                </xs:redefine>
+                <xs:element name="root" type="m:T" />
+            </xs:schema>
+        "#;
+        const BASE: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:main">
+                <xs:complexType name="T">
+                    <xs:sequence>
+                        <xs:element name="a" type="xs:string" />
+                    </xs:sequence>
+                </xs:complexType>
+            </xs:schema>
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+            [Box::new(StaticIncludeResolver("base.xsd", BASE))];
+        let (schema, components) =
+            crate::testing::read_schema_with_includes(SCHEMA, None, &include_resolvers).unwrap();
+
+        assert_eq!(schema.type_definitions.len(), 1);
+        let redefined = schema.type_definitions[0].complex().unwrap();
+        let root = schema
+            .find_element_by_name(Some("urn:main"), "root", &components)
+            .unwrap();
+        assert_eq!(
+            root.get(&components).type_definition.complex(),
+            Some(redefined)
+        );
+        let original = redefined
+            .get(&components)
+            .base_type_definition
+            .complex()
+            .unwrap();
+        assert_ne!(original, redefined);
+        assert_eq!(
+            original.name(&components),
+            Some(QName::with_namespace("urn:main", "T"))
+        );
+    }
+
+    #[test]
+    fn override_replaces_component() {
+        const SCHEMA: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:main">
+                <xs:override schemaLocation="base.xsd">
+                    <xs:element name="a" type="xs:int" />
+                    <xs:element name="unused" type="xs:int" />
+                </xs:override>
+            </xs:schema>
+        "#;
+        const BASE: &str = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:main">
+                <xs:element name="a" type="xs:string" />
+                <xs:element name="b" type="xs:string" />
+            </xs:schema>
+        "#;
+        let include_resolvers: [Box<dyn IncludeResolver>; 1] =
+            [Box::new(StaticIncludeResolver("base.xsd", BASE))];
+        let (schema, components) =
+            crate::testing::read_schema_with_includes(SCHEMA, None, &include_resolvers).unwrap();
+
+        assert_eq!(schema.element_declarations.len(), 2);
+        let a = schema
+            .find_element_by_name(Some("urn:main"), "a", &components)
+            .unwrap();
+        assert_eq!(
+            a.get(&components).type_definition.name(&components),
+            Some(QName::with_namespace(
+                "http://www.w3.org/2001/XMLSchema",
+                "int"
+            ))
+        );
+        assert!(
+            schema
+                .find_element_by_name(Some("urn:main"), "unused", &components)
+                .is_none()
+        );
+    }
+}
@@ -9,11 +9,14 @@ pub mod attribute_group_def;
 pub mod attribute_use;
 pub mod complex_type_def;
 pub mod constraining_facet;
+pub mod constraints;
+pub mod diagnostics;
 pub mod element_decl;
 pub mod error;
 pub mod fundamental_facet;
 pub mod identity_constraint_def;
 pub mod import;
+pub mod include;
 pub mod model_group;
 pub mod model_group_def;
 pub mod notation_decl;
@@ -25,11 +28,14 @@ pub mod state_machine;
 pub mod type_alternative;
 pub mod validation;
 pub mod wildcard;
+pub mod xpath;
 pub mod xstypes;
 
 mod builtins;
 pub mod components;
 mod mapping_context;
+#[cfg(test)]
+mod testing;
 mod values;
 
 pub use annotation::Annotation;
@@ -53,9 +59,12 @@ pub use type_alternative::TypeAlternative;
 pub use wildcard::Wildcard;
 
 pub use components::{Ref, RefNamed};
+use diagnostics::DiagnosticSink;
 use import::ImportResolver;
+use include::IncludeResolver;
 use mapping_context::MappingContext;
 pub use mapping_context::RootContext;
+use shared::ValueConstraint;
 use xstypes::{Sequence, Set};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -73,21 +82,87 @@ pub enum RegisterBuiltins {
 
 pub use components::SchemaComponentTable;
 
+/// Reads the schema from the schema document `schema`. If given, `document_uri` is used to refer to
+/// the schema document in the source locations of errors.
+///
+/// Mapping stops at the first error; see [`read_schema_with_diagnostics()`] for collecting all
+/// errors instead. Warnings are passed to `diagnostic_sink`.
 pub fn read_schema(
     schema: roxmltree::Document,
+    document_uri: Option<&str>,
     builtin_overwrite: BuiltinOverwriteAction,
     register_builtins: RegisterBuiltins,
     import_resolvers: &[Box<dyn ImportResolver>],
+    include_resolvers: &[Box<dyn IncludeResolver>],
+    diagnostic_sink: &mut dyn DiagnosticSink,
 ) -> Result<(Schema, SchemaComponentTable), error::XsdError> {
-    let schema = schema.root_element();
-    let mut root_context = RootContext::new(builtin_overwrite, import_resolvers);
-    if register_builtins == RegisterBuiltins::Yes {
-        builtins::register_builtins(&mut root_context);
-    }
-    let schema = Schema::map_from_xml(&mut root_context, schema)?;
-    let components = root_context
+    let mut root_context = RootContext::new(
+        builtin_overwrite,
+        import_resolvers,
+        include_resolvers,
+        diagnostic_sink,
+    );
+    let schema = map_schema(&mut root_context, &schema, document_uri, register_builtins)?;
+    let mut components = root_context
         .into_components()
         .convert_to_schema_table()
         .ok_or(error::XsdError::AbsentComponentValue)?;
+    if let Some(error) = ValueConstraint::resolve_values(&mut components)
+        .into_iter()
+        .next()
+    {
+        return Err(error);
+    }
     Ok((schema, components))
 }
+
+/// Like [`read_schema()`], but keeps mapping after recoverable errors (such as unresolved
+/// references, which are replaced by ·xs:anyType· or similar), and returns all errors and warnings
+/// along with the partial schema.
+pub fn read_schema_with_diagnostics(
+    schema: roxmltree::Document,
+    document_uri: Option<&str>,
+    builtin_overwrite: BuiltinOverwriteAction,
+    register_builtins: RegisterBuiltins,
+    import_resolvers: &[Box<dyn ImportResolver>],
+    include_resolvers: &[Box<dyn IncludeResolver>],
+) -> diagnostics::Diagnostics {
+    let mut warnings = Vec::new();
+    let mut root_context = RootContext::new(
+        builtin_overwrite,
+        import_resolvers,
+        include_resolvers,
+        &mut warnings,
+    );
+    root_context.set_error_recovery(true);
+    let result = map_schema(&mut root_context, &schema, document_uri, register_builtins);
+    let mut errors = root_context.take_errors();
+    let schema = result
+        .and_then(|schema| {
+            let mut components = root_context
+                .into_components()
+                .convert_to_schema_table()
+                .ok_or(error::XsdError::AbsentComponentValue)?;
+            errors.extend(ValueConstraint::resolve_values(&mut components));
+            Ok((schema, components))
+        })
+        .map_err(|e| errors.push(e))
+        .ok();
+    diagnostics::Diagnostics {
+        schema,
+        errors,
+        warnings,
+    }
+}
+
+fn map_schema(
+    root_context: &mut RootContext,
+    schema: &roxmltree::Document,
+    document_uri: Option<&str>,
+    register_builtins: RegisterBuiltins,
+) -> Result<Schema, error::XsdError> {
+    if register_builtins == RegisterBuiltins::Yes {
+        builtins::register_builtins(root_context);
+    }
+    Schema::map_from_xml(root_context, schema.root_element(), document_uri)
+}
@@ -1,4 +1,4 @@
-use roxmltree::{Node, NodeId};
+use roxmltree::Node;
 use std::collections::{HashMap, HashSet};
 
 use super::{
@@ -9,22 +9,25 @@ use super::{
         Component, ComponentResolver, ComponentTraits, ConstructionComponentTable, DynamicRef,
         HasArenaContainer, Lookup, LookupTables, RefNamed,
     },
+    diagnostics::{DiagnosticSink, Warning},
     error::XsdError,
     import::{Import, ImportResolver},
+    include::{Include, IncludeResolver},
+    values::actual_value,
     xstypes::QName,
 };
 use crate::BuiltinOverwriteAction;
 
 #[derive(Default)]
 pub(super) struct TopLevelElements<'a, 'input> {
-    simple_type_definitions: HashMap<NodeId, Ref<SimpleTypeDefinition>>,
-    complex_type_definitions: HashMap<NodeId, Ref<ComplexTypeDefinition>>,
-    attribute_declarations: HashMap<NodeId, Ref<AttributeDeclaration>>,
-    element_declarations: HashMap<NodeId, Ref<ElementDeclaration>>,
-    attribute_group_definitions: HashMap<NodeId, Ref<AttributeGroupDefinition>>,
-    model_group_definitions: HashMap<NodeId, Ref<ModelGroupDefinition>>,
-    notation_declarations: HashMap<NodeId, Ref<NotationDeclaration>>,
-    identity_constraint_definitions: HashMap<NodeId, Ref<IdentityConstraintDefinition>>,
+    simple_type_definitions: HashMap<Node<'a, 'input>, Ref<SimpleTypeDefinition>>,
+    complex_type_definitions: HashMap<Node<'a, 'input>, Ref<ComplexTypeDefinition>>,
+    attribute_declarations: HashMap<Node<'a, 'input>, Ref<AttributeDeclaration>>,
+    element_declarations: HashMap<Node<'a, 'input>, Ref<ElementDeclaration>>,
+    attribute_group_definitions: HashMap<Node<'a, 'input>, Ref<AttributeGroupDefinition>>,
+    model_group_definitions: HashMap<Node<'a, 'input>, Ref<ModelGroupDefinition>>,
+    notation_declarations: HashMap<Node<'a, 'input>, Ref<NotationDeclaration>>,
+    identity_constraint_definitions: HashMap<Node<'a, 'input>, Ref<IdentityConstraintDefinition>>,
 
     ref_to_node: HashMap<DynamicRef, Node<'a, 'input>>,
 }
@@ -41,7 +44,7 @@ where
     ComponentTraits: HasArenaContainer<C>,
 {
     fn insert(&mut self, id: Node<'a, 'input>, ref_: Ref<C>);
-    fn get_ref_by_node_id(&self, id: NodeId) -> Ref<C>;
+    fn get_ref_by_node(&self, node: Node<'a, 'input>) -> Ref<C>;
     fn get_node_by_ref(&self, ref_: Ref<C>) -> Option<Node<'a, 'input>>;
 }
 
@@ -49,12 +52,12 @@ macro_rules! impl_top_level {
     ($field_name:ident: $value_type:ty) => {
         impl<'a, 'input> TopLevel<'a, 'input, $value_type> for TopLevelElements<'a, 'input> {
             fn insert(&mut self, node: Node<'a, 'input>, ref_: Ref<$value_type>) {
-                self.$field_name.insert(node.id(), ref_);
+                self.$field_name.insert(node, ref_);
                 self.ref_to_node.insert(ref_.into(), node);
             }
 
-            fn get_ref_by_node_id(&self, id: NodeId) -> Ref<$value_type> {
-                *self.$field_name.get(&id).unwrap()
+            fn get_ref_by_node(&self, node: Node<'a, 'input>) -> Ref<$value_type> {
+                *self.$field_name.get(&node).unwrap()
             }
 
             fn get_node_by_ref(&self, ref_: Ref<$value_type>) -> Option<Node<'a, 'input>> {
@@ -78,8 +81,8 @@ where
     ComponentTraits: HasArenaContainer<Self>,
 {
     /// Map this component from a top-level XML element. `self_ref` is the pre-reserved [`Ref`] for
-    /// this component, `self_node` is the actual element, and `schema_node` is the root `<schema>`
-    /// element.
+    /// this component, `self_node` is the actual element, and `schema_node` is the `<schema>`
+    /// element of the (possibly included) schema document containing it.
     fn map_from_top_level_xml(
         context: &mut MappingContext,
         self_ref: Ref<Self>,
@@ -94,21 +97,53 @@ pub struct RootContext<'a> {
 
     import_resolvers: &'a [Box<dyn ImportResolver>],
     resolved_imports: HashSet<Option<String>>,
+
+    include_resolvers: &'a [Box<dyn IncludeResolver>],
+
+    /// Whether to continue mapping after recoverable errors, see [`MappingContext::recover()`]
+    recover_errors: bool,
+    /// The recoverable errors encountered so far
+    errors: Vec<XsdError>,
+    diagnostic_sink: &'a mut dyn DiagnosticSink,
 }
 
 impl<'a> RootContext<'a> {
+    /// Creates a new context for mapping schemas. Warnings are passed to `diagnostic_sink` as
+    /// they are emitted.
     pub fn new(
         builtin_overwrite: BuiltinOverwriteAction,
         import_resolvers: &'a [Box<dyn ImportResolver>],
+        include_resolvers: &'a [Box<dyn IncludeResolver>],
+        diagnostic_sink: &'a mut dyn DiagnosticSink,
     ) -> Self {
         Self {
             components: ConstructionComponentTable::new(),
             resolver: ComponentResolver::new(builtin_overwrite),
             import_resolvers,
             resolved_imports: HashSet::new(),
+            include_resolvers,
+            recover_errors: false,
+            errors: Vec::new(),
+            diagnostic_sink,
         }
     }
 
+    /// Enables or disables error recovery. With error recovery enabled, mapping continues after
+    /// recoverable errors (such as unresolved references), which are collected instead (see
+    /// [`take_errors()`](Self::take_errors)).
+    pub fn set_error_recovery(&mut self, enabled: bool) {
+        self.recover_errors = enabled;
+    }
+
+    /// Returns the errors recovered from so far, leaving none behind
+    pub fn take_errors(&mut self) -> Vec<XsdError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub(super) fn warn(&mut self, warning: Warning) {
+        self.diagnostic_sink.warning(warning);
+    }
+
     pub(super) fn components(&self) -> &ConstructionComponentTable {
         &self.components
     }
@@ -117,13 +152,15 @@ impl<'a> RootContext<'a> {
         self.components
     }
 
-    /// Wrapper for [`ComponentResolver::register()`]
+    /// Wrapper for [`ComponentResolver::register()`], for registering the built-in components
     pub(super) fn register<R>(&mut self, value: R)
     where
         R: RefNamed + Copy,
         LookupTables: Lookup<R>,
     {
-        self.resolver.register(value, &self.components)
+        self.resolver
+            .register(value, &self.components, self.diagnostic_sink)
+            .expect("Built-in components should not conflict")
     }
 
     pub(super) fn reserve<R>(&mut self) -> Ref<R>
@@ -176,7 +213,33 @@ impl<'a> RootContext<'a> {
         for resolver in self.import_resolvers {
             match resolver.resolve_import(self, import) {
                 Ok(schema) => return Some(schema),
-                Err(e) => eprintln!("Error during import resolution: {:?}", e),
+                Err(error) => self.warn(Warning::ImportResolution {
+                    import: import.clone(),
+                    error,
+                }),
+            }
+        }
+
+        None
+    }
+
+    /// Loads the source text of the schema document referenced by `include`, which is contained
+    /// in the schema document at `base_uri`.
+    pub(super) fn resolve_include(
+        &mut self,
+        include: &Include,
+        base_uri: Option<&str>,
+    ) -> Option<String> {
+        // "It is not an error for the ·actual value· of the schemaLocation [attribute] to fail to
+        // resolve at all, in which case the corresponding inclusion must not be performed."
+        // (https://www.w3.org/TR/xmlschema11-1/#compound-schema)
+        for resolver in self.include_resolvers {
+            match resolver.resolve_include(include, base_uri) {
+                Ok(source) => return Some(source),
+                Err(error) => self.warn(Warning::IncludeResolution {
+                    include: include.clone(),
+                    error,
+                }),
             }
         }
 
@@ -187,19 +250,37 @@ impl<'a> RootContext<'a> {
 pub(super) struct MappingContext<'a, 'b, 'input: 'a, 'p> {
     root: &'p mut RootContext<'b>,
 
-    schema_node: Node<'a, 'input>,
+    /// The target namespace of the schema being mapped, which is shared by all of its schema
+    /// documents (§4.2.3)
+    target_namespace: Option<String>,
+    /// The URIs of the schema documents being mapped, if known, by their <schema> element. They
+    /// are used for the source locations of errors.
+    document_uris: Vec<(Node<'a, 'input>, String)>,
 
     pub top_level_refs: TopLevelElements<'a, 'input>,
     in_progress_top_level: HashSet<DynamicRef>,
+
+    /// The top-level elements currently being mapped, innermost last
+    top_level_stack: Vec<Node<'a, 'input>>,
+    /// Lookup tables for the original components of redefinitions (§4.2.4), keyed by the
+    /// top-level element redefining them
+    redefined_components: HashMap<Node<'a, 'input>, LookupTables>,
 }
 
 impl<'a, 'b, 'input: 'a, 'p> MappingContext<'a, 'b, 'input, 'p> {
-    pub(super) fn new(root: &'p mut RootContext<'b>, schema_node: Node<'a, 'input>) -> Self {
+    pub(super) fn new(
+        root: &'p mut RootContext<'b>,
+        target_namespace: Option<String>,
+        document_uris: Vec<(Node<'a, 'input>, String)>,
+    ) -> Self {
         Self {
             root,
-            schema_node,
+            target_namespace,
+            document_uris,
             top_level_refs: TopLevelElements::default(),
             in_progress_top_level: HashSet::new(),
+            top_level_stack: Vec::new(),
+            redefined_components: HashMap::new(),
         }
     }
 
@@ -207,10 +288,62 @@ impl<'a, 'b, 'input: 'a, 'p> MappingContext<'a, 'b, 'input, 'p> {
         self.root
     }
 
+    /// Attaches the source location of `node` and the kind of the enclosing component to `error`
+    pub(super) fn locate(&self, error: XsdError, node: Node, component: &'static str) -> XsdError {
+        let schema = node.document().root_element();
+        let uri = self
+            .document_uris
+            .iter()
+            .find(|(s, _)| *s == schema)
+            .map(|(_, uri)| uri.as_str());
+        error.at(node, uri, component)
+    }
+
     pub(super) fn components(&self) -> &ConstructionComponentTable {
         &self.root.components
     }
 
+    /// Returns the ·actual value· of the targetNamespace [attribute] of `schema`. A chameleon
+    /// include, i.e. an included schema document without targetNamespace [attribute], takes on
+    /// the target namespace of the including schema document (Appendix F.1):
+    /// "1 Adds a targetNamespace [attribute] to D2, whose value is the same as that of the
+    ///  targetNamespace [attribute] of D1."
+    pub(super) fn target_namespace(&self, schema: Node) -> Result<Option<String>, XsdError> {
+        Ok(schema
+            .attribute("targetNamespace")
+            .map(|v| actual_value::<String>(v, schema))
+            .transpose()?
+            .or_else(|| self.target_namespace.clone()))
+    }
+
+    /// Returns the ·actual value· of the QName `value` of an attribute of `node`, applying the
+    /// chameleon inclusion transformation (Appendix F.1):
+    /// "2 Updates all QName references in D2 that do not have a namespace name so that their
+    ///  namespace names become the ·actual value· of the targetNamespace [attribute]."
+    pub(super) fn qname(&self, value: &str, node: Node) -> Result<QName, XsdError> {
+        let qname = actual_value::<QName>(value, node)?;
+        let is_chameleon = node
+            .document()
+            .root_element()
+            .attribute("targetNamespace")
+            .is_none();
+        Ok(match &self.target_namespace {
+            Some(target_namespace) if is_chameleon && qname.namespace_name().is_none() => {
+                QName::with_namespace(target_namespace, qname.local_name())
+            }
+            _ => qname,
+        })
+    }
+
+    /// Like [`qname()`](Self::qname), for a list of QNames
+    pub(super) fn qnames(&self, value: &str, node: Node) -> Result<Vec<QName>, XsdError> {
+        // NOTE: This assumes a list with whiteSpace="collapse", like `Vec<T>::convert()`
+        value
+            .split_ascii_whitespace()
+            .map(|v| self.qname(v, node))
+            .collect()
+    }
+
     pub(super) fn reserve<R>(&mut self) -> Ref<R>
     where
         R: Component,
@@ -235,14 +368,57 @@ impl<'a, 'b, 'input: 'a, 'p> MappingContext<'a, 'b, 'input, 'p> {
         self.root.insert(ref_, value)
     }
 
-    pub(super) fn register_with_name<R>(&mut self, name: QName, value: R)
+    pub(super) fn register_with_name<R>(&mut self, name: QName, value: R) -> Result<(), XsdError>
     where
         R: Copy,
         LookupTables: Lookup<R>,
     {
-        self.root
-            .resolver
-            .register_with_name(name, value, &self.root.components)
+        self.root.resolver.register_with_name(
+            name,
+            value,
+            &self.root.components,
+            self.root.diagnostic_sink,
+        )
+    }
+
+    /// Handles a recoverable error in `result`, which is located at `node` (see
+    /// [`locate()`](Self::locate)). With error recovery enabled (see
+    /// [`RootContext::set_error_recovery()`]), the error is recorded, and mapping continues with
+    /// the value returned by `fallback`. Otherwise, the error is returned.
+    pub(super) fn recover<T>(
+        &mut self,
+        result: Result<T, XsdError>,
+        node: Node,
+        component: &'static str,
+        fallback: impl FnOnce(&mut Self) -> Result<T, XsdError>,
+    ) -> Result<T, XsdError> {
+        result.or_else(|error| {
+            let error = self.locate(error, node, component);
+            if self.root.recover_errors {
+                self.root.errors.push(error);
+                fallback(self)
+            } else {
+                Err(error)
+            }
+        })
+    }
+
+    /// Registers `value` as the original component redefined by the top-level element
+    /// `redefining`. Instead of being registered globally, it is only visible to
+    /// [`resolve()`](Self::resolve) while mapping the redefining component.
+    pub(super) fn register_redefined<R>(
+        &mut self,
+        redefining: Node<'a, 'input>,
+        name: QName,
+        value: R,
+    ) where
+        R: Copy,
+        LookupTables: Lookup<R>,
+    {
+        self.redefined_components
+            .entry(redefining)
+            .or_default()
+            .register_value_for_lookup(name, value);
     }
 
     pub(super) fn resolve<R>(&self, key: &QName) -> Option<R>
@@ -250,10 +426,24 @@ impl<'a, 'b, 'input: 'a, 'p> MappingContext<'a, 'b, 'input, 'p> {
         R: Copy,
         LookupTables: Lookup<R>,
     {
-        self.root.resolve(key)
+        // Within a redefinition, the self-reference is to the original component: "[...] the
+        // <redefine>d components [...] with the same name [...] [as] their base type definition"
+        // (§4.2.4).
+        // NOTE: This applies to all references to the own name within the redefining component,
+        //   while strictly, it should only apply to the base type definition in case of type
+        //   definitions.
+        self.top_level_stack
+            .last()
+            .and_then(|node| self.redefined_components.get(node))
+            .and_then(|lookup_tables| lookup_tables.lookup_value(key))
+            .or_else(|| self.root.resolve(key))
     }
 
-    fn ensure_top_level_is_present<C>(&mut self, ref_: Ref<C>, node: Node) -> Result<(), XsdError>
+    fn ensure_top_level_is_present<C>(
+        &mut self,
+        ref_: Ref<C>,
+        node: Node<'a, 'input>,
+    ) -> Result<(), XsdError>
     where
         C: Component + TopLevelMappable + 'static,
         ComponentTraits: HasArenaContainer<C>,
@@ -267,7 +457,11 @@ impl<'a, 'b, 'input: 'a, 'p> MappingContext<'a, 'b, 'input, 'p> {
         if !self.root.components.is_present(ref_) {
             self.in_progress_top_level.insert(dynref);
 
-            C::map_from_top_level_xml(self, ref_, node, self.schema_node)?;
+            let schema_node = node.document().root_element();
+            self.top_level_stack.push(node);
+            C::map_from_top_level_xml(self, ref_, node, schema_node)
+                .map_err(|e| self.locate(e, node, C::DISPLAY_NAME))?;
+            self.top_level_stack.pop();
             assert!(self.root.components.is_present(ref_));
 
             let was_removed = self.in_progress_top_level.remove(&dynref);
@@ -290,13 +484,16 @@ impl<'a, 'b, 'input: 'a, 'p> MappingContext<'a, 'b, 'input, 'p> {
         Ok(ref_.get(&self.root.components))
     }
 
-    pub(super) fn request_ref_by_node<C>(&mut self, node: Node) -> Result<Ref<C>, XsdError>
+    pub(super) fn request_ref_by_node<C>(
+        &mut self,
+        node: Node<'a, 'input>,
+    ) -> Result<Ref<C>, XsdError>
     where
         C: Component + TopLevelMappable + 'static,
         ComponentTraits: HasArenaContainer<C>,
         TopLevelElements<'a, 'input>: TopLevel<'a, 'input, C>,
     {
-        let ref_: Ref<C> = self.top_level_refs.get_ref_by_node_id(node.id());
+        let ref_: Ref<C> = self.top_level_refs.get_ref_by_node(node);
         self.ensure_top_level_is_present(ref_, node)?;
         Ok(ref_)
     }
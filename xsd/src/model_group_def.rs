@@ -21,21 +21,23 @@ pub struct ModelGroupDefinition {
 }
 
 impl NamedXml for ModelGroupDefinition {
-    fn get_name_from_xml(group: Node, schema: Node) -> QName {
+    fn get_name_from_xml(
+        context: &MappingContext,
+        group: Node,
+        schema: Node,
+    ) -> Result<QName, XsdError> {
         // {name} The ·actual value· of the name [attribute]
         let name = group
             .attribute("name")
-            .map(|n| actual_value::<NCName>(n, group))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(group, "name"))
+            .and_then(|n| actual_value::<NCName>(n, group))?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the <schema> ancestor element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|t| actual_value::<AnyURI>(t, schema));
+        let target_namespace = context.target_namespace(schema)?;
 
-        QName::with_optional_namespace(target_namespace, name)
+        Ok(QName::with_optional_namespace(target_namespace, name))
     }
 }
 
@@ -50,7 +52,8 @@ impl ModelGroupDefinition {
     ) -> Result<Ref<Self>, XsdError> {
         // {name}, {target namespace}
         //   [see `get_name_from_xml()` above.]
-        let (target_namespace, name) = Self::get_name_from_xml(group, schema).into_parts();
+        let (target_namespace, name) =
+            Self::get_name_from_xml(context, group, schema)?.into_parts();
 
         let self_ref = tlref.unwrap_or_else(|| context.reserve());
 
@@ -61,8 +64,12 @@ impl ModelGroupDefinition {
         let choice_child = group.children().find(|n| n.tag_name().name() == "choice");
         let sequence_child = group.children().find(|n| n.tag_name().name() == "sequence");
         let particle = all_child.xor(choice_child).xor(sequence_child);
-        let particle = particle
-            .expect("Model group definition needs to have EXACTLY one of all, choice or sequence");
+        let particle = particle.ok_or_else(|| {
+            XsdError::invalid_content(
+                group,
+                "expected exactly one of <all>, <choice> or <sequence>",
+            )
+        })?;
 
         let model_group = Particle::map_from_xml_model_group_term(
             context,
@@ -20,21 +20,23 @@ pub struct NotationDeclaration {
 }
 
 impl NamedXml for NotationDeclaration {
-    fn get_name_from_xml(notation: Node, schema: Node) -> QName {
+    fn get_name_from_xml(
+        context: &MappingContext,
+        notation: Node,
+        schema: Node,
+    ) -> Result<QName, XsdError> {
         // {name} The ·actual value· of the name [attribute]
         let name = notation
             .attribute("name")
-            .map(|v| actual_value::<NCName>(v, notation))
-            .unwrap();
+            .ok_or_else(|| XsdError::missing_attribute(notation, "name"))
+            .and_then(|v| actual_value::<NCName>(v, notation))?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the <schema> ancestor element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<AnyURI>(v, notation));
+        let target_namespace = context.target_namespace(schema)?;
 
-        QName::with_optional_namespace(target_namespace, name)
+        Ok(QName::with_optional_namespace(target_namespace, name))
     }
 }
 
@@ -52,19 +54,22 @@ impl NotationDeclaration {
 
         let self_ref = tlref.unwrap_or_else(|| context.reserve());
 
-        let (target_namespace, name) = Self::get_name_from_xml(notation, schema).into_parts();
+        let (target_namespace, name) =
+            Self::get_name_from_xml(context, notation, schema)?.into_parts();
 
         // {system identifier}
         //   The ·actual value· of the system [attribute], if present, otherwise ·absent·.
         let system_identifier = notation
             .attribute("system")
-            .map(|v| actual_value::<AnyURI>(v, notation));
+            .map(|v| actual_value::<AnyURI>(v, notation))
+            .transpose()?;
 
         // {public identifier}
         //   The ·actual value· of the public [attribute], if present, otherwise ·absent·.
         let public_identifier = notation
             .attribute("public")
-            .map(|v| actual_value::<AnyURI>(v, notation));
+            .map(|v| actual_value::<AnyURI>(v, notation))
+            .transpose()?;
 
         // {annotations}
         //   The ·annotation mapping· of the <notation> element, as defined in XML Representation
@@ -7,7 +7,7 @@ use crate::{
     model_group::Compositor,
     shared::Term,
     values::actual_value,
-    xstypes::{QName, Sequence},
+    xstypes::Sequence,
 };
 use roxmltree::Node;
 
@@ -20,12 +20,30 @@ pub struct Particle {
     pub(crate) annotations: Option<Sequence<Ref<Annotation>>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MaxOccurs {
     Unbounded,
     Count(u64), // TODO NonZeroU64
 }
 
+/// `Unbounded` is greater than any count
+impl Ord for MaxOccurs {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (Self::Unbounded, Self::Unbounded) => std::cmp::Ordering::Equal,
+            (Self::Unbounded, Self::Count(_)) => std::cmp::Ordering::Greater,
+            (Self::Count(_), Self::Unbounded) => std::cmp::Ordering::Less,
+            (Self::Count(a), Self::Count(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for MaxOccurs {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl MaxOccurs {
     pub(crate) fn add(&self, other: &Self) -> Self {
         match (self, other) {
@@ -40,13 +58,6 @@ impl MaxOccurs {
             (Self::Count(a), Self::Count(b)) => Self::Count(a * b),
         }
     }
-
-    pub(crate) fn max(&self, other: &Self) -> Self {
-        match (self, other) {
-            (Self::Unbounded, _) | (_, Self::Unbounded) => Self::Unbounded,
-            (Self::Count(a), Self::Count(b)) => Self::Count((*a).max(*b)),
-        }
-    }
 }
 
 impl Particle {
@@ -58,17 +69,14 @@ impl Particle {
         // 1 Its {min occurs} is 0.
         // 2 Its {term} is a group and the minimum part of the effective total range of that group
         //   [...] is 0.
-        self.min_occurs == 0
-            || (self.term.is_model_group() && self.effective_total_range(components).0 == 0)
+        self.min_occurs == 0 || self.effective_total_range(components).0 == 0
     }
 
-    /// Schema Component Constraint: Effective Total Range
-    ///
-    /// # Panics
-    /// Panics if the particle's term is not a model group.
+    /// Schema Component Constraint: Effective Total Range. For a particle whose {term} is not a
+    /// model group, this is its own occurrence range, as counted for the particles of a group.
     pub fn effective_total_range(&self, components: &impl ComponentTable) -> (u64, MaxOccurs) {
         let Term::ModelGroup(group) = self.term else {
-            panic!("effective_total_range needs term to be a model group");
+            return (self.min_occurs, self.max_occurs.clone());
         };
         let group = group.get(components);
 
@@ -79,11 +87,7 @@ impl Particle {
                 let mut max_acc = MaxOccurs::Count(0);
                 for particle in group.particles.iter() {
                     let particle = particle.get(components);
-                    let (min, max) = if particle.term.is_model_group() {
-                        particle.effective_total_range(components)
-                    } else {
-                        (particle.min_occurs, particle.max_occurs.clone())
-                    };
+                    let (min, max) = particle.effective_total_range(components);
                     min_acc += min;
                     max_acc = max_acc.add(&max);
                 }
@@ -91,18 +95,16 @@ impl Particle {
             }
             Compositor::Choice => {
                 // Pt. 2, 3.8.6.6 Effective Total Range (choice)
-                let mut min_acc = 0;
+                let mut min_acc = None::<u64>;
                 let mut max_acc = MaxOccurs::Count(0);
                 for particle in group.particles.iter() {
                     let particle = particle.get(components);
-                    let (min, max) = if particle.term.is_model_group() {
-                        particle.effective_total_range(components)
-                    } else {
-                        (particle.min_occurs, particle.max_occurs.clone())
-                    };
-                    min_acc = min_acc.min(min);
-                    max_acc = max_acc.max(&max);
+                    let (min, max) = particle.effective_total_range(components);
+                    min_acc = Some(min_acc.map_or(min, |acc| acc.min(min)));
+                    max_acc = max_acc.max(max);
                 }
+                // The minimum of an empty choice is 0
+                let min_acc = min_acc.unwrap_or(0);
                 (self.min_occurs * min_acc, self.max_occurs.mul(&max_acc))
             }
         }
@@ -130,22 +132,32 @@ impl Particle {
         //   <any>, <group> or <element> items among the [children], in order.
         let particles = particle
             .children()
-            .filter_map(|child| match child.tag_name().name() {
-                "all" | "choice" | "sequence" => Some(Self::map_from_xml_model_group(
-                    context,
-                    child,
-                    schema,
-                    element_parent,
-                )),
-                "any" => Some(Particle::map_from_xml_wildcard_any(context, child, schema)),
-                "group" => Some(Particle::map_from_xml_group_reference(context, child)),
-                "element" => Some(ElementDeclaration::map_from_xml_local(
-                    context,
-                    child,
-                    schema,
-                    element_parent,
-                )),
-                _ => None,
+            .filter_map(|child| {
+                let (result, component) = match child.tag_name().name() {
+                    "all" | "choice" | "sequence" => (
+                        Self::map_from_xml_model_group(context, child, schema, element_parent),
+                        ModelGroup::DISPLAY_NAME,
+                    ),
+                    "any" => (
+                        Particle::map_from_xml_wildcard_any(context, child, schema),
+                        Wildcard::DISPLAY_NAME,
+                    ),
+                    "group" => (
+                        Particle::map_from_xml_group_reference(context, child),
+                        Particle::DISPLAY_NAME,
+                    ),
+                    "element" => (
+                        ElementDeclaration::map_from_xml_local(
+                            context,
+                            child,
+                            schema,
+                            element_parent,
+                        ),
+                        ElementDeclaration::DISPLAY_NAME,
+                    ),
+                    _ => return None,
+                };
+                Some(result.map_err(|e| context.locate(e, child, component)))
             })
             .collect::<Result<Vec<_>, _>>()?;
 
@@ -179,6 +191,7 @@ impl Particle {
         let min_occurs = particle
             .attribute("minOccurs")
             .map(|min_occurs| actual_value::<u64>(min_occurs, particle))
+            .transpose()?
             .unwrap_or(1);
 
         // {max occurs}
@@ -188,11 +201,12 @@ impl Particle {
             .attribute("maxOccurs")
             .map(|max_occurs| {
                 if max_occurs == "unbounded" {
-                    MaxOccurs::Unbounded
+                    Ok(MaxOccurs::Unbounded)
                 } else {
-                    MaxOccurs::Count(actual_value::<u64>(max_occurs, particle))
+                    actual_value::<u64>(max_occurs, particle).map(MaxOccurs::Count)
                 }
             })
+            .transpose()?
             .unwrap_or(MaxOccurs::Count(1));
 
         // {term}
@@ -227,6 +241,7 @@ impl Particle {
         let min_occurs = group
             .attribute("minOccurs")
             .map(|v| actual_value::<u64>(v, group))
+            .transpose()?
             .unwrap_or(1);
 
         // unbounded, if the maxOccurs [attribute] equals unbounded, otherwise the ·actual value·
@@ -235,18 +250,33 @@ impl Particle {
             .attribute("maxOccurs")
             .map(|v| {
                 if v == "unbounded" {
-                    MaxOccurs::Unbounded
+                    Ok(MaxOccurs::Unbounded)
                 } else {
-                    MaxOccurs::Count(actual_value::<u64>(v, group))
+                    actual_value::<u64>(v, group).map(MaxOccurs::Count)
                 }
             })
+            .transpose()?
             .unwrap_or(MaxOccurs::Count(1));
 
         // {term}: The {model group} of the model group definition ·resolved· to by the ·actual value· of the ref [attribute]
-        // TODO: handle missing ref?
-        let ref_ = actual_value::<QName>(group.attribute("ref").unwrap(), group);
-        let ref_model_group_definition: Ref<ModelGroupDefinition> = context.resolve(&ref_).unwrap(); // TODO
-        let term = Term::ModelGroup(context.request(ref_model_group_definition)?.model_group);
+        let ref_ = group
+            .attribute("ref")
+            .ok_or_else(|| XsdError::missing_attribute(group, "ref"))
+            .and_then(|v| context.qname(v, group))?;
+        let result = context
+            .resolve::<Ref<ModelGroupDefinition>>(&ref_)
+            .ok_or(XsdError::UnresolvedGroupReference(ref_))
+            .map(Some);
+        let model_group = match context.recover(result, group, Self::DISPLAY_NAME, |_| Ok(None))? {
+            Some(model_group_def) => context.request(model_group_def)?.model_group,
+            // An unresolved group is replaced by an empty sequence when recovering from errors
+            None => context.create(ModelGroup {
+                annotations: Sequence::new(),
+                compositor: Compositor::Sequence,
+                particles: Sequence::new(),
+            }),
+        };
+        let term = Term::ModelGroup(model_group);
 
         // The ·annotation mapping· of the <group> element, as defined in XML Representation of
         // Annotation Schema Components (§3.15.2).
@@ -271,12 +301,13 @@ impl Particle {
         // TODO handle minOccurs=maxOccurs=0
         assert_eq!(any.tag_name().name(), "any");
 
-        let wildcard = Wildcard::map_from_xml_any(context, any, schema);
+        let wildcard = Wildcard::map_from_xml_any(context, any, schema)?;
 
         // The ·actual value· of the minOccurs [attribute], if present, otherwise 1.
         let min_occurs = any
             .attribute("minOccurs")
             .map(|v| actual_value::<u64>(v, any))
+            .transpose()?
             .unwrap_or(1);
 
         // unbounded, if maxOccurs = unbounded, otherwise the ·actual value· of the maxOccurs
@@ -285,11 +316,12 @@ impl Particle {
             .attribute("maxOccurs")
             .map(|v| {
                 if v == "unbounded" {
-                    MaxOccurs::Unbounded
+                    Ok(MaxOccurs::Unbounded)
                 } else {
-                    MaxOccurs::Count(actual_value::<u64>(v, any))
+                    actual_value::<u64>(v, any).map(MaxOccurs::Count)
                 }
             })
+            .transpose()?
             .unwrap_or(MaxOccurs::Count(1));
 
         // A wildcard as above.
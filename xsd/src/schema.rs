@@ -7,17 +7,20 @@ use crate::{
         Component, ComponentTable, ComponentTraits, HasArenaContainer, Lookup, LookupTables,
         NamedXml,
     },
+    diagnostics::Warning,
     element_decl::ElementDeclaration,
     identity_constraint_def::IdentityConstraintDefinition,
     import::Import,
+    include::{Composition, Include, IncludedSchema, Modifications, collect_included_schemas},
     mapping_context::{RootContext, TopLevel, TopLevelElements},
     model_group_def::ModelGroupDefinition,
     notation_decl::NotationDeclaration,
     shared::TypeDefinition,
     values::actual_value,
-    xstypes::{Sequence, Set},
+    xstypes::{QName, Sequence, Set},
 };
 use roxmltree::Node;
+use typed_arena::Arena;
 
 /// Schema Component: Schema, a kind of Annotated Component (§3.17)
 #[derive(Clone, Debug)]
@@ -37,17 +40,73 @@ pub struct Schema {
 }
 
 impl Schema {
+    const DISPLAY_NAME: &'static str = "Schema";
+
+    /// Maps the schema document whose <schema> element is `schema`. If given, `document_uri` is
+    /// used to refer to the schema document in the source locations of errors.
     pub fn map_from_xml(
         root_context: &mut RootContext,
         schema: Node,
+        document_uri: Option<&str>,
     ) -> Result<Self, crate::error::XsdError> {
         assert_eq!(schema.tag_name().name(), "schema");
 
         let target_namespace = schema
             .attribute("targetNamespace")
-            .map(|s| actual_value(s, schema));
+            .map(|s| actual_value(s, schema))
+            .transpose()
+            .map_err(|e| e.at(schema, document_uri, Self::DISPLAY_NAME))?;
+
+        // § 4.2.3 Assembling a schema for a single target namespace from multiple schema
+        //   definition documents (<include>)
+        // "[...] the schema corresponding to <schema> contains not only the components
+        // corresponding to its definition and declaration [children], but also all the components
+        // of all the XML Schemas corresponding to any <include>d schema documents."
+        // The same holds for <redefine> (§4.2.4) and <override> (§4.2.5), except that the
+        // components are modified first.
+        let sources = Arena::new();
+        let included_schemas =
+            collect_included_schemas(root_context, schema, document_uri, &sources)
+                .map_err(|e| e.at(schema, document_uri, Self::DISPLAY_NAME))?;
+        // All schema documents contributing to this schema, starting with `schema` itself
+        let schema_documents = std::iter::once(schema)
+            .chain(included_schemas.iter().map(|i| i.document.root_element()))
+            .collect::<Vec<_>>();
+        let document_uris = document_uri
+            .map(|uri| (schema, uri.to_string()))
+            .into_iter()
+            .chain(
+                included_schemas
+                    .iter()
+                    .map(|i| (i.document.root_element(), i.location.clone())),
+            )
+            .collect();
 
-        let mut context = MappingContext::new(root_context, schema);
+        Self::map_from_documents(
+            root_context,
+            target_namespace,
+            &schema_documents,
+            &included_schemas,
+            document_uris,
+        )
+    }
+
+    /// Maps the schema made up of `schema_documents`, where `schema_documents[0]` is the including
+    /// schema document, and the other ones are its `included_schemas`. `document_uris` are the
+    /// known URIs of the schema documents.
+    fn map_from_documents<'a, 'input: 'a>(
+        root_context: &mut RootContext,
+        target_namespace: Option<String>,
+        schema_documents: &[Node<'a, 'input>],
+        included_schemas: &[IncludedSchema],
+        document_uris: Vec<(Node<'a, 'input>, String)>,
+    ) -> Result<Self, crate::error::XsdError> {
+        let schema = schema_documents[0];
+        let mut context =
+            MappingContext::new(root_context, target_namespace.clone(), document_uris);
+        let modifications = Modifications::compute(&context, schema_documents, included_schemas)
+            .map_err(|e| context.locate(e, schema, Self::DISPLAY_NAME))?;
+        let top_level_elements = modifications.top_level_elements(schema_documents);
 
         let mut type_definitions = Set::new();
         let mut attribute_declarations = Set::new();
@@ -57,16 +116,17 @@ impl Schema {
         let mut notation_declarations = Set::new();
         let mut identity_constraint_definitions = Set::new();
 
-        for import in schema
-            .children()
-            .filter(|c| c.tag_name().name() == Import::TAG_NAME)
-        {
-            let import = Import::map_from_xml(import, schema)?;
+        // All schema documents share the target namespace, including chameleon includes
+        for import in schema_documents.iter().flat_map(|schema| {
+            schema
+                .children()
+                .filter(|c| c.tag_name().name() == Import::TAG_NAME)
+        }) {
+            let import = Import::map_from_xml(import, target_namespace.as_deref())
+                .map_err(|e| context.locate(e, import, Self::DISPLAY_NAME))?;
+            // NOTE: Import failure is not an error, so it is only reported as a warning.
             let child_schema = context.root_mut().resolve_import(&import);
 
-            // NOTE: Import failure is not an error, but there should be a way to emit a
-            // notification (and corresponding errors) to the user.
-
             if let Some(child_schema) = child_schema {
                 type_definitions.extend(child_schema.type_definitions);
                 attribute_declarations.extend(child_schema.attribute_declarations);
@@ -77,107 +137,195 @@ impl Schema {
                 identity_constraint_definitions
                     .extend(child_schema.identity_constraint_definitions);
             } else {
-                eprintln!("Failed to resolve import: {:?}", import);
+                context.root_mut().warn(Warning::UnresolvedImport(import));
+            }
+        }
+
+        /// Registers a top-level component, unless it is redefined, in which case it is only
+        /// visible to the redefining component.
+        fn register_top_level<'a, 'input: 'a, R>(
+            context: &mut MappingContext<'a, '_, 'input, '_>,
+            modifications: &Modifications<'a, 'input>,
+            node: Node<'a, 'input>,
+            name: QName,
+            value: R,
+        ) -> Result<(), crate::error::XsdError>
+        where
+            R: Copy,
+            LookupTables: Lookup<R>,
+        {
+            if let Some(&redefining) = modifications.redefined.get(&node) {
+                context.register_redefined(redefining, name, value);
+                Ok(())
+            } else {
+                context.register_with_name(name, value)
             }
         }
 
         fn reserve_top_level<'a, 'input: 'a, C>(
             context: &mut MappingContext<'a, '_, 'input, '_>,
+            modifications: &Modifications<'a, 'input>,
             node: Node<'a, 'input>,
             schema: Node,
-        ) where
+        ) -> Result<(), crate::error::XsdError>
+        where
             C: Component + NamedXml,
             ComponentTraits: HasArenaContainer<C>,
             LookupTables: Lookup<Ref<C>>,
             TopLevelElements<'a, 'input>: TopLevel<'a, 'input, C>,
         {
-            let name = C::get_name_from_xml(node, schema);
+            let name = C::get_name_from_xml(context, node, schema)
+                .map_err(|e| context.locate(e, node, C::DISPLAY_NAME))?;
             let ref_ = context.reserve::<C>();
-            context.register_with_name(name, ref_);
+            // A duplicate component is still mapped, it just can't be referenced by name
+            let result = register_top_level(context, modifications, node, name, ref_);
+            context.recover(result, node, C::DISPLAY_NAME, |_| Ok(()))?;
             context.top_level_refs.insert(node, ref_);
+            Ok(())
         }
 
-        for top_level_element in schema.children().filter(|e| e.is_element()) {
+        for &top_level_element in &top_level_elements {
+            let schema = top_level_element.document().root_element();
             match top_level_element.tag_name().name() {
                 SimpleTypeDefinition::TAG_NAME => {
                     // TODO unnamed top level allowed?
-                    let name = SimpleTypeDefinition::name_from_xml(top_level_element, schema)
-                        .ok_or(crate::error::XsdError::UnnamedTopLevelElement)?;
+                    let locate = |context: &MappingContext, e| {
+                        context.locate(e, top_level_element, SimpleTypeDefinition::DISPLAY_NAME)
+                    };
+                    let name =
+                        SimpleTypeDefinition::name_from_xml(&context, top_level_element, schema)
+                            .and_then(|name| {
+                                name.ok_or(crate::error::XsdError::UnnamedTopLevelElement)
+                            })
+                            .map_err(|e| locate(&context, e))?;
                     let std_ref = context.reserve();
-                    context.register_with_name(name, TypeDefinition::Simple(std_ref));
+                    let result = register_top_level(
+                        &mut context,
+                        &modifications,
+                        top_level_element,
+                        name,
+                        TypeDefinition::Simple(std_ref),
+                    );
+                    context.recover(
+                        result,
+                        top_level_element,
+                        SimpleTypeDefinition::DISPLAY_NAME,
+                        |_| Ok(()),
+                    )?;
                     context.top_level_refs.insert(top_level_element, std_ref);
                 }
                 ComplexTypeDefinition::TAG_NAME => {
-                    let name = ComplexTypeDefinition::name_from_xml(top_level_element, schema)
-                        .ok_or(crate::error::XsdError::UnnamedTopLevelElement)?;
+                    let locate = |context: &MappingContext, e| {
+                        context.locate(e, top_level_element, ComplexTypeDefinition::DISPLAY_NAME)
+                    };
+                    let name =
+                        ComplexTypeDefinition::name_from_xml(&context, top_level_element, schema)
+                            .and_then(|name| {
+                                name.ok_or(crate::error::XsdError::UnnamedTopLevelElement)
+                            })
+                            .map_err(|e| locate(&context, e))?;
                     let ctd_ref = context.reserve();
-                    context.register_with_name(name, TypeDefinition::Complex(ctd_ref));
+                    let result = register_top_level(
+                        &mut context,
+                        &modifications,
+                        top_level_element,
+                        name,
+                        TypeDefinition::Complex(ctd_ref),
+                    );
+                    context.recover(
+                        result,
+                        top_level_element,
+                        ComplexTypeDefinition::DISPLAY_NAME,
+                        |_| Ok(()),
+                    )?;
                     context.top_level_refs.insert(top_level_element, ctd_ref);
                 }
                 AttributeDeclaration::TAG_NAME => {
                     reserve_top_level::<AttributeDeclaration>(
                         &mut context,
+                        &modifications,
                         top_level_element,
                         schema,
-                    );
+                    )?;
                 }
                 ElementDeclaration::TAG_NAME => {
                     reserve_top_level::<ElementDeclaration>(
                         &mut context,
+                        &modifications,
                         top_level_element,
                         schema,
-                    );
+                    )?;
                 }
                 AttributeGroupDefinition::TAG_NAME => {
                     reserve_top_level::<AttributeGroupDefinition>(
                         &mut context,
+                        &modifications,
                         top_level_element,
                         schema,
-                    );
+                    )?;
                 }
                 ModelGroupDefinition::TAG_NAME => {
                     reserve_top_level::<ModelGroupDefinition>(
                         &mut context,
+                        &modifications,
                         top_level_element,
                         schema,
-                    );
+                    )?;
                 }
                 NotationDeclaration::TAG_NAME => {
                     reserve_top_level::<NotationDeclaration>(
                         &mut context,
+                        &modifications,
                         top_level_element,
                         schema,
-                    );
+                    )?;
                 }
                 IdentityConstraintDefinition::KEY_TAG_NAME
                 | IdentityConstraintDefinition::KEYREF_TAG_NAME
                 | IdentityConstraintDefinition::UNIQUE_TAG_NAME => {
                     reserve_top_level::<IdentityConstraintDefinition>(
                         &mut context,
+                        &modifications,
                         top_level_element,
                         schema,
-                    );
+                    )?;
                 }
 
                 // These tags don't directly contribute top-level components
-                Annotation::TAG_NAME | Import::TAG_NAME => {}
+                Annotation::TAG_NAME
+                | Import::TAG_NAME
+                | Include::TAG_NAME
+                | Composition::REDEFINE_TAG_NAME
+                | Composition::OVERRIDE_TAG_NAME => {}
+
+                // Applied by the complex type definitions' {content type} mapping
+                "defaultOpenContent" => {}
 
                 _ => {
-                    return Err(crate::error::XsdError::UnknownTopLevelElement(
+                    let error = crate::error::XsdError::UnknownTopLevelElement(
                         top_level_element.tag_name().name().into(),
-                    ));
+                    );
+                    context.recover(Err(error), top_level_element, Self::DISPLAY_NAME, |_| {
+                        Ok(())
+                    })?;
+                    continue;
                 }
             }
 
-            top_level_element.descendants().for_each(|e| {
+            for e in top_level_element.descendants() {
                 // "The identity-constraint definitions corresponding to all the <key>, <keyref>,
                 // and <unique> element information items *anywhere within* the [children], if any
                 // [...]" - Spec pt.1, 3.17.2 XML Representation of Schema Components,
                 // {identity-constraint definitions} representation
                 if IdentityConstraintDefinition::TAG_NAMES.contains(&e.tag_name().name()) {
-                    reserve_top_level::<IdentityConstraintDefinition>(&mut context, e, schema);
+                    reserve_top_level::<IdentityConstraintDefinition>(
+                        &mut context,
+                        &modifications,
+                        e,
+                        schema,
+                    )?;
                 }
-            });
+            }
         }
 
         // {type definitions}
@@ -188,27 +336,36 @@ impl Schema {
         //   (see Overriding component definitions (<override>) (§4.2.5)), <redefine> (see
         //   Including modified component definitions (<redefine>) (§4.2.4)), and <import> (see
         //   References to schema components across namespaces (<import>) (§4.2.6)).
-        for simple_type in schema
-            .children()
+        for simple_type in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == SimpleTypeDefinition::TAG_NAME)
         {
             let simple_type_def = context.request_ref_by_node(simple_type)?;
-            type_definitions.push(TypeDefinition::Simple(simple_type_def));
+            // The original components of redefinitions are only part of the schema through
+            // the redefining components
+            if !modifications.redefined.contains_key(&simple_type) {
+                type_definitions.push(TypeDefinition::Simple(simple_type_def));
+            }
         }
-        for complex_type in schema
-            .children()
+        for complex_type in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == ComplexTypeDefinition::TAG_NAME)
         {
             let complex_type_def = context.request_ref_by_node(complex_type)?;
-            type_definitions.push(TypeDefinition::Complex(complex_type_def));
+            if !modifications.redefined.contains_key(&complex_type) {
+                type_definitions.push(TypeDefinition::Complex(complex_type_def));
+            }
         }
 
         // {attribute declarations}
         //   The (top-level) attribute declarations corresponding to all the <attribute> element
         //   information items in the [children], if any, plus any declarations brought in via
         //   <include>, <override>, <redefine>, and <import>.
-        for attribute_decl in schema
-            .children()
+        for attribute_decl in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == AttributeDeclaration::TAG_NAME)
         {
             let attribute_decl = context.request_ref_by_node(attribute_decl)?;
@@ -219,8 +376,9 @@ impl Schema {
         //   The (top-level) element declarations corresponding to all the <element> element
         //   information items in the [children], if any, plus any declarations brought in via
         //   <include>, <override>, <redefine>, and <import>.
-        for element_decl in schema
-            .children()
+        for element_decl in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == ElementDeclaration::TAG_NAME)
         {
             let element_decl = context.request_ref_by_node(element_decl)?;
@@ -231,32 +389,39 @@ impl Schema {
         //   The attribute group definitions corresponding to all the <attributeGroup> element
         //   information items in the [children], if any, plus any definitions brought in via
         //   <include>, <override>, <redefine>, and <import>.
-        for attribute_group_def in schema
-            .children()
+        for attribute_group_def in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == AttributeGroupDefinition::TAG_NAME)
         {
-            let attribute_group_def = context.request_ref_by_node(attribute_group_def)?;
-            attribute_group_definitions.push(attribute_group_def);
+            let attribute_group_def_ref = context.request_ref_by_node(attribute_group_def)?;
+            if !modifications.redefined.contains_key(&attribute_group_def) {
+                attribute_group_definitions.push(attribute_group_def_ref);
+            }
         }
 
         // {model group definitions}
         //   The model group definitions corresponding to all the <group> element information items
         //   in the [children], if any, plus any definitions brought in via <include>, <redefine>
         //   and <import>.
-        for model_group_def in schema
-            .children()
+        for model_group_def in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == ModelGroupDefinition::TAG_NAME)
         {
-            let model_group_def = context.request_ref_by_node(model_group_def)?;
-            model_group_definitions.push(model_group_def);
+            let model_group_def_ref = context.request_ref_by_node(model_group_def)?;
+            if !modifications.redefined.contains_key(&model_group_def) {
+                model_group_definitions.push(model_group_def_ref);
+            }
         }
 
         // {notation declarations}
         //   The notation declarations corresponding to all the <notation> element information
         //   items in the [children], if any, plus any declarations brought in via <include>,
         //   <override>, <redefine>, and <import>.
-        for notation_decl in schema
-            .children()
+        for notation_decl in top_level_elements
+            .iter()
+            .copied()
             .filter(|e| e.tag_name().name() == NotationDeclaration::TAG_NAME)
         {
             let notation_decl = context.request_ref_by_node(notation_decl)?;
@@ -267,8 +432,9 @@ impl Schema {
         //   The identity-constraint definitions corresponding to all the <key>, <keyref>, and
         //   <unique> element information items anywhere within the [children], if any, plus any
         //   definitions brought in via <include>, <override>, <redefine>, and <import>.
-        for icd in schema
-            .descendants()
+        for icd in top_level_elements
+            .iter()
+            .flat_map(|e| e.descendants())
             .filter(|e| IdentityConstraintDefinition::TAG_NAMES.contains(&e.tag_name().name()))
         {
             let icd = context.request_ref_by_node(icd)?;
@@ -327,3 +493,209 @@ impl Schema {
 }
 
 // TODO: impl AnnotatedComponent
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BuiltinOverwriteAction, RegisterBuiltins, TypeDefinition,
+        builtins::XS_ANY_TYPE_NAME,
+        components::RefNamed,
+        diagnostics::{Diagnostics, Warning},
+        error::XsdError,
+        xstypes::QName,
+    };
+    use roxmltree::Document;
+
+    fn read(schema: &str) -> Result<(), XsdError> {
+        crate::testing::read_schema(schema).map(|_| ())
+    }
+
+    fn read_with_diagnostics(schema: &str) -> Diagnostics {
+        let schema = Document::parse(schema).unwrap();
+        crate::read_schema_with_diagnostics(
+            schema,
+            Some("test.xsd"),
+            BuiltinOverwriteAction::Deny,
+            RegisterBuiltins::Yes,
+            &[],
+            &[],
+        )
+    }
+
+    #[test]
+    fn unresolved_type_reference() {
+        let result = read(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:element name="root" type="missing" />
+            </xs:schema>
+            "#,
+        );
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            XsdError::UnresolvedTypeReference(name) if *name == QName::without_namespace("missing")
+        ));
+
+        let location = error.location().unwrap();
+        assert_eq!(location.document.as_deref(), Some("test.xsd"));
+        assert_eq!((location.line, location.column), (3, 17));
+        assert_eq!(
+            error.to_string(),
+            "error: Unresolved type definition reference missing\n \
+             --> test.xsd:3:17\n  \
+             |\n\
+             3 |                 <xs:element name=\"root\" type=\"missing\" />\n  \
+             |                 ^ in ElementDeclaration"
+        );
+    }
+
+    #[test]
+    fn error_location_in_local_component() {
+        let result = read(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:complexType name="T">
+                    <xs:sequence>
+                        <xs:element name="child" maxOccurs="many" />
+                    </xs:sequence>
+                </xs:complexType>
+            </xs:schema>
+            "#,
+        );
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error,
+            XsdError::Located {
+                component: "ElementDeclaration",
+                ..
+            }
+        ));
+        assert_eq!(error.location().unwrap().line, 5);
+    }
+
+    #[test]
+    fn invalid_attribute_value() {
+        let result = read(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:element name="root" nillable="maybe" />
+            </xs:schema>
+            "#,
+        );
+        assert!(matches!(
+            result.as_ref().map_err(XsdError::kind),
+            Err(XsdError::InvalidAttributeValue { value, .. }) if value == "maybe"
+        ));
+    }
+
+    #[test]
+    fn invalid_identity_constraint_selector() {
+        let result = read(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:element name="root">
+                    <xs:key name="k">
+                        <xs:selector xpath="item/@id" />
+                        <xs:field xpath="@id" />
+                    </xs:key>
+                </xs:element>
+            </xs:schema>
+            "#,
+        );
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            XsdError::InvalidXPath { expression, .. } if expression == "item/@id"
+        ));
+        assert_eq!(error.location().map(|l| l.line), Some(5));
+    }
+
+    #[test]
+    fn diagnostics_collect_recoverable_errors() {
+        let diagnostics = read_with_diagnostics(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:element name="a" type="missingA" />
+                <xs:element name="b" type="missingB" />
+                <xs:complexType name="T">
+                    <xs:sequence>
+                        <xs:element ref="missingElement" />
+                        <xs:group ref="missingGroup" />
+                    </xs:sequence>
+                </xs:complexType>
+            </xs:schema>
+            "#,
+        );
+        // Components are mapped in order of their kind, so the errors are not in document order
+        let mut lines = diagnostics
+            .errors
+            .iter()
+            .map(|e| e.location().unwrap().line)
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, [3, 4, 7, 8]);
+        assert!(!diagnostics.is_valid());
+
+        // The unresolved type definitions are replaced by xs:anyType
+        let (schema, components) = diagnostics.schema.unwrap();
+        for element_decl in schema.element_declarations {
+            let type_definition = element_decl.get(&components).type_definition;
+            let TypeDefinition::Complex(type_definition) = type_definition else {
+                panic!("expected xs:anyType");
+            };
+            assert_eq!(type_definition.name(&components), Some(XS_ANY_TYPE_NAME));
+        }
+    }
+
+    #[test]
+    fn diagnostics_fatal_error() {
+        let diagnostics = read_with_diagnostics(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+                <xs:element name="a" type="missing" />
+                <xs:element name="b" nillable="maybe" />
+            </xs:schema>
+            "#,
+        );
+        assert!(diagnostics.schema.is_none());
+        assert!(matches!(
+            diagnostics
+                .errors
+                .iter()
+                .map(XsdError::kind)
+                .collect::<Vec<_>>()[..],
+            [
+                XsdError::UnresolvedTypeReference(_),
+                XsdError::InvalidAttributeValue { .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn warnings_are_passed_to_sink() {
+        let schema = Document::parse(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:main">
+                <xs:import namespace="urn:other" />
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        let mut warnings = Vec::new();
+        crate::read_schema(
+            schema,
+            None,
+            BuiltinOverwriteAction::Deny,
+            RegisterBuiltins::Yes,
+            &[],
+            &[],
+            &mut warnings,
+        )
+        .unwrap();
+        assert!(matches!(
+            warnings[..],
+            [Warning::UnresolvedImport(ref import)] if import.namespace.as_deref() == Some("urn:other")
+        ));
+    }
+}
@@ -1,13 +1,18 @@
 use crate::{
-    Annotation, Ref,
+    Annotation, Ref, SchemaComponentTable,
+    assertion::NamespaceBinding,
+    builtins::XS_STRING_NAME,
     complex_type_def::ComplexTypeDefinition,
     components::{ComponentTable, IsBuiltinRef, Named, RefNamed},
     element_decl::ElementDeclaration,
+    error::XsdError,
     model_group::ModelGroup,
     simple_type_def::SimpleTypeDefinition,
+    validation::{Value, string_valid},
     wildcard::Wildcard,
-    xstypes::QName,
+    xstypes::{QName, Set},
 };
+use roxmltree::Node;
 
 /// Common type for [attribute_decl::ScopeVariety](super::attribute_decl::ScopeVariety) and
 /// [element_decl::ScopeVariety](super::element_decl::ScopeVariety)
@@ -71,20 +76,125 @@ pub enum ValueConstraintVariety {
 #[derive(Clone, Debug)]
 pub struct ValueConstraint {
     pub variety: ValueConstraintVariety,
-    pub value: String, // TODO *actual value*
+    /// The ·actual value· of the {lexical form} with respect to the effective simple type
+    /// definition of the declaration. As that type may not be available while mapping, this is
+    /// the {lexical form} as an `xs:string` value until the schema has been read completely
+    /// (see [`ValueConstraint::resolve_values()`]).
+    pub value: Value,
     pub lexical_form: String,
+    /// The [in-scope namespaces] of the declaration's element information item, against which
+    /// QName and NOTATION values are resolved. Not part of the property record.
+    pub namespace_bindings: Set<NamespaceBinding>,
+}
+
+impl ValueConstraint {
+    /// Constructs a value constraint from the default or fixed [attribute] of `element`. The
+    /// {value} is resolved later on by [`ValueConstraint::resolve_values()`].
+    pub(crate) fn new(variety: ValueConstraintVariety, lexical_form: &str, element: Node) -> Self {
+        Self {
+            variety,
+            value: Value::String(lexical_form.to_string()),
+            lexical_form: lexical_form.to_string(),
+            namespace_bindings: NamespaceBinding::in_scope(element),
+        }
+    }
+
+    /// Maps the {lexical form} of each value constraint in `components` to its {value}, i.e. to
+    /// its ·actual value· with respect to the effective simple type definition of the element
+    /// declaration, or the {type definition} of the attribute declaration. Returns an error for
+    /// each value constraint whose {lexical form} is not ·valid· with respect to that type, naming
+    /// the declaration imposing it.
+    pub(crate) fn resolve_values(components: &mut SchemaComponentTable) -> Vec<XsdError> {
+        let mut errors = Vec::new();
+        let c = &*components;
+
+        let mut resolve = |vc: &ValueConstraint,
+                           t: Option<Ref<SimpleTypeDefinition>>,
+                           component: &'static str,
+                           declaration: QName| {
+            let Some(t) = t else {
+                errors.push(XsdError::UnresolvedReference(XS_STRING_NAME));
+                return None;
+            };
+            string_valid(&vc.lexical_form, t.get(c), &vc.namespace_bindings, c)
+                .map_err(|error| {
+                    errors.push(XsdError::InvalidValueConstraint {
+                        component,
+                        declaration,
+                        error,
+                    })
+                })
+                .ok()
+        };
+
+        let element_values = c
+            .element_declarations
+            .iter()
+            .map(|e| {
+                let vc = e.value_constraint.as_ref()?;
+                let t = e.effective_simple_type_definition(c);
+                let name = QName::with_optional_namespace(e.target_namespace.as_ref(), &e.name);
+                resolve(vc, t, "ElementDeclaration", name)
+            })
+            .collect::<Vec<_>>();
+        let attribute_values = c
+            .attribute_declarations
+            .iter()
+            .map(|a| {
+                let vc = a.value_constraint.as_ref()?;
+                let name = QName::with_optional_namespace(a.target_namespace.as_ref(), &a.name);
+                resolve(vc, Some(a.type_definition), "AttributeDeclaration", name)
+            })
+            .collect::<Vec<_>>();
+        let use_values = c
+            .attribute_uses
+            .iter()
+            .map(|u| {
+                let vc = u.value_constraint.as_ref()?;
+                let a = u.attribute_declaration.get(c);
+                let name = QName::with_optional_namespace(a.target_namespace.as_ref(), &a.name);
+                resolve(vc, Some(a.type_definition), "AttributeUse", name)
+            })
+            .collect::<Vec<_>>();
+
+        for (e, value) in components
+            .element_declarations
+            .iter_mut()
+            .zip(element_values)
+        {
+            if let (Some(vc), Some(value)) = (&mut e.value_constraint, value) {
+                vc.value = value;
+            }
+        }
+        for (a, value) in components
+            .attribute_declarations
+            .iter_mut()
+            .zip(attribute_values)
+        {
+            if let (Some(vc), Some(value)) = (&mut a.value_constraint, value) {
+                vc.value = value;
+            }
+        }
+        for (u, value) in components.attribute_uses.iter_mut().zip(use_values) {
+            if let (Some(vc), Some(value)) = (&mut u.value_constraint, value) {
+                vc.value = value;
+            }
+        }
+
+        errors
+    }
 }
 
 /// Supertype of [simple](SimpleTypeDefinition) and [complex](ComplexTypeDefinition) type
 /// definition (§2.2.1)
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TypeDefinition {
     Simple(Ref<SimpleTypeDefinition>),
     Complex(Ref<ComplexTypeDefinition>),
 }
 
 /// Helper: Resolved [`TypeDefinition`]
-enum RTD<'a> {
+enum Rtd<'a> {
     Simple(&'a SimpleTypeDefinition),
     Complex(&'a ComplexTypeDefinition),
 }
@@ -92,8 +202,8 @@ enum RTD<'a> {
 impl RefNamed for TypeDefinition {
     fn name(&self, ct: &impl ComponentTable) -> Option<QName> {
         match self.get(ct) {
-            RTD::Simple(s) => s.name(),
-            RTD::Complex(c) => c.name(),
+            Rtd::Simple(s) => s.name(),
+            Rtd::Complex(c) => c.name(),
         }
     }
 }
@@ -113,17 +223,17 @@ impl TypeDefinition {
         }
     }
 
-    fn get<'a>(&self, components: &'a impl ComponentTable) -> RTD<'a> {
+    fn get<'a>(&self, components: &'a impl ComponentTable) -> Rtd<'a> {
         match self {
-            Self::Simple(s) => RTD::Simple(s.get(components)),
-            Self::Complex(c) => RTD::Complex(c.get(components)),
+            Self::Simple(s) => Rtd::Simple(s.get(components)),
+            Self::Complex(c) => Rtd::Complex(c.get(components)),
         }
     }
 
     pub fn base_type_definition(&self, components: &impl ComponentTable) -> TypeDefinition {
         match self.get(components) {
-            RTD::Simple(s) => s.base_type_definition,
-            RTD::Complex(c) => c.base_type_definition,
+            Rtd::Simple(s) => s.base_type_definition,
+            Rtd::Complex(c) => c.base_type_definition,
         }
     }
 
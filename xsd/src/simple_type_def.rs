@@ -2,7 +2,10 @@ use crate::{
     Ref,
     annotation::Annotation,
     attribute_decl::AttributeDeclaration,
-    builtins::{XS_ANY_ATOMIC_TYPE_NAME, XS_ANY_SIMPLE_TYPE_NAME, XS_ANY_TYPE_NAME, XS_NAMESPACE},
+    builtins::{
+        XS_ANY_ATOMIC_TYPE_NAME, XS_ANY_SIMPLE_TYPE_NAME, XS_ANY_TYPE_NAME, XS_NAMESPACE,
+        XS_STRING_NAME,
+    },
     complex_type_def::ComplexTypeDefinition,
     components::{Component, Named, RefNamed},
     constraining_facet::{ConstrainingFacet, ConstrainingFacets, WhiteSpace, WhiteSpaceValue},
@@ -90,22 +93,38 @@ enum ChildType {
 impl SimpleTypeDefinition {
     pub const TAG_NAME: &'static str = "simpleType";
 
-    pub(super) fn name_from_xml(simple_type: Node, schema: Node) -> Option<QName> {
+    /// Resolves the type definition replacing unresolved simple type definitions when recovering
+    /// from errors. This is ·xs:string· rather than ·xs:anySimpleType·, as the latter has no
+    /// {primitive type definition} and may not be used as a base type definition, item type or
+    /// member type.
+    fn fallback_type(ctx: &mut MappingContext) -> Result<Ref<Self>, XsdError> {
+        let string: TypeDefinition = ctx
+            .resolve(&XS_STRING_NAME)
+            .ok_or(XsdError::UnresolvedReference(XS_STRING_NAME))?;
+        string
+            .simple()
+            .ok_or(XsdError::UnresolvedReference(XS_STRING_NAME))
+    }
+
+    pub(super) fn name_from_xml(
+        context: &MappingContext,
+        simple_type: Node,
+        schema: Node,
+    ) -> Result<Option<QName>, XsdError> {
         // {name}
         //   The ·actual value· of the name [attribute] if present on the <simpleType> element,
         //   otherwise ·absent·.
         let name = simple_type
             .attribute("name")
-            .map(|v| actual_value::<String>(v, simple_type));
+            .map(|v| actual_value::<String>(v, simple_type))
+            .transpose()?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the ancestor <schema> element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<AnyURI>(v, simple_type));
+        let target_namespace = context.target_namespace(schema)?;
 
-        name.map(|name| QName::with_optional_namespace(target_namespace, name))
+        Ok(name.map(|name| QName::with_optional_namespace(target_namespace, name)))
     }
 
     /// Maps a Simple Type Definition from its XML representation
@@ -128,14 +147,13 @@ impl SimpleTypeDefinition {
         //   otherwise ·absent·.
         let name = simple_type
             .attribute("name")
-            .map(|v| actual_value::<String>(v, simple_type));
+            .map(|v| actual_value::<String>(v, simple_type))
+            .transpose()?;
 
         // {target namespace}
         //   The ·actual value· of the targetNamespace [attribute] of the ancestor <schema> element
         //   information item if present, otherwise ·absent·.
-        let target_namespace = schema
-            .attribute("targetNamespace")
-            .map(|v| actual_value::<AnyURI>(v, simple_type));
+        let target_namespace = ctx.target_namespace(schema)?;
 
         let (child_type, child) = if let Some(restriction) = simple_type
             .children()
@@ -153,7 +171,10 @@ impl SimpleTypeDefinition {
         {
             (ChildType::Union, union)
         } else {
-            unreachable!()
+            return Err(XsdError::invalid_content(
+                simple_type,
+                "expected one of <restriction>, <list> or <union>",
+            ));
         };
 
         // {base type definition} The appropriate case among the following:
@@ -162,29 +183,33 @@ impl SimpleTypeDefinition {
             //   by the ·actual value· of the base [attribute] of <restriction>, if present,
             //   otherwise the type definition corresponding to the <simpleType> among the
             //   [children] of <restriction>.
-            child
-                .attribute("base")
-                .map(|v| actual_value::<QName>(v, child))
-                .map(|name| ctx.resolve(&name).unwrap()) // TODO
-                .unwrap_or_else(|| {
-                    let st = child
-                        .children()
-                        .find(|c| c.tag_name().name() == Self::TAG_NAME)
-                        .unwrap();
-                    let st = Self::map_from_xml(
-                        ctx,
-                        st,
-                        schema,
-                        None,
-                        Some(Context::SimpleType(self_ref)),
-                    )
-                    .unwrap(); // TODO
-                    TypeDefinition::Simple(st)
-                })
+            if let Some(base) = child.attribute("base") {
+                let name = ctx.qname(base, child)?;
+                let result = ctx
+                    .resolve(&name)
+                    .ok_or(XsdError::UnresolvedTypeReference(name));
+                ctx.recover(result, child, Self::DISPLAY_NAME, |ctx| {
+                    Self::fallback_type(ctx).map(TypeDefinition::Simple)
+                })?
+            } else {
+                let st = child
+                    .children()
+                    .find(|c| c.tag_name().name() == Self::TAG_NAME)
+                    .ok_or_else(|| {
+                        XsdError::invalid_content(
+                            child,
+                            "expected base [attribute] or <simpleType>",
+                        )
+                    })?;
+                let st =
+                    Self::map_from_xml(ctx, st, schema, None, Some(Context::SimpleType(self_ref)))
+                        .map_err(|e| ctx.locate(e, st, Self::DISPLAY_NAME))?;
+                TypeDefinition::Simple(st)
+            }
         } else {
             // 2 If the <list> or <union> alternative is chosen, then ·xs:anySimpleType·.
             ctx.resolve(&XS_ANY_SIMPLE_TYPE_NAME)
-                .expect("Built-in type xs:anySimpleType must be present")
+                .ok_or(XsdError::UnresolvedBuiltin(&XS_ANY_SIMPLE_TYPE_NAME))?
         };
 
         // {final}
@@ -196,6 +221,7 @@ impl SimpleTypeDefinition {
             .attribute("final")
             .or_else(|| schema.attribute("finalDefault"))
             .map(|v| actual_value::<String>(v, simple_type))
+            .transpose()?
             .unwrap_or_default();
         // Then the property value is the appropriate case among the following:
         //   1 If ·FS· is the empty string, then the empty set;
@@ -216,13 +242,16 @@ impl SimpleTypeDefinition {
         } else {
             let fs = fs.split_whitespace();
             fs.map(|v| match v {
-                "restriction" => DerivationMethod::Restriction,
-                "extension" => DerivationMethod::Extension,
-                "list" => DerivationMethod::List,
-                "union" => DerivationMethod::Union,
-                _ => panic!("Invalid value in final set"),
+                "restriction" => Ok(DerivationMethod::Restriction),
+                "extension" => Ok(DerivationMethod::Extension),
+                "list" => Ok(DerivationMethod::List),
+                "union" => Ok(DerivationMethod::Union),
+                _ => Err(XsdError::InvalidAttributeValue {
+                    value: v.to_string(),
+                    expected: "derivation set",
+                }),
             })
-            .collect()
+            .collect::<Result<_, _>>()?
         };
 
         // {facets} The appropriate case among the following:
@@ -249,9 +278,9 @@ impl SimpleTypeDefinition {
                     // have any facets, we just yield the empty set here.
                     ConstrainingFacets::new()
                 } else {
-                    let base_type_definition = base_type_definition.simple().expect(
-                        "Any type which is not anySimpleType must have a simple type as base",
-                    );
+                    let base_type_definition = base_type_definition
+                        .simple()
+                        .ok_or_else(|| Self::complex_base_error(child))?;
 
                     let mut facet_nodes = Vec::new();
                     for facet in child.children() {
@@ -264,11 +293,27 @@ impl SimpleTypeDefinition {
                         }
                         facet_nodes.push(facet);
                     }
-                    let facets =
-                        ConstrainingFacet::map_from_xml(ctx, &facet_nodes, schema).unwrap();
-
                     // Request the component here to avoid a mutable borrow through b
                     ctx.request(base_type_definition)?;
+                    let base_assertions = base_type_definition
+                        .get(ctx.components())
+                        .facets
+                        .iter_resolved(ctx.components())
+                        .find_map(|facet| match facet {
+                            ConstrainingFacet::Assertions(assertions) => {
+                                Some(assertions.value.clone())
+                            }
+                            _ => None,
+                        })
+                        .unwrap_or_default();
+
+                    let facets = ConstrainingFacet::map_from_xml(
+                        ctx,
+                        &facet_nodes,
+                        &base_assertions,
+                        schema,
+                    )
+                    .map_err(|e| ctx.locate(e, child, ConstrainingFacet::DISPLAY_NAME))?;
 
                     // Given two sets of facets B and S, the result of overlaying B with S is the
                     // set of facets R for which all of the following are true:
@@ -333,14 +378,19 @@ impl SimpleTypeDefinition {
                     // (See pt. 1, §3.16.6.2, clause 1.1)
                     Some(Variety::Atomic)
                 } else {
-                    let base_type_definition = base_type_definition.simple().expect(
-                        "Any type which is not anySimpleType must have a simple type as base",
-                    );
+                    let base_type_definition = base_type_definition
+                        .simple()
+                        .ok_or_else(|| Self::complex_base_error(child))?;
 
                     let variety = ctx
                         .request(base_type_definition)?
                         .variety
-                        .expect("Any type which is not anySimpleType must have a variety");
+                        .ok_or_else(|| {
+                            XsdError::invalid_content(
+                                child,
+                                "only xs:anyAtomicType may be derived from xs:anySimpleType by restriction",
+                            )
+                        })?;
 
                     Some(variety)
                 }
@@ -365,16 +415,17 @@ impl SimpleTypeDefinition {
                 // Definition which corresponds to a primitive datatype.
                 let ancestors = std::iter::once(base_type_definition)
                     .chain(base_type_definition.ancestors(ctx.components()));
-                primitive_type_definition = Some(
-                    ancestors
-                        .take_while(|r| {
-                            r.name(ctx.components()).as_ref() != Some(&XS_ANY_TYPE_NAME)
-                        })
-                        .find(|t| t.is_primitive(ctx.components()))
-                        .unwrap() // TODO can this fail?
-                        .simple()
-                        .unwrap(),
-                );
+                let primitive = ancestors
+                    .take_while(|r| r.name(ctx.components()).as_ref() != Some(&XS_ANY_TYPE_NAME))
+                    .find(|t| t.is_primitive(ctx.components()))
+                    .and_then(|t| t.simple());
+                primitive_type_definition = Some(primitive.ok_or_else(|| {
+                    let error = XsdError::invalid_content(
+                        child,
+                        "an atomic type definition must be derived from a primitive type",
+                    );
+                    ctx.locate(error, child, Self::DISPLAY_NAME)
+                })?);
             }
             Variety::List => {
                 let list = child;
@@ -390,31 +441,43 @@ impl SimpleTypeDefinition {
                         //       <list>, or
                         //   (b) corresponding to the <simpleType> among the [children] of <list>,
                         //       whichever is present.
-                        list.attribute("itemType")
-                            .map(|item_type| actual_value::<QName>(item_type, list))
-                            .map(|item_type| ctx.resolve(&item_type).unwrap()) // TODO
-                            .or_else(|| {
-                                list.children()
-                                    .find(|c| c.tag_name().name() == Self::TAG_NAME)
-                                    .map(|simple_type| {
-                                        Self::map_from_xml(
-                                            ctx,
-                                            simple_type,
-                                            schema,
-                                            None,
-                                            Some(Context::SimpleType(self_ref)),
-                                        )
-                                        .unwrap() // TODO
-                                    })
-                            })
-                            .unwrap()
+                        if let Some(item_type) = list.attribute("itemType") {
+                            let item_type = ctx.qname(item_type, list)?;
+                            let result = ctx
+                                .resolve(&item_type)
+                                .ok_or(XsdError::UnresolvedTypeReference(item_type));
+                            ctx.recover(result, list, Self::DISPLAY_NAME, Self::fallback_type)?
+                        } else {
+                            let simple_type = list
+                                .children()
+                                .find(|c| c.tag_name().name() == Self::TAG_NAME)
+                                .ok_or_else(|| {
+                                    XsdError::invalid_content(
+                                        list,
+                                        "expected itemType [attribute] or <simpleType>",
+                                    )
+                                })?;
+                            Self::map_from_xml(
+                                ctx,
+                                simple_type,
+                                schema,
+                                None,
+                                Some(Context::SimpleType(self_ref)),
+                            )
+                            .map_err(|e| ctx.locate(e, simple_type, Self::DISPLAY_NAME))?
+                        }
                     } else {
                         // 2 otherwise (that is, the {base type definition} is not
                         //   ·xs:anySimpleType·), the {item type definition} of the {base type
                         //   definition}.
-                        ctx.request(base_type_definition.simple().unwrap())?
+                        let base_type_definition = base_type_definition
+                            .simple()
+                            .ok_or_else(|| Self::complex_base_error(child))?;
+                        ctx.request(base_type_definition)?
                             .item_type_definition
-                            .unwrap() // TODO unwrap allowed?
+                            .ok_or_else(|| {
+                                XsdError::invalid_content(list, "base type is not a list type")
+                            })?
                     },
                 )
             }
@@ -422,7 +485,10 @@ impl SimpleTypeDefinition {
                 let union_ = child;
 
                 // {member type definitions} The appropriate case among the following:
-                let base_type_definition = ctx.request(base_type_definition.simple().unwrap())?;
+                let base_type_definition = base_type_definition
+                    .simple()
+                    .ok_or_else(|| Self::complex_base_error(child))?;
+                let base_type_definition = ctx.request(base_type_definition)?;
                 member_type_definitions = Some(
                     if base_type_definition.name().as_ref() == Some(&XS_ANY_SIMPLE_TYPE_NAME) {
                         // 1 If the {base type definition} is ·xs:anySimpleType·, then the sequence
@@ -433,33 +499,42 @@ impl SimpleTypeDefinition {
                         //       <union>, if any, in order.
                         let mut member_types = union_
                             .attribute("memberTypes")
-                            .map(|member_types| actual_value::<Vec<QName>>(member_types, union_))
+                            .map(|member_types| ctx.qnames(member_types, union_))
+                            .transpose()?
                             .map(|member_types| {
                                 member_types
                                     .into_iter()
                                     .map(|member_type| {
-                                        ctx.resolve::<Ref<SimpleTypeDefinition>>(&member_type)
-                                            .unwrap() // TODO
+                                        let result = ctx
+                                            .resolve::<Ref<SimpleTypeDefinition>>(&member_type)
+                                            .ok_or(XsdError::UnresolvedTypeReference(member_type));
+                                        ctx.recover(
+                                            result,
+                                            union_,
+                                            Self::DISPLAY_NAME,
+                                            Self::fallback_type,
+                                        )
                                     })
-                                    .collect::<Vec<_>>()
+                                    .collect::<Result<Vec<_>, _>>()
                             })
+                            .transpose()?
                             .unwrap_or_default();
 
-                        member_types.extend(
-                            union_
-                                .children()
-                                .filter(|c| c.tag_name().name() == Self::TAG_NAME)
-                                .map(|simple_type| {
-                                    Self::map_from_xml(
-                                        ctx,
-                                        simple_type,
-                                        schema,
-                                        None,
-                                        Some(Context::SimpleType(self_ref)),
-                                    )
-                                    .unwrap() // TODO
-                                }),
-                        );
+                        for simple_type in union_
+                            .children()
+                            .filter(|c| c.tag_name().name() == Self::TAG_NAME)
+                        {
+                            member_types.push(
+                                Self::map_from_xml(
+                                    ctx,
+                                    simple_type,
+                                    schema,
+                                    None,
+                                    Some(Context::SimpleType(self_ref)),
+                                )
+                                .map_err(|e| ctx.locate(e, simple_type, Self::DISPLAY_NAME))?,
+                            );
+                        }
 
                         member_types
                     } else {
@@ -469,7 +544,9 @@ impl SimpleTypeDefinition {
                         base_type_definition
                             .member_type_definitions
                             .clone()
-                            .unwrap()
+                            .ok_or_else(|| {
+                                XsdError::invalid_content(union_, "base type is not a union type")
+                            })?
                     },
                 );
             }
@@ -477,7 +554,9 @@ impl SimpleTypeDefinition {
 
         // TODO make fundamental facets non-optional
         let fundamental_facets = {
-            let base_type_definition = base_type_definition.simple().unwrap();
+            let base_type_definition = base_type_definition
+                .simple()
+                .ok_or_else(|| Self::complex_base_error(child))?;
             ctx.request(base_type_definition)?;
 
             // === ordered ===
@@ -508,10 +587,13 @@ impl SimpleTypeDefinition {
                     // 3.2 If each member of the ·owner's· {member type definitions} has an ordered
                     //     component in its {fundamental facets} whose {value} is false, then
                     //     {value} is false.
-                    if member_type_definitions.iter().copied().all(|member| {
-                        let member = ctx.request(member).unwrap(); // TODO
-                        member.fundamental_facets.ordered() == Some(OrderedValue::False)
-                    }) {
+                    let mut all_unordered = true;
+                    for &member in member_type_definitions {
+                        let member = ctx.request(member)?;
+                        all_unordered &=
+                            member.fundamental_facets.ordered() == Some(OrderedValue::False);
+                    }
+                    if all_unordered {
                         OrderedValue::False
                     } else {
                         // 3.3 otherwise {value} is partial.
@@ -604,19 +686,13 @@ impl SimpleTypeDefinition {
                     // When the ·owner's· {variety} is union, if cardinality's {value} is finite
                     // for every member of the ·owner's· {member type definitions} set then {value}
                     // is finite, otherwise {value} is countably infinite.
-                    if member_type_definitions
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .copied()
-                        .map(|member| {
-                            ctx.request(member)
-                                .unwrap() // TODO
-                                .fundamental_facets
-                                .cardinality()
-                        })
-                        .all(|cardinality| cardinality == Some(CardinalityValue::Finite))
-                    {
+                    let mut all_finite = true;
+                    for &member in member_type_definitions.as_ref().unwrap() {
+                        let member = ctx.request(member)?;
+                        all_finite &= member.fundamental_facets.cardinality()
+                            == Some(CardinalityValue::Finite);
+                    }
+                    if all_finite {
                         CardinalityValue::Finite
                     } else {
                         CardinalityValue::CountablyInfinite
@@ -644,18 +720,12 @@ impl SimpleTypeDefinition {
                     // When the ·owner's· {variety} is union, if numeric's {value} is true for
                     // every member of the ·owner's· {member type definitions} set then {value} is
                     // true, otherwise {value} is false.
-                    member_type_definitions
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .copied()
-                        .map(|member| {
-                            ctx.request(member)
-                                .unwrap() // TODO
-                                .fundamental_facets
-                                .numeric()
-                        })
-                        .all(|numeric| numeric == Some(true))
+                    let mut all_numeric = true;
+                    for &member in member_type_definitions.as_ref().unwrap() {
+                        let member = ctx.request(member)?;
+                        all_numeric &= member.fundamental_facets.numeric() == Some(true);
+                    }
+                    all_numeric
                 }
             };
 
@@ -689,6 +759,10 @@ impl SimpleTypeDefinition {
         Ok(self_ref)
     }
 
+    fn complex_base_error(child: Node) -> XsdError {
+        XsdError::invalid_content(child, "base type of a simple type must be a simple type")
+    }
+
     fn map_cardinality_atomic(
         ctx: &MappingContext,
         facets: &ConstrainingFacets,
@@ -1,13 +1,14 @@
 //! https://www.cogsci.ed.ac.uk/~ht/XML_Europe_2003.html
 use crate::{
     ElementDeclaration, Particle, Ref, SchemaComponentTable, Term, Wildcard,
-    model_group::Compositor,
+    element_decl::ScopeVariety, model_group::Compositor, particle::MaxOccurs,
 };
 use std::{
     borrow::Borrow,
     collections::{BTreeMap, BTreeSet, HashMap},
     rc::Rc,
 };
+use thiserror::Error;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Transition {
@@ -85,6 +86,8 @@ impl EpsilonNfa {
             })
     }
 
+    /// Prints the automaton in the Graphviz DOT format, for debugging
+    #[allow(dead_code)]
     fn print_dot(&self) {
         println!("digraph fsm {{");
         for state in 0..self.transitions.len() as u32 {
@@ -142,8 +145,23 @@ impl EpsilonNfa {
     }
 }
 
-fn t_t(term: &Term, sm: &mut EpsilonNfa, s: u32, components: &SchemaComponentTable) -> u32 {
-    match term {
+/// A content model which no state machine can be created for, as it violates All Group Limited
+/// (§3.8.6.2)
+#[derive(Debug, Error)]
+pub enum ContentModelError {
+    #[error("an all group must not appear within a sequence or choice")]
+    NestedAllGroup,
+    #[error("an all group must only contain elements, wildcards and all groups")]
+    GroupInAllGroup,
+}
+
+fn t_t(
+    term: &Term,
+    sm: &mut EpsilonNfa,
+    s: u32,
+    components: &SchemaComponentTable,
+) -> Result<u32, ContentModelError> {
+    Ok(match term {
         Term::ElementDeclaration(element) => {
             let b = sm.create_state();
             sm.add_element_transition(b, s, *element);
@@ -152,12 +170,21 @@ fn t_t(term: &Term, sm: &mut EpsilonNfa, s: u32, components: &SchemaComponentTab
         Term::ModelGroup(group) => {
             let group = group.get(components);
             match group.compositor {
-                Compositor::All => unimplemented!(),
-                Compositor::Choice => todo!(),
+                // All-groups are matched by an AllGroup instead, they can only appear at the top
+                // of a content model (cos-all-limited)
+                Compositor::All => return Err(ContentModelError::NestedAllGroup),
+                Compositor::Choice => {
+                    let b = sm.create_state();
+                    for particle in group.particles.iter() {
+                        let n = t_p(particle.get(components), sm, s, components)?;
+                        sm.add_epsilon_transition(b, n);
+                    }
+                    b
+                }
                 Compositor::Sequence => {
                     let mut n = s;
                     for particle in group.particles.iter().rev() {
-                        n = t_p(particle.get(components), sm, n, components);
+                        n = t_p(particle.get(components), sm, n, components)?;
                     }
                     n
                 }
@@ -168,22 +195,27 @@ fn t_t(term: &Term, sm: &mut EpsilonNfa, s: u32, components: &SchemaComponentTab
             sm.add_wildcard_transition(b, s, *wildcard);
             b
         }
-    }
+    })
 }
 
-fn t_p(particle: &Particle, sm: &mut EpsilonNfa, s: u32, components: &SchemaComponentTable) -> u32 {
+fn t_p(
+    particle: &Particle,
+    sm: &mut EpsilonNfa,
+    s: u32,
+    components: &SchemaComponentTable,
+) -> Result<u32, ContentModelError> {
     let mut n = s;
     match particle.max_occurs {
         crate::particle::MaxOccurs::Unbounded => {
             let t = sm.create_state();
-            let b = t_t(&particle.term, sm, t, components);
+            let b = t_t(&particle.term, sm, t, components)?;
             sm.add_epsilon_transition(t, b);
             sm.add_epsilon_transition(b, n);
             n = b;
         }
         crate::particle::MaxOccurs::Count(max_occurs) => {
             for _ in 0..(max_occurs - particle.min_occurs) {
-                let b = t_t(&particle.term, sm, n, components);
+                let b = t_t(&particle.term, sm, n, components)?;
                 sm.add_epsilon_transition(b, s);
                 n = b;
             }
@@ -191,10 +223,10 @@ fn t_p(particle: &Particle, sm: &mut EpsilonNfa, s: u32, components: &SchemaComp
     }
 
     for _ in 0..particle.min_occurs {
-        n = t_t(&particle.term, sm, n, components);
+        n = t_t(&particle.term, sm, n, components)?;
     }
 
-    n
+    Ok(n)
 }
 
 #[derive(Default)]
@@ -272,6 +304,8 @@ impl<L: std::hash::Hash + std::cmp::Eq> LabeledDfa<L> {
         self.dfa
     }
 
+    /// Prints the automaton in the Graphviz DOT format, for debugging
+    #[allow(dead_code)]
     fn print_dot(&self)
     where
         L: std::fmt::Debug,
@@ -303,16 +337,230 @@ impl<L: std::hash::Hash + std::cmp::Eq> LabeledDfa<L> {
     }
 }
 
-pub fn create_state_machine(particle: &Particle, components: &SchemaComponentTable) -> Dfa {
+/// Matcher for the content model of an all-group. All-groups are not translated into a DFA, as
+/// the number of states would grow exponentially with the number of particles (and with their
+/// {max occurs}, which may be greater than one in XSD 1.1). Instead, the state is the number of
+/// matched elements per particle.
+pub struct AllGroup {
+    /// The all-group particle has {min occurs} = 0, i.e. the empty sequence is accepted even if
+    /// some of the particles are required.
+    emptiable: bool,
+    particles: Vec<AllGroupParticle>,
+}
+
+struct AllGroupParticle {
+    transition: Transition,
+    min_occurs: u64,
+    max_occurs: MaxOccurs,
+}
+
+impl AllGroup {
+    /// Creates the state machine for `particle`, whose {term} is the all-group `group`
+    fn new(
+        particle: &Particle,
+        group: &crate::ModelGroup,
+        components: &SchemaComponentTable,
+    ) -> Result<Self, ContentModelError> {
+        fn add_particles(
+            particles: &mut Vec<AllGroupParticle>,
+            group: &crate::ModelGroup,
+            optional: bool,
+            components: &SchemaComponentTable,
+        ) -> Result<(), ContentModelError> {
+            for particle in group.particles.iter().map(|p| p.get(components)) {
+                let transition = match &particle.term {
+                    Term::ElementDeclaration(element) => Transition::ElementDeclaration(*element),
+                    Term::Wildcard(wildcard) => Transition::Wildcard(*wildcard),
+                    // Nested all-groups (via <group ref>) are flattened. If the nested group is
+                    // optional, so are its particles, which accepts slightly more than the spec
+                    // if it contains more than one required particle.
+                    Term::ModelGroup(nested) => {
+                        let nested = nested.get(components);
+                        if nested.compositor != Compositor::All {
+                            return Err(ContentModelError::GroupInAllGroup);
+                        }
+                        add_particles(
+                            particles,
+                            nested,
+                            optional || particle.min_occurs == 0,
+                            components,
+                        )?;
+                        continue;
+                    }
+                };
+                particles.push(AllGroupParticle {
+                    transition,
+                    min_occurs: if optional { 0 } else { particle.min_occurs },
+                    max_occurs: particle.max_occurs.clone(),
+                });
+            }
+            Ok(())
+        }
+
+        let mut particles = Vec::new();
+        add_particles(&mut particles, group, false, components)?;
+        Ok(Self {
+            emptiable: particle.min_occurs == 0,
+            particles,
+        })
+    }
+}
+
+/// The state machine for a content model
+pub enum StateMachine {
+    Dfa(Dfa),
+    All(AllGroup),
+}
+
+/// A state of a [`StateMachine`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    Dfa(u32),
+    /// The number of elements matched by each particle of the all-group
+    All(Vec<u64>),
+}
+
+impl StateMachine {
+    pub fn start_state(&self) -> State {
+        match self {
+            Self::Dfa(dfa) => State::Dfa(dfa.start_state.unwrap()),
+            Self::All(all) => State::All(vec![0; all.particles.len()]),
+        }
+    }
+
+    /// Follows a transition from `state` whose label is accepted by `matches` and returns that
+    /// label, or `None` if there is no such transition. Element declarations take precedence
+    /// over wildcards.
+    pub fn step(
+        &self,
+        state: &mut State,
+        mut matches: impl FnMut(&Transition) -> bool,
+    ) -> Option<Transition> {
+        let is_element = |t: &Transition| matches!(t, Transition::ElementDeclaration(_));
+        match (self, state) {
+            (Self::Dfa(dfa), State::Dfa(state)) => {
+                let transitions = dfa.get_transitions(*state);
+                let (&label, &to) = transitions
+                    .iter()
+                    .filter(|(t, _)| is_element(t))
+                    .chain(transitions.iter().filter(|(t, _)| !is_element(t)))
+                    .find(|(t, _)| matches(t))?;
+                *state = to;
+                Some(label)
+            }
+            (Self::All(all), State::All(counts)) => {
+                let candidates = || {
+                    all.particles
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, p)| MaxOccurs::Count(counts[*i]) < p.max_occurs)
+                };
+                let (i, particle) = candidates()
+                    .filter(|(_, p)| is_element(&p.transition))
+                    .chain(candidates().filter(|(_, p)| !is_element(&p.transition)))
+                    .find(|(_, p)| matches(&p.transition))?;
+                counts[i] += 1;
+                Some(particle.transition)
+            }
+            _ => panic!("state does not belong to this state machine"),
+        }
+    }
+
+    /// Returns the labels of the transitions from `state`, i.e. the terms which may match the
+    /// next element
+    pub fn transitions(&self, state: &State) -> Vec<Transition> {
+        match (self, state) {
+            (Self::Dfa(dfa), State::Dfa(state)) => {
+                dfa.get_transitions(*state).keys().copied().collect()
+            }
+            (Self::All(all), State::All(counts)) => all
+                .particles
+                .iter()
+                .zip(counts)
+                .filter(|(p, count)| MaxOccurs::Count(**count) < p.max_occurs)
+                .map(|(p, _)| p.transition)
+                .collect(),
+            _ => panic!("state does not belong to this state machine"),
+        }
+    }
+
+    /// Maps `state` to an equivalent state, so that a state machine only has finitely many
+    /// distinct states: the count of an unbounded all-group particle is capped once it has
+    /// reached its {min occurs}.
+    pub fn canonicalize(&self, state: &mut State) {
+        if let (Self::All(all), State::All(counts)) = (self, state) {
+            for (p, count) in all.particles.iter().zip(counts) {
+                if p.max_occurs == MaxOccurs::Unbounded {
+                    *count = (*count).min(p.min_occurs.max(1));
+                }
+            }
+        }
+    }
+
+    pub fn is_end_state(&self, state: &State) -> bool {
+        match (self, state) {
+            (Self::Dfa(dfa), State::Dfa(state)) => dfa.is_end_state(*state),
+            (Self::All(all), State::All(counts)) => {
+                (all.emptiable && counts.iter().all(|&c| c == 0))
+                    || all
+                        .particles
+                        .iter()
+                        .zip(counts)
+                        .all(|(p, &count)| count >= p.min_occurs)
+            }
+            _ => panic!("state does not belong to this state machine"),
+        }
+    }
+}
+
+/// Creates the state machine for the content model `particle`
+pub fn create_state_machine(
+    particle: &Particle,
+    components: &SchemaComponentTable,
+) -> Result<StateMachine, ContentModelError> {
+    if let Term::ModelGroup(group) = &particle.term
+        && let group = group.get(components)
+        && group.compositor == Compositor::All
+    {
+        Ok(StateMachine::All(AllGroup::new(
+            particle, group, components,
+        )?))
+    } else {
+        Ok(StateMachine::Dfa(create_dfa(particle, components)?))
+    }
+}
+
+/// The state machines of the content models assessed so far, each of which is only created once
+#[derive(Default)]
+pub struct StateMachineCache(HashMap<Ref<Particle>, Rc<StateMachine>>);
+
+impl StateMachineCache {
+    /// Returns the state machine for the content model `particle`
+    pub fn get(
+        &mut self,
+        particle: Ref<Particle>,
+        components: &SchemaComponentTable,
+    ) -> Result<Rc<StateMachine>, ContentModelError> {
+        if let Some(state_machine) = self.0.get(&particle) {
+            return Ok(Rc::clone(state_machine));
+        }
+        let state_machine = Rc::new(create_state_machine(particle.get(components), components)?);
+        self.0.insert(particle, Rc::clone(&state_machine));
+        Ok(state_machine)
+    }
+}
+
+fn create_dfa(
+    particle: &Particle,
+    components: &SchemaComponentTable,
+) -> Result<Dfa, ContentModelError> {
     let mut fsm = EpsilonNfa::default();
     let s = fsm.create_state();
     fsm.add_end_state(s);
-    let starting_state = t_p(particle, &mut fsm, s, components);
+    let starting_state = t_p(particle, &mut fsm, s, components)?;
     fsm.set_starting_state(starting_state);
-    fsm.print_dot();
 
     let epsilon_closure = fsm.compute_epsilon_closure();
-    println!("{:#?}", epsilon_closure);
 
     let mut pending_states = BTreeSet::new();
     let starting_state = Rc::new(epsilon_closure[&starting_state].clone());
@@ -359,52 +607,199 @@ pub fn create_state_machine(particle: &Particle, components: &SchemaComponentTab
         }
     }
 
-    new_dfa.print_dot();
-
-    new_dfa.into_inner()
+    Ok(new_dfa.into_inner())
 }
 
 /// Checks if the Unique Particle Attribution (UPA) constraint is satisfied.
-pub fn verify_upa_satisfied(dfa: &Dfa, components: &SchemaComponentTable) -> bool {
-    // See Algorithm 2 from https://www.cogsci.ed.ac.uk/~ht/XML_Europe_2003.html [1] and W3C XML
-    // Schema Definition Language (XSD) 1.1 Part 1, Appendix J [2]
-
-    // Steps 1-2 of [1] are performed in the construction of the DFA.
-
-    // 3. M2 violates the UPA if it is non-deterministic ignoring term identity, that is, if there
-    //    is any state in M2 which has two outgoing edges such that any of the following hold: [1]
-    for transitions in &dfa.transitions {
-        // NOTE: This is O(n^2) for now, but usually the number of transitions is small enough.
-        for (ti_a, transition_a) in transitions.keys().enumerate() {
-            for transition_b in transitions.keys().skip(ti_a + 1) {
-                match (transition_a, transition_b) {
-                    (Transition::ElementDeclaration(e_a), Transition::ElementDeclaration(e_b)) => {
-                        // 1. Their labels are both element declarations with the same {local name}
-                        //    and {namespace name}. [1]
-                        // TODO: Substitution groups [2]
-                        let e_a = e_a.get(components);
-                        let e_b = e_b.get(components);
-                        if e_a.name == e_b.name && e_a.target_namespace == e_b.target_namespace {
-                            return false;
-                        }
-                    }
-                    (Transition::Wildcard(w_a), Transition::Wildcard(w_b)) => {
-                        // 2. Their labels are both wildcards whose ranges overlap. [1]
-                        let _w_a = w_a.get(components);
-                        let _w_b = w_b.get(components);
-                        // TODO
+pub fn verify_upa_satisfied(
+    state_machine: &StateMachine,
+    components: &SchemaComponentTable,
+) -> bool {
+    match state_machine {
+        StateMachine::Dfa(dfa) => {
+            // See Algorithm 2 from https://www.cogsci.ed.ac.uk/~ht/XML_Europe_2003.html [1] and
+            // W3C XML Schema Definition Language (XSD) 1.1 Part 1, Appendix J [2]
+
+            // Steps 1-2 of [1] are performed in the construction of the DFA.
+
+            // 3. M2 violates the UPA if it is non-deterministic ignoring term identity, that is,
+            //    if there is any state in M2 which has two outgoing edges such that any of the
+            //    following hold: [1]
+            dfa.transitions.iter().all(|transitions| {
+                // NOTE: This is O(n^2) for now, but usually the number of transitions is small
+                // enough.
+                let labels = transitions.keys().collect::<Vec<_>>();
+                !any_pair_competes(&labels, components)
+            })
+        }
+        StateMachine::All(all) => {
+            // Any element of the sequence may be matched by any particle of an all-group that has
+            // not reached its {max occurs}, so no two of the particles may compete.
+            let labels = all
+                .particles
+                .iter()
+                .map(|p| &p.transition)
+                .collect::<Vec<_>>();
+            !any_pair_competes(&labels, components)
+        }
+    }
+}
+
+/// Returns the expanded names of the elements which may be matched by the element declaration
+/// `d`, i.e. its own name and those of its ·substitution group·, if it is global
+fn substitutable_names(
+    d: Ref<ElementDeclaration>,
+    components: &SchemaComponentTable,
+) -> Vec<(Option<&str>, &str)> {
+    let name = |d: &Ref<ElementDeclaration>| {
+        let d = d.get(components);
+        (d.target_namespace.as_deref(), d.name.as_str())
+    };
+    let mut names = vec![name(&d)];
+    if d.get(components).scope.variety() == ScopeVariety::Global {
+        names.extend(
+            ElementDeclaration::substitution_group(d, components)
+                .iter()
+                .map(name),
+        );
+    }
+    names
+}
+
+/// Returns whether any two of `labels` compete for an element, see [`verify_upa_satisfied`]
+fn any_pair_competes(labels: &[&Transition], components: &SchemaComponentTable) -> bool {
+    for (ti_a, transition_a) in labels.iter().enumerate() {
+        for transition_b in labels.iter().skip(ti_a + 1) {
+            match (transition_a, transition_b) {
+                (Transition::ElementDeclaration(e_a), Transition::ElementDeclaration(e_b)) => {
+                    // 1. Their labels are both element declarations with the same {local name}
+                    //    and {namespace name}. [1]
+                    //    This includes the members of their substitution groups, any of which may
+                    //    appear in place of the declaration. [2]
+                    let names_a = substitutable_names(*e_a, components);
+                    if substitutable_names(*e_b, components)
+                        .iter()
+                        .any(|name| names_a.contains(name))
+                    {
+                        return true;
                     }
-                    (Transition::ElementDeclaration(e), Transition::Wildcard(w))
-                    | (Transition::Wildcard(w), Transition::ElementDeclaration(e)) => {
-                        // 3. Their labels are a wildcard and an element declaration and the {namespace name}
-                        //    of the element declaration is in the range of the wildcard. [1]
-                        let _w = w.get(components);
-                        let _e = e.get(components);
-                        // TODO
+                }
+                (Transition::Wildcard(w_a), Transition::Wildcard(w_b)) => {
+                    // 2. Their labels are both wildcards whose ranges overlap. [1]
+                    let w_a = w_a.get(components);
+                    let w_b = w_b.get(components);
+                    if w_a.namespace_constraint.overlaps(&w_b.namespace_constraint) {
+                        return true;
                     }
                 }
+                (Transition::ElementDeclaration(_), Transition::Wildcard(_))
+                | (Transition::Wildcard(_), Transition::ElementDeclaration(_)) => {
+                    // 3. Their labels are a wildcard and an element declaration and the {namespace
+                    //    name} of the element declaration is in the range of the wildcard. [1]
+                    // NOTE: This was relaxed in XSD 1.1: an element declaration particle and a
+                    //   wildcard particle do not compete, the element declaration takes
+                    //   precedence (Unique Particle Attribution (§3.8.6.4)). This precedence is
+                    //   implemented by StateMachine::step.
+                }
             }
         }
     }
-    true
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateMachine, Transition, create_state_machine, verify_upa_satisfied};
+    use crate::{SchemaComponentTable, TypeDefinition, complex_type_def::ContentType};
+
+    fn accepts(sm: &StateMachine, names: &[&str], components: &SchemaComponentTable) -> bool {
+        let mut state = sm.start_state();
+        for name in names {
+            let matched = sm.step(&mut state, |t| match t {
+                Transition::ElementDeclaration(e) => e.get(components).name == *name,
+                Transition::Wildcard(_) => false,
+            });
+            if matched.is_none() {
+                return false;
+            }
+        }
+        sm.is_end_state(&state)
+    }
+
+    #[test]
+    fn choice_and_all() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="choice">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="a"/>
+                    <xs:choice maxOccurs="2">
+                      <xs:element name="b"/>
+                      <xs:element name="c"/>
+                    </xs:choice>
+                    <xs:element name="d" minOccurs="0"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="all">
+                <xs:complexType>
+                  <xs:all>
+                    <xs:element name="a"/>
+                    <xs:element name="b" maxOccurs="3"/>
+                    <xs:element name="c" minOccurs="0"/>
+                  </xs:all>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="ambiguous">
+                <xs:complexType>
+                  <xs:choice>
+                    <xs:element name="a"/>
+                    <xs:sequence>
+                      <xs:element name="a"/>
+                      <xs:element name="b"/>
+                    </xs:sequence>
+                  </xs:choice>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        let state_machine = |name: &str| {
+            let d = schema
+                .find_element_by_name(None, name, &components)
+                .unwrap()
+                .get(&components);
+            let TypeDefinition::Complex(t) = d.type_definition else {
+                panic!("expected a complex type");
+            };
+            let ContentType::ElementOnly { particle, .. } = &t.get(&components).content_type else {
+                panic!("expected element-only content");
+            };
+            create_state_machine(particle.get(&components), &components).unwrap()
+        };
+
+        let choice = state_machine("choice");
+        assert!(verify_upa_satisfied(&choice, &components));
+        assert!(accepts(&choice, &["a", "b"], &components));
+        assert!(accepts(&choice, &["a", "c", "b", "d"], &components));
+        assert!(!accepts(&choice, &["a"], &components));
+        assert!(!accepts(&choice, &["a", "b", "c", "b"], &components));
+
+        let all = state_machine("all");
+        assert!(matches!(all, StateMachine::All(_)));
+        assert!(verify_upa_satisfied(&all, &components));
+        assert!(accepts(&all, &["b", "a"], &components));
+        assert!(accepts(&all, &["b", "c", "b", "a", "b"], &components));
+        assert!(!accepts(&all, &["a"], &components));
+        assert!(!accepts(&all, &["a", "b", "b", "b", "b"], &components));
+        assert!(!accepts(&all, &["a", "b", "c", "c"], &components));
+
+        assert!(!verify_upa_satisfied(
+            &state_machine("ambiguous"),
+            &components
+        ));
+    }
 }
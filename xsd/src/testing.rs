@@ -0,0 +1,30 @@
+//! Helpers shared by the unit tests of several modules
+
+use crate::{
+    BuiltinOverwriteAction, RegisterBuiltins, Schema, SchemaComponentTable, error::XsdError,
+    include::IncludeResolver,
+};
+use roxmltree::Document;
+
+/// Reads the schema from the schema document with the source text `schema`, which is called
+/// `test.xsd` in errors. Imports and includes are not resolved.
+pub(crate) fn read_schema(schema: &str) -> Result<(Schema, SchemaComponentTable), XsdError> {
+    read_schema_with_includes(schema, Some("test.xsd"), &[])
+}
+
+/// Like [`read_schema()`], but with the given document URI and include resolvers
+pub(crate) fn read_schema_with_includes(
+    schema: &str,
+    document_uri: Option<&str>,
+    include_resolvers: &[Box<dyn IncludeResolver>],
+) -> Result<(Schema, SchemaComponentTable), XsdError> {
+    crate::read_schema(
+        Document::parse(schema).unwrap(),
+        document_uri,
+        BuiltinOverwriteAction::Deny,
+        RegisterBuiltins::Yes,
+        &[],
+        include_resolvers,
+        &mut (),
+    )
+}
@@ -1,9 +1,13 @@
 use crate::{
-    Ref,
+    ComplexTypeDefinition, ElementDeclaration, MappingContext, Ref, SimpleTypeDefinition,
     annotation::Annotation,
     assertion::XPathExpression,
+    builtins::XS_ANY_TYPE_NAME,
     components::{AnnotatedComponent, Component},
+    error::XsdError,
     shared::TypeDefinition,
+    simple_type_def::Context as SimpleContext,
+    xpath::TypeAlternativeTest,
     xstypes::Sequence,
 };
 use roxmltree::Node;
@@ -14,11 +18,105 @@ pub struct TypeAlternative {
     pub annotations: Sequence<Ref<Annotation>>,
     pub test: Option<XPathExpression>,
     pub type_definition: TypeDefinition,
+    /// The parsed {test}
+    pub test_expression: Option<TypeAlternativeTest>,
 }
 
 impl TypeAlternative {
-    pub fn map_from_xml(_alternative: Node, _schema: Node) -> Ref<Self> {
-        todo!()
+    pub const TAG_NAME: &'static str = "alternative";
+
+    /// Maps an `<alternative>` among the [children] of the `<element>` corresponding to
+    /// `element_decl`
+    pub(super) fn map_from_xml(
+        context: &mut MappingContext,
+        alternative: Node,
+        schema: Node,
+        element_decl: Ref<ElementDeclaration>,
+    ) -> Result<Ref<Self>, XsdError> {
+        assert_eq!(alternative.tag_name().name(), Self::TAG_NAME);
+
+        // {test}
+        //   If the test [attribute] is not present, then ·absent·; otherwise an XPath Expression
+        //   property record, as described in section XML Representation of Assertion Schema
+        //   Components (§3.13.2), with <alternative> as the "host element" and test as the
+        //   designated expression [attribute].
+        let test = alternative
+            .attribute("test")
+            .map(|test| XPathExpression::map_from_xml(context, test, alternative, schema))
+            .transpose()?;
+        // Schema Component Constraint: Type Alternative Properties Correct (§3.12.6)
+        // 2 If the {test} is present, it conforms to the XPath subset of §3.12.6 (or
+        //   implementation-defined extensions of it).
+        let test_expression = test
+            .as_ref()
+            .map(|test| {
+                TypeAlternativeTest::parse(test).map_err(|error| XsdError::InvalidXPath {
+                    expression: test.expression.clone(),
+                    error,
+                })
+            })
+            .transpose()?;
+
+        // {type definition}
+        //   The type definition ·resolved· to by the ·actual value· of the type [attribute], if
+        //   one is present, otherwise the type definition corresponding to the complexType or
+        //   simpleType among the [children] of the <alternative> element.
+        let type_definition = if let Some(type_) = alternative.attribute("type") {
+            let type_ = context.qname(type_, alternative)?;
+            let result = context
+                .resolve(&type_)
+                .ok_or(XsdError::UnresolvedTypeReference(type_));
+            context.recover(result, alternative, Self::DISPLAY_NAME, |context| {
+                context
+                    .resolve(&XS_ANY_TYPE_NAME)
+                    .ok_or(XsdError::UnresolvedReference(XS_ANY_TYPE_NAME))
+            })?
+        } else if let Some(simple_type) = alternative
+            .children()
+            .find(|c| c.tag_name().name() == SimpleTypeDefinition::TAG_NAME)
+        {
+            // The {context} of an anonymous type definition within an <alternative> is the
+            // Element Declaration corresponding to the nearest enclosing <element>.
+            let simple_type_def = SimpleTypeDefinition::map_from_xml(
+                context,
+                simple_type,
+                schema,
+                None,
+                Some(SimpleContext::Element(element_decl)),
+            )
+            .map_err(|e| context.locate(e, simple_type, SimpleTypeDefinition::DISPLAY_NAME))?;
+            TypeDefinition::Simple(simple_type_def)
+        } else if let Some(complex_type) = alternative
+            .children()
+            .find(|c| c.tag_name().name() == "complexType")
+        {
+            let complex_type_def = ComplexTypeDefinition::map_from_xml(
+                context,
+                complex_type,
+                schema,
+                Some(element_decl),
+                None,
+            )
+            .map_err(|e| context.locate(e, complex_type, ComplexTypeDefinition::DISPLAY_NAME))?;
+            TypeDefinition::Complex(complex_type_def)
+        } else {
+            return Err(XsdError::invalid_content(
+                alternative,
+                "expected a type attribute or a type definition",
+            ));
+        };
+
+        // {annotations}
+        //   The ·annotation mapping· of the <alternative> element, as defined in XML
+        //   Representation of Annotation Schema Components (§3.15.2).
+        let annotations = Annotation::xml_element_annotation_mapping(context, alternative);
+
+        Ok(context.create(Self {
+            annotations,
+            test,
+            type_definition,
+            test_expression,
+        }))
     }
 }
 
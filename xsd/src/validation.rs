@@ -1,143 +1,599 @@
 use crate::{
-    ComplexTypeDefinition, ElementDeclaration, Particle, SchemaComponentTable,
-    SimpleTypeDefinition, TypeDefinition,
+    AttributeDeclaration, AttributeUse, ComplexTypeDefinition, ElementDeclaration, Particle, Ref,
+    SchemaComponentTable, SimpleTypeDefinition, Term, TypeDefinition, Wildcard,
+    attribute_decl::ScopeVariety,
     builtins::XSI_NAMESPACE,
-    complex_type_def::{ContentType, ContentTypeVariety, OpenContent, OpenContentMode},
+    complex_type_def::{ContentType, ContentTypeVariety, DerivationMethod, OpenContent},
+    constraints::validly_substitutable,
+    element_decl::{SubstitutionMethod, TypeTable},
     shared::ValueConstraintVariety,
-    state_machine::Transition,
+    state_machine::{StateMachineCache, Transition},
+    wildcard::ProcessContents,
+    xstypes::{QName, Set},
 };
+use clauses::{
+    AttributeAttribution, Attribution, Children, ContentModelMatcher, WildcardAssessment,
+    attribute_attribution, attribute_fixed_value_valid, attribute_not_allowed_by_simple_type,
+    attribute_use_fixed_value_valid, declaration_not_abstract, default_valid_for_instance_type,
+    defaulted_attribute_valid, defaulted_attributes, element_only_content_valid,
+    empty_content_valid, fixed_value_valid, instance_specified_type_valid,
+    missing_required_attributes, nilled, nilled_element_valid, simple_content_children_valid,
+    simple_type_children_valid, type_not_abstract, wildcard_assessment,
+};
+use id_idref::IdIdrefTable;
+use std::collections::HashMap;
 
-/// 3.9.4.2 Element Sequence Locally Valid (Particle)
-/// <=> 3.9.4.3 Element Sequence Accepted (Particle)
-fn element_sequence_locally_valid_particle(
-    _e: &roxmltree::Node,
-    s: &[roxmltree::Node],
+mod assertion;
+mod augment;
+mod clauses;
+mod datatype;
+mod id_idref;
+mod identity;
+mod pattern;
+mod psvi;
+mod report;
+mod streaming;
+mod value;
+
+pub use augment::augmented_document;
+use datatype::normalized_string_valid;
+pub use datatype::{InvalidValue, string_valid};
+pub use id_idref::{IdIdrefViolation, IdIdrefViolationKind};
+pub use identity::{
+    IdentityConstraintViolation, IdentityConstraintViolationKind, check_identity_constraints,
+};
+pub use pattern::PatternError;
+pub(crate) use pattern::compile_pattern;
+pub use psvi::{AttributePsvi, ElementPsvi, Psvi, SchemaSpecified, ValidationAttempted, Validity};
+pub use report::{ValidationReport, Violation};
+pub use streaming::{
+    EventAttribute, EventReader, ReadError, StartElement, StreamingValidator, XmlEvent,
+    validate_stream,
+};
+pub use value::{DateTime, Decimal, Duration, NamespaceContext, Primitive, Value};
+
+/// The state of assessing a ·validation root·, which is shared by the assessment of its
+/// descendants
+#[derive(Default)]
+struct ValidationState {
+    id_idref_table: IdIdrefTable,
+    state_machines: StateMachineCache,
+}
+
+/// Returns a path identifying `node` in its document, e.g. `/order/item[2]`. Each element below
+/// the document element is given with its position among the preceding siblings with the same
+/// expanded name, so that paths can also be built while streaming (see [`validate_stream`]).
+fn node_path(node: roxmltree::Node) -> String {
+    let same_name = |a: &roxmltree::Node, b: &roxmltree::Node| {
+        a.is_element()
+            && a.tag_name().name() == b.tag_name().name()
+            && element_namespace(a) == element_namespace(b)
+    };
+    let mut steps = node
+        .ancestors()
+        .filter(|n| n.is_element())
+        .map(|n| {
+            let name = n.tag_name().name();
+            if n.parent_element().is_none() {
+                return name.to_string();
+            }
+            // The iterator starts with `n` itself
+            let position = n.prev_siblings().filter(|s| same_name(s, &n)).count();
+            format!("{name}[{position}]")
+        })
+        .collect::<Vec<_>>();
+    steps.reverse();
+    format!("/{}", steps.join("/"))
+}
+
+/// Returns a path identifying attribute `a` of element `e`, e.g. `/order/item[2]/@id`
+fn attribute_path(e: &roxmltree::Node, a: &roxmltree::Attribute) -> String {
+    format!("{}/@{}", node_path(*e), a.name())
+}
+
+/// Returns the namespace name of element `e`, if any. roxmltree reports the namespace of an
+/// element in the scope of `xmlns=""` as the empty string, which is not a namespace name.
+pub fn element_namespace<'a>(e: &roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    e.tag_name()
+        .namespace()
+        .filter(|namespace| !namespace.is_empty())
+}
+
+/// Returns the expanded name of element `e`
+fn element_name(e: &roxmltree::Node) -> QName {
+    QName::with_optional_namespace(element_namespace(e), e.tag_name().name())
+}
+
+/// Returns the expanded name of attribute `a`
+fn attribute_name(a: &roxmltree::Attribute) -> QName {
+    let namespace = a.namespace().filter(|namespace| !namespace.is_empty());
+    QName::with_optional_namespace(namespace, a.name())
+}
+
+/// Returns whether `a` is one of the attributes xsi:type, xsi:nil, xsi:schemaLocation or
+/// xsi:noNamespaceSchemaLocation, which are not governed by the type of their element (see
+/// Built-in Attribute Declarations (§3.2.7))
+fn is_builtin_xsi_attribute(a: &roxmltree::Attribute) -> bool {
+    is_builtin_xsi_attribute_name(a.namespace(), a.name())
+}
+
+/// Returns whether the expanded name (`namespace`, `local_name`) is that of xsi:type, xsi:nil,
+/// xsi:schemaLocation or xsi:noNamespaceSchemaLocation
+fn is_builtin_xsi_attribute_name(namespace: Option<&str>, local_name: &str) -> bool {
+    namespace == Some(XSI_NAMESPACE)
+        && matches!(
+            local_name,
+            "type" | "nil" | "schemaLocation" | "noNamespaceSchemaLocation"
+        )
+}
+
+/// Returns the global attribute declaration with the given expanded name, if any
+fn global_attribute_declaration(
+    name: &QName,
+    components: &SchemaComponentTable,
+) -> Option<Ref<AttributeDeclaration>> {
+    components
+        .global_names()
+        .attribute_declarations
+        .get(name)
+        .copied()
+}
+
+/// Returns the global element declaration with the given expanded name, if any
+fn global_element_declaration(
+    name: &QName,
+    components: &SchemaComponentTable,
+) -> Option<Ref<ElementDeclaration>> {
+    components
+        .global_names()
+        .element_declarations
+        .get(name)
+        .copied()
+}
+
+/// Returns whether element declaration `d` has the expanded name `name`
+fn has_expanded_name(d: &ElementDeclaration, name: &QName) -> bool {
+    d.name == name.local_name() && d.target_namespace.as_deref() == name.namespace_name()
+}
+
+/// The ·substitution group·s of element declarations, computed on demand
+type SubstitutionGroups = HashMap<Ref<ElementDeclaration>, Vec<Ref<ElementDeclaration>>>;
+
+/// Returns whether an element named `name` may be matched by the transition `label` of the state
+/// machine for the content model `particle`
+fn transition_matches(
+    label: &Transition,
+    name: &QName,
+    particle: &Particle,
+    substitution_groups: &mut SubstitutionGroups,
+    components: &SchemaComponentTable,
+) -> bool {
+    match label {
+        Transition::ElementDeclaration(label) => {
+            let d = label.get(components);
+            // 2.3.1 E and D have the same expanded name, or
+            // 2.3.2 D is top-level (i.e. D.{scope}.{variety} = global), its {disallowed
+            //   substitutions} does not contain substitution, E's expanded name ·resolves· to an
+            //   element declaration S — [Definition:]  call this declaration the substituting
+            //   declaration — and ·S· is ·substitutable· for D as defined in Substitution Group OK
+            //   (Transitive) (§3.3.6.3).
+            has_expanded_name(d, name)
+                || (d.scope.variety() == ScopeVariety::Global
+                    && substitution_groups
+                        .entry(*label)
+                        .or_insert_with(|| {
+                            ElementDeclaration::substitution_group(*label, components)
+                        })
+                        .iter()
+                        .any(|s| has_expanded_name(s.get(components), name)))
+        }
+        Transition::Wildcard(label) => {
+            wildcard_allows_element(label.get(components), name, particle, components)
+        }
+    }
+}
+
+/// Returns the ·context-determined declaration· of an element named `name`, which was matched by
+/// the element declaration `label`: `label` if they have the same expanded name, and the
+/// ·substituting declaration· otherwise (3.3.4.6 Schema-Validity Assessment (Element), clause
+/// 1.1.1.2). An abstract declaration can thus only be used through its substitution group (see
+/// Element Locally Valid (Element) (§3.3.4.3), clause 2).
+fn context_determined_declaration(
+    label: Ref<ElementDeclaration>,
+    name: &QName,
+    substitution_groups: &SubstitutionGroups,
+    components: &SchemaComponentTable,
+) -> Ref<ElementDeclaration> {
+    if has_expanded_name(label.get(components), name) {
+        label
+    } else {
+        *substitution_groups[&label]
+            .iter()
+            .find(|s| has_expanded_name(s.get(components), name))
+            .unwrap()
+    }
+}
+
+/// Returns whether `particle` ·contains· an element declaration with the given expanded name
+fn particle_contains_element(
     particle: &Particle,
-    _open_content: &Option<OpenContent>,
+    name: &QName,
     components: &SchemaComponentTable,
 ) -> bool {
-    // TODO: cache
-    let state_machine = crate::state_machine::create_state_machine(particle, &components);
-    let upa = crate::state_machine::verify_upa_satisfied(&state_machine, &components);
-    assert!(upa);
+    match &particle.term {
+        Term::ElementDeclaration(d) => {
+            let d = d.get(components);
+            d.name == name.local_name() && d.target_namespace.as_deref() == name.namespace_name()
+        }
+        Term::ModelGroup(group) => group
+            .get(components)
+            .particles
+            .iter()
+            .any(|p| particle_contains_element(p.get(components), name, components)),
+        Term::Wildcard(_) => false,
+    }
+}
 
-    let mut current_state = state_machine.start_state.unwrap();
+/// 3.10.4.2 Wildcard allows Expanded Name, for an element information item matched by a wildcard
+/// in the content model `particle`
+fn wildcard_allows_element(
+    w: &Wildcard,
+    name: &QName,
+    particle: &Particle,
+    components: &SchemaComponentTable,
+) -> bool {
+    // For an expanded name E, i.e. a (namespace name, local name) pair, to be ·valid· with
+    // respect to a namespace constraint C all of the following must be true:
+    // 1 The namespace name is ·valid· with respect to C, as defined in Wildcard allows Namespace
+    //   Name (§3.10.4.3);
+    // 2 C.{disallowed names} does not contain E.
+    if !w.allows_name(name) {
+        return false;
+    }
 
-    // deviating from the spec here
-    for el in s {
-        let ts = state_machine.get_transitions(current_state);
-        let mut found = false;
-        for (label, to) in ts {
-            match label {
-                Transition::ElementDeclaration(label) => {
-                    let element = label.get(components);
-                    if element.target_namespace.as_deref() == el.tag_name().namespace()
-                        && element.name == el.tag_name().name()
-                    {
-                        println!(
-                            "Matched element {:?} to {label:?} (going to state {to})",
-                            element.name
-                        );
-                        // TODO: 2.3.2 D is top-level (i.e. D.{scope}.{variety} = global), its
-                        // {disallowed substitutions} does not contain substitution, E's expanded
-                        // name ·resolves· to an element declaration S — [Definition:]  call this
-                        // declaration the substituting declaration — and ·S· is ·substitutable·
-                        // for D as defined in Substitution Group OK (Transitive) (§3.3.6.3).
-                        current_state = *to;
-                        found = true;
-                        break;
-                    }
-                }
-                Transition::Wildcard(label) => {
-                    let _wildcard = label.get(components);
-                    todo!()
+    // 3 If C.{disallowed names} contains the keyword defined, then E does not ·resolve· to a
+    //   global element declaration.
+    let disallowed_names = &w.namespace_constraint.disallowed_names;
+    if disallowed_names.contains_defined() && global_element_declaration(name, components).is_some()
+    {
+        return false;
+    }
+
+    // 4 If C.{disallowed names} contains the keyword sibling, then E is not the expanded name of
+    //   an element declaration ·contained· in the content model containing the wildcard.
+    if disallowed_names.contains_sibling() && particle_contains_element(particle, name, components)
+    {
+        return false;
+    }
+
+    true
+}
+
+/// Assesses an element information item matched by a wildcard, as determined by the wildcard's
+/// {process contents} (3.3.4.6 Schema-Validity Assessment (Element))
+fn element_assessed_by_wildcard(
+    e: &roxmltree::Node,
+    process_contents: &ProcessContents,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> bool {
+    let assessment = wildcard_assessment(
+        &element_name(e),
+        process_contents,
+        instance_specified_type_definition(e, components),
+        components,
+    );
+    match assessment {
+        Ok(WildcardAssessment::Skip) => true,
+        Ok(WildcardAssessment::Declaration(d)) => {
+            element_locally_valid_element_inner(e, Some(d), state, report, components)
+        }
+        Ok(WildcardAssessment::Type(t)) => {
+            let mark = report.mark();
+            report.psvi.element_mut(e).type_definition = Some(t);
+            let valid = element_locally_valid_type(
+                e,
+                Some(t),
+                false,
+                &initial_value(e),
+                state,
+                report,
+                components,
+            );
+            report.record_assessment(e, mark);
+            valid
+        }
+        // E itself is not ·strictly assessed·, but its attributes and children are assessed
+        // laxly, i.e. with respect to their global declarations, if any (clause 2).
+        Ok(WildcardAssessment::Lax) => {
+            let mut valid = true;
+            for a in e.attributes().filter(|a| !is_builtin_xsi_attribute(a)) {
+                if let Some(d) = global_attribute_declaration(&attribute_name(&a), components) {
+                    valid &= attribute_locally_valid(e, &a, d, state, report, components).is_some();
                 }
             }
+            for child in e.children().filter(|c| c.is_element()) {
+                valid &= element_assessed_by_wildcard(
+                    &child,
+                    &ProcessContents::Lax,
+                    state,
+                    report,
+                    components,
+                );
+            }
+            report.record_lax_assessment(e);
+            valid
+        }
+        Err(violation) => {
+            report.element_violation(e, violation);
+            false
+        }
+    }
+}
+
+/// 3.2.4.1 Attribute Locally Valid
+///
+/// Returns the ·actual value· of A if it is locally ·valid· with respect to D, and records the
+/// PSVI properties of A (3.2.5 Attribute Declaration Information Set Contributions).
+fn attribute_locally_valid(
+    e: &roxmltree::Node,
+    a: &roxmltree::Attribute,
+    d: Ref<AttributeDeclaration>,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> Option<Value> {
+    let mark = report.mark();
+    let value = attribute_locally_valid_clauses(e, a, d.get(components), state, report, components);
+    let psvi = report.psvi.attribute_mut(e, a);
+    psvi.attribute_declaration = Some(d);
+    psvi.type_definition = Some(d.get(components).type_definition);
+    report.record_attribute_assessment(e, a, mark);
+    value
+}
+
+/// The clauses of Attribute Locally Valid (§3.2.4.1), see [`attribute_locally_valid`]
+fn attribute_locally_valid_clauses(
+    e: &roxmltree::Node,
+    a: &roxmltree::Attribute,
+    d: &AttributeDeclaration,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> Option<Value> {
+    // For an attribute information item A to be locally ·valid· with respect to an attribute
+    // declaration D all of the following must be true:
+
+    // 1 D is not ·absent· (see Missing Sub-components (§5.3) for how this can fail to be the
+    //   case) and D and A have the same expanded name.
+    if d.name != a.name() || d.target_namespace.as_deref() != a.namespace() {
+        report.attribute(
+            e,
+            a,
+            "cvc-attribute.1",
+            format!(
+                "attribute {} does not match its declaration {}",
+                attribute_name(a),
+                QName::with_optional_namespace(d.target_namespace.as_deref(), d.name.as_str())
+            ),
+        );
+        return None;
+    }
+
+    // 2 D.{type definition} is not absent.
+    // NOTE: always present in this implementation
+
+    // 3 A's ·initial value· is locally ·valid· with respect to D.{type definition} as per String
+    //   Valid (§3.16.4).
+    let t = d.type_definition.get(components);
+    let value = match normalized_string_valid(a.value(), t, e, components) {
+        Ok((normalized, value, member_types)) => {
+            state
+                .id_idref_table
+                .record_attribute(e, a, t, &value, &member_types, components);
+            let psvi = report.psvi.attribute_mut(e, a);
+            psvi.schema_normalized_value = Some(normalized);
+            psvi.schema_actual_value = Some(value.clone());
+            psvi.member_type_definition = member_types.member_type_definition();
+            value
         }
+        Err(error) => {
+            report.attribute(e, a, error.code(), error.to_string());
+            return None;
+        }
+    };
+    // 4 If D.{value constraint} is present and D.{value constraint}.{variety} = fixed, then A's
+    //   ·actual value· is equal or identical to D.{value constraint}.{value}.
+    if let Err(violation) = attribute_fixed_value_valid(d, a.value(), &value) {
+        report.attribute_violation(e, a, violation);
+        return None;
+    }
+
+    Some(value)
+}
 
-        if !found {
+/// 3.5.4.2 Attribute Locally Valid (Use)
+fn attribute_locally_valid_use(
+    e: &roxmltree::Node,
+    a: &roxmltree::Attribute,
+    u: &AttributeUse,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> bool {
+    // The ·actual value· of A is determined by U.{attribute declaration}, see Attribute Locally
+    // Valid (§3.2.4.1).
+    let mark = report.mark();
+    let Some(value) =
+        attribute_locally_valid(e, a, u.attribute_declaration, state, report, components)
+    else {
+        return false;
+    };
+
+    if let Err(violation) = attribute_use_fixed_value_valid(u, a.value(), &value, components) {
+        report.attribute_violation(e, a, violation);
+        report.record_attribute_assessment(e, a, mark);
+        return false;
+    }
+    true
+}
+
+/// 3.9.4.2 Element Sequence Locally Valid (Particle)
+/// <=> 3.9.4.3 Element Sequence Accepted (Particle)
+///
+/// With `open_content`, the elements of S which have no ·path· in `particle` are matched by its
+/// wildcard instead, see Element Sequence Locally Valid (Complex Content) (§3.4.4.3).
+fn element_sequence_locally_valid_particle(
+    e: &roxmltree::Node,
+    s: &[roxmltree::Node],
+    particle: Ref<Particle>,
+    open_content: &Option<OpenContent>,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> bool {
+    let mut matcher = match ContentModelMatcher::new(
+        particle,
+        open_content,
+        &mut state.state_machines,
+        components,
+    ) {
+        Ok(matcher) => matcher,
+        Err(violation) => {
+            report.element_violation(e, violation);
             return false;
         }
+    };
+
+    let mut valid = true;
+    // deviating from the spec here
+    for el in s {
+        valid &= match matcher.next(&element_name(el)) {
+            Ok(Attribution::Declaration(declaration)) => element_locally_valid_element_inner(
+                el,
+                Some(declaration),
+                state,
+                report,
+                components,
+            ),
+            Ok(Attribution::Wildcard(process_contents)) => {
+                element_assessed_by_wildcard(el, &process_contents, state, report, components)
+            }
+            // The sequence is not matched any further, as its remaining elements cannot be
+            // attributed to the particles reliably.
+            Err(violation) => {
+                report.element_violation(el, violation);
+                return false;
+            }
+        };
     }
 
-    state_machine.is_end_state(current_state)
+    if let Err(violation) = matcher.end(&element_name(e)) {
+        report.element_violation(e, violation);
+        return false;
+    }
+    valid
 }
 
 /// 3.4.4.3 Element Sequence Locally Valid (Complex Content)
 fn element_sequence_locally_valid_complex_content(
     e: &roxmltree::Node,
     s: &[roxmltree::Node],
-    particle: &Particle,
+    particle: Ref<Particle>,
     open_content: &Option<OpenContent>,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
     components: &SchemaComponentTable,
 ) -> bool {
     // For a sequence S (possibly empty) of element information items to be locally ·valid· with
     // respect to a Content Type CT, the appropriate case among the following must be true:
 
-    match open_content {
-        None => {
-            // 1 If CT.{open content} is ·absent·, then S is ·valid· with respect to CT.{particle},
-            //   as defined in Element Sequence Locally Valid (Particle) (§3.9.4.2).
-            element_sequence_locally_valid_particle(e, s, particle, open_content, components)
-        }
-        Some(open_content) => match open_content.mode {
-            OpenContentMode::Suffix => {
-                // 2 If CT.{open content}.{mode} = suffix , then S can be represented as two
-                //   subsequences S1 and S2 (either can be empty) such that all of the following
-                //   are true:
-                todo!()
-            }
-            OpenContentMode::Interleave => {
-                // 3 otherwise (CT.{open content}.{mode} = interleave) S can be represented as two
-                //   subsequences S1 and S2 (either can be empty) such that all of the following
-                //   are true:
-                todo!()
-            }
-        },
-    }
+    // 1 If CT.{open content} is ·absent·, then S is ·valid· with respect to CT.{particle}, as
+    //   defined in Element Sequence Locally Valid (Particle) (§3.9.4.2).
+    // 2 If CT.{open content}.{mode} = suffix , then S can be represented as two subsequences S1
+    //   and S2 (either can be empty) such that all of the following are true:
+    //   2.1 S = S1 + S2
+    //   2.2 S1 is ·valid· with respect to CT.{particle}, as defined in Element Sequence Locally
+    //     Valid (Particle) (§3.9.4.2).
+    //   2.3 If S2 is not empty, let E be the first element in S2, then S1 + E does not have a
+    //     ·path· in CT.{particle}
+    //   2.4 Every element in S2 is ·valid· with respect to the wildcard CT.{open
+    //     content}.{wildcard}, as defined in Item Valid (Wildcard) (§3.10.4.1).
+    // 3 otherwise (CT.{open content}.{mode} = interleave) S can be represented as two
+    //   subsequences S1 and S2 (either can be empty) such that all of the following are true:
+    //   3.1 S is a member of S1 × S2 (where × is the ·interleave· operator)
+    //   3.2 S1 is ·valid· with respect to CT.{particle}, as defined in Element Sequence Locally
+    //     Valid (Particle) (§3.9.4.2).
+    //   3.3 For every element E in S2, let S3 be the sequence of elements in S1 which precede E
+    //     in S, then S3 + E does not have a ·path· in CT.{particle}.
+    //   3.4 Every element in S2 is ·valid· with respect to the wildcard CT.{open
+    //     content}.{wildcard}, as defined in Item Valid (Wildcard) (§3.10.4.1).
+    // As the particle is deterministic (UPA), S1 and S2 are determined by matching each element
+    // against the particle first, and against the wildcard if it has no ·path·.
+    element_sequence_locally_valid_particle(e, s, particle, open_content, state, report, components)
 }
 
 /// 3.4.4.2 Element Locally Valid (Complex Type)
+///
+/// `initial_value` is the ·initial value· of E, or the value used in its stead (see clause 5.1.2
+/// of Element Locally Valid (Element)).
 fn element_locally_valid_complex_type(
     e: &roxmltree::Node,
     e_is_nilled: bool,
+    initial_value: &str,
     t: &ComplexTypeDefinition,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
     components: &SchemaComponentTable,
 ) -> bool {
+    let mut valid = true;
+    let name = element_name(e);
+    let children = children(e);
+
     // For an element information item E to be locally ·valid· with respect to a complex type
     // definition T all of the following must be true:
     if !e_is_nilled {
         // 1 If E is not ·nilled·, then all of the following are true:
         match &t.content_type {
             ContentType::Empty => {
-                // 1.1 If T.{content type}.{variety} = empty, then E has no character or element
-                //   information item [children].
-                if e.has_children() {
-                    return false;
+                if let Err(violation) = empty_content_valid(&name, children) {
+                    report.element_violation(e, violation);
+                    valid = false;
                 }
             }
             ContentType::Simple {
                 simple_type_definition,
             } => {
-                // 1.2 If T.{content type}.{variety} = simple, then E has no element information
-                //   item [children], and the ·initial value· of E is ·valid· with respect to
-                //   T.{content type}.{simple type definition} as defined by String Valid
-                //   (§3.16.4).
-                if e.children().any(|c| c.is_element()) {
-                    return false;
-                }
-
-                // (...) the initial value of an element information item is the string composed
-                // of, in order, the [character code] of each character information item in the
-                // [children] of that element information item. (Part 1, 3.1.4)
-                let initial_value: String = e
-                    .children()
-                    .filter(|c| c.is_text())
-                    .map(|c| c.text().unwrap())
-                    .collect();
-
-                if !string_valid(&initial_value, simple_type_definition.get(components)) {
-                    return false;
+                if let Err(violation) = simple_content_children_valid(&name, children) {
+                    report.element_violation(e, violation);
+                    valid = false;
+                } else {
+                    let simple_type_definition = simple_type_definition.get(components);
+                    match normalized_string_valid(
+                        initial_value,
+                        simple_type_definition,
+                        e,
+                        components,
+                    ) {
+                        Ok((normalized, value, member_types)) => {
+                            state.id_idref_table.record_element(
+                                e,
+                                simple_type_definition,
+                                &value,
+                                &member_types,
+                                components,
+                            );
+                            let psvi = report.psvi.element_mut(e);
+                            psvi.schema_normalized_value = Some(normalized);
+                            psvi.schema_actual_value = Some(value);
+                            psvi.member_type_definition = member_types.member_type_definition();
+                        }
+                        Err(error) => {
+                            report.element(e, error.code(), error.to_string());
+                            valid = false;
+                        }
+                    }
                 }
             }
             ContentType::ElementOnly {
@@ -148,16 +604,11 @@ fn element_locally_valid_complex_type(
                 particle,
                 open_content,
             } => {
-                if t.content_type.variety() == ContentTypeVariety::ElementOnly {
-                    // 1.3 If T.{content type}.{variety} = element-only, then E has no character
-                    //   information item [children] other than those whose [character code] is
-                    //   defined as a white space in [XML 1.1].
-                    // TODO: whitespace, refer to generator utility lib
-                    if e.children()
-                        .any(|c| c.is_text() && !c.text().unwrap().trim().is_empty())
-                    {
-                        return false;
-                    }
+                if t.content_type.variety() == ContentTypeVariety::ElementOnly
+                    && let Err(violation) = element_only_content_valid(&name, children)
+                {
+                    report.element_violation(e, violation);
+                    valid = false;
                 }
 
                 // 1.4 If T.{content type}.{variety} = element-only or T.{content
@@ -166,16 +617,15 @@ fn element_locally_valid_complex_type(
                 //   T.{content type}, as defined in Element Sequence Locally Valid (Complex
                 //   Content) (§3.4.4.3).
                 let s = e.children().filter(|c| c.is_element()).collect::<Vec<_>>();
-                let particle = particle.get(components);
-                if !element_sequence_locally_valid_complex_content(
+                valid &= element_sequence_locally_valid_complex_content(
                     e,
                     &s,
-                    particle,
+                    *particle,
                     open_content,
+                    state,
+                    report,
                     components,
-                ) {
-                    return false;
-                }
+                );
             }
         }
     }
@@ -183,14 +633,57 @@ fn element_locally_valid_complex_type(
     // 2 For each attribute information item A in E.[attributes] excepting those named xsi:type,
     //   xsi:nil, xsi:schemaLocation, or xsi:noNamespaceSchemaLocation (see Built-in Attribute
     //   Declarations (§3.2.7)), the appropriate case among the following is true:
+    for a in e.attributes().filter(|a| !is_builtin_xsi_attribute(a)) {
+        valid &= match attribute_attribution(t, &attribute_name(&a), components) {
+            Ok(AttributeAttribution::Use(attribute_use)) => {
+                attribute_locally_valid_use(e, &a, attribute_use, state, report, components)
+            }
+            Ok(AttributeAttribution::Wildcard(None)) => true,
+            Ok(AttributeAttribution::Wildcard(Some(d))) => {
+                attribute_locally_valid(e, &a, d, state, report, components).is_some()
+            }
+            Err(violation) => {
+                report.attribute_violation(e, &a, violation);
+                false
+            }
+        };
+    }
 
     // 3 For each attribute use U in T.{attribute uses}, if U.{required} = true, then U.{attribute
     //   declaration} has the same expanded name as one of the attribute information items in
     //   E.[attributes].
+    let has_attribute = |name: &QName| e.attributes().any(|a| attribute_name(&a) == *name);
+    for violation in missing_required_attributes(t, has_attribute, components) {
+        report.element_violation(e, violation);
+        valid = false;
+    }
 
     // 4 For each ·defaulted attribute· A belonging to E, the {lexical form} of A's ·effective
     //   value constraint· is ·valid· with respect to A.{attribute declaration}.{type definition}
     //   as defined by String Valid (§3.16.4).
+    for (attribute_use, value_constraint) in defaulted_attributes(t, has_attribute, components) {
+        let d = attribute_use.attribute_declaration.get(components);
+        let name = QName::with_optional_namespace(d.target_namespace.as_deref(), d.name.as_str());
+        // Attribute Default Value (§3.4.5.1): A is added to E.[attributes], with its ·effective
+        // value constraint·'s {lexical form} as its [normalized value].
+        let result = defaulted_attribute_valid(attribute_use, value_constraint, e, components);
+        let psvi = report.psvi.defaulted_attribute_mut(e, name);
+        psvi.attribute_declaration = Some(attribute_use.attribute_declaration);
+        psvi.type_definition = Some(d.type_definition);
+        psvi.validation_attempted = ValidationAttempted::Full;
+        psvi.schema_normalized_value = Some(value_constraint.lexical_form.clone());
+        match result {
+            Ok(value) => {
+                psvi.validity = Validity::Valid;
+                psvi.schema_actual_value = Some(value);
+            }
+            Err(violation) => {
+                psvi.validity = Validity::Invalid;
+                report.element_violation(e, violation);
+                valid = false;
+            }
+        }
+    }
 
     // 5 For each element information item in E.[children] and each attribute information item in
     //   E.[attributes], if neither the ·governing type definition· nor the ·locally declared type·
@@ -199,101 +692,277 @@ fn element_locally_valid_complex_type(
 
     // 6 E is ·valid· with respect to each of the assertions in T.{assertions} as per Assertion
     //   Satisfied (§3.13.4.1).
-    // TODO
+    valid &= assertion::assertions_satisfied(e, t, report, components);
 
-    true
+    valid
+}
+
+/// (...) the initial value of an element information item is the string composed of, in order,
+/// the [character code] of each character information item in the [children] of that element
+/// information item. (Part 1, 3.1.4)
+fn initial_value(e: &roxmltree::Node) -> String {
+    e.children()
+        .filter(|c| c.is_text())
+        .map(|c| c.text().unwrap())
+        .collect()
 }
 
-fn string_valid(string: &str, t: &SimpleTypeDefinition) -> bool {
-    todo!()
+/// Returns what the [children] of `e` consist of
+fn children(e: &roxmltree::Node) -> Children {
+    let mut children = Children::default();
+    for c in e.children() {
+        if c.is_element() {
+            children.elements = true;
+        } else if c.is_text() {
+            children.add_characters(c.text().unwrap());
+        }
+    }
+    children
 }
 
+/// Returns the simple type definition constraining the ·initial value· of an element whose
+/// governing type definition is `t`: `t` itself if it is a simple type definition, or its {content
+/// type}.{simple type definition} if its {content type}.{variety} = simple. Returns `None` for all
+/// other complex type definitions.
+fn simple_content_type(
+    t: TypeDefinition,
+    components: &SchemaComponentTable,
+) -> Option<&SimpleTypeDefinition> {
+    match t {
+        TypeDefinition::Simple(t) => Some(t.get(components)),
+        TypeDefinition::Complex(t) => match &t.get(components).content_type {
+            ContentType::Simple {
+                simple_type_definition,
+            } => Some(simple_type_definition.get(components)),
+            _ => None,
+        },
+    }
+}
+
+/// 3.3.6.2 Element Default Valid (Immediate), for the {lexical form} of a value constraint of an
+/// element whose ·governing type definition· is `t`
+fn element_default_valid_immediate(
+    t: TypeDefinition,
+    lexical_form: &str,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> bool {
+    // T is a simple type definition or has a simple {content type} and V.{lexical form} is
+    // ·valid· with respect to that simple type definition as defined by String Valid (§3.16.4),
+    // or T has a mixed {content type} with an ·emptiable· particle.
+    match simple_content_type(t, components) {
+        Some(st) => string_valid(lexical_form, st, context, components).is_ok(),
+        None => matches!(
+            &t.complex().unwrap().get(components).content_type,
+            ContentType::Mixed { particle, .. } if particle.get(components).is_emptiable(components)
+        ),
+    }
+}
+
+/// 3.3.4.4 Element Locally Valid (Type)
+///
+/// `initial_value` is the ·initial value· of E, or the value used in its stead (see clause 5.1.2
+/// of Element Locally Valid (Element)).
 fn element_locally_valid_type(
     e: &roxmltree::Node,
     t: Option<TypeDefinition>,
     e_is_nilled: bool,
+    initial_value: &str,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
     components: &SchemaComponentTable,
 ) -> bool {
     // For an element information item E to be locally ·valid· with respect to a type definition T all of the following must be true:
 
     // 1 T is not ·absent·;
     let Some(t) = t else {
+        report.element(
+            e,
+            "cvc-type.1",
+            format!("element {} has no type definition", element_name(e)),
+        );
         return false;
     };
 
+    if let Err(violation) = type_not_abstract(t, components) {
+        report.element_violation(e, violation);
+        return false;
+    }
+
     // 3 The appropriate case among the following is true:
     match t {
         TypeDefinition::Simple(t) => {
             let t = t.get(components);
+            let name = element_name(e);
+            let mut valid = true;
 
             // 3.1 If T is a simple type definition, then all of the following are true:
-
-            //   3.1.1 E.[attributes] is empty, except for attributes named xsi:type, xsi:nil,
-            //     xsi:schemaLocation, or xsi:noNamespaceSchemaLocation.
-            if e.attributes().any(|a| {
-                a.namespace() != Some(XSI_NAMESPACE)
-                    || a.name() != "type"
-                    || a.name() != "nil"
-                    || a.name() != "schemaLocation"
-                    || a.name() != "noNamespaceSchemaLocation"
-            }) {
-                return false;
+            for a in e.attributes().filter(|a| !is_builtin_xsi_attribute(a)) {
+                let violation = attribute_not_allowed_by_simple_type(&attribute_name(&a), &name);
+                report.attribute_violation(e, &a, violation);
+                valid = false;
             }
-
-            // 3.1.2 E has no element information item [children].
-            if e.children().any(|c| c.is_element()) {
+            if let Err(violation) = simple_type_children_valid(&name, children(e)) {
+                report.element_violation(e, violation);
                 return false;
             }
 
             // 3.1.3 If E is not ·nilled·, then the ·initial value· is ·valid· with respect to T as
             //   defined by String Valid (§3.16.4).
             if !e_is_nilled {
-                // (...) the initial value of an element information item is the string composed
-                // of, in order, the [character code] of each character information item in the
-                // [children] of that element information item. (Part 1, 3.1.4)
-                let initial_value: String = e
-                    .children()
-                    .filter(|c| c.is_text())
-                    .map(|c| c.text().unwrap())
-                    .collect();
-
-                string_valid(&initial_value, t)
-            } else {
-                true
+                match normalized_string_valid(initial_value, t, e, components) {
+                    Ok((normalized, value, member_types)) => {
+                        state.id_idref_table.record_element(
+                            e,
+                            t,
+                            &value,
+                            &member_types,
+                            components,
+                        );
+                        let psvi = report.psvi.element_mut(e);
+                        psvi.schema_normalized_value = Some(normalized);
+                        psvi.schema_actual_value = Some(value);
+                        psvi.member_type_definition = member_types.member_type_definition();
+                    }
+                    Err(error) => {
+                        report.element(e, error.code(), error.to_string());
+                        valid = false;
+                    }
+                }
             }
+            valid
         }
         TypeDefinition::Complex(t) => {
             let t = t.get(components);
 
-            // 2 If T is a complex type definition, then T.{abstract} = false.
-            if t.abstract_ {
-                return false;
-            }
-
             // 3.2 If T is a complex type definition, then E is locally ·valid· with respect to T
             //   as per Element Locally Valid (Complex Type) (§3.4.4.2);
-            element_locally_valid_complex_type(e, e_is_nilled, t, components)
+            element_locally_valid_complex_type(
+                e,
+                e_is_nilled,
+                initial_value,
+                t,
+                state,
+                report,
+                components,
+            )
         }
     }
 }
 
-fn selected_type_definition(_e: &roxmltree::Node, d: &ElementDeclaration) -> TypeDefinition {
+fn selected_type_definition(
+    e: &roxmltree::Node,
+    d: &ElementDeclaration,
+    components: &SchemaComponentTable,
+) -> TypeDefinition {
     // The selected type definition S of an element information item E is a type definition
     // associated with E in the following way. Let D be the ·governing element declaration· of E.
     // Then:
-    if let Some(_type_table) = d.type_table.as_ref() {
+    if let Some(type_table) = d.type_table.as_ref() {
         // 1 If D has a {type table}, then S is the type ·conditionally selected· for E by D.{type
         //   table}.
-        todo!()
+        conditionally_selected_type(&untyped_attributes(e), type_table, components)
     } else {
         // 2 If D has no {type table}, then S is D.{type definition}.
         d.type_definition
     }
 }
 
+/// Returns the [attributes] of `e` as they are seen by the {test} of a Type Alternative
+fn untyped_attributes(e: &roxmltree::Node) -> Set<(QName, String)> {
+    // The {test} is evaluated with E's [attributes] (but not its [children] or [parent]) in the
+    // data model instance, all of which are untyped.
+    e.attributes()
+        .map(|a| (attribute_name(&a), a.value().to_string()))
+        .collect()
+}
+
+/// 3.12.4 Type Alternative Satisfied, and the type ·conditionally selected· by `type_table` for
+/// an element with the given `attributes` (§3.3.4.1)
+fn conditionally_selected_type(
+    attributes: &Set<(QName, String)>,
+    type_table: &TypeTable,
+    components: &SchemaComponentTable,
+) -> TypeDefinition {
+    // Given a Type Table T and an element information item E, T conditionally selects a type S
+    // for E in the following way. The {test} expressions in T's {alternatives} are evaluated, in
+    // order, until one of the Type Alternatives successfully selects a type definition for E, or
+    // until all have been tried without success. If any Type Alternative successfully selects a
+    // type definition, none of the following Type Alternatives are tried. Then the type S
+    // conditionally selected for E by T is as described in the appropriate case among the
+    // following:
+    type_table
+        .alternatives
+        .iter()
+        .map(|alternative| alternative.get(components))
+        .find(|alternative| {
+            // A Type Alternative A successfully selects a type definition S for an element
+            // information item E if and only if A.{test} evaluates to true and A.{type
+            // definition} = S.
+            alternative
+                .test_expression
+                .as_ref()
+                .is_some_and(|test| test.evaluate(attributes))
+        })
+        // 1 If at least one Type Alternative in T.{alternatives} successfully selects a type
+        //   definition for E, then S is the type definition selected by the first such Type
+        //   Alternative.
+        // 2 If no Type Alternative in T.{alternatives} successfully selects a type definition,
+        //   then S is T.{default type definition}.{type definition}.
+        .unwrap_or_else(|| type_table.default_type_definition.get(components))
+        .type_definition
+}
+
+/// Returns the type definition with the given expanded name, if any
+fn type_definition_by_name(
+    name: &QName,
+    components: &SchemaComponentTable,
+) -> Option<TypeDefinition> {
+    components
+        .global_names()
+        .type_definitions
+        .get(name)
+        .copied()
+}
+
+/// [Definition:] An instance-specified type definition is a type definition associated with an
+/// element information item E by an xsi:type attribute, i.e. the type definition its ·actual
+/// value· ·resolves· to, in the namespace context of E. Returns `None` if E has no xsi:type
+/// attribute or it does not resolve to a type definition.
+fn instance_specified_type_definition(
+    e: &roxmltree::Node,
+    components: &SchemaComponentTable,
+) -> Option<TypeDefinition> {
+    let xsi_type = e.attribute((XSI_NAMESPACE, "type"))?;
+    let name = QName::parse(xsi_type.trim(), *e).ok()?;
+    type_definition_by_name(&name, components)
+}
+
+/// [Definition:] An ·instance-specified type definition· T overrides a type definition S if T is
+/// ·validly substitutable· for S, subject to the blocking keywords of D.{disallowed
+/// substitutions}, where D is the ·governing element declaration· of the element.
+fn overrides(
+    t: TypeDefinition,
+    s: TypeDefinition,
+    d: &ElementDeclaration,
+    components: &SchemaComponentTable,
+) -> bool {
+    let blocking = d
+        .disallowed_substitutions
+        .iter()
+        .filter_map(|s| match s {
+            SubstitutionMethod::Extension => Some(DerivationMethod::Extension),
+            SubstitutionMethod::Restriction => Some(DerivationMethod::Restriction),
+            SubstitutionMethod::Substitution => None,
+        })
+        .collect::<Vec<_>>();
+    validly_substitutable(t, s, &blocking, components)
+}
+
 fn governing_type_definition(
     e: &roxmltree::Node,
     governing_element_declaration: Option<&ElementDeclaration>,
+    components: &SchemaComponentTable,
 ) -> Option<TypeDefinition> {
     // The governing type definition of an element information item E, in a given schema-validity
     // ·assessment· episode, is the first of the following which applies:
@@ -303,101 +972,158 @@ fn governing_type_definition(
     // 2 A type definition stipulated by the processor (see Assessing Schema-Validity (§5.2)).
     // NOTE: doesn't apply (yet)
 
-    // 3 An ·instance-specified type definition· which ·overrides· the ·selected type definition·
-    //   of E.
-    // TODO
+    let instance_specified_type_definition = instance_specified_type_definition(e, components);
 
-    // 4 The ·selected type definition· of E.
-    //   [from "selected type definition"] If E has no ·governing element declaration·, then E has
-    //   no selected type definition.
+    // [from "selected type definition"] If E has no ·governing element declaration·, then E has no
+    // selected type definition.
     if let Some(d) = governing_element_declaration {
-        return Some(selected_type_definition(e, d));
+        let selected_type_definition = selected_type_definition(e, d, components);
+
+        // 3 An ·instance-specified type definition· which ·overrides· the ·selected type
+        //   definition· of E.
+        if let Some(t) = instance_specified_type_definition
+            && overrides(t, selected_type_definition, d, components)
+        {
+            return Some(t);
+        }
+
+        // 4 The ·selected type definition· of E.
+        return Some(selected_type_definition);
     }
 
     // 5 The value ·absent· if E is ·skipped·.
     // TODO
 
     // 6 An ·instance-specified type definition· which ·overrides· the ·locally declared type·.
-    // TODO
-
     // 7 The ·locally declared type·.
-    // TODO
+    // TODO: locally declared types are not tracked yet
 
     // 8 An ·instance-specified type definition·.
-    // TODO
+    if let Some(t) = instance_specified_type_definition {
+        return Some(t);
+    }
 
     // If none of these applies, there is no ·governing type definition· (or, in equivalent words,
     // it is ·absent·).
     None
 }
 
+/// 3.3.4.3 Element Locally Valid (Element), with `e` as the ·validation root·
+///
+/// Returns a report of all violations found while assessing `e` with respect to `d`, which also
+/// holds the PSVI of `e` and its descendants. `e` is ·valid· if there are no violations.
 pub fn element_locally_valid_element(
     e: &roxmltree::Node,
-    d: Option<&ElementDeclaration>,
+    d: Option<Ref<ElementDeclaration>>,
+    components: &SchemaComponentTable,
+) -> ValidationReport {
+    let mut state = ValidationState::default();
+    let mut report = ValidationReport::default();
+    let mark = report.mark();
+    element_locally_valid_element_inner(e, d, &mut state, &mut report, components);
+    // 7 If E is the ·validation root·, then it is ·valid· per Validation Root Valid (ID/IDREF)
+    //   (§3.3.4.5).
+    state.id_idref_table.report(e, &mut report);
+    report.record_assessment(e, mark);
+    report.sort();
+    report
+}
+
+/// 3.3.4.3 Element Locally Valid (Element), recording the ID and IDREF values of E and its
+/// descendants in `state` and all violations in `report`
+fn element_locally_valid_element_inner(
+    e: &roxmltree::Node,
+    d: Option<Ref<ElementDeclaration>>,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> bool {
+    let mark = report.mark();
+    let valid = element_locally_valid_element_clauses(e, d, state, report, components);
+    report.record_assessment(e, mark);
+    valid
+}
+
+/// The clauses of Element Locally Valid (Element) (§3.3.4.3), see
+/// [`element_locally_valid_element_inner`]
+fn element_locally_valid_element_clauses(
+    e: &roxmltree::Node,
+    d: Option<Ref<ElementDeclaration>>,
+    state: &mut ValidationState,
+    report: &mut ValidationReport,
     components: &SchemaComponentTable,
 ) -> bool {
     // 1 D is not ·absent· and E and D have the same expanded name.
-    let Some(d) = d else {
+    let Some(declaration) = d else {
+        report.element(
+            e,
+            "cvc-elt.1",
+            format!("no declaration found for element {}", element_name(e)),
+        );
         return false;
     };
-    if e.tag_name().namespace() != d.target_namespace.as_deref() || e.tag_name().name() != d.name {
+    report.psvi.element_mut(e).element_declaration = Some(declaration);
+    let d = declaration.get(components);
+    if element_namespace(e) != d.target_namespace.as_deref() || e.tag_name().name() != d.name {
+        report.element(
+            e,
+            "cvc-elt.1",
+            format!(
+                "element {} does not match its declaration {}",
+                element_name(e),
+                QName::with_optional_namespace(d.target_namespace.as_deref(), d.name.as_str())
+            ),
+        );
         return false;
     }
 
-    // 2 D.{abstract} = false.
-    if d.abstract_ {
-        return false;
+    let name = element_name(e);
+    let mut valid = true;
+
+    if let Err(violation) = declaration_not_abstract(d, &name) {
+        report.element_violation(e, violation);
+        valid = false;
     }
 
-    // 3 One of the following is true:
+    let children = children(e);
     let xsi_nil = e
         .attributes()
         .find(|a| a.namespace() == Some(XSI_NAMESPACE) && a.name() == "nil");
-    let is_nilled = if !d.nillable {
-        // 3.1 D.{nillable} = false, and E has no xsi:nil attribute.
-        if xsi_nil.is_some() {
-            return false;
-        }
-        false
-    } else {
-        // 3.2 D.{nillable} = true and one of the following is true
-        if let Some(xsi_nil) = xsi_nil {
-            // TODO: normalization
-            if xsi_nil.value() == "false" {
-                // 3.2.2 E has xsi:nil = false.
-                false
-            } else if xsi_nil.value() == "true" {
-                // 3.2.3 E has xsi:nil = true (that is, E is ·nilled·), and all of the following
-                //   are true:
-
-                //   3.2.3.1 E has no character or element information item [children].
-                if e.has_children() {
-                    return false;
-                }
-
-                //   3.2.3.2 D has no {value constraint} with {variety} = fixed.
-                if d.value_constraint
-                    .as_ref()
-                    .map_or(false, |vc| vc.variety == ValueConstraintVariety::Fixed)
-                {
-                    return false;
-                }
-
-                true
-            } else {
-                return false;
-            }
-        } else {
-            // 3.2.1 E has no xsi:nil attribute information item.
+    let is_nilled = match nilled(d, &name, xsi_nil.map(|a| a.value())) {
+        Ok(is_nilled) => is_nilled,
+        Err(violation) => {
+            report.attribute_violation(e, &xsi_nil.unwrap(), violation);
+            valid = false;
             false
         }
     };
+    if is_nilled {
+        for violation in nilled_element_valid(d, &name, children) {
+            report.element_violation(e, violation);
+            valid = false;
+        }
+    }
 
-    // 4 If E has an ·instance-specified type definition· T, then T ·overrides· the ·selected type
-    //   definition· of E.
-    // NOTE: this step is already covered by the definition of "governing type definition"
+    if let Some(xsi_type) = e
+        .attributes()
+        .find(|a| a.namespace() == Some(XSI_NAMESPACE) && a.name() == "type")
+        && let Err(violation) = instance_specified_type_valid(
+            xsi_type.value(),
+            instance_specified_type_definition(e, components),
+            selected_type_definition(e, d, components),
+            d,
+            &name,
+            components,
+        )
+    {
+        report.attribute_violation(e, &xsi_type, violation);
+        valid = false;
+    }
 
-    let governing_type_definition = governing_type_definition(e, Some(d));
+    let governing_type_definition = governing_type_definition(e, Some(d), components);
+    let psvi = report.psvi.element_mut(e);
+    psvi.type_definition = governing_type_definition;
+    psvi.nil = is_nilled;
 
     debug_assert!(
         governing_type_definition.is_some(),
@@ -406,61 +1132,1166 @@ pub fn element_locally_valid_element(
     );
 
     // 5 The appropriate case among the following is true:
-    if d.value_constraint.is_some() && !e.has_children() && !is_nilled {
+    if let Some(value_constraint) = d
+        .value_constraint
+        .as_ref()
+        .filter(|_| !children.elements && !children.characters && !is_nilled)
+    {
         // 5.1 If D has a {value constraint}, and E has neither element nor character [children],
         //   and E is not ·nilled· with respect to D , then all of the following are true:
-        let _value_constraint = d.value_constraint.as_ref().unwrap();
-
-        //   5.1.1 If E's ·governing type definition· is an ·instance-specified type definition·,
-        //     then D.{value constraint} is a valid default for the ·governing type definition· as
-        //     defined in Element Default Valid (Immediate) (§3.3.6.2).
-        // TODO
+        if let Some(t) = governing_type_definition
+            && instance_specified_type_definition(e, components) == Some(t)
+            && let Err(violation) =
+                default_valid_for_instance_type(value_constraint, t, &name, e, components)
+        {
+            report.element_violation(e, violation);
+            valid = false;
+        }
 
         //   5.1.2 The element information item with D.{value constraint}.{lexical form} used as
         //     its ·normalized value· is locally ·valid· with respect to the ·governing type
         //     definition· as defined by Element Locally Valid (Type) (§3.3.4.4).
-        // TODO
+        valid &= element_locally_valid_type(
+            e,
+            governing_type_definition,
+            is_nilled,
+            &value_constraint.lexical_form,
+            state,
+            report,
+            components,
+        );
+        // Element Default Value (§3.3.5.5): the {lexical form} of D.{value constraint} is the
+        // [schema normalized value] of E, if not already set as that of its simple content.
+        let psvi = report.psvi.element_mut(e);
+        psvi.schema_specified = SchemaSpecified::Schema;
+        psvi.schema_normalized_value
+            .get_or_insert_with(|| value_constraint.lexical_form.clone());
     } else {
         // 5.2 If D has no {value constraint}, or E has either element or character [children], or
         //   E is ·nilled· with respect to D, then all of the following are true:
 
         //   5.2.1 E is locally ·valid· with respect to the ·governing type definition· as defined
         //     by Element Locally Valid (Type) (§3.3.4.4).
-        if !element_locally_valid_type(e, governing_type_definition, is_nilled, components) {
-            return false;
-        }
-
-        if let Some(value_constraint) = d.value_constraint.as_ref() {
-            if value_constraint.variety == ValueConstraintVariety::Fixed && !is_nilled {
-                // 5.2.2 If D.{value constraint}.{variety} = fixed and E is not ·nilled· with
-                //   respect to D, then all of the following are true:
-
-                //   5.2.2.1 E has no element information item [children].
-                if e.has_children() {
-                    return false;
-                }
+        let initial_value = initial_value(e);
+        valid &= element_locally_valid_type(
+            e,
+            governing_type_definition,
+            is_nilled,
+            &initial_value,
+            state,
+            report,
+            components,
+        );
 
-                //   5.2.2.2 The appropriate case among the following is true:
-                if false {
-                    // 5.2.2.2.1 If E's ·governing type definition· is a Complex Type Definition
-                    //   with {content type}.{variety} = mixed , then the ·initial value· of E
-                    //   matches D.{value constraint}.{lexical form}.
-                } else {
-                    // 5.2.2.2.2 If E's ·governing type definition· is a Simple Type Definition or
-                    //   a Complex Type Definition with {content type}.{variety} = simple, then the
-                    //   ·actual value· of E is equal or identical to D.{value constraint}.{value}.
-                }
+        // The governing type definition is present, see above.
+        if let Some(value_constraint) = d
+            .value_constraint
+            .as_ref()
+            .filter(|vc| vc.variety == ValueConstraintVariety::Fixed && !is_nilled)
+        {
+            let actual_value = report
+                .psvi
+                .element(e.id())
+                .and_then(|psvi| psvi.schema_actual_value.clone());
+            if let Err(violation) = fixed_value_valid(
+                d,
+                value_constraint,
+                governing_type_definition.unwrap(),
+                children,
+                &initial_value,
+                actual_value.as_ref(),
+                components,
+            ) {
+                report.element_violation(e, violation);
+                valid = false;
             }
         }
     }
 
     // 6 E is ·valid· with respect to each of the {identity-constraint definitions} as per
     //   Identity-constraint Satisfied (§3.11.4).
-    // TODO
+    valid &= identity::identity_constraints_satisfied(e, d, components, report);
 
     // 7 If E is the ·validation root·, then it is ·valid· per Validation Root Valid (ID/IDREF)
     //   (§3.3.4.5).
-    // TODO
+    // This is checked by element_locally_valid_element() once the whole tree has been assessed.
 
-    true
+    valid
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{components::RefNamed, xstypes::QName};
+    use roxmltree::Document;
+
+    #[test]
+    fn conditional_type_assignment() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test">
+              <xs:complexType name="vehicle">
+                <xs:attribute name="kind" type="xs:string"/>
+                <xs:attribute name="wheels" type="xs:int"/>
+              </xs:complexType>
+              <xs:complexType name="car">
+                <xs:complexContent>
+                  <xs:extension base="vehicle"/>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:complexType name="truck">
+                <xs:complexContent>
+                  <xs:extension base="vehicle"/>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:element name="vehicle" type="vehicle">
+                <xs:alternative test="@kind = 'car'" type="car"/>
+                <xs:alternative test="@wheels > 4" type="truck"/>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "vehicle", &components)
+            .unwrap()
+            .get(&components);
+
+        let selected_type = |instance: &str| {
+            let instance = Document::parse(instance).unwrap();
+            super::selected_type_definition(&instance.root_element(), d, &components)
+                .name(&components)
+                .map(|name| name.local_name().to_string())
+        };
+        assert_eq!(
+            selected_type(r#"<vehicle xmlns="urn:test" kind="car"/>"#).as_deref(),
+            Some("car")
+        );
+        assert_eq!(
+            selected_type(r#"<vehicle xmlns="urn:test" wheels="6"/>"#).as_deref(),
+            Some("truck")
+        );
+        assert_eq!(
+            selected_type(r#"<vehicle xmlns="urn:test" wheels="four"/>"#).as_deref(),
+            Some("vehicle")
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let (schema, components) = crate::testing::read_schema(
+            r###"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test">
+              <xs:element name="item">
+                <xs:complexType>
+                  <xs:attribute name="id" type="xs:int" use="required"/>
+                  <xs:attribute name="size" type="xs:byte" default="10"/>
+                  <xs:anyAttribute namespace="##other" processContents="lax"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="count" type="xs:int"/>
+            </xs:schema>
+            "###,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let valid = |name: &str, instance: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .is_valid()
+        };
+        assert!(valid("item", r#"<item xmlns="urn:test" id="1" size="3"/>"#));
+        assert!(!valid("item", r#"<item xmlns="urn:test"/>"#));
+        assert!(!valid("item", r#"<item xmlns="urn:test" id="one"/>"#));
+        assert!(!valid(
+            "item",
+            r#"<item xmlns="urn:test" id="1" size="300"/>"#
+        ));
+        assert!(!valid(
+            "item",
+            r#"<item xmlns="urn:test" id="1" extra="x"/>"#
+        ));
+        assert!(valid(
+            "item",
+            r#"<item xmlns="urn:test" xmlns:o="urn:other" id="1" o:extra="x"/>"#
+        ));
+
+        assert!(valid("count", r#"<count xmlns="urn:test">3</count>"#));
+        assert!(!valid(
+            "count",
+            r#"<count xmlns="urn:test" unit="m">3</count>"#
+        ));
+        assert!(valid(
+            "count",
+            r#"<count xmlns="urn:test" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+                xsi:schemaLocation="urn:test test.xsd">3</count>"#
+        ));
+    }
+
+    #[test]
+    fn wildcards() {
+        let (schema, components) = crate::testing::read_schema(
+            r###"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="known" type="xs:int"/>
+              <xs:element name="free"/>
+              <xs:element name="open">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="a"/>
+                    <xs:any namespace="##targetNamespace" notQName="##defined"
+                        processContents="lax" minOccurs="0"/>
+                    <xs:any namespace="##other" minOccurs="0"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="strict">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:any namespace="##targetNamespace"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "###,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let valid = |name: &str, instance: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .is_valid()
+        };
+        assert!(valid(
+            "free",
+            r#"<free xmlns="urn:test"><x><y/></x><known>1</known></free>"#
+        ));
+        assert!(!valid(
+            "free",
+            r#"<free xmlns="urn:test"><known>one</known></free>"#
+        ));
+
+        assert!(valid("open", r#"<open xmlns="urn:test"><a/><x/></open>"#));
+        assert!(!valid(
+            "open",
+            r#"<open xmlns="urn:test"><a/><known>1</known></open>"#
+        ));
+        assert!(!valid(
+            "open",
+            r#"<open xmlns="urn:test"><a/><o:y xmlns:o="urn:other"/></open>"#
+        ));
+
+        assert!(valid(
+            "strict",
+            r#"<strict xmlns="urn:test"><known>1</known></strict>"#
+        ));
+        assert!(!valid(
+            "strict",
+            r#"<strict xmlns="urn:test"><known>x</known></strict>"#
+        ));
+        assert!(!valid(
+            "strict",
+            r#"<strict xmlns="urn:test"><x/></strict>"#
+        ));
+    }
+
+    #[test]
+    fn substitution_groups() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test">
+              <xs:complexType name="message">
+                <xs:sequence>
+                  <xs:element name="id" type="xs:int" minOccurs="0"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:element name="message" type="message" abstract="true"/>
+              <xs:element name="order" substitutionGroup="message"/>
+              <xs:element name="express" substitutionGroup="order"/>
+              <xs:element name="cancel" substitutionGroup="message" abstract="true"/>
+              <xs:element name="note" type="message" block="substitution"/>
+              <xs:element name="memo" substitutionGroup="note"/>
+              <xs:element name="inbox">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element ref="message" maxOccurs="unbounded"/>
+                    <xs:element ref="note" minOccurs="0"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="ambiguous">
+                <xs:complexType>
+                  <xs:choice>
+                    <xs:element ref="message"/>
+                    <xs:element ref="express"/>
+                  </xs:choice>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        // The choice of "ambiguous" violates Unique Particle Attribution, as "express" is in the
+        // substitution group of "message"
+        let violations = crate::constraints::check_schema(&schema, &components)
+            .into_iter()
+            .map(|v| v.constraint)
+            .collect::<Vec<_>>();
+        assert_eq!(violations, ["cos-nonambig"]);
+
+        let valid = |instance: &str| {
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(
+                &instance.root_element(),
+                schema.find_element_by_name(Some("urn:test"), "inbox", &components),
+                &components,
+            )
+            .is_valid()
+        };
+        assert!(valid(
+            r#"<inbox xmlns="urn:test"><order/><express/><note/></inbox>"#
+        ));
+        assert!(!valid(r#"<inbox xmlns="urn:test"><message/></inbox>"#));
+        assert!(!valid(r#"<inbox xmlns="urn:test"><cancel/></inbox>"#));
+        assert!(!valid(r#"<inbox xmlns="urn:test"><order/><memo/></inbox>"#));
+    }
+
+    #[test]
+    fn instance_specified_types() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:complexType name="shape" abstract="true">
+                <xs:sequence>
+                  <xs:element name="name" type="xs:string"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:complexType name="circle">
+                <xs:complexContent>
+                  <xs:extension base="shape">
+                    <xs:sequence>
+                      <xs:element name="radius" type="xs:decimal"/>
+                    </xs:sequence>
+                  </xs:extension>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:complexType name="sealed" block="extension">
+                <xs:sequence>
+                  <xs:element name="name" type="xs:string"/>
+                </xs:sequence>
+              </xs:complexType>
+              <xs:complexType name="opened">
+                <xs:complexContent>
+                  <xs:extension base="sealed"/>
+                </xs:complexContent>
+              </xs:complexType>
+              <xs:element name="shape" type="shape"/>
+              <xs:element name="fixedShape" type="shape" block="extension"/>
+              <xs:element name="sealed" type="sealed"/>
+              <xs:element name="amount" type="xs:decimal"/>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let valid = |name: &str, xsi_type: Option<&str>, content: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let xsi_type = xsi_type
+                .map(|t| format!(r#" xsi:type="{t}""#))
+                .unwrap_or_default();
+            let instance = format!(
+                r#"<{name} xmlns="urn:test" xmlns:t="urn:test"
+                    xmlns:xs="http://www.w3.org/2001/XMLSchema"
+                    xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"{xsi_type}>{content}</{name}>"#
+            );
+            let instance = Document::parse(&instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .is_valid()
+        };
+        let circle = "<name>c</name><radius>2</radius>";
+        assert!(valid("shape", Some("t:circle"), circle));
+        assert!(!valid("shape", None, "<name>c</name>"));
+        assert!(!valid("shape", Some("t:shape"), "<name>c</name>"));
+        assert!(!valid("shape", Some("t:sealed"), "<name>c</name>"));
+        assert!(!valid("shape", Some("t:unknown"), circle));
+        assert!(!valid("fixedShape", Some("t:circle"), circle));
+        assert!(valid("sealed", Some("t:sealed"), "<name>s</name>"));
+        assert!(!valid("sealed", Some("t:opened"), "<name>s</name>"));
+
+        assert!(valid("amount", Some("xs:int"), "3"));
+        assert!(!valid("amount", Some("xs:int"), "1.5"));
+        assert!(!valid("amount", Some("xs:string"), "3"));
+    }
+
+    #[test]
+    fn open_content() {
+        let (schema, components) = crate::testing::read_schema(
+            r###"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:defaultOpenContent mode="suffix">
+                <xs:any namespace="urn:extra" processContents="lax"/>
+              </xs:defaultOpenContent>
+              <xs:element name="plain">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="a"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="interleaved">
+                <xs:complexType>
+                  <xs:openContent>
+                    <xs:any namespace="##other" processContents="skip"/>
+                  </xs:openContent>
+                  <xs:sequence>
+                    <xs:element name="a"/>
+                    <xs:element name="b"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="suffixed">
+                <xs:complexType>
+                  <xs:openContent mode="suffix">
+                    <xs:any namespace="##other" processContents="skip"/>
+                  </xs:openContent>
+                  <xs:sequence>
+                    <xs:element name="a"/>
+                    <xs:element name="b"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "###,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let valid = |name: &str, content: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = format!(
+                r#"<{name} xmlns="urn:test" xmlns:o="urn:other" xmlns:e="urn:extra">{content}</{name}>"#
+            );
+            let instance = Document::parse(&instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .is_valid()
+        };
+        assert!(valid("interleaved", "<o:x/><a/><o:y/><b/><o:z/>"));
+        assert!(!valid("interleaved", "<a/><c/><b/>"));
+        assert!(!valid("interleaved", "<a/><o:x/>"));
+
+        assert!(valid("suffixed", "<a/><b/><o:x/><o:y/>"));
+        assert!(!valid("suffixed", "<a/><o:x/><b/>"));
+
+        assert!(valid("plain", "<a/><e:x/>"));
+        assert!(!valid("plain", "<e:x/><a/>"));
+        assert!(!valid("plain", "<a/><o:x/>"));
+    }
+
+    #[test]
+    fn value_constraints() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                xmlns:t="urn:test" targetNamespace="urn:test">
+              <xs:element name="price" type="xs:decimal" fixed="1.50"/>
+              <xs:element name="code" type="xs:QName" fixed="t:x"/>
+              <xs:element name="note" fixed="hi">
+                <xs:complexType mixed="true"/>
+              </xs:element>
+              <xs:element name="amount" default="abc"/>
+              <xs:element name="item">
+                <xs:complexType>
+                  <xs:attribute name="version" type="xs:decimal" fixed="1.0"/>
+                  <xs:attribute ref="t:lang" fixed="en"/>
+                  <xs:attribute ref="t:region"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:attribute name="lang" type="xs:language"/>
+              <xs:attribute name="region" type="xs:string" fixed="eu"/>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let valid = |name: &str, instance: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .is_valid()
+        };
+        // Fixed values are compared in the value space
+        assert!(valid("price", r#"<price xmlns="urn:test">1.5</price>"#));
+        assert!(valid("price", r#"<price xmlns="urn:test"> 1.500 </price>"#));
+        assert!(valid("price", r#"<price xmlns="urn:test"/>"#));
+        assert!(!valid("price", r#"<price xmlns="urn:test">2</price>"#));
+        assert!(valid(
+            "code",
+            r#"<code xmlns="urn:test" xmlns:p="urn:test">p:x</code>"#
+        ));
+        assert!(!valid(
+            "code",
+            r#"<p:code xmlns:p="urn:test" xmlns="urn:other">x</p:code>"#
+        ));
+        // ... except for mixed content, where the lexical form must match
+        assert!(valid("note", r#"<note xmlns="urn:test">hi</note>"#));
+        assert!(!valid("note", r#"<note xmlns="urn:test">hello</note>"#));
+        // The default must be valid for an instance-specified type definition
+        let xsi = r#"xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+            xmlns:xs="http://www.w3.org/2001/XMLSchema""#;
+        assert!(valid(
+            "amount",
+            &format!(r#"<amount xmlns="urn:test" {xsi} xsi:type="xs:string"/>"#)
+        ));
+        assert!(!valid(
+            "amount",
+            &format!(r#"<amount xmlns="urn:test" {xsi} xsi:type="xs:int"/>"#)
+        ));
+        assert!(valid(
+            "amount",
+            &format!(r#"<amount xmlns="urn:test" {xsi} xsi:type="xs:int">5</amount>"#)
+        ));
+
+        assert!(valid("item", r#"<item xmlns="urn:test" version="1"/>"#));
+        assert!(!valid("item", r#"<item xmlns="urn:test" version="1.1"/>"#));
+
+        // The messages name the declaration or attribute use imposing the fixed value
+        let messages = |name: &str, instance: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .violations
+                .into_iter()
+                .map(|v| format!("{}: {}", v.code, v.message))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages("price", r#"<price xmlns="urn:test">2</price>"#),
+            [
+                r#"cvc-elt.5.2.2.2.2: "2" does not match the fixed value "1.50" of element declaration {urn:test}:price"#
+            ]
+        );
+        assert_eq!(
+            messages("note", r#"<note xmlns="urn:test">hello</note>"#),
+            [
+                r#"cvc-elt.5.2.2.2.1: "hello" does not match the fixed value "hi" of element declaration {urn:test}:note"#
+            ]
+        );
+        assert_eq!(
+            messages(
+                "item",
+                r#"<item xmlns="urn:test" xmlns:t="urn:test" t:lang="de" t:region="us"/>"#
+            ),
+            [
+                r#"cvc-au: "de" does not match the fixed value "en" of the attribute use of {urn:test}:lang"#,
+                r#"cvc-attribute.4: "us" does not match the fixed value "eu" of attribute declaration {urn:test}:region"#,
+            ]
+        );
+
+        let Err(error) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="count" type="xs:int" default="many"/>
+            </xs:schema>
+            "#,
+        ) else {
+            panic!("the default value is invalid");
+        };
+        assert!(matches!(
+            error.kind(),
+            crate::error::XsdError::InvalidValueConstraint { declaration, .. }
+                if declaration.local_name() == "count"
+        ));
+    }
+
+    #[test]
+    fn comments_and_processing_instructions() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test">
+              <xs:element name="flag">
+                <xs:complexType/>
+              </xs:element>
+              <xs:element name="size" type="xs:int" nillable="true"/>
+              <xs:element name="unit" type="xs:string" default="cm"/>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let violations = |name: &str, instance: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .violations
+                .iter()
+                .map(|v| v.code)
+                .collect::<Vec<_>>()
+        };
+        let xsi = r#"xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance""#;
+        // Comments and processing instructions are not content
+        assert!(
+            violations(
+                "flag",
+                r#"<flag xmlns="urn:test"><!-- c --><?pi x?></flag>"#
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            violations("flag", r#"<flag xmlns="urn:test"> </flag>"#),
+            ["cvc-complex-type.1.1"]
+        );
+        assert!(
+            violations(
+                "size",
+                &format!(r#"<size xmlns="urn:test" {xsi} xsi:nil="true"><!-- c --></size>"#)
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            violations(
+                "size",
+                &format!(r#"<size xmlns="urn:test" {xsi} xsi:nil="true">1</size>"#)
+            ),
+            ["cvc-elt.3.2.3.1"]
+        );
+        // ... and an element with only a comment is given its default value
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "unit", &components)
+            .unwrap();
+        let instance = Document::parse(r#"<unit xmlns="urn:test"><!-- c --></unit>"#).unwrap();
+        let report =
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components);
+        assert_eq!(
+            report
+                .psvi
+                .element(instance.root_element().id())
+                .unwrap()
+                .schema_normalized_value
+                .as_deref(),
+            Some("cm")
+        );
+    }
+
+    #[test]
+    fn id_idrefs() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:simpleType name="code">
+                <xs:restriction base="xs:ID">
+                  <xs:pattern value="c[0-9]+"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="target">
+                <xs:union memberTypes="xs:int xs:IDREF"/>
+              </xs:simpleType>
+              <xs:simpleType name="targets">
+                <xs:list itemType="target"/>
+              </xs:simpleType>
+              <xs:element name="doc">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:any processContents="lax" minOccurs="0" maxOccurs="unbounded"/>
+                  </xs:sequence>
+                  <xs:attribute name="id" type="xs:ID"/>
+                  <xs:attribute name="refs" type="xs:IDREFS"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="item">
+                <xs:complexType>
+                  <xs:attribute name="code" type="code"/>
+                  <xs:attribute name="ref" type="xs:IDREF"/>
+                  <xs:attribute name="target" type="target"/>
+                  <xs:attribute name="targets" type="targets"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="key" type="xs:ID"/>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "doc", &components)
+            .unwrap();
+        let violations = |instance: &str| {
+            let instance = Document::parse(instance).unwrap();
+            let e = instance.root_element();
+            let report = super::element_locally_valid_element(&e, Some(d), &components);
+            assert_eq!(report.is_valid(), report.id_idref_violations.is_empty());
+            report
+                .id_idref_violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert!(
+            violations(
+                r#"<doc xmlns="urn:test" id="d" refs="d c1 k">
+                    <item code="c1" ref="k"/><key>k</key></doc>"#
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            violations(r#"<doc xmlns="urn:test" refs="d c1"><item code="c1"/></doc>"#),
+            [r#"/doc/@refs: IDREF "d" does not refer to any ID"#]
+        );
+        assert_eq!(
+            violations(r#"<doc xmlns="urn:test" id="c1"><item ref="c1"/><item code="c1"/></doc>"#),
+            [r#"/doc/item[2]/@code: duplicate ID "c1", which is also used at /doc/@id"#]
+        );
+        assert_eq!(
+            violations(r#"<doc xmlns="urn:test"><key>k</key><key> k </key></doc>"#),
+            [r#"/doc/key[2]: duplicate ID "k", which is also used at /doc/key[1]"#]
+        );
+        // IDREFs validated by a member of a union, also within a list
+        assert!(
+            violations(r#"<doc xmlns="urn:test" id="d"><item target="d" targets="1 d"/></doc>"#)
+                .is_empty()
+        );
+        assert_eq!(
+            violations(r#"<doc xmlns="urn:test"><item target="x" targets="1 y 2"/></doc>"#),
+            [
+                r#"/doc/item[1]/@target: IDREF "x" does not refer to any ID"#,
+                r#"/doc/item[1]/@targets: IDREF "y" does not refer to any ID"#
+            ]
+        );
+    }
+
+    #[test]
+    fn assertions() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns:t="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="range">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" type="xs:integer" maxOccurs="unbounded"/>
+                  </xs:sequence>
+                  <xs:attribute name="min" type="xs:integer"/>
+                  <xs:attribute name="max" type="xs:integer"/>
+                  <xs:assert test="@min le @max"/>
+                  <xs:assert test="every $i in t:item satisfies $i ge @min and $i le @max"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:complexType name="positive">
+                <xs:attribute name="a" type="xs:int"/>
+                <xs:assert test="@a > 0"/>
+              </xs:complexType>
+              <xs:element name="pair">
+                <xs:complexType>
+                  <xs:complexContent>
+                    <xs:extension base="t:positive">
+                      <xs:attribute name="b" type="xs:int"/>
+                      <xs:assert test="@b > @a"/>
+                    </xs:extension>
+                  </xs:complexContent>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="even">
+                <xs:complexType>
+                  <xs:simpleContent>
+                    <xs:extension base="xs:integer">
+                      <xs:assert test="$value mod 2 = 0"/>
+                    </xs:extension>
+                  </xs:simpleContent>
+                </xs:complexType>
+              </xs:element>
+              <xs:simpleType name="evenInteger">
+                <xs:restriction base="xs:integer">
+                  <xs:assertion test="$value mod 2 = 0"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="smallEvenInteger">
+                <xs:restriction base="t:evenInteger">
+                  <xs:assertion test="$value lt 10"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:element name="small" type="t:smallEvenInteger"/>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let valid = |name: &str, instance: &str| {
+            let d = schema
+                .find_element_by_name(Some("urn:test"), name, &components)
+                .unwrap();
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+                .is_valid()
+        };
+        // Attributes and children are compared using their typed values
+        let range = |attributes: &str, items: &str| {
+            valid(
+                "range",
+                &format!(r#"<t:range xmlns:t="urn:test" {attributes}>{items}</t:range>"#),
+            )
+        };
+        assert!(range(
+            r#"min="2" max="10""#,
+            "<t:item>3</t:item><t:item>10</t:item>"
+        ));
+        assert!(!range(r#"min="10" max="2""#, "<t:item>3</t:item>"));
+        assert!(!range(
+            r#"min="2" max="10""#,
+            "<t:item>3</t:item><t:item>11</t:item>"
+        ));
+        // A missing attribute makes the comparison empty, and thus false
+        assert!(!range(r#"min="2""#, "<t:item>3</t:item>"));
+
+        // Assertions are inherited from the base type definition
+        assert!(valid("pair", r#"<t:pair xmlns:t="urn:test" a="1" b="2"/>"#));
+        assert!(!valid(
+            "pair",
+            r#"<t:pair xmlns:t="urn:test" a="2" b="1"/>"#
+        ));
+        assert!(!valid(
+            "pair",
+            r#"<t:pair xmlns:t="urn:test" a="-2" b="1"/>"#
+        ));
+
+        // $value is bound to the typed value of simple content, and to the value in facets
+        assert!(valid("even", r#"<t:even xmlns:t="urn:test"> 4 </t:even>"#));
+        assert!(!valid("even", r#"<t:even xmlns:t="urn:test">5</t:even>"#));
+        assert!(valid("small", r#"<t:small xmlns:t="urn:test">4</t:small>"#));
+        assert!(!valid(
+            "small",
+            r#"<t:small xmlns:t="urn:test">3</t:small>"#
+        ));
+        assert!(!valid(
+            "small",
+            r#"<t:small xmlns:t="urn:test">12</t:small>"#
+        ));
+
+        // Tests outside of the supported subset are rejected when mapping the schema
+        let Err(error) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:complexType name="t">
+                <xs:assert test="@a instance of xs:integer"/>
+              </xs:complexType>
+            </xs:schema>
+            "#,
+        ) else {
+            panic!("unsupported assertion test was accepted");
+        };
+        assert!(error.to_string().contains("Invalid XPath expression"));
+    }
+
+    #[test]
+    fn report() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="order">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" type="xs:string" maxOccurs="unbounded"/>
+                    <xs:element name="total" type="xs:decimal"/>
+                  </xs:sequence>
+                  <xs:attribute name="id" type="xs:ID" use="required"/>
+                  <xs:attribute name="count" type="xs:positiveInteger"/>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "order", &components)
+            .unwrap();
+        let report = |instance: &str| {
+            let instance = Document::parse(instance).unwrap();
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components)
+        };
+
+        // All violations are reported, in document order
+        let violations = report(
+            r#"<order xmlns="urn:test"
+  count="0" color="red">
+  <item/>
+</order>"#,
+        )
+        .violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            [
+                "1:1: /order: cvc-complex-type.1.4: incomplete content of element \
+                {urn:test}:order, expected {urn:test}:item or {urn:test}:total",
+                "1:1: /order: cvc-complex-type.3: missing required attribute id",
+                r#"2:3: /order/@count: cvc-minInclusive-valid: "0" does not satisfy the minInclusive facet"#,
+                "2:13: /order/@color: cvc-complex-type.2.2.1: attribute color is not allowed",
+            ]
+        );
+
+        let report = report(r#"<order xmlns="urn:test" id="o"><total>1</total></order>"#);
+        let [violation] = report.violations.as_slice() else {
+            panic!("expected a single violation");
+        };
+        assert_eq!(violation.code, "cvc-complex-type.1.4");
+        assert_eq!(violation.path, "/order/total[1]");
+        assert_eq!((violation.line, violation.column), (1, 32));
+        assert_eq!(
+            violation.message,
+            "unexpected element {urn:test}:total, expected {urn:test}:item"
+        );
+    }
+
+    #[test]
+    fn recursive_assessment() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="order">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:sequence>
+                          <xs:element name="price" type="xs:decimal"/>
+                        </xs:sequence>
+                        <xs:attribute name="id" type="xs:ID" use="required"/>
+                      </xs:complexType>
+                    </xs:element>
+                    <xs:element ref="note" minOccurs="0"/>
+                    <xs:element name="extra" minOccurs="0">
+                      <xs:complexType>
+                        <xs:sequence>
+                          <xs:any processContents="lax"/>
+                        </xs:sequence>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="note" type="xs:string"/>
+              <xs:element name="comment" type="xs:token" substitutionGroup="note"/>
+              <xs:element name="count" type="xs:int"/>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "order", &components)
+            .unwrap();
+
+        let instance = Document::parse(
+            r#"<order xmlns="urn:test">
+                <item id="a"><price>1.5</price></item>
+                <item id="b"><price>cheap</price></item>
+                <comment>hi</comment>
+                <extra><unknown><count>x</count></unknown></extra>
+            </order>"#,
+        )
+        .unwrap();
+        let report =
+            super::element_locally_valid_element(&instance.root_element(), Some(d), &components);
+        // Children are assessed with respect to the declarations they match, and descendants of
+        // elements matched by a lax wildcard with respect to their global declarations
+        assert_eq!(
+            report
+                .violations
+                .iter()
+                .map(|v| (v.path.as_str(), v.code))
+                .collect::<Vec<_>>(),
+            [
+                ("/order/item[2]/price[1]", "cvc-datatype-valid.1"),
+                (
+                    "/order/extra[1]/unknown[1]/count[1]",
+                    "cvc-datatype-valid.1"
+                ),
+            ]
+        );
+
+        // The validity of each element accounts for its descendants
+        let validity = |name: &str, position: usize| {
+            let e = instance
+                .descendants()
+                .filter(|n| n.tag_name().name() == name)
+                .nth(position)
+                .unwrap();
+            report.validity(e.id())
+        };
+        assert_eq!(validity("order", 0), super::Validity::Invalid);
+        assert_eq!(validity("item", 0), super::Validity::Valid);
+        assert_eq!(validity("price", 0), super::Validity::Valid);
+        assert_eq!(validity("item", 1), super::Validity::Invalid);
+        assert_eq!(validity("price", 1), super::Validity::Invalid);
+        assert_eq!(validity("comment", 0), super::Validity::Valid);
+        assert_eq!(validity("extra", 0), super::Validity::Invalid);
+        assert_eq!(validity("unknown", 0), super::Validity::NotKnown);
+        assert_eq!(validity("count", 0), super::Validity::Invalid);
+    }
+
+    #[test]
+    fn psvi() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="doc">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="title" type="xs:token"/>
+                    <xs:any processContents="lax"/>
+                  </xs:sequence>
+                  <xs:attribute name="count" type="xs:int"/>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "doc", &components)
+            .unwrap();
+
+        let instance = Document::parse(
+            r#"<doc xmlns="urn:test" count=" 3 "><title>  a   b </title><other/></doc>"#,
+        )
+        .unwrap();
+        let root = instance.root_element();
+        let report = super::element_locally_valid_element(&root, Some(d), &components);
+        assert!(report.is_valid());
+        let element = |name: &str| {
+            let e = instance
+                .descendants()
+                .find(|n| n.tag_name().name() == name)
+                .unwrap();
+            report.psvi.element(e.id()).unwrap()
+        };
+
+        // The root was strictly assessed, but one of its children was not
+        let doc = element("doc");
+        assert_eq!(doc.element_declaration, Some(d));
+        assert_eq!(
+            doc.type_definition,
+            Some(d.get(&components).type_definition)
+        );
+        assert_eq!(doc.validity, super::Validity::Valid);
+        assert_eq!(
+            doc.validation_attempted,
+            super::ValidationAttempted::Partial
+        );
+        assert!(!doc.nil);
+
+        let title = element("title");
+        assert_eq!(title.validity, super::Validity::Valid);
+        assert_eq!(title.validation_attempted, super::ValidationAttempted::Full);
+        assert_eq!(title.schema_normalized_value.as_deref(), Some("a b"));
+        assert!(matches!(
+            &title.schema_actual_value,
+            Some(super::Value::String(s)) if s == "a b"
+        ));
+
+        // An element matched by a lax wildcard without a declaration is not assessed
+        let other = element("other");
+        assert_eq!(other.element_declaration, None);
+        assert_eq!(other.validity, super::Validity::NotKnown);
+        assert_eq!(other.validation_attempted, super::ValidationAttempted::None);
+
+        let count = report
+            .psvi
+            .attribute(root.id(), &QName::without_namespace("count"))
+            .unwrap();
+        assert!(count.attribute_declaration.is_some());
+        assert_eq!(
+            count
+                .type_definition
+                .and_then(|t| t.name(&components))
+                .map(|name| name.local_name.into_owned()),
+            Some("int".to_string())
+        );
+        assert_eq!(count.validity, super::Validity::Valid);
+        assert_eq!(count.validation_attempted, super::ValidationAttempted::Full);
+        assert_eq!(count.schema_normalized_value.as_deref(), Some("3"));
+        assert!(matches!(
+            count.schema_actual_value,
+            Some(super::Value::Decimal(_))
+        ));
+    }
+
+    #[test]
+    fn global_names() {
+        let (_, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test">
+              <xs:element name="outer">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="inner" type="xs:string"/>
+                  </xs:sequence>
+                  <xs:attribute name="local" type="xs:string"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:attribute name="global" type="xs:int"/>
+              <xs:simpleType name="code">
+                <xs:restriction base="xs:token"/>
+              </xs:simpleType>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        let name = |local_name: &str| QName::with_namespace("urn:test", local_name);
+        let xs =
+            |local_name: &str| QName::with_namespace(crate::builtins::XS_NAMESPACE, local_name);
+
+        let outer = super::global_element_declaration(&name("outer"), &components).unwrap();
+        assert_eq!(outer.name(&components), Some(name("outer")));
+        assert!(super::global_element_declaration(&name("inner"), &components).is_none());
+
+        let global = super::global_attribute_declaration(&name("global"), &components).unwrap();
+        assert_eq!(global.name(&components), Some(name("global")));
+        assert!(super::global_attribute_declaration(&name("local"), &components).is_none());
+
+        let code = super::type_definition_by_name(&name("code"), &components).unwrap();
+        assert_eq!(code.name(&components), Some(name("code")));
+        let any_type = super::type_definition_by_name(&xs("anyType"), &components).unwrap();
+        assert!(any_type.complex().is_some());
+        let int = super::type_definition_by_name(&xs("int"), &components).unwrap();
+        assert!(int.simple().is_some());
+        assert!(super::type_definition_by_name(&name("outer"), &components).is_none());
+    }
 }
@@ -0,0 +1,132 @@
+//! Validation Rule: Assertion Satisfied (§3.13.4.1)
+
+use super::{
+    Value, element_namespace, initial_value, report::ValidationReport, simple_content_type,
+    string_valid,
+};
+use crate::{
+    ComplexTypeDefinition, ElementDeclaration, Particle, SchemaComponentTable, Term,
+    complex_type_def::ContentType, xpath::TypeAnnotations,
+};
+use roxmltree::{Attribute, Node};
+
+/// The type annotations of the data model instance an assertion of T is evaluated on, which is
+/// constructed from E as validated against T without its assertions (clause 1).
+///
+/// Only the attributes of E governed by T.{attribute uses} and the children of E governed by an
+/// element declaration in T.{content type} with a simple type are annotated. All other nodes are
+/// untyped.
+struct PartialPsvi<'a, 'input> {
+    e: Node<'a, 'input>,
+    t: &'a ComplexTypeDefinition,
+    components: &'a SchemaComponentTable,
+}
+
+impl TypeAnnotations for PartialPsvi<'_, '_> {
+    fn typed_value(&self, node: Node, attribute: Option<&Attribute>) -> Option<Value> {
+        match attribute {
+            Some(a) if node == self.e => {
+                let d = self
+                    .t
+                    .attribute_uses
+                    .iter()
+                    .map(|u| {
+                        u.get(self.components)
+                            .attribute_declaration
+                            .get(self.components)
+                    })
+                    .find(|d| {
+                        d.name == a.name() && d.target_namespace.as_deref() == a.namespace()
+                    })?;
+                let t = d.type_definition.get(self.components);
+                string_valid(a.value(), t, &node, self.components).ok()
+            }
+            None if node.parent_element() == Some(self.e) => {
+                let particle = self.t.content_type.particle()?;
+                let d = particle_element_declaration(
+                    particle.get(self.components),
+                    node,
+                    self.components,
+                )?;
+                let t = simple_content_type(d.type_definition, self.components)?;
+                string_valid(&initial_value(&node), t, &node, self.components).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the element declaration with the expanded name of `e` contained in `particle`
+fn particle_element_declaration<'c>(
+    particle: &Particle,
+    e: Node,
+    components: &'c SchemaComponentTable,
+) -> Option<&'c ElementDeclaration> {
+    match &particle.term {
+        Term::ElementDeclaration(d) => Some(d.get(components)).filter(|d| {
+            d.name == e.tag_name().name() && d.target_namespace.as_deref() == element_namespace(&e)
+        }),
+        Term::ModelGroup(group) => group
+            .get(components)
+            .particles
+            .iter()
+            .find_map(|p| particle_element_declaration(p.get(components), e, components)),
+        Term::Wildcard(_) => None,
+    }
+}
+
+/// Returns whether E is ·valid· with respect to each of the assertions in T.{assertions}, adding
+/// a violation to `report` for each assertion which is not satisfied
+pub(super) fn assertions_satisfied(
+    e: &Node,
+    t: &ComplexTypeDefinition,
+    report: &mut ValidationReport,
+    components: &SchemaComponentTable,
+) -> bool {
+    if t.assertions.is_empty() {
+        return true;
+    }
+
+    // 3 The dynamic context (...) [variable values]: If T.{content type}.{variety} = simple, the
+    //   variable $value is bound to the typed value of E, otherwise to the empty sequence.
+    let initial_value = initial_value(e);
+    let value = match &t.content_type {
+        ContentType::Simple {
+            simple_type_definition,
+        } => string_valid(
+            &initial_value,
+            simple_type_definition.get(components),
+            e,
+            components,
+        )
+        .ok(),
+        _ => None,
+    };
+
+    let psvi = PartialPsvi {
+        e: *e,
+        t,
+        components,
+    };
+    let mut valid = true;
+    for assertion in &t.assertions {
+        let assertion = assertion.get(components);
+        // An element information item E is locally ·valid· with respect to an assertion if and
+        // only if the {test} evaluates to true (see below) without raising any dynamic error or
+        // type error.
+        let result = assertion.test_expression.evaluate(
+            Some(*e),
+            value.as_ref().map(|value| (initial_value.as_str(), value)),
+            &psvi,
+        );
+        let expression = &assertion.test.expression;
+        let message = match result {
+            Ok(true) => continue,
+            Err(error) => format!("assertion {expression:?} raised {error}"),
+            Ok(false) => format!("assertion {expression:?} is not satisfied"),
+        };
+        report.element(e, "cvc-assertion", message);
+        valid = false;
+    }
+    valid
+}
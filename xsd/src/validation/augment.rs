@@ -0,0 +1,233 @@
+//! Infoset augmentation: serializing a validated document together with the default values
+//! supplied by the schema
+
+use super::psvi::{Psvi, SchemaSpecified};
+use roxmltree::{Document, Node};
+
+/// Returns the text of `document` with the PSVI contributions of Attribute Default Value
+/// (§3.4.5.1) and Element Default Value (§3.3.5.5) in `psvi` applied: each ·defaulted attribute·
+/// is added to the start tag of its element, and each empty element whose [schema normalized
+/// value] was supplied by the {value constraint} of its declaration is given that value as its
+/// content.
+///
+/// The rest of the document, including its formatting, comments and namespace prefixes, is
+/// copied unchanged. Namespace declarations are added where a defaulted attribute has a namespace
+/// which is not bound to a prefix.
+pub fn augmented_document(document: &Document, psvi: &Psvi) -> String {
+    let text = document.input_text();
+    let mut output = String::with_capacity(text.len());
+    let mut copied = 0;
+    for e in document.descendants().filter(Node::is_element) {
+        let attributes = defaulted_attributes(&e, psvi);
+        let content = psvi
+            .element(e.id())
+            .filter(|psvi| psvi.schema_specified == SchemaSpecified::Schema)
+            .and_then(|psvi| psvi.schema_normalized_value.as_deref());
+        if attributes.is_empty() && content.is_none() {
+            continue;
+        }
+
+        let start = e.range().start;
+        let end = start_tag_end(text, start);
+        let empty_element_tag = text[..end].ends_with('/');
+        let attributes_position = if empty_element_tag { end - 1 } else { end };
+        output.push_str(&text[copied..attributes_position]);
+        output.push_str(&attributes);
+        match content {
+            Some(content) if empty_element_tag => {
+                output.push('>');
+                escape_text(content, &mut output);
+                output.push_str("</");
+                output.push_str(tag_name(text, start));
+                output.push('>');
+            }
+            Some(content) => {
+                output.push('>');
+                escape_text(content, &mut output);
+            }
+            None => output.push_str(&text[attributes_position..=end]),
+        }
+        copied = end + 1;
+    }
+    output.push_str(&text[copied..]);
+    output
+}
+
+/// Serializes the ·defaulted attributes· of `e`, each preceded by a space
+fn defaulted_attributes(e: &Node, psvi: &Psvi) -> String {
+    let mut attributes = String::new();
+    let mut declared_prefixes: Vec<(String, String)> = Vec::new();
+    for (name, attribute) in psvi.defaulted_attributes(e.id()) {
+        let Some(value) = attribute.schema_normalized_value.as_deref() else {
+            continue;
+        };
+        attributes.push(' ');
+        if let Some(namespace) = name.namespace_name.as_deref() {
+            // Unlike elements, attributes are not in the default namespace, so the namespace must
+            // be bound to a prefix
+            let prefix = e
+                .namespaces()
+                .find(|ns| ns.uri() == namespace && ns.name().is_some())
+                .and_then(|ns| ns.name())
+                .map(str::to_string)
+                .or_else(|| {
+                    declared_prefixes
+                        .iter()
+                        .find(|(_, uri)| uri == namespace)
+                        .map(|(prefix, _)| prefix.clone())
+                })
+                .unwrap_or_else(|| {
+                    let prefix = (1..)
+                        .map(|i| format!("ns{i}"))
+                        .find(|prefix| {
+                            e.lookup_namespace_uri(Some(prefix)).is_none()
+                                && declared_prefixes.iter().all(|(p, _)| p != prefix)
+                        })
+                        .unwrap();
+                    attributes.push_str("xmlns:");
+                    attributes.push_str(&prefix);
+                    attributes.push_str("=\"");
+                    escape_attribute_value(namespace, &mut attributes);
+                    attributes.push_str("\" ");
+                    declared_prefixes.push((prefix.clone(), namespace.to_string()));
+                    prefix
+                });
+            attributes.push_str(&prefix);
+            attributes.push(':');
+        }
+        attributes.push_str(&name.local_name);
+        attributes.push_str("=\"");
+        escape_attribute_value(value, &mut attributes);
+        attributes.push('"');
+    }
+    attributes
+}
+
+/// Returns the position of the `>` which ends the start tag beginning at `start`
+fn start_tag_end(text: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (i, c) in text[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return start + i,
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    unreachable!("the document is well-formed")
+}
+
+/// Returns the qualified name of the start tag beginning at `start`
+fn tag_name(text: &str, start: usize) -> &str {
+    let name = &text[start + 1..];
+    let end = name
+        .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+fn escape_text(value: &str, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\r' => output.push_str("&#13;"),
+            c => output.push(c),
+        }
+    }
+}
+
+/// Escapes an attribute value such that its whitespace is not normalized when it is parsed again
+fn escape_attribute_value(value: &str, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '"' => output.push_str("&quot;"),
+            '\t' => output.push_str("&#9;"),
+            '\n' => output.push_str("&#10;"),
+            '\r' => output.push_str("&#13;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use roxmltree::Document;
+
+    #[test]
+    fn defaults_are_inserted() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:attribute name="lang" type="xs:language" default="en"/>
+              <xs:element name="doc">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="flag" type="xs:boolean" default="true"
+                        maxOccurs="unbounded" nillable="true"/>
+                    <xs:element name="sign" type="xs:string" fixed="a &lt; b"/>
+                  </xs:sequence>
+                  <xs:attribute name="version" type="xs:string" default="1.0"/>
+                  <xs:attribute ref="lang"/>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+        let d = schema
+            .find_element_by_name(Some("urn:test"), "doc", &components)
+            .unwrap();
+
+        let instance = Document::parse(
+            r#"<t:doc xmlns:t="urn:test" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <t:flag/>
+  <t:flag>false</t:flag>
+  <t:flag xsi:nil="true"/>
+  <t:flag><!-- empty --></t:flag>
+  <t:sign></t:sign>
+</t:doc>"#,
+        )
+        .unwrap();
+        let report = super::super::element_locally_valid_element(
+            &instance.root_element(),
+            Some(d),
+            &components,
+        );
+        assert!(report.is_valid());
+        // Elements are filled in unless they have content or are nilled, and attributes in a
+        // namespace are given a prefix
+        assert_eq!(
+            super::augmented_document(&instance, &report.psvi),
+            r#"<t:doc xmlns:t="urn:test" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" version="1.0" t:lang="en">
+  <t:flag>true</t:flag>
+  <t:flag>false</t:flag>
+  <t:flag xsi:nil="true"/>
+  <t:flag>true<!-- empty --></t:flag>
+  <t:sign>a &lt; b</t:sign>
+</t:doc>"#
+        );
+
+        // The augmented document is valid as well, and has no defaulted attributes
+        let augmented = super::augmented_document(&instance, &report.psvi);
+        let augmented = Document::parse(&augmented).unwrap();
+        let report = super::super::element_locally_valid_element(
+            &augmented.root_element(),
+            Some(d),
+            &components,
+        );
+        assert!(report.is_valid());
+        assert_eq!(
+            report
+                .psvi
+                .defaulted_attributes(augmented.root_element().id())
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,759 @@
+//! The clauses of the validation rules which concern a single element or attribute information
+//! item, checked from the facts gathered about it. They are shared by the validation of a tree and
+//! the [streaming](super::streaming) validation, each of which reports the returned violations at
+//! the item concerned.
+
+use std::rc::Rc;
+
+use super::{
+    SubstitutionGroups, Value, context_determined_declaration, datatype::normalize_whitespace,
+    element_default_valid_immediate, global_attribute_declaration, global_element_declaration,
+    overrides, simple_content_type, string_valid, transition_matches, value::NamespaceContext,
+    wildcard_allows_element,
+};
+use crate::{
+    AttributeDeclaration, AttributeUse, ComplexTypeDefinition, ElementDeclaration, Particle, Ref,
+    SchemaComponentTable, TypeDefinition,
+    complex_type_def::{ContentTypeVariety, OpenContent, OpenContentMode},
+    components::RefNamed,
+    constraining_facet::WhiteSpaceValue,
+    shared::{ValueConstraint, ValueConstraintVariety},
+    state_machine::{State, StateMachine, StateMachineCache, Transition},
+    wildcard::ProcessContents,
+    xstypes::QName,
+};
+
+/// A violated clause, to be reported at the element or attribute information item it concerns
+#[derive(Debug)]
+pub(super) struct ClauseViolation {
+    pub(super) code: &'static str,
+    pub(super) message: String,
+}
+
+impl ClauseViolation {
+    fn new(code: &'static str, message: String) -> Self {
+        Self { code, message }
+    }
+}
+
+/// The [children] of an element information item, as far as they concern its validation.
+/// Comments and processing instructions are not part of the content of an element.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Children {
+    pub(super) elements: bool,
+    /// Whether there are character information items
+    pub(super) characters: bool,
+    /// Whether there are character information items other than white space
+    pub(super) significant_characters: bool,
+}
+
+impl Children {
+    /// Records character data among the [children]
+    pub(super) fn add_characters(&mut self, text: &str) {
+        self.characters |= !text.is_empty();
+        self.significant_characters |= !is_white_space(text);
+    }
+
+    fn any(self) -> bool {
+        self.elements || self.characters
+    }
+}
+
+/// Returns whether `text` only consists of characters defined as white space in [XML 1.1]
+fn is_white_space(text: &str) -> bool {
+    text.chars()
+        .all(|c| matches!(c, '\u{20}' | '\u{9}' | '\u{D}' | '\u{A}'))
+}
+
+fn declaration_name(d: &ElementDeclaration) -> QName {
+    QName::with_optional_namespace(d.target_namespace.as_deref(), d.name.as_str())
+}
+
+fn attribute_declaration_name(d: &AttributeDeclaration) -> QName {
+    QName::with_optional_namespace(d.target_namespace.as_deref(), d.name.as_str())
+}
+
+fn is_fixed(value_constraint: &&ValueConstraint) -> bool {
+    value_constraint.variety == ValueConstraintVariety::Fixed
+}
+
+/// Clause 2 of Element Locally Valid (Element) (§3.3.4.3) for an element named `name`
+pub(super) fn declaration_not_abstract(
+    d: &ElementDeclaration,
+    name: &QName,
+) -> Result<(), ClauseViolation> {
+    // 2 D.{abstract} = false.
+    if d.abstract_ {
+        return Err(ClauseViolation::new(
+            "cvc-elt.2",
+            format!("element declaration {name} is abstract"),
+        ));
+    }
+    Ok(())
+}
+
+/// Clause 3 of Element Locally Valid (Element) (§3.3.4.3) for an element named `name`, whose
+/// xsi:nil attribute, if any, has the value `xsi_nil`. Returns whether the element is ·nilled·;
+/// the violation concerns the xsi:nil attribute. The clauses concerning a ·nilled· element are
+/// checked by [`nilled_element_valid`].
+pub(super) fn nilled(
+    d: &ElementDeclaration,
+    name: &QName,
+    xsi_nil: Option<&str>,
+) -> Result<bool, ClauseViolation> {
+    // 3 One of the following is true:
+    let Some(xsi_nil) = xsi_nil else {
+        // 3.2.1 E has no xsi:nil attribute information item.
+        return Ok(false);
+    };
+    // 3.1 D.{nillable} = false, and E has no xsi:nil attribute.
+    if !d.nillable {
+        return Err(ClauseViolation::new(
+            "cvc-elt.3.1",
+            format!("element {name} is not nillable"),
+        ));
+    }
+    // 3.2 D.{nillable} = true and one of the following is true
+    //   3.2.2 E has xsi:nil = false.
+    //   3.2.3 E has xsi:nil = true (that is, E is ·nilled·) (...)
+    // xsi:nil is an xs:boolean, whose whiteSpace facet is collapse
+    match normalize_whitespace(xsi_nil, WhiteSpaceValue::Collapse).as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(ClauseViolation::new(
+            "cvc-datatype-valid.1",
+            format!("{xsi_nil:?} is not a valid boolean literal"),
+        )),
+    }
+}
+
+/// Clause 3.2.3 of Element Locally Valid (Element) (§3.3.4.3) for a ·nilled· element named `name`
+pub(super) fn nilled_element_valid(
+    d: &ElementDeclaration,
+    name: &QName,
+    children: Children,
+) -> Vec<ClauseViolation> {
+    let mut violations = Vec::new();
+    // 3.2.3 E has xsi:nil = true (that is, E is ·nilled·), and all of the following are true:
+    //   3.2.3.1 E has no character or element information item [children].
+    if children.any() {
+        violations.push(ClauseViolation::new(
+            "cvc-elt.3.2.3.1",
+            format!("nilled element {name} must be empty"),
+        ));
+    }
+    //   3.2.3.2 D has no {value constraint} with {variety} = fixed.
+    if d.value_constraint.as_ref().is_some_and(|vc| is_fixed(&vc)) {
+        violations.push(ClauseViolation::new(
+            "cvc-elt.3.2.3.2",
+            format!("element {name} has a fixed value and must not be nilled"),
+        ));
+    }
+    violations
+}
+
+/// Clause 4 of Element Locally Valid (Element) (§3.3.4.3) for an element named `name` with an
+/// xsi:type attribute whose value `xsi_type` ·resolves· to the type definition `t`, if any. The
+/// violation concerns the xsi:type attribute.
+pub(super) fn instance_specified_type_valid(
+    xsi_type: &str,
+    t: Option<TypeDefinition>,
+    selected_type_definition: TypeDefinition,
+    d: &ElementDeclaration,
+    name: &QName,
+    components: &SchemaComponentTable,
+) -> Result<(), ClauseViolation> {
+    // 4 If E has an ·instance-specified type definition· T, then T ·overrides· the ·selected type
+    //   definition· of E.
+    // An xsi:type attribute which does not ·resolve· to a type definition is an error as well.
+    match t {
+        None => Err(ClauseViolation::new(
+            "cvc-elt.4",
+            format!("{xsi_type:?} does not resolve to a type definition"),
+        )),
+        Some(t) if !overrides(t, selected_type_definition, d, components) => {
+            Err(ClauseViolation::new(
+                "cvc-elt.4",
+                format!(
+                    "type definition {xsi_type:?} cannot be used in place of the declared type of \
+                    element {name}"
+                ),
+            ))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Clause 5.1.1 of Element Locally Valid (Element) (§3.3.4.3) for an element named `name`, whose
+/// ·governing type definition· `t` is an ·instance-specified type definition·
+pub(super) fn default_valid_for_instance_type(
+    value_constraint: &ValueConstraint,
+    t: TypeDefinition,
+    name: &QName,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Result<(), ClauseViolation> {
+    // 5.1.1 If E's ·governing type definition· is an ·instance-specified type definition·, then
+    //   D.{value constraint} is a valid default for the ·governing type definition· as defined in
+    //   Element Default Valid (Immediate) (§3.3.6.2).
+    if !element_default_valid_immediate(t, &value_constraint.lexical_form, context, components) {
+        return Err(ClauseViolation::new(
+            "cvc-elt.5.1.1",
+            format!(
+                "the value constraint {:?} of element {name} is not valid for its \
+                instance-specified type definition",
+                value_constraint.lexical_form
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Clause 5.2.2 of Element Locally Valid (Element) (§3.3.4.3) for an element which is not ·nilled·
+/// and whose declaration `d` has the fixed `value_constraint`. `actual_value` is the ·actual
+/// value· of its ·initial value· `initial_value` with respect to its ·governing type definition·
+/// `t`, if that is valid.
+pub(super) fn fixed_value_valid(
+    d: &ElementDeclaration,
+    value_constraint: &ValueConstraint,
+    t: TypeDefinition,
+    children: Children,
+    initial_value: &str,
+    actual_value: Option<&Value>,
+    components: &SchemaComponentTable,
+) -> Result<(), ClauseViolation> {
+    // 5.2.2 If D.{value constraint}.{variety} = fixed and E is not ·nilled· with respect to D,
+    //   then all of the following are true:
+    let name = declaration_name(d);
+    //   5.2.2.1 E has no element information item [children].
+    if children.elements {
+        return Err(ClauseViolation::new(
+            "cvc-elt.5.2.2.1",
+            format!("element {name} has a fixed value and must not have element children"),
+        ));
+    }
+    //   5.2.2.2 The appropriate case among the following is true:
+    if t.complex()
+        .is_some_and(|t| t.get(components).content_type.variety() == ContentTypeVariety::Mixed)
+    {
+        // 5.2.2.2.1 If E's ·governing type definition· is a Complex Type Definition with
+        //   {content type}.{variety} = mixed , then the ·initial value· of E matches D.{value
+        //   constraint}.{lexical form}.
+        if initial_value != value_constraint.lexical_form {
+            return Err(ClauseViolation::new(
+                "cvc-elt.5.2.2.2.1",
+                format!(
+                    "{initial_value:?} does not match the fixed value {:?} of element \
+                    declaration {name}",
+                    value_constraint.lexical_form
+                ),
+            ));
+        }
+    } else if simple_content_type(t, components).is_some() {
+        // 5.2.2.2.2 If E's ·governing type definition· is a Simple Type Definition or a Complex
+        //   Type Definition with {content type}.{variety} = simple, then the ·actual value· of E
+        //   is equal or identical to D.{value constraint}.{value}.
+        // An invalid ·initial value· has been reported by Element Locally Valid (Type) already
+        if actual_value.is_some_and(|value| *value != value_constraint.value) {
+            return Err(ClauseViolation::new(
+                "cvc-elt.5.2.2.2.2",
+                format!(
+                    "{initial_value:?} does not match the fixed value {:?} of element \
+                    declaration {name}",
+                    value_constraint.lexical_form
+                ),
+            ));
+        }
+    } else {
+        return Err(ClauseViolation::new(
+            "cvc-elt.5.2.2.2",
+            format!("element {name} has a fixed value, but neither simple nor mixed content"),
+        ));
+    }
+    Ok(())
+}
+
+/// Clause 2 of Element Locally Valid (Type) (§3.3.4.4)
+pub(super) fn type_not_abstract(
+    t: TypeDefinition,
+    components: &SchemaComponentTable,
+) -> Result<(), ClauseViolation> {
+    // 2 If T is a complex type definition, then T.{abstract} = false.
+    if let TypeDefinition::Complex(t) = t
+        && t.get(components).abstract_
+    {
+        let name = t
+            .name(components)
+            .map_or_else(|| "(anonymous)".to_string(), |name| name.to_string());
+        return Err(ClauseViolation::new(
+            "cvc-type.2",
+            format!("type definition {name} is abstract"),
+        ));
+    }
+    Ok(())
+}
+
+/// Clause 3.1.1 of Element Locally Valid (Type) (§3.3.4.4), violated by each attribute named
+/// `attribute` of an element named `name` with a simple type, other than xsi:type, xsi:nil,
+/// xsi:schemaLocation and xsi:noNamespaceSchemaLocation
+pub(super) fn attribute_not_allowed_by_simple_type(
+    attribute: &QName,
+    name: &QName,
+) -> ClauseViolation {
+    // 3.1.1 E.[attributes] is empty, except for attributes named xsi:type, xsi:nil,
+    //   xsi:schemaLocation, or xsi:noNamespaceSchemaLocation.
+    ClauseViolation::new(
+        "cvc-type.3.1.1",
+        format!("attribute {attribute} is not allowed, as element {name} has a simple type"),
+    )
+}
+
+/// Clause 3.1.2 of Element Locally Valid (Type) (§3.3.4.4) for an element named `name` with a
+/// simple type
+pub(super) fn simple_type_children_valid(
+    name: &QName,
+    children: Children,
+) -> Result<(), ClauseViolation> {
+    // 3.1.2 E has no element information item [children].
+    if children.elements {
+        return Err(ClauseViolation::new(
+            "cvc-type.3.1.2",
+            format!("element {name} has a simple type and must not have element children"),
+        ));
+    }
+    Ok(())
+}
+
+/// Clause 1.1 of Element Locally Valid (Complex Type) (§3.4.4.2) for an element named `name`
+pub(super) fn empty_content_valid(name: &QName, children: Children) -> Result<(), ClauseViolation> {
+    // 1.1 If T.{content type}.{variety} = empty, then E has no character or element information
+    //   item [children].
+    if children.any() {
+        return Err(ClauseViolation::new(
+            "cvc-complex-type.1.1",
+            format!("element {name} must be empty"),
+        ));
+    }
+    Ok(())
+}
+
+/// The first part of clause 1.2 of Element Locally Valid (Complex Type) (§3.4.4.2) for an element
+/// named `name`. Its ·initial value· is only checked if it holds.
+pub(super) fn simple_content_children_valid(
+    name: &QName,
+    children: Children,
+) -> Result<(), ClauseViolation> {
+    // 1.2 If T.{content type}.{variety} = simple, then E has no element information item
+    //   [children], and the ·initial value· of E is ·valid· with respect to T.{content
+    //   type}.{simple type definition} as defined by String Valid (§3.16.4).
+    if children.elements {
+        return Err(ClauseViolation::new(
+            "cvc-complex-type.1.2",
+            format!("element {name} has simple content and must not have element children"),
+        ));
+    }
+    Ok(())
+}
+
+/// Clause 1.3 of Element Locally Valid (Complex Type) (§3.4.4.2) for an element named `name`
+pub(super) fn element_only_content_valid(
+    name: &QName,
+    children: Children,
+) -> Result<(), ClauseViolation> {
+    // 1.3 If T.{content type}.{variety} = element-only, then E has no character information item
+    //   [children] other than those whose [character code] is defined as a white space in [XML
+    //   1.1].
+    if children.significant_characters {
+        return Err(ClauseViolation::new(
+            "cvc-complex-type.1.3",
+            format!("element {name} has element-only content and must not have character children"),
+        ));
+    }
+    Ok(())
+}
+
+/// How an attribute of an element governed by a complex type definition is assessed, see clause 2
+/// of Element Locally Valid (Complex Type) (§3.4.4.2)
+pub(super) enum AttributeAttribution<'c> {
+    /// The attribute is ·valid· with respect to an attribute use (clause 2.1)
+    Use(&'c AttributeUse),
+    /// The attribute is allowed by the {attribute wildcard} (clause 2.2), and assessed with respect
+    /// to its ·context-determined declaration·, if any
+    Wildcard(Option<Ref<AttributeDeclaration>>),
+}
+
+/// Clause 2 of Element Locally Valid (Complex Type) (§3.4.4.2) for an attribute named `name`,
+/// which is not one of xsi:type, xsi:nil, xsi:schemaLocation and xsi:noNamespaceSchemaLocation
+pub(super) fn attribute_attribution<'c>(
+    t: &'c ComplexTypeDefinition,
+    name: &QName,
+    components: &'c SchemaComponentTable,
+) -> Result<AttributeAttribution<'c>, ClauseViolation> {
+    // 2 For each attribute information item A in E.[attributes] excepting those named xsi:type,
+    //   xsi:nil, xsi:schemaLocation, or xsi:noNamespaceSchemaLocation (see Built-in Attribute
+    //   Declarations (§3.2.7)), the appropriate case among the following is true:
+    if let Some(attribute_use) = t
+        .attribute_uses
+        .iter()
+        .map(|u| u.get(components))
+        .find(|u| attribute_declaration_name(u.attribute_declaration.get(components)) == *name)
+    {
+        // 2.1 If there is among the {attribute uses} an attribute use U whose {attribute
+        //   declaration} has the same expanded name as A, then A is ·valid· with respect to U as
+        //   per Attribute Locally Valid (Use) (§3.5.4.2).
+        return Ok(AttributeAttribution::Use(attribute_use));
+    }
+
+    // 2.2 otherwise all of the following are true:
+    //   2.2.1 There is an {attribute wildcard}.
+    let Some(wildcard) = t.attribute_wildcard.map(|w| w.get(components)) else {
+        return Err(ClauseViolation::new(
+            "cvc-complex-type.2.2.1",
+            format!("attribute {name} is not allowed"),
+        ));
+    };
+
+    //   2.2.2 A is ·valid· with respect to it as defined in Item Valid (Wildcard) (§3.10.4.1).
+    let global_declaration = global_attribute_declaration(name, components);
+    if !wildcard.allows_name(name)
+        || (wildcard
+            .namespace_constraint
+            .disallowed_names
+            .contains_defined()
+            && global_declaration.is_some())
+    {
+        return Err(ClauseViolation::new(
+            "cvc-complex-type.2.2.2",
+            format!("attribute {name} is not allowed by the attribute wildcard"),
+        ));
+    }
+
+    // The ·context-determined declaration· of A is the declaration its expanded name ·resolves·
+    // to, which A must be ·valid· with respect to unless {process contents} is skip
+    // (Schema-Validity Assessment (Attribute) (§3.2.4.3)). With {process contents} = strict, such
+    // a declaration must exist.
+    match (&wildcard.process_contents, global_declaration) {
+        (ProcessContents::Skip, _) => Ok(AttributeAttribution::Wildcard(None)),
+        (ProcessContents::Strict, None) => Err(ClauseViolation::new(
+            "cvc-assess-attr",
+            format!(
+                "no declaration found for attribute {name}, which is matched by a strict wildcard"
+            ),
+        )),
+        (_, d) => Ok(AttributeAttribution::Wildcard(d)),
+    }
+}
+
+/// Clause 3 of Element Locally Valid (Complex Type) (§3.4.4.2) for an element with an attribute
+/// of each name for which `has_attribute` holds
+pub(super) fn missing_required_attributes(
+    t: &ComplexTypeDefinition,
+    has_attribute: impl Fn(&QName) -> bool,
+    components: &SchemaComponentTable,
+) -> Vec<ClauseViolation> {
+    // 3 For each attribute use U in T.{attribute uses}, if U.{required} = true, then U.{attribute
+    //   declaration} has the same expanded name as one of the attribute information items in
+    //   E.[attributes].
+    t.attribute_uses
+        .iter()
+        .map(|u| u.get(components))
+        .filter(|u| u.required)
+        .map(|u| attribute_declaration_name(u.attribute_declaration.get(components)))
+        .filter(|name| !has_attribute(name))
+        .map(|name| {
+            ClauseViolation::new(
+                "cvc-complex-type.3",
+                format!("missing required attribute {name}"),
+            )
+        })
+        .collect()
+}
+
+/// Returns the ·defaulted attributes· belonging to an element governed by `t`, which has an
+/// attribute of each name for which `has_attribute` holds, with their ·effective value
+/// constraints·
+pub(super) fn defaulted_attributes<'c>(
+    t: &'c ComplexTypeDefinition,
+    has_attribute: impl Fn(&QName) -> bool,
+    components: &'c SchemaComponentTable,
+) -> Vec<(&'c AttributeUse, &'c ValueConstraint)> {
+    // [Definition:] A defaulted attribute belonging to an element information item E governed by
+    // a complex type T is any Attribute Use U for which all of the following are true: U is a
+    // member of T.{attribute uses}, U.{required} = false, U's ·effective value constraint· is not
+    // ·absent· and E has no attribute information item with the same expanded name as
+    // U.{attribute declaration}.
+    t.attribute_uses
+        .iter()
+        .map(|u| u.get(components))
+        .filter(|u| !u.required)
+        .filter(|u| {
+            !has_attribute(&attribute_declaration_name(
+                u.attribute_declaration.get(components),
+            ))
+        })
+        .filter_map(|u| Some((u, u.effective_value_constraint(components)?)))
+        .collect()
+}
+
+/// Clause 4 of Element Locally Valid (Complex Type) (§3.4.4.2) for the ·defaulted attribute· `u`
+/// with the ·effective value constraint· `value_constraint`. Returns the ·actual value· of the
+/// attribute.
+pub(super) fn defaulted_attribute_valid(
+    u: &AttributeUse,
+    value_constraint: &ValueConstraint,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Result<Value, ClauseViolation> {
+    // 4 For each ·defaulted attribute· A belonging to E, the {lexical form} of A's ·effective
+    //   value constraint· is ·valid· with respect to A.{attribute declaration}.{type definition}
+    //   as defined by String Valid (§3.16.4).
+    let d = u.attribute_declaration.get(components);
+    string_valid(
+        &value_constraint.lexical_form,
+        d.type_definition.get(components),
+        context,
+        components,
+    )
+    .map_err(|error| {
+        ClauseViolation::new(
+            "cvc-complex-type.4",
+            format!(
+                "invalid value constraint of defaulted attribute {}: {error}",
+                attribute_declaration_name(d)
+            ),
+        )
+    })
+}
+
+/// Clause 4 of Attribute Locally Valid (§3.2.4.1) for an attribute with the ·initial value·
+/// `initial_value`, whose ·actual value· is `value`
+pub(super) fn attribute_fixed_value_valid(
+    d: &AttributeDeclaration,
+    initial_value: &str,
+    value: &Value,
+) -> Result<(), ClauseViolation> {
+    // 4 If D.{value constraint} is present and D.{value constraint}.{variety} = fixed, then A's
+    //   ·actual value· is equal or identical to D.{value constraint}.{value}.
+    if let Some(value_constraint) = d.value_constraint.as_ref().filter(is_fixed)
+        && *value != value_constraint.value
+    {
+        return Err(ClauseViolation::new(
+            "cvc-attribute.4",
+            format!(
+                "{initial_value:?} does not match the fixed value {:?} of attribute declaration \
+                {}",
+                value_constraint.lexical_form,
+                attribute_declaration_name(d)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Attribute Locally Valid (Use) (§3.5.4.2) for an attribute with the ·initial value·
+/// `initial_value`, whose ·actual value· is `value`
+pub(super) fn attribute_use_fixed_value_valid(
+    u: &AttributeUse,
+    initial_value: &str,
+    value: &Value,
+    components: &SchemaComponentTable,
+) -> Result<(), ClauseViolation> {
+    // For an attribute information item to be ·valid· with respect to an attribute use its
+    // ·actual value· must be equal or identical to the {value} of the attribute use's ·effective
+    // value constraint·, if it is present and has {variety} fixed.
+    if let Some(value_constraint) = u.effective_value_constraint(components).filter(is_fixed)
+        && *value != value_constraint.value
+    {
+        return Err(ClauseViolation::new(
+            "cvc-au",
+            format!(
+                "{initial_value:?} does not match the fixed value {:?} of the attribute use of \
+                {}",
+                value_constraint.lexical_form,
+                attribute_declaration_name(u.attribute_declaration.get(components))
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// How an element matched by a content model is assessed
+pub(super) enum Attribution {
+    /// The element is governed by its ·context-determined declaration·
+    Declaration(Ref<ElementDeclaration>),
+    /// The element is matched by a wildcard, see [`wildcard_assessment`]
+    Wildcard(ProcessContents),
+}
+
+/// Element Sequence Locally Valid (Complex Content) (§3.4.4.3), for a sequence of element
+/// information items attributed to the content model one at a time
+pub(super) struct ContentModelMatcher<'c> {
+    particle: &'c Particle,
+    open_content: &'c Option<OpenContent>,
+    state_machine: Rc<StateMachine>,
+    state: State,
+    /// Set once the first element of S2 is encountered in suffix mode
+    in_suffix: bool,
+    substitution_groups: SubstitutionGroups,
+    components: &'c SchemaComponentTable,
+}
+
+impl<'c> ContentModelMatcher<'c> {
+    pub(super) fn new(
+        particle: Ref<Particle>,
+        open_content: &'c Option<OpenContent>,
+        state_machines: &mut StateMachineCache,
+        components: &'c SchemaComponentTable,
+    ) -> Result<Self, ClauseViolation> {
+        // The content model violates a Schema Component Constraint, which check_schema reports
+        let state_machine = state_machines
+            .get(particle, components)
+            .map_err(|err| ClauseViolation::new("cos-all-limited", err.to_string()))?;
+        Ok(Self {
+            particle: particle.get(components),
+            open_content,
+            state: state_machine.start_state(),
+            state_machine,
+            in_suffix: false,
+            substitution_groups: SubstitutionGroups::new(),
+            components,
+        })
+    }
+
+    /// Attributes the next element of the sequence, named `name`, to the particle or the wildcard
+    /// of the open content. The violation concerns that element, after which the remaining
+    /// elements cannot be attributed reliably.
+    pub(super) fn next(&mut self, name: &QName) -> Result<Attribution, ClauseViolation> {
+        let components = self.components;
+        // As the particle is deterministic (UPA), S1 and S2 are determined by matching each
+        // element against the particle first, and against the wildcard if it has no ·path·.
+        let open_content_wildcard = |open_content: &OpenContent| {
+            let w = open_content.wildcard.get(components);
+            wildcard_allows_element(w, name, self.particle, components)
+                .then(|| Attribution::Wildcard(w.process_contents.clone()))
+        };
+        let attribution = if self.in_suffix {
+            open_content_wildcard(self.open_content.as_ref().unwrap())
+        } else {
+            let particle = self.particle;
+            let substitution_groups = &mut self.substitution_groups;
+            let matched = self.state_machine.step(&mut self.state, |label| {
+                transition_matches(label, name, particle, substitution_groups, components)
+            });
+            match matched {
+                // The element has no ·path· in the particle, it must belong to S2
+                None => self.open_content.as_ref().and_then(|open_content| {
+                    let attribution = open_content_wildcard(open_content)?;
+                    self.in_suffix = matches!(open_content.mode, OpenContentMode::Suffix);
+                    Some(attribution)
+                }),
+                Some(Transition::ElementDeclaration(label)) => {
+                    Some(Attribution::Declaration(context_determined_declaration(
+                        label,
+                        name,
+                        &self.substitution_groups,
+                        components,
+                    )))
+                }
+                Some(Transition::Wildcard(label)) => Some(Attribution::Wildcard(
+                    label.get(components).process_contents.clone(),
+                )),
+            }
+        };
+        // An element without a ·path· in the particle, which is not allowed by the wildcard of
+        // the open content either
+        attribution.ok_or_else(|| {
+            ClauseViolation::new(
+                "cvc-complex-type.1.4",
+                format!("unexpected element {name}{}", self.expected_elements()),
+            )
+        })
+    }
+
+    /// Checks that the elements attributed so far form a complete sequence for the content model
+    /// of the element named `name`
+    pub(super) fn end(&self, name: &QName) -> Result<(), ClauseViolation> {
+        if !self.state_machine.is_end_state(&self.state) {
+            return Err(ClauseViolation::new(
+                "cvc-complex-type.1.4",
+                format!(
+                    "incomplete content of element {name}{}",
+                    self.expected_elements()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Describes the elements which may follow, for reporting a violation of the content model
+    fn expected_elements(&self) -> String {
+        let mut expected = self
+            .state_machine
+            .transitions(&self.state)
+            .into_iter()
+            .map(|label| match label {
+                Transition::ElementDeclaration(d) => {
+                    declaration_name(d.get(self.components)).to_string()
+                }
+                Transition::Wildcard(_) => "any element matching a wildcard".to_string(),
+            })
+            .collect::<Vec<_>>();
+        expected.sort();
+        expected.dedup();
+        if expected.is_empty() {
+            String::new()
+        } else {
+            format!(", expected {}", expected.join(" or "))
+        }
+    }
+}
+
+/// How an element information item matched by a wildcard is assessed, as determined by the
+/// wildcard's {process contents} (3.3.4.6 Schema-Validity Assessment (Element))
+pub(super) enum WildcardAssessment {
+    /// skip: No constraints at all: the item must simply be well-formed XML.
+    Skip,
+    /// The element is governed by its ·context-determined declaration·
+    Declaration(Ref<ElementDeclaration>),
+    /// Without a declaration, the ·instance-specified type definition· is the ·governing type
+    /// definition·
+    Type(TypeDefinition),
+    /// lax: The element is not ·strictly assessed·, but its attributes and children are assessed
+    /// laxly, i.e. with respect to their global declarations, if any
+    Lax,
+}
+
+/// Determines how an element named `name`, which is matched by a wildcard with the given
+/// `process_contents`, is assessed. `instance_specified_type_definition` is the type definition
+/// its xsi:type attribute ·resolves· to, if any.
+pub(super) fn wildcard_assessment(
+    name: &QName,
+    process_contents: &ProcessContents,
+    instance_specified_type_definition: Option<TypeDefinition>,
+    components: &SchemaComponentTable,
+) -> Result<WildcardAssessment, ClauseViolation> {
+    if let ProcessContents::Skip = process_contents {
+        return Ok(WildcardAssessment::Skip);
+    }
+    // The ·context-determined declaration· of E is the global element declaration its expanded
+    // name ·resolves· to, if any.
+    if let Some(d) = global_element_declaration(name, components) {
+        return Ok(WildcardAssessment::Declaration(d));
+    }
+    if let Some(t) = instance_specified_type_definition {
+        return Ok(WildcardAssessment::Type(t));
+    }
+    match process_contents {
+        // strict: There must be a top-level declaration for the item available, and the item
+        //   must be ·valid· as appropriate.
+        ProcessContents::Strict => Err(ClauseViolation::new(
+            "cvc-assess-elt",
+            format!(
+                "no declaration found for element {name}, which is matched by a strict wildcard"
+            ),
+        )),
+        // lax: Validate where you can: If the item has a uniquely determined declaration
+        //   available, it must be ·valid· with respect to that definition.
+        _ => Ok(WildcardAssessment::Lax),
+    }
+}
@@ -0,0 +1,560 @@
+//! String Valid (pt. 1, §3.16.4) and Datatype Valid (pt. 2, §4.1.4)
+
+use super::{
+    pattern::pattern_matches,
+    value::{NamespaceContext, Primitive, Value},
+};
+use crate::{
+    Ref, SchemaComponentTable, SimpleTypeDefinition, TypeDefinition,
+    constraining_facet::{ConstrainingFacet, ExplicitTimezoneValue, WhiteSpaceValue},
+    simple_type_def::Variety,
+};
+use std::{cmp::Ordering, fmt};
+
+/// The reason why a string is not valid with respect to a simple type definition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidValue {
+    /// The (whitespace-normalized) literal is not in the lexical space of the datatype
+    NotInLexicalSpace {
+        literal: String,
+        datatype: &'static str,
+    },
+    /// The value does not satisfy a constraining facet
+    Facet {
+        literal: String,
+        facet: &'static str,
+    },
+    /// The literal is not valid with respect to any member type of a union
+    NoMemberTypeMatched { literal: String },
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInLexicalSpace { literal, datatype } => {
+                write!(f, "{literal:?} is not a valid {datatype} literal")
+            }
+            Self::Facet { literal, facet } => {
+                write!(f, "{literal:?} does not satisfy the {facet} facet")
+            }
+            Self::NoMemberTypeMatched { literal } => {
+                write!(
+                    f,
+                    "{literal:?} is not valid for any member type of the union"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidValue {}
+
+impl InvalidValue {
+    /// Returns the name of the violated validation rule of Part 2, e.g. `cvc-pattern-valid`
+    pub fn code(&self) -> &'static str {
+        match self {
+            // 4.1.4 Datatype Valid, clause 1: the literal is in the lexical space
+            Self::NotInLexicalSpace { .. } | Self::NoMemberTypeMatched { .. } => {
+                "cvc-datatype-valid.1"
+            }
+            Self::Facet { facet, .. } => match *facet {
+                "length" => "cvc-length-valid",
+                "minLength" => "cvc-minLength-valid",
+                "maxLength" => "cvc-maxLength-valid",
+                "pattern" => "cvc-pattern-valid",
+                "enumeration" => "cvc-enumeration-valid",
+                "maxInclusive" => "cvc-maxInclusive-valid",
+                "maxExclusive" => "cvc-maxExclusive-valid",
+                "minInclusive" => "cvc-minInclusive-valid",
+                "minExclusive" => "cvc-minExclusive-valid",
+                "totalDigits" => "cvc-totalDigits-valid",
+                "fractionDigits" => "cvc-fractionDigits-valid",
+                "assertions" => "cvc-assertions-valid",
+                "explicitTimezone" => "cvc-explicitTimezone-valid",
+                // 4.1.4 Datatype Valid, clause 2: the value is facet-valid
+                _ => "cvc-datatype-valid.2",
+            },
+        }
+    }
+}
+
+/// The ·member type definition·s of union types which validated a value, in the shape of the
+/// value: the [member type definition] PSVI property (§3.3.5.2, §3.2.5.2), and that of each list
+/// item
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum MemberTypes {
+    /// The value was validated by an atomic type definition or ·xs:anySimpleType·
+    Atomic,
+    /// The member types of the items of a list value
+    List(Vec<MemberTypes>),
+    /// The value was validated by the given member of a union, with the member types of that
+    /// validation
+    Union(Ref<SimpleTypeDefinition>, Box<MemberTypes>),
+}
+
+impl MemberTypes {
+    /// The member type definition which validated a value of a union type, if any
+    pub(super) fn member_type_definition(&self) -> Option<Ref<SimpleTypeDefinition>> {
+        match self {
+            Self::Union(member, _) => Some(*member),
+            _ => None,
+        }
+    }
+}
+
+/// 3.16.4 String Valid
+///
+/// Returns the actual value of `string` if it is valid with respect to `t`. QName and NOTATION
+/// values are resolved against `context`.
+pub fn string_valid(
+    string: &str,
+    t: &SimpleTypeDefinition,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Result<Value, InvalidValue> {
+    normalized_string_valid(string, t, context, components).map(|(_, value, _)| value)
+}
+
+/// String Valid (§3.16.4), returning the ·normalized value· of `string` together with its actual
+/// value and the member types which validated it
+pub(super) fn normalized_string_valid(
+    string: &str,
+    t: &SimpleTypeDefinition,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Result<(String, Value, MemberTypes), InvalidValue> {
+    // A string is locally ·valid· with respect to a simple type definition T if and only if all
+    // of the following are true:
+
+    // 1 The ·normalized value· of the string, after the whitespace normalization specified by T's
+    //   whiteSpace facet, is ·valid· with respect to T as defined by Datatype Valid in [XML
+    //   Schema: Datatypes].
+    // 2 If T is ENTITY or ENTITIES or derived from them, the string (or each item of the list)
+    //   is a declared entity name.
+    // TODO: clause 2, roxmltree does not report unparsed entity declarations
+    datatype_valid(string, t, context, components)
+}
+
+/// Applies the whitespace normalization of the whiteSpace facet (pt. 2, §4.3.6)
+pub(crate) fn normalize_whitespace(literal: &str, white_space: WhiteSpaceValue) -> String {
+    let replaced = || literal.replace(['\t', '\n', '\r'], " ");
+    match white_space {
+        WhiteSpaceValue::Preserve => literal.to_string(),
+        WhiteSpaceValue::Replace => replaced(),
+        WhiteSpaceValue::Collapse => replaced()
+            .split(' ')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Returns the constraining facets applying to `t`: those of `t` itself and of its simple
+/// ancestors, from which they are inherited when deriving by restriction
+fn facets<'c>(
+    t: &'c SimpleTypeDefinition,
+    components: &'c SchemaComponentTable,
+) -> impl Iterator<Item = &'c ConstrainingFacet> {
+    std::iter::successors(Some(t), |t| match t.base_type_definition {
+        TypeDefinition::Simple(base) => Some(base.get(components)),
+        TypeDefinition::Complex(_) => None,
+    })
+    .flat_map(|t| t.facets.iter_resolved(components))
+}
+
+fn white_space(t: &SimpleTypeDefinition, components: &SchemaComponentTable) -> WhiteSpaceValue {
+    match t.variety {
+        // The whiteSpace of list types is fixed to collapse (pt. 2, §4.3.6.1)
+        Some(Variety::List) => WhiteSpaceValue::Collapse,
+        _ => facets(t, components)
+            .find_map(|f| match f {
+                ConstrainingFacet::WhiteSpace(w) => Some(w.value),
+                _ => None,
+            })
+            .unwrap_or(WhiteSpaceValue::Preserve),
+    }
+}
+
+fn primitive(t: &SimpleTypeDefinition, components: &SchemaComponentTable) -> Option<Primitive> {
+    t.primitive_type_definition
+        .and_then(|p| p.get(components).name.as_deref())
+        .and_then(Primitive::from_name)
+}
+
+/// Datatype Valid (pt. 2, §4.1.4)
+///
+/// Returns the normalized value and the actual value of `literal`, and the member types which
+/// validated it.
+fn datatype_valid(
+    literal: &str,
+    t: &SimpleTypeDefinition,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Result<(String, Value, MemberTypes), InvalidValue> {
+    let normalized = normalize_whitespace(literal, white_space(t, components));
+
+    // A string S is datatype-valid with respect to a datatype T if and only if all of the
+    // following are true:
+    let (normalized, value, member_types) = match t.variety {
+        // xs:anySimpleType: any string is valid
+        None => {
+            return Ok((
+                normalized.clone(),
+                Value::String(normalized),
+                MemberTypes::Atomic,
+            ));
+        }
+        // 1 S is in the ·lexical space· of T, with the value V as determined by the ·lexical
+        //   mapping·.
+        Some(Variety::Atomic) => {
+            let value = match primitive(t, components) {
+                Some(primitive) => {
+                    Value::parse(primitive, &normalized, context).ok_or_else(|| {
+                        InvalidValue::NotInLexicalSpace {
+                            literal: normalized.clone(),
+                            datatype: primitive.name(),
+                        }
+                    })?
+                }
+                // xs:anyAtomicType
+                None => Value::String(normalized.clone()),
+            };
+            (normalized, value, MemberTypes::Atomic)
+        }
+        // 2 If T.{variety} is list, then each space-separated item of S is datatype-valid with
+        //   respect to T.{item type definition}.
+        Some(Variety::List) => {
+            let item_type = t
+                .item_type_definition
+                .expect("list types have an item type definition")
+                .get(components);
+            let (items, member_types) = normalized
+                .split(' ')
+                .filter(|item| !item.is_empty())
+                .map(|item| {
+                    datatype_valid(item, item_type, context, components).map(|(_, v, m)| (v, m))
+                })
+                .collect::<Result<(Vec<_>, Vec<_>), _>>()?;
+            (
+                normalized,
+                Value::List(items),
+                MemberTypes::List(member_types),
+            )
+        }
+        // 3 If T.{variety} is union, then S is datatype-valid with respect to at least one of
+        //   T.{member type definitions}; V is the value for the first such member.
+        Some(Variety::Union) => t
+            .member_type_definitions
+            .iter()
+            .flatten()
+            .find_map(|&member| {
+                let (normalized, value, member_types) =
+                    datatype_valid(literal, member.get(components), context, components).ok()?;
+                let member_types = MemberTypes::Union(member, Box::new(member_types));
+                Some((normalized, value, member_types))
+            })
+            .ok_or_else(|| InvalidValue::NoMemberTypeMatched {
+                literal: literal.to_string(),
+            })?,
+    };
+
+    // 4 V is facet-valid with respect to each of the constraining facets of T.
+    check_facets(&normalized, &value, t, context, components)?;
+
+    Ok((normalized, value, member_types))
+}
+
+/// Maps a facet value (of enumeration, or of the bounds facets) to a value of `t`, interpreting
+/// it as an instance of the datatype without checking the facets of `t` itself
+fn facet_value(
+    literal: &str,
+    t: &SimpleTypeDefinition,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Option<Value> {
+    let normalized = normalize_whitespace(literal, white_space(t, components));
+    match t.variety {
+        None => Some(Value::String(normalized)),
+        Some(Variety::Atomic) => match primitive(t, components) {
+            Some(primitive) => Value::parse(primitive, &normalized, context),
+            None => Some(Value::String(normalized)),
+        },
+        Some(Variety::List) => {
+            let item_type = t.item_type_definition?.get(components);
+            normalized
+                .split(' ')
+                .filter(|item| !item.is_empty())
+                .map(|item| {
+                    datatype_valid(item, item_type, context, components)
+                        .ok()
+                        .map(|(_, v, _)| v)
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(Value::List)
+        }
+        Some(Variety::Union) => t
+            .member_type_definitions
+            .iter()
+            .flatten()
+            .find_map(|member| {
+                datatype_valid(literal, member.get(components), context, components)
+                    .ok()
+                    .map(|(_, v, _)| v)
+            }),
+    }
+}
+
+/// Checks the value against each of the constraining facets of `t` (pt. 2, §4.3)
+fn check_facets(
+    normalized: &str,
+    value: &Value,
+    t: &SimpleTypeDefinition,
+    context: &dyn NamespaceContext,
+    components: &SchemaComponentTable,
+) -> Result<(), InvalidValue> {
+    let violated = |facet| {
+        Err(InvalidValue::Facet {
+            literal: normalized.to_string(),
+            facet,
+        })
+    };
+    // A bound is satisfied if the value compares as required; incomparable values do not satisfy
+    // any bound.
+    let satisfies_bound = |bound: &str, allowed: &[Ordering]| {
+        facet_value(bound, t, context, components)
+            .and_then(|bound| value.compare(&bound))
+            .is_some_and(|ordering| allowed.contains(&ordering))
+    };
+
+    // The {value} of an assertions facet includes the assertions of the base type definition, so
+    // only the first one found needs to be checked
+    let mut assertions_checked = false;
+    for facet in facets(t, components) {
+        match facet {
+            // 4.3.1.4 Length Valid, 4.3.2.4 minLength Valid, 4.3.3.4 maxLength Valid
+            ConstrainingFacet::Length(length) => {
+                if value.length().is_some_and(|l| l != length.value) {
+                    return violated("length");
+                }
+            }
+            ConstrainingFacet::MinLength(length) => {
+                if value.length().is_some_and(|l| l < length.value) {
+                    return violated("minLength");
+                }
+            }
+            ConstrainingFacet::MaxLength(length) => {
+                if value.length().is_some_and(|l| l > length.value) {
+                    return violated("maxLength");
+                }
+            }
+            // 4.3.4.4 pattern valid: the ·lexical representation· is among the strings denoted by
+            // each of the regular expressions in {value}
+            ConstrainingFacet::Pattern(pattern) => {
+                // Invalid regular expressions are reported by check_schema(), so they are
+                // ignored here rather than once per value
+                for regex in &pattern.value {
+                    if pattern_matches(regex, normalized) == Ok(false) {
+                        return violated("pattern");
+                    }
+                }
+            }
+            // 4.3.5.4 enumeration valid: the value is equal or identical to one of the values of
+            // {value}
+            ConstrainingFacet::Enumeration(enumeration) => {
+                // TODO: QName enumeration values are resolved against the namespace bindings of
+                //   the instance instead of those of the schema document
+                if !enumeration
+                    .value
+                    .iter()
+                    .any(|e| facet_value(e, t, context, components).as_ref() == Some(value))
+                {
+                    return violated("enumeration");
+                }
+            }
+            // 4.3.7.4 maxInclusive Valid, 4.3.8.4 maxExclusive Valid, 4.3.9.4 minExclusive Valid,
+            // 4.3.10.4 minInclusive Valid
+            ConstrainingFacet::MaxInclusive(bound) => {
+                if !satisfies_bound(&bound.value, &[Ordering::Less, Ordering::Equal]) {
+                    return violated("maxInclusive");
+                }
+            }
+            ConstrainingFacet::MaxExclusive(bound) => {
+                if !satisfies_bound(&bound.value, &[Ordering::Less]) {
+                    return violated("maxExclusive");
+                }
+            }
+            ConstrainingFacet::MinExclusive(bound) => {
+                if !satisfies_bound(&bound.value, &[Ordering::Greater]) {
+                    return violated("minExclusive");
+                }
+            }
+            ConstrainingFacet::MinInclusive(bound) => {
+                if !satisfies_bound(&bound.value, &[Ordering::Greater, Ordering::Equal]) {
+                    return violated("minInclusive");
+                }
+            }
+            // 4.3.11.4 totalDigits Valid, 4.3.12.4 fractionDigits Valid
+            ConstrainingFacet::TotalDigits(total_digits) => {
+                if let Value::Decimal(d) = value
+                    && d.total_digits() > total_digits.value
+                {
+                    return violated("totalDigits");
+                }
+            }
+            ConstrainingFacet::FractionDigits(fraction_digits) => {
+                if let Value::Decimal(d) = value
+                    && d.fraction_digits() > fraction_digits.value
+                {
+                    return violated("fractionDigits");
+                }
+            }
+            // 4.3.14.4 explicitTimezone Valid
+            ConstrainingFacet::ExplicitTimezone(explicit_timezone) => {
+                if let Value::DateTime(_, d) = value {
+                    let has_timezone = d.timezone_offset().is_some();
+                    match explicit_timezone.value {
+                        ExplicitTimezoneValue::Required if !has_timezone => {
+                            return violated("explicitTimezone");
+                        }
+                        ExplicitTimezoneValue::Prohibited if has_timezone => {
+                            return violated("explicitTimezone");
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            // whiteSpace is applied before the lexical mapping
+            ConstrainingFacet::WhiteSpace(_) => {}
+            // 4.3.13.4 Assertions Valid: each of the {value}'s {test}s evaluates to true, with
+            // $value bound to the value and without a context item
+            ConstrainingFacet::Assertions(assertions) if !assertions_checked => {
+                assertions_checked = true;
+                let satisfied = assertions.value.iter().all(|assertion| {
+                    let test = &assertion.get(components).test_expression;
+                    test.evaluate(None, Some((normalized, value)), &()) == Ok(true)
+                });
+                if !satisfied {
+                    return violated("assertions");
+                }
+            }
+            ConstrainingFacet::Assertions(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvalidValue, string_valid};
+    use crate::{SimpleTypeDefinition, components::RefNamed, xstypes::QName};
+    use roxmltree::Document;
+
+    #[test]
+    fn facets() {
+        let (_, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:test">
+              <xs:simpleType name="sku">
+                <xs:restriction base="xs:token">
+                  <xs:pattern value="\d{3}-[A-Z]{2}"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="price">
+                <xs:restriction base="xs:decimal">
+                  <xs:minExclusive value="0"/>
+                  <xs:totalDigits value="5"/>
+                  <xs:fractionDigits value="2"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="size">
+                <xs:restriction base="xs:string">
+                  <xs:enumeration value="S"/>
+                  <xs:enumeration value="M"/>
+                  <xs:enumeration value="L"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="sizes">
+                <xs:restriction>
+                  <xs:simpleType>
+                    <xs:list itemType="xs:unsignedByte"/>
+                  </xs:simpleType>
+                  <xs:maxLength value="3"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="sizeOrNumber">
+                <xs:union memberTypes="xs:int xs:NCName"/>
+              </xs:simpleType>
+              <xs:simpleType name="utcDate">
+                <xs:restriction base="xs:date">
+                  <xs:explicitTimezone value="required"/>
+                  <xs:minInclusive value="2000-01-01Z"/>
+                </xs:restriction>
+              </xs:simpleType>
+              <xs:simpleType name="brokenPattern">
+                <xs:restriction base="xs:string">
+                  <xs:pattern value="[a-"/>
+                  <xs:maxLength value="3"/>
+                </xs:restriction>
+              </xs:simpleType>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        let context = Document::parse("<root/>").unwrap();
+        let valid = |namespace, name, literal| {
+            let name = QName::with_namespace(namespace, name);
+            let t = components
+                .refs::<SimpleTypeDefinition>()
+                .find(|t| t.name(&components).as_ref() == Some(&name))
+                .unwrap()
+                .get(&components);
+            string_valid(literal, t, &context.root_element(), &components)
+        };
+        let test = |name, literal| valid("urn:test", name, literal);
+        let xs = |name, literal| valid("http://www.w3.org/2001/XMLSchema", name, literal);
+
+        assert!(test("sku", "  123-AB ").is_ok());
+        assert!(test("sku", "123-ab").is_err());
+        assert!(test("price", "999.99").is_ok());
+        assert_eq!(
+            test("price", "0"),
+            Err(InvalidValue::Facet {
+                literal: "0".into(),
+                facet: "minExclusive"
+            })
+        );
+        assert!(test("price", "1000.01").is_err());
+        assert!(test("price", "1.005").is_err());
+        assert!(test("size", "M").is_ok());
+        assert!(test("size", " M").is_err());
+        assert!(test("sizes", "1 2\n 255").is_ok());
+        assert!(test("sizes", "1 2 3 4").is_err());
+        assert!(test("sizes", "256").is_err());
+        assert!(test("sizeOrNumber", " 42 ").is_ok());
+        assert!(test("sizeOrNumber", "XL").is_ok());
+        assert!(test("sizeOrNumber", "4XL").is_err());
+        assert!(test("utcDate", "2000-01-01Z").is_ok());
+        assert!(test("utcDate", "2000-01-01").is_err());
+        assert!(test("utcDate", "1999-12-31Z").is_err());
+        // Invalid patterns are reported by check_schema() instead
+        assert!(test("brokenPattern", "abc").is_ok());
+        assert_eq!(
+            test("brokenPattern", "abcd"),
+            Err(InvalidValue::Facet {
+                literal: "abcd".into(),
+                facet: "maxLength"
+            })
+        );
+
+        assert!(xs("int", "2147483647").is_ok());
+        assert!(xs("int", "2147483648").is_err());
+        assert!(xs("unsignedLong", "18446744073709551615").is_ok());
+        assert!(xs("unsignedLong", "18446744073709551616").is_err());
+        assert!(xs("integer", "1.0").is_err());
+        assert!(xs("boolean", " true ").is_ok());
+        assert!(xs("NMTOKENS", "").is_err());
+        assert!(xs("language", "en-US").is_ok());
+        assert!(xs("QName", "undeclared:name").is_err());
+        assert!(xs("anySimpleType", "anything").is_ok());
+    }
+}
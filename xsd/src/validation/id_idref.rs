@@ -0,0 +1,260 @@
+//! Validation Rule: Validation Root Valid (ID/IDREF) (§3.3.4.5)
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt,
+};
+
+use super::{Value, attribute_path, datatype::MemberTypes, node_path, report::ValidationReport};
+use crate::{
+    SchemaComponentTable, SimpleTypeDefinition, TypeDefinition, builtins::XS_NAMESPACE,
+    components::Named, simple_type_def::Variety, xstypes::QName,
+};
+use roxmltree::{Attribute, Node, NodeId};
+
+/// A violation of Validation Root Valid (ID/IDREF) (§3.3.4.5)
+#[derive(Clone, Debug)]
+pub struct IdIdrefViolation {
+    /// The offending ID or IDREF value
+    pub value: String,
+    pub kind: IdIdrefViolationKind,
+    /// The path of the offending node, e.g. `/order/item[2]/@id`
+    pub path: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum IdIdrefViolationKind {
+    /// The ID is also used at the given path
+    DuplicateId { other: String },
+    /// No element has the ID referenced by an IDREF
+    UnresolvedIdref,
+}
+
+impl IdIdrefViolation {
+    /// Describes the violation, without the path
+    pub(super) fn message(&self) -> String {
+        match &self.kind {
+            IdIdrefViolationKind::DuplicateId { other } => {
+                format!(
+                    "duplicate ID {:?}, which is also used at {other}",
+                    self.value
+                )
+            }
+            IdIdrefViolationKind::UnresolvedIdref => {
+                format!("IDREF {:?} does not refer to any ID", self.value)
+            }
+        }
+    }
+}
+
+impl fmt::Display for IdIdrefViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message())
+    }
+}
+
+/// An ID or IDREF value, and the item it occurs on
+#[derive(Debug)]
+struct Occurrence {
+    value: String,
+    /// The element, or the element owning the attribute, the value occurs on
+    node: NodeId,
+    /// Whether the value occurs on the attribute starting at `position`
+    is_attribute: bool,
+    /// The position of the item in the document, as items are not assessed in document order
+    position: usize,
+}
+
+impl Occurrence {
+    /// Returns the path of the item, which is only built once a violation is reported
+    fn path(&self, document: &roxmltree::Document) -> String {
+        let e = document.get_node(self.node).unwrap();
+        match e
+            .attributes()
+            .find(|a| self.is_attribute && a.range().start == self.position)
+        {
+            Some(a) => attribute_path(&e, &a),
+            None => node_path(e),
+        }
+    }
+}
+
+/// The ID and IDREF values of the element and attribute information items assessed below the
+/// ·validation root·. This is the information the [ID/IDREF table] (§3.17.5.2) is constructed
+/// from.
+#[derive(Debug, Default)]
+pub(super) struct IdIdrefTable {
+    ids: Vec<Occurrence>,
+    idrefs: Vec<Occurrence>,
+}
+
+impl IdIdrefTable {
+    /// Records the ID and IDREF values of element `e`, whose ·actual value· with respect to `t`
+    /// is `value`, validated by `member_types`
+    pub(super) fn record_element(
+        &mut self,
+        e: &Node,
+        t: &SimpleTypeDefinition,
+        value: &Value,
+        member_types: &MemberTypes,
+        components: &SchemaComponentTable,
+    ) {
+        self.record(e, None, t, value, member_types, components);
+    }
+
+    /// Records the ID and IDREF values of attribute `a` of element `e`, whose ·actual value· with
+    /// respect to `t` is `value`, validated by `member_types`
+    pub(super) fn record_attribute(
+        &mut self,
+        e: &Node,
+        a: &Attribute,
+        t: &SimpleTypeDefinition,
+        value: &Value,
+        member_types: &MemberTypes,
+        components: &SchemaComponentTable,
+    ) {
+        self.record(e, Some(a), t, value, member_types, components);
+    }
+
+    /// Records the ID and IDREF values of element `e`, or of its attribute `a` if given
+    fn record(
+        &mut self,
+        e: &Node,
+        a: Option<&Attribute>,
+        t: &SimpleTypeDefinition,
+        value: &Value,
+        member_types: &MemberTypes,
+        components: &SchemaComponentTable,
+    ) {
+        let position = a.map_or(e.range().start, |a| a.range().start);
+        for_each_id_idref(t, value, member_types, components, &mut |kind, value| {
+            let occurrence = Occurrence {
+                value: value.to_string(),
+                node: e.id(),
+                is_attribute: a.is_some(),
+                position,
+            };
+            match kind {
+                IdIdrefKind::Id => self.ids.push(occurrence),
+                IdIdrefKind::Idref => self.idrefs.push(occurrence),
+            }
+        });
+    }
+
+    /// Checks the recorded values once the whole tree below the ·validation root· `e` has been
+    /// assessed, adding the violations to `report`
+    pub(super) fn report(mut self, e: &Node, report: &mut ValidationReport) {
+        let document = e.document();
+        self.ids.sort_by_key(|id| id.position);
+        self.idrefs.sort_by_key(|idref| idref.position);
+        let mut violations = Vec::new();
+
+        // The first occurrence of each ID, which later occurrences are reported as duplicates of
+        let mut ids: HashMap<&str, &Occurrence> = HashMap::with_capacity(self.ids.len());
+        for id in &self.ids {
+            // 2 There is no ·ID/IDREF binding· in E.[ID/IDREF table] whose [binding] has more
+            //   than one member.
+            match ids.entry(id.value.as_str()) {
+                Entry::Occupied(other) => {
+                    let kind = IdIdrefViolationKind::DuplicateId {
+                        other: other.get().path(document),
+                    };
+                    violations.push((id, kind));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(id);
+                }
+            }
+        }
+
+        // 1 There is no ·ID/IDREF binding· in E.[ID/IDREF table] whose [binding] is the empty
+        //   set.
+        for idref in &self.idrefs {
+            if !ids.contains_key(idref.value.as_str()) {
+                violations.push((idref, IdIdrefViolationKind::UnresolvedIdref));
+            }
+        }
+
+        violations.sort_by_key(|(occurrence, _)| occurrence.position);
+        for (occurrence, kind) in violations {
+            let violation = IdIdrefViolation {
+                value: occurrence.value.clone(),
+                kind,
+                path: occurrence.path(document),
+            };
+            let code = match violation.kind {
+                IdIdrefViolationKind::UnresolvedIdref => "cvc-id.1",
+                IdIdrefViolationKind::DuplicateId { .. } => "cvc-id.2",
+            };
+            report.push(
+                e,
+                occurrence.position,
+                violation.path.clone(),
+                code,
+                violation.message(),
+            );
+            report.id_idref_violations.push(violation);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum IdIdrefKind {
+    Id,
+    Idref,
+}
+
+/// Calls `f` with each ID and IDREF value contained in `value`, the ·actual value· of an item
+/// with respect to `t`, which was validated by the union members `member_types`
+pub(super) fn for_each_id_idref(
+    t: &SimpleTypeDefinition,
+    value: &Value,
+    member_types: &MemberTypes,
+    components: &SchemaComponentTable,
+    f: &mut dyn FnMut(IdIdrefKind, &str),
+) {
+    match (t.variety, value, member_types) {
+        (Some(Variety::Atomic), Value::String(value), _) => {
+            if derived_from_builtin(t, "ID", components) {
+                f(IdIdrefKind::Id, value);
+            } else if derived_from_builtin(t, "IDREF", components) {
+                f(IdIdrefKind::Idref, value);
+            }
+        }
+        // List types such as IDREFS contribute each of their items
+        (Some(Variety::List), Value::List(items), MemberTypes::List(item_member_types)) => {
+            let item_type = t.item_type_definition.unwrap().get(components);
+            for (item, member_types) in items.iter().zip(item_member_types) {
+                for_each_id_idref(item_type, item, member_types, components, f);
+            }
+        }
+        // The value of a union contributes the IDs and IDREFs of the member type which
+        // validated it
+        (Some(Variety::Union), _, MemberTypes::Union(member, member_types)) => {
+            for_each_id_idref(member.get(components), value, member_types, components, f);
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether `t` is, or is derived from, the built-in simple type definition with the given
+/// local name
+fn derived_from_builtin(
+    t: &SimpleTypeDefinition,
+    local_name: &str,
+    components: &SchemaComponentTable,
+) -> bool {
+    let name = QName::with_namespace(XS_NAMESPACE, local_name);
+    let mut current = t;
+    loop {
+        if current.is_builtin && current.name() == Some(name.clone()) {
+            return true;
+        }
+        // The chain of simple base types ends with xs:anySimpleType, whose base type is
+        // xs:anyType
+        match current.base_type_definition {
+            TypeDefinition::Simple(base) => current = base.get(components),
+            TypeDefinition::Complex(_) => return false,
+        }
+    }
+}
@@ -0,0 +1,516 @@
+//! Validation Rule: Identity-constraint Satisfied (§3.11.4)
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use super::{Psvi, Validity, Value, attribute_name, node_path, report::ValidationReport};
+use crate::{
+    ElementDeclaration, IdentityConstraintDefinition, Ref, SchemaComponentTable, components::Named,
+    identity_constraint_def::IdentityConstraintCategory, xpath::FieldNode, xstypes::QName,
+};
+use roxmltree::Node;
+
+/// A violation of Identity-constraint Satisfied (§3.11.4)
+#[derive(Clone, Debug)]
+pub struct IdentityConstraintViolation {
+    /// The name of the violated identity-constraint definition
+    pub constraint: QName,
+    pub kind: IdentityConstraintViolationKind,
+    /// The path of the offending node, e.g. `/order/item[2]/@id`
+    pub path: String,
+    /// The position of the offending node in the document
+    position: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum IdentityConstraintViolationKind {
+    /// A field selects more than one node
+    MultipleFieldNodes,
+    /// A field selects an element which does not have simple content
+    ComplexFieldNode,
+    /// A node selected by a key lacks a value for one of the fields
+    MissingKeyField,
+    /// A field of a key selects an element which was assessed as valid by reference to a
+    /// nillable element declaration
+    NillableKeyField,
+    /// The key-sequence of the node equals that of another node, at the given path
+    Duplicate { other: String },
+    /// No key-sequence of the referenced key equals that of the node
+    UnresolvedKeyref { key_sequence: Vec<String> },
+}
+
+impl IdentityConstraintViolationKind {
+    /// Returns the violated clause of Identity-constraint Satisfied (§3.11.4), for a violation of
+    /// a constraint of the given category
+    pub(super) fn code(&self, category: &IdentityConstraintCategory) -> &'static str {
+        match self {
+            Self::MultipleFieldNodes | Self::ComplexFieldNode => "cvc-identity-constraint.3",
+            Self::MissingKeyField => "cvc-identity-constraint.4.2.1",
+            Self::NillableKeyField => "cvc-identity-constraint.4.2.3",
+            Self::Duplicate { .. } => match category {
+                IdentityConstraintCategory::Key => "cvc-identity-constraint.4.2.2",
+                _ => "cvc-identity-constraint.4.1",
+            },
+            Self::UnresolvedKeyref { .. } => "cvc-identity-constraint.4.3",
+        }
+    }
+
+    /// Describes the violation of the identity-constraint definition named `constraint`
+    pub(super) fn message(&self, constraint: &QName) -> String {
+        match self {
+            Self::MultipleFieldNodes => format!("a field of {constraint} selects multiple nodes"),
+            Self::ComplexFieldNode => {
+                format!("a field of {constraint} selects an element with element children")
+            }
+            Self::MissingKeyField => format!("missing a field of key {constraint}"),
+            Self::NillableKeyField => {
+                format!(
+                    "a field of key {constraint} selects an element with a nillable declaration"
+                )
+            }
+            Self::Duplicate { other } => {
+                format!("duplicate value for {constraint}, which is also used at {other}")
+            }
+            Self::UnresolvedKeyref { key_sequence } => format!(
+                "keyref {constraint} refers to ({}), which is not a value of the referenced key",
+                key_sequence.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for IdentityConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind.message(&self.constraint))
+    }
+}
+
+/// Checks all {identity-constraint definitions} of `d` for `e` as in clause 6 of Element Locally
+/// Valid (Element) (§3.3.4.3), adding the violations to `report`. The descendants of `e` must
+/// have been assessed already, as the fields are evaluated to their [schema actual value].
+pub(super) fn identity_constraints_satisfied(
+    e: &Node,
+    d: &ElementDeclaration,
+    components: &SchemaComponentTable,
+    report: &mut ValidationReport,
+) -> bool {
+    let violations = check_identity_constraints(e, d, &report.psvi, components);
+    for violation in &violations {
+        let category = &d
+            .identity_constraint_definitions
+            .iter()
+            .map(|icd| icd.get(components))
+            .find(|icd| icd.name().as_ref() == Some(&violation.constraint))
+            .unwrap()
+            .identity_constraint_category;
+        report.push(
+            e,
+            violation.position,
+            violation.path.clone(),
+            violation.kind.code(category),
+            violation.kind.message(&violation.constraint),
+        );
+    }
+    violations.is_empty()
+}
+
+/// A member of a ·key-sequence·: the [schema actual value] of a field node. Members are equal if
+/// their actual values are, as defined by Equality in XML Schema: Datatypes.
+#[derive(Clone, Debug)]
+pub(super) struct KeyValue {
+    pub(super) actual: Value,
+    /// The [schema normalized value], for describing the key-sequence
+    pub(super) normalized: String,
+}
+
+impl PartialEq for KeyValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.actual == other.actual
+    }
+}
+
+impl Eq for KeyValue {}
+
+impl Hash for KeyValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.actual.hash(state);
+    }
+}
+
+/// The key-sequences of the qualified node set of an identity-constraint, together with the
+/// target nodes they belong to
+type NodeTable<'a, 'input> = Vec<(Vec<KeyValue>, Node<'a, 'input>)>;
+
+/// Checks all {identity-constraint definitions} of `d` for `e`, returning the violations. `psvi`
+/// holds the properties of `e` and its descendants after their assessment.
+pub fn check_identity_constraints(
+    e: &Node,
+    d: &ElementDeclaration,
+    psvi: &Psvi,
+    components: &SchemaComponentTable,
+) -> Vec<IdentityConstraintViolation> {
+    let mut violations = Vec::new();
+    for &icd_ref in &d.identity_constraint_definitions {
+        let icd = icd_ref.get(components);
+        let table = node_table(*e, icd, psvi, components, &mut |node, kind| {
+            violations.push(IdentityConstraintViolation {
+                constraint: icd.name().unwrap(),
+                kind,
+                path: node_path(node),
+                position: node.range().start,
+            });
+        });
+        match icd.identity_constraint_category {
+            // 4.1 If the {identity-constraint category} is unique, then no two members of the
+            //   ·qualified node set· have ·key-sequences· whose members are pairwise equal, as
+            //   defined by Equality in XML Schema: Datatypes.
+            IdentityConstraintCategory::Unique => check_duplicates(icd, &table, &mut violations),
+            // 4.2 If the {identity-constraint category} is key, then all of the following are
+            //   true:
+            //   4.2.1 The ·target node set· and the ·qualified node set· are equal, that is,
+            //     every member of the ·target node set· is also a member of the ·qualified node
+            //     set· and vice versa.
+            //   (checked by node_table())
+            //   4.2.2 No two members of the ·qualified node set· have ·key-sequences· whose
+            //     members are pairwise equal, as defined by Equality in XML Schema: Datatypes.
+            //   4.2.3 No element member of the ·key-sequence· of any member of the ·qualified
+            //     node set· was assessed as ·valid· by reference to an element declaration whose
+            //     {nillable} is true.
+            //   (checked by node_table())
+            IdentityConstraintCategory::Key => check_duplicates(icd, &table, &mut violations),
+            // 4.3 If the {identity-constraint category} is keyref, then for each member of the
+            //   ·qualified node set· (call this the keyref member), there is a ·node table·
+            //   associated with the {referenced key} in the [identity-constraint table] of E
+            //   which contains an entry whose ·key-sequence· is equal to the keyref member's
+            //   ·key-sequence· member for member, as defined by Equality in XML Schema:
+            //   Datatypes.
+            IdentityConstraintCategory::KeyRef => {
+                let Some(referenced_key) = icd.referenced_key else {
+                    continue;
+                };
+                let key_table = identity_constraint_table(*e, d, referenced_key, psvi, components);
+                for (key_sequence, node) in table {
+                    if !key_table.contains_key(&key_sequence) {
+                        violations.push(IdentityConstraintViolation {
+                            constraint: icd.name().unwrap(),
+                            kind: IdentityConstraintViolationKind::UnresolvedKeyref {
+                                key_sequence: key_sequence
+                                    .into_iter()
+                                    .map(|value| value.normalized)
+                                    .collect(),
+                            },
+                            path: node_path(node),
+                            position: node.range().start,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    violations
+}
+
+/// Evaluates the {selector} and {fields} of `icd` with `e` as the context node, returning the
+/// ·node table· of the ·qualified node set·. Violations of clauses 3, 4.2.1 and 4.2.3 are passed
+/// to `violation` together with the offending node.
+fn node_table<'a, 'input>(
+    e: Node<'a, 'input>,
+    icd: &IdentityConstraintDefinition,
+    psvi: &Psvi,
+    components: &SchemaComponentTable,
+    violation: &mut dyn FnMut(Node<'a, 'input>, IdentityConstraintViolationKind),
+) -> NodeTable<'a, 'input> {
+    let is_key = matches!(
+        icd.identity_constraint_category,
+        IdentityConstraintCategory::Key
+    );
+    let mut table = Vec::new();
+    // 1 The {selector}, with the element information item E as the context node, evaluates to a
+    //   sequence of element nodes. [Definition:] Call this sequence the target node set.
+    'targets: for target in icd.selector_path.select(e) {
+        // 2 Each node in the ·target node set· is either the context node or an element node
+        //   among its descendants. [...] [Definition:] Call the subset of the ·target node set·
+        //   for which all the {fields} evaluate to a node with a non-·absent· [schema actual
+        //   value] the qualified node set.
+        let mut key_sequence = Vec::with_capacity(icd.field_paths.len());
+        for field in &icd.field_paths {
+            let mut selected = field.select_fields(target);
+            // 3 For each node in the ·target node set· all of the {fields}, with that node as the
+            //   context node, evaluate to either an empty sequence or a sequence with exactly one
+            //   member, which is either an element or attribute node with a simple type.
+            if selected.len() > 1 {
+                violation(target, IdentityConstraintViolationKind::MultipleFieldNodes);
+                continue 'targets;
+            }
+            let value = match selected.pop() {
+                Some(FieldNode::Attribute(element, attribute)) => psvi
+                    .attribute(element.id(), &attribute_name(&attribute))
+                    .and_then(|psvi| {
+                        Some(KeyValue {
+                            actual: psvi.schema_actual_value.clone()?,
+                            normalized: psvi.schema_normalized_value.clone()?,
+                        })
+                    }),
+                Some(FieldNode::Element(element)) => {
+                    if element.children().any(|c| c.is_element()) {
+                        violation(element, IdentityConstraintViolationKind::ComplexFieldNode);
+                        continue 'targets;
+                    }
+                    let element_psvi = psvi.element(element.id());
+                    // 4.2.3 (see check_identity_constraints())
+                    if is_key
+                        && let Some(element_psvi) = element_psvi
+                        && element_psvi.validity == Validity::Valid
+                        && element_psvi
+                            .element_declaration
+                            .is_some_and(|d| d.get(components).nillable)
+                    {
+                        violation(element, IdentityConstraintViolationKind::NillableKeyField);
+                        continue 'targets;
+                    }
+                    element_psvi.and_then(|psvi| {
+                        Some(KeyValue {
+                            actual: psvi.schema_actual_value.clone()?,
+                            normalized: psvi.schema_normalized_value.clone()?,
+                        })
+                    })
+                }
+                None => None,
+            };
+            // Nodes whose fields evaluate to an ·absent· [schema actual value] are not in the
+            // ·qualified node set·
+            let Some(value) = value else {
+                if is_key {
+                    violation(target, IdentityConstraintViolationKind::MissingKeyField);
+                }
+                continue 'targets;
+            };
+            key_sequence.push(value);
+        }
+        table.push((key_sequence, target));
+    }
+    table
+}
+
+fn check_duplicates(
+    icd: &IdentityConstraintDefinition,
+    table: &NodeTable,
+    violations: &mut Vec<IdentityConstraintViolation>,
+) {
+    // The first node with each key-sequence, which later nodes are reported as duplicates of
+    let mut first = HashMap::with_capacity(table.len());
+    for (key_sequence, node) in table {
+        match first.entry(key_sequence) {
+            Entry::Occupied(other) => violations.push(IdentityConstraintViolation {
+                constraint: icd.name().unwrap(),
+                kind: IdentityConstraintViolationKind::Duplicate {
+                    other: node_path(*other.get()),
+                },
+                path: node_path(*node),
+                position: node.range().start,
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(*node);
+            }
+        }
+    }
+}
+
+/// The ·node table· associated with `key` in the [identity-constraint table] of `e` (§3.11.5):
+/// the entries of `e`'s own node table, if `d` declares `key`, together with those propagated
+/// from the descendants of `e` whose ·governing element declaration· declares `key`.
+fn identity_constraint_table<'a, 'input>(
+    e: Node<'a, 'input>,
+    d: &ElementDeclaration,
+    key: Ref<IdentityConstraintDefinition>,
+    psvi: &Psvi,
+    components: &SchemaComponentTable,
+) -> HashMap<Vec<KeyValue>, Node<'a, 'input>> {
+    let icd = key.get(components);
+    // Violations of the referenced key are reported where it is declared
+    let ignore = &mut |_, _| {};
+
+    // The entries of E's own node table take precedence
+    let mut table = HashMap::new();
+    if d.identity_constraint_definitions.contains(&key) {
+        for (key_sequence, node) in node_table(e, icd, psvi, components, ignore) {
+            table.entry(key_sequence).or_insert(node);
+        }
+    }
+
+    // [Definition:] the ·node table· [...] contains the entries of the node tables of all the
+    // descendants of E, except those whose ·key-sequence· is the same as that of a different
+    // node in another descendant's table (a ·duplicate·), and those already in E's own table.
+    // Conflicting key-sequences are mapped to `None`.
+    let mut propagated = HashMap::new();
+    for descendant in e.descendants().skip(1).filter(Node::is_element) {
+        let declares_key = psvi
+            .element(descendant.id())
+            .and_then(|psvi| psvi.element_declaration)
+            .is_some_and(|d| {
+                d.get(components)
+                    .identity_constraint_definitions
+                    .contains(&key)
+            });
+        if !declares_key {
+            continue;
+        }
+        for (key_sequence, node) in node_table(descendant, icd, psvi, components, ignore) {
+            match propagated.entry(key_sequence) {
+                Entry::Occupied(mut other) if *other.get() != Some(node) => {
+                    other.insert(None);
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(Some(node));
+                }
+            }
+        }
+    }
+    for (key_sequence, node) in propagated {
+        if let Some(node) = node {
+            table.entry(key_sequence).or_insert(node);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdentityConstraintViolationKind;
+    use roxmltree::Document;
+
+    #[test]
+    fn keys_and_keyrefs() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="library">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element ref="authors"/>
+                    <xs:element name="book" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:attribute name="isbn" type="xs:decimal"/>
+                        <xs:attribute name="author" type="xs:string"/>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+                <xs:key name="isbn">
+                  <xs:selector xpath="book"/>
+                  <xs:field xpath="@isbn"/>
+                </xs:key>
+                <xs:keyref name="authorRef" refer="authorKey">
+                  <xs:selector xpath="book"/>
+                  <xs:field xpath="@author"/>
+                </xs:keyref>
+              </xs:element>
+              <xs:element name="authors">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="author" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:attribute name="id" type="xs:string"/>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+                <xs:key name="authorKey">
+                  <xs:selector xpath="author"/>
+                  <xs:field xpath="@id"/>
+                </xs:key>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        let d = schema.find_element_by_name(None, "library", &components);
+
+        let instance = Document::parse(
+            r#"
+            <library>
+              <authors><author id="a1"/><author id="a2"/></authors>
+              <book isbn="1" author="a1"/>
+              <book isbn="1.0" author=" a1 "/>
+              <book author="a2"/>
+            </library>
+            "#,
+        )
+        .unwrap();
+        // The key-sequences are compared by the actual values of the fields, i.e. as decimals
+        // and strings respectively
+        let e = instance.root_element();
+        let report = crate::validation::element_locally_valid_element(&e, d, &components);
+        let violations = super::check_identity_constraints(
+            &e,
+            d.unwrap().get(&components),
+            &report.psvi,
+            &components,
+        );
+        let violations = violations
+            .iter()
+            .map(|v| (v.constraint.local_name(), &v.kind, v.path.as_str()))
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            violations.as_slice(),
+            [
+                ("isbn", IdentityConstraintViolationKind::MissingKeyField, "/library/book[3]"),
+                ("isbn", IdentityConstraintViolationKind::Duplicate { other }, "/library/book[2]"),
+                ("authorRef", IdentityConstraintViolationKind::UnresolvedKeyref { key_sequence }, "/library/book[2]"),
+            ] if other == "/library/book[1]" && key_sequence == &[" a1 "]
+        ));
+    }
+
+    #[test]
+    fn nillable_key_fields() {
+        let (schema, components) = crate::testing::read_schema(
+            r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="people">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="person" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:sequence>
+                          <xs:element name="id" type="xs:int" nillable="true"/>
+                        </xs:sequence>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+                <xs:key name="id">
+                  <xs:selector xpath="person"/>
+                  <xs:field xpath="id"/>
+                </xs:key>
+              </xs:element>
+            </xs:schema>
+            "#,
+        )
+        .unwrap();
+        let instance = Document::parse(
+            r#"<people><person><id>1</id></person><person><id>2</id></person></people>"#,
+        )
+        .unwrap();
+        let report = crate::validation::element_locally_valid_element(
+            &instance.root_element(),
+            schema.find_element_by_name(None, "people", &components),
+            &components,
+        );
+        let codes = report
+            .violations
+            .iter()
+            .map(|v| (v.code, v.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            [
+                ("cvc-identity-constraint.4.2.3", "/people/person[1]/id[1]"),
+                ("cvc-identity-constraint.4.2.3", "/people/person[2]/id[1]"),
+            ]
+        );
+    }
+}
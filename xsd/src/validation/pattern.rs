@@ -0,0 +1,487 @@
+//! Regular expressions of the pattern facet (pt. 2, §G), translated to the syntax of the `regex`
+//! crate.
+//!
+//! The translation keeps the structure of the expression and only rewrites the constructs whose
+//! meaning differs between the two dialects:
+//! - XSD expressions are implicitly anchored at both ends, and `^` and `$` are ordinary characters
+//! - `.` matches any character except newline and carriage return
+//! - the multi-character escapes `\s`, `\i`, `\c`, `\w` and their complements have XML-specific
+//!   definitions
+//! - block escapes (`\p{IsBasicLatin}`) are expanded to their code point ranges
+//! - character class subtraction (`[a-z-[aeiou]]`) becomes the `--` class operator
+
+use regex::Regex;
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternError {
+    /// Character offset of the error in the pattern
+    pub position: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// XML 1.1 / XML 1.0 (fifth edition) NameStartChar, without the class brackets
+const NAME_START_CHARS: &str = r":A-Z_a-z\x{C0}-\x{D6}\x{D8}-\x{F6}\x{F8}-\x{2FF}\x{370}-\x{37D}\x{37F}-\x{1FFF}\x{200C}-\x{200D}\x{2070}-\x{218F}\x{2C00}-\x{2FEF}\x{3001}-\x{D7FF}\x{F900}-\x{FDCF}\x{FDF0}-\x{FFFD}\x{10000}-\x{EFFFF}";
+/// The NameChars that are not NameStartChars, without the class brackets
+const NAME_CHARS: &str = r"\-.0-9\x{B7}\x{300}-\x{36F}\x{203F}-\x{2040}";
+
+/// Unicode blocks recognized in block escapes, with the spaces removed from their names (pt. 2,
+/// §G.4.2.4). Blocks made up of more than one range are listed once per range.
+const BLOCKS: &[(&str, u32, u32)] = &[
+    ("BasicLatin", 0x0000, 0x007F),
+    ("Latin-1Supplement", 0x0080, 0x00FF),
+    ("LatinExtended-A", 0x0100, 0x017F),
+    ("LatinExtended-B", 0x0180, 0x024F),
+    ("IPAExtensions", 0x0250, 0x02AF),
+    ("SpacingModifierLetters", 0x02B0, 0x02FF),
+    ("CombiningDiacriticalMarks", 0x0300, 0x036F),
+    ("Greek", 0x0370, 0x03FF),
+    ("GreekandCoptic", 0x0370, 0x03FF),
+    ("Cyrillic", 0x0400, 0x04FF),
+    ("CyrillicSupplement", 0x0500, 0x052F),
+    ("Armenian", 0x0530, 0x058F),
+    ("Hebrew", 0x0590, 0x05FF),
+    ("Arabic", 0x0600, 0x06FF),
+    ("Syriac", 0x0700, 0x074F),
+    ("Thaana", 0x0780, 0x07BF),
+    ("Devanagari", 0x0900, 0x097F),
+    ("Bengali", 0x0980, 0x09FF),
+    ("Gurmukhi", 0x0A00, 0x0A7F),
+    ("Gujarati", 0x0A80, 0x0AFF),
+    ("Oriya", 0x0B00, 0x0B7F),
+    ("Tamil", 0x0B80, 0x0BFF),
+    ("Telugu", 0x0C00, 0x0C7F),
+    ("Kannada", 0x0C80, 0x0CFF),
+    ("Malayalam", 0x0D00, 0x0D7F),
+    ("Sinhala", 0x0D80, 0x0DFF),
+    ("Thai", 0x0E00, 0x0E7F),
+    ("Lao", 0x0E80, 0x0EFF),
+    ("Tibetan", 0x0F00, 0x0FFF),
+    ("Myanmar", 0x1000, 0x109F),
+    ("Georgian", 0x10A0, 0x10FF),
+    ("HangulJamo", 0x1100, 0x11FF),
+    ("Ethiopic", 0x1200, 0x137F),
+    ("Cherokee", 0x13A0, 0x13FF),
+    ("UnifiedCanadianAboriginalSyllabics", 0x1400, 0x167F),
+    ("Ogham", 0x1680, 0x169F),
+    ("Runic", 0x16A0, 0x16FF),
+    ("Tagalog", 0x1700, 0x171F),
+    ("Hanunoo", 0x1720, 0x173F),
+    ("Buhid", 0x1740, 0x175F),
+    ("Tagbanwa", 0x1760, 0x177F),
+    ("Khmer", 0x1780, 0x17FF),
+    ("Mongolian", 0x1800, 0x18AF),
+    ("Limbu", 0x1900, 0x194F),
+    ("TaiLe", 0x1950, 0x197F),
+    ("KhmerSymbols", 0x19E0, 0x19FF),
+    ("PhoneticExtensions", 0x1D00, 0x1D7F),
+    ("LatinExtendedAdditional", 0x1E00, 0x1EFF),
+    ("GreekExtended", 0x1F00, 0x1FFF),
+    ("GeneralPunctuation", 0x2000, 0x206F),
+    ("SuperscriptsandSubscripts", 0x2070, 0x209F),
+    ("CurrencySymbols", 0x20A0, 0x20CF),
+    ("CombiningMarksforSymbols", 0x20D0, 0x20FF),
+    ("CombiningDiacriticalMarksforSymbols", 0x20D0, 0x20FF),
+    ("LetterlikeSymbols", 0x2100, 0x214F),
+    ("NumberForms", 0x2150, 0x218F),
+    ("Arrows", 0x2190, 0x21FF),
+    ("MathematicalOperators", 0x2200, 0x22FF),
+    ("MiscellaneousTechnical", 0x2300, 0x23FF),
+    ("ControlPictures", 0x2400, 0x243F),
+    ("OpticalCharacterRecognition", 0x2440, 0x245F),
+    ("EnclosedAlphanumerics", 0x2460, 0x24FF),
+    ("BoxDrawing", 0x2500, 0x257F),
+    ("BlockElements", 0x2580, 0x259F),
+    ("GeometricShapes", 0x25A0, 0x25FF),
+    ("MiscellaneousSymbols", 0x2600, 0x26FF),
+    ("Dingbats", 0x2700, 0x27BF),
+    ("MiscellaneousMathematicalSymbols-A", 0x27C0, 0x27EF),
+    ("SupplementalArrows-A", 0x27F0, 0x27FF),
+    ("BraillePatterns", 0x2800, 0x28FF),
+    ("SupplementalArrows-B", 0x2900, 0x297F),
+    ("MiscellaneousMathematicalSymbols-B", 0x2980, 0x29FF),
+    ("SupplementalMathematicalOperators", 0x2A00, 0x2AFF),
+    ("MiscellaneousSymbolsandArrows", 0x2B00, 0x2BFF),
+    ("CJKRadicalsSupplement", 0x2E80, 0x2EFF),
+    ("KangxiRadicals", 0x2F00, 0x2FDF),
+    ("IdeographicDescriptionCharacters", 0x2FF0, 0x2FFF),
+    ("CJKSymbolsandPunctuation", 0x3000, 0x303F),
+    ("Hiragana", 0x3040, 0x309F),
+    ("Katakana", 0x30A0, 0x30FF),
+    ("Bopomofo", 0x3100, 0x312F),
+    ("HangulCompatibilityJamo", 0x3130, 0x318F),
+    ("Kanbun", 0x3190, 0x319F),
+    ("BopomofoExtended", 0x31A0, 0x31BF),
+    ("KatakanaPhoneticExtensions", 0x31F0, 0x31FF),
+    ("EnclosedCJKLettersandMonths", 0x3200, 0x32FF),
+    ("CJKCompatibility", 0x3300, 0x33FF),
+    ("CJKUnifiedIdeographsExtensionA", 0x3400, 0x4DBF),
+    ("YijingHexagramSymbols", 0x4DC0, 0x4DFF),
+    ("CJKUnifiedIdeographs", 0x4E00, 0x9FFF),
+    ("YiSyllables", 0xA000, 0xA48F),
+    ("YiRadicals", 0xA490, 0xA4CF),
+    ("HangulSyllables", 0xAC00, 0xD7AF),
+    ("PrivateUse", 0xE000, 0xF8FF),
+    ("PrivateUse", 0xF0000, 0xFFFFF),
+    ("PrivateUse", 0x100000, 0x10FFFF),
+    ("PrivateUseArea", 0xE000, 0xF8FF),
+    ("CJKCompatibilityIdeographs", 0xF900, 0xFAFF),
+    ("AlphabeticPresentationForms", 0xFB00, 0xFB4F),
+    ("ArabicPresentationForms-A", 0xFB50, 0xFDFF),
+    ("VariationSelectors", 0xFE00, 0xFE0F),
+    ("CombiningHalfMarks", 0xFE20, 0xFE2F),
+    ("CJKCompatibilityForms", 0xFE30, 0xFE4F),
+    ("SmallFormVariants", 0xFE50, 0xFE6F),
+    ("ArabicPresentationForms-B", 0xFE70, 0xFEFF),
+    ("HalfwidthandFullwidthForms", 0xFF00, 0xFFEF),
+    ("Specials", 0xFFF0, 0xFFFF),
+    ("LinearBSyllabary", 0x10000, 0x1007F),
+    ("LinearBIdeograms", 0x10080, 0x100FF),
+    ("AegeanNumbers", 0x10100, 0x1013F),
+    ("OldItalic", 0x10300, 0x1032F),
+    ("Gothic", 0x10330, 0x1034F),
+    ("Ugaritic", 0x10380, 0x1039F),
+    ("Deseret", 0x10400, 0x1044F),
+    ("Shavian", 0x10450, 0x1047F),
+    ("Osmanya", 0x10480, 0x104AF),
+    ("CypriotSyllabary", 0x10800, 0x1083F),
+    ("ByzantineMusicalSymbols", 0x1D000, 0x1D0FF),
+    ("MusicalSymbols", 0x1D100, 0x1D1FF),
+    ("TaiXuanJingSymbols", 0x1D300, 0x1D35F),
+    ("MathematicalAlphanumericSymbols", 0x1D400, 0x1D7FF),
+    ("CJKUnifiedIdeographsExtensionB", 0x20000, 0x2A6DF),
+    ("CJKCompatibilityIdeographsSupplement", 0x2F800, 0x2FA1F),
+    ("Tags", 0xE0000, 0xE007F),
+    ("VariationSelectorsSupplement", 0xE0100, 0xE01EF),
+    ("SupplementaryPrivateUseArea-A", 0xF0000, 0xFFFFF),
+    ("SupplementaryPrivateUseArea-B", 0x100000, 0x10FFFF),
+    ("HighSurrogates", 0xD800, 0xDB7F),
+    ("HighPrivateUseSurrogates", 0xDB80, 0xDBFF),
+    ("LowSurrogates", 0xDC00, 0xDFFF),
+];
+
+/// The general categories allowed in category escapes (pt. 2, §G.4.2.4)
+const CATEGORIES: &[&str] = &[
+    "L", "Lu", "Ll", "Lt", "Lm", "Lo", "M", "Mn", "Mc", "Me", "N", "Nd", "Nl", "No", "P", "Pc",
+    "Pd", "Ps", "Pe", "Pi", "Pf", "Po", "Z", "Zs", "Zl", "Zp", "S", "Sm", "Sc", "Sk", "So", "C",
+    "Cc", "Cf", "Co", "Cn",
+];
+
+/// An atom of a character class: a single character, which can be the bound of a range, or a
+/// class expression in `regex` syntax
+enum ClassAtom {
+    Char(char),
+    Class(String),
+}
+
+struct Translator {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Translator {
+    fn error<T>(&self, reason: impl Into<String>) -> Result<T, PatternError> {
+        Err(PatternError {
+            position: self.position,
+            reason: reason.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.chars.get(self.position + 1).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn expression(&mut self) -> Result<String, PatternError> {
+        let mut output = String::new();
+        let mut depth = 0usize;
+        while let Some(c) = self.next() {
+            match c {
+                '\\' => match self.escape()? {
+                    ClassAtom::Char(c) => output.push_str(&regex::escape(&c.to_string())),
+                    ClassAtom::Class(class) => output.push_str(&class),
+                },
+                '[' => output.push_str(&self.class()?),
+                '.' => output.push_str(r"[^\n\r]"),
+                '(' => {
+                    if self.peek() == Some('?') {
+                        return self.error("'(?' is not allowed");
+                    }
+                    depth += 1;
+                    output.push_str("(?:");
+                }
+                ')' => {
+                    if depth == 0 {
+                        return self.error("unmatched ')'");
+                    }
+                    depth -= 1;
+                    output.push(')');
+                }
+                '{' => output.push_str(&self.quantity()?),
+                '|' | '?' | '*' | '+' => output.push(c),
+                ']' | '}' => return self.error(format!("unescaped '{c}'")),
+                // Including '^' and '$', which are not anchors in XSD regular expressions
+                c => output.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        if depth > 0 {
+            return self.error("unclosed '('");
+        }
+        Ok(output)
+    }
+
+    /// Translates a quantity `{n}`, `{n,}` or `{n,m}`, after the opening brace
+    fn quantity(&mut self) -> Result<String, PatternError> {
+        let mut quantity = String::from("{");
+        let mut seen_comma = false;
+        let mut digits = 0;
+        loop {
+            match self.next() {
+                Some('}') if digits > 0 => break,
+                Some(',') if !seen_comma && digits > 0 => {
+                    seen_comma = true;
+                    quantity.push(',');
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    digits += 1;
+                    quantity.push(c);
+                }
+                _ => return self.error("invalid quantifier"),
+            }
+        }
+        quantity.push('}');
+        Ok(quantity)
+    }
+
+    /// Translates a character class expression, after the opening bracket
+    fn class(&mut self) -> Result<String, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+        let mut items = String::new();
+        let mut is_first = true;
+        loop {
+            let Some(c) = self.next() else {
+                return self.error("unterminated character class");
+            };
+            let atom = match c {
+                ']' if !is_first => break,
+                ']' => return self.error("empty character class"),
+                '[' => return self.error("'[' must be escaped in a character class"),
+                '-' if !is_first && self.peek() == Some('[') => {
+                    // Character class subtraction, which must be the last part of the class
+                    self.position += 1;
+                    let subtracted = self.class()?;
+                    if self.next() != Some(']') {
+                        return self.error("expected ']' after character class subtraction");
+                    }
+                    let negated = if negated { "^" } else { "" };
+                    return Ok(format!("[[{negated}{items}]--{subtracted}]"));
+                }
+                '\\' => self.escape()?,
+                c => ClassAtom::Char(c),
+            };
+            is_first = false;
+            match atom {
+                ClassAtom::Char(start)
+                    if self.peek() == Some('-')
+                        && !matches!(self.peek_second(), None | Some(']' | '[')) =>
+                {
+                    self.position += 1;
+                    let end = match self.next() {
+                        Some('\\') => match self.escape()? {
+                            ClassAtom::Char(end) => end,
+                            ClassAtom::Class(_) => {
+                                return self.error("a range cannot end in a class escape");
+                            }
+                        },
+                        Some(end) => end,
+                        None => return self.error("unterminated character class"),
+                    };
+                    if end < start {
+                        return self.error("invalid character range");
+                    }
+                    items.push_str(&regex::escape(&start.to_string()));
+                    items.push('-');
+                    items.push_str(&regex::escape(&end.to_string()));
+                }
+                ClassAtom::Char(c) => items.push_str(&regex::escape(&c.to_string())),
+                ClassAtom::Class(class) => items.push_str(&class),
+            }
+        }
+        let negated = if negated { "^" } else { "" };
+        Ok(format!("[{negated}{items}]"))
+    }
+
+    /// Translates an escape, after the backslash
+    fn escape(&mut self) -> Result<ClassAtom, PatternError> {
+        let Some(c) = self.next() else {
+            return self.error("unterminated escape");
+        };
+        let class = match c {
+            // Single-character escapes
+            'n' => return Ok(ClassAtom::Char('\n')),
+            'r' => return Ok(ClassAtom::Char('\r')),
+            't' => return Ok(ClassAtom::Char('\t')),
+            '\\' | '|' | '.' | '?' | '*' | '+' | '(' | ')' | '{' | '}' | '-' | '[' | ']' | '^' => {
+                return Ok(ClassAtom::Char(c));
+            }
+            // Multi-character escapes
+            's' => r"[\x20\t\n\r]".to_string(),
+            'S' => r"[^\x20\t\n\r]".to_string(),
+            'i' => format!("[{NAME_START_CHARS}]"),
+            'I' => format!("[^{NAME_START_CHARS}]"),
+            'c' => format!("[{NAME_START_CHARS}{NAME_CHARS}]"),
+            'C' => format!("[^{NAME_START_CHARS}{NAME_CHARS}]"),
+            'd' => r"\p{Nd}".to_string(),
+            'D' => r"\P{Nd}".to_string(),
+            'w' => r"[^\p{P}\p{Z}\p{C}]".to_string(),
+            'W' => r"[\p{P}\p{Z}\p{C}]".to_string(),
+            // Category and block escapes
+            'p' | 'P' => self.property(c == 'P')?,
+            _ => return self.error(format!("invalid escape '\\{c}'")),
+        };
+        Ok(ClassAtom::Class(class))
+    }
+
+    /// Translates a category or block escape, after the `p` or `P`
+    fn property(&mut self, complement: bool) -> Result<String, PatternError> {
+        if self.next() != Some('{') {
+            return self.error("expected '{' after category escape");
+        }
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != '}') {
+            self.position += 1;
+        }
+        let name = self.chars[start..self.position].iter().collect::<String>();
+        if self.next() != Some('}') {
+            return self.error("unterminated category escape");
+        }
+        if let Some(block) = name.strip_prefix("Is") {
+            let ranges = BLOCKS
+                .iter()
+                .filter(|(name, _, _)| *name == block)
+                .map(|(_, start, end)| format!(r"\x{{{start:X}}}-\x{{{end:X}}}"))
+                .collect::<String>();
+            if ranges.is_empty() {
+                self.position = start;
+                return self.error(format!("unknown block {block:?}"));
+            }
+            let ranges = if block.ends_with("Surrogates") {
+                // Surrogate code points are not characters, so this class matches nothing
+                r"[^\x{0}-\x{10FFFF}]".to_string()
+            } else {
+                format!("[{ranges}]")
+            };
+            Ok(if complement {
+                format!("[^{ranges}]")
+            } else {
+                ranges
+            })
+        } else if CATEGORIES.contains(&name.as_str()) {
+            let p = if complement { 'P' } else { 'p' };
+            Ok(format!(r"\{p}{{{name}}}"))
+        } else {
+            self.position = start;
+            self.error(format!("unknown character category {name:?}"))
+        }
+    }
+}
+
+/// Translates an XSD regular expression to an anchored expression in `regex` syntax
+pub(crate) fn translate(pattern: &str) -> Result<String, PatternError> {
+    let expression = Translator {
+        chars: pattern.chars().collect(),
+        position: 0,
+    }
+    .expression()?;
+    Ok(format!(r"\A(?:{expression})\z"))
+}
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, Result<Regex, PatternError>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Calls `f` with the compiled XSD regular expression `pattern`. Patterns are compiled once per
+/// thread.
+fn with_regex<T>(pattern: &str, f: impl FnOnce(&Regex) -> T) -> Result<T, PatternError> {
+    CACHE.with_borrow_mut(|cache| {
+        let regex = cache.entry(pattern.to_string()).or_insert_with(|| {
+            let translated = translate(pattern)?;
+            Regex::new(&translated).map_err(|e| PatternError {
+                position: 0,
+                reason: e.to_string(),
+            })
+        });
+        regex.as_ref().map(f).map_err(Clone::clone)
+    })
+}
+
+/// Checks that `pattern` is a valid XSD regular expression (pt. 2, §G)
+pub(crate) fn compile_pattern(pattern: &str) -> Result<(), PatternError> {
+    with_regex(pattern, |_| ())
+}
+
+/// Returns whether `value` is in the set of strings denoted by the XSD regular expression
+/// `pattern`
+pub(crate) fn pattern_matches(pattern: &str, value: &str) -> Result<bool, PatternError> {
+    with_regex(pattern, |regex| regex.is_match(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pattern_matches;
+
+    #[test]
+    fn xsd_regex_semantics() {
+        let matches = |pattern, value| pattern_matches(pattern, value).unwrap();
+
+        // Implicitly anchored, with '^' and '$' as ordinary characters
+        assert!(matches("[0-9]+", "123"));
+        assert!(!matches("[0-9]+", "123a"));
+        assert!(matches("^a$", "^a$"));
+
+        // Multi-character escapes
+        assert!(matches(r"\i\c*", "xs:element"));
+        assert!(!matches(r"\i\c*", "1st"));
+        assert!(matches(r"\s\S", " x"));
+        assert!(!matches(".", "\n"));
+
+        // Class subtraction and negation
+        assert!(matches("[a-z-[aeiou]]+", "xyz"));
+        assert!(!matches("[a-z-[aeiou]]+", "xaz"));
+        assert!(matches("[^a-z-[0-9]]", "A"));
+        assert!(!matches("[^a-z-[0-9]]", "5"));
+        assert!(matches(r"[\i-[:]][\c-[:]]*", "ncname"));
+        assert!(!matches(r"[\i-[:]][\c-[:]]*", "q:name"));
+
+        // Category and block escapes
+        assert!(matches(r"\p{Lu}\P{Lu}", "Ab"));
+        assert!(matches(r"\p{IsBasicLatin}+", "abc"));
+        assert!(!matches(r"\p{IsBasicLatin}+", "äbc"));
+        assert!(!matches(r"\p{IsHighSurrogates}", "a"));
+
+        assert!(pattern_matches("(?i)a", "a").is_err());
+        assert!(pattern_matches("[a", "a").is_err());
+        assert!(pattern_matches(r"\p{IsUnknownBlock}", "a").is_err());
+    }
+}
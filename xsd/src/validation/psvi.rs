@@ -0,0 +1,189 @@
+//! The post-schema-validation infoset (PSVI) contributions of Schema-Validity Assessment
+//! (Element) (§3.3.5) and Schema-Validity Assessment (Attribute) (§3.2.5)
+
+use std::collections::HashMap;
+
+use super::{Value, attribute_name, is_builtin_xsi_attribute};
+use crate::{
+    AttributeDeclaration, ElementDeclaration, Ref, SimpleTypeDefinition, TypeDefinition,
+    xstypes::QName,
+};
+use roxmltree::{Attribute, Node, NodeId};
+
+/// [validity] of an element or attribute information item (§3.3.5.1, §3.2.5.1)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Validity {
+    /// The item was ·strictly assessed· and neither it nor any of its descendants is invalid
+    Valid,
+    /// The item was ·strictly assessed· and it, or one of its descendants, is invalid
+    Invalid,
+    /// The item was not ·strictly assessed·
+    #[default]
+    NotKnown,
+}
+
+/// [validation attempted] of an element or attribute information item (§3.3.5.1, §3.2.5.1)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidationAttempted {
+    /// The item and all of its descendants were ·strictly assessed·
+    Full,
+    /// Some, but not all, of the item and its descendants were ·strictly assessed·
+    Partial,
+    /// Neither the item nor any of its descendants were ·strictly assessed·
+    #[default]
+    None,
+}
+
+/// [schema specified] of an element or attribute information item (§3.3.5.5, §3.4.5.1)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchemaSpecified {
+    /// The item, or its value, is present in the instance
+    #[default]
+    Infoset,
+    /// The item was added, or its value was supplied, by the {value constraint} of its
+    /// declaration
+    Schema,
+}
+
+/// The PSVI properties of an element information item
+#[derive(Clone, Debug, Default)]
+pub struct ElementPsvi {
+    /// [element declaration]: the ·governing element declaration·, if any (§3.3.5.3)
+    pub element_declaration: Option<Ref<ElementDeclaration>>,
+    /// [type definition]: the ·governing type definition·, if any (§3.3.5.2)
+    pub type_definition: Option<TypeDefinition>,
+    pub validity: Validity,
+    pub validation_attempted: ValidationAttempted,
+    /// [nil]: whether the element is ·nilled· (§3.3.5.3)
+    pub nil: bool,
+    /// [schema specified]: `Schema` if the element was empty and its [schema normalized value]
+    /// is the {lexical form} of the value constraint of its declaration (§3.3.5.5)
+    pub schema_specified: SchemaSpecified,
+    /// [schema normalized value]: the ·normalized value· of an element with a simple type or
+    /// simple content, which may be the {lexical form} of its value constraint (§3.3.5.4)
+    pub schema_normalized_value: Option<String>,
+    /// [schema actual value]: the ·actual value· of the [schema normalized value] (§3.3.5.4)
+    pub schema_actual_value: Option<Value>,
+    /// [member type definition]: the member of a union type (or of the union simple content
+    /// type) which validated the [schema normalized value] (§3.3.5.2)
+    pub member_type_definition: Option<Ref<SimpleTypeDefinition>>,
+}
+
+/// The PSVI properties of an attribute information item
+#[derive(Clone, Debug, Default)]
+pub struct AttributePsvi {
+    /// [attribute declaration]: the ·governing attribute declaration·, if any (§3.2.5.3)
+    pub attribute_declaration: Option<Ref<AttributeDeclaration>>,
+    /// [type definition]: the {type definition} of the ·governing attribute declaration·, if any
+    /// (§3.2.5.2)
+    pub type_definition: Option<Ref<SimpleTypeDefinition>>,
+    pub validity: Validity,
+    pub validation_attempted: ValidationAttempted,
+    /// [schema specified]: `Schema` for a ·defaulted attribute· (§3.4.5.1)
+    pub schema_specified: SchemaSpecified,
+    /// [schema normalized value]: the ·normalized value· of the attribute (§3.2.5.4)
+    pub schema_normalized_value: Option<String>,
+    /// [schema actual value]: the ·actual value· of the [schema normalized value] (§3.2.5.4)
+    pub schema_actual_value: Option<Value>,
+    /// [member type definition]: the member of a union type which validated the [schema
+    /// normalized value] (§3.2.5.2)
+    pub member_type_definition: Option<Ref<SimpleTypeDefinition>>,
+}
+
+/// The PSVI of a validated document, i.e. the properties of the element and attribute
+/// information items which were assessed. Items are identified by the [`NodeId`] of their
+/// element, and attributes additionally by their expanded name.
+#[derive(Clone, Debug, Default)]
+pub struct Psvi {
+    elements: HashMap<NodeId, ElementPsvi>,
+    attributes: HashMap<(NodeId, QName), AttributePsvi>,
+    /// The names of the ·defaulted attributes· of each element, in the order of the {attribute
+    /// uses} of its type
+    defaulted_attributes: HashMap<NodeId, Vec<QName>>,
+}
+
+impl Psvi {
+    /// Returns the properties of element `e`, or `None` if it was not assessed
+    pub fn element(&self, e: NodeId) -> Option<&ElementPsvi> {
+        self.elements.get(&e)
+    }
+
+    /// Returns the properties of the attribute named `name` of element `e`, or `None` if it
+    /// was not assessed
+    pub fn attribute(&self, e: NodeId, name: &QName) -> Option<&AttributePsvi> {
+        self.attributes.get(&(e, name.clone()))
+    }
+
+    /// Returns the names and properties of the ·defaulted attributes· which were added to
+    /// element `e` (§3.4.5.1)
+    pub fn defaulted_attributes(
+        &self,
+        e: NodeId,
+    ) -> impl Iterator<Item = (&QName, &AttributePsvi)> {
+        self.defaulted_attributes
+            .get(&e)
+            .into_iter()
+            .flatten()
+            .map(move |name| (name, &self.attributes[&(e, name.clone())]))
+    }
+
+    pub(super) fn element_mut(&mut self, e: &Node) -> &mut ElementPsvi {
+        self.elements.entry(e.id()).or_default()
+    }
+
+    pub(super) fn attribute_mut(&mut self, e: &Node, a: &Attribute) -> &mut AttributePsvi {
+        self.attributes
+            .entry((e.id(), attribute_name(a)))
+            .or_default()
+    }
+
+    /// Adds a ·defaulted attribute· named `name` to element `e`
+    pub(super) fn defaulted_attribute_mut(&mut self, e: &Node, name: QName) -> &mut AttributePsvi {
+        self.defaulted_attributes
+            .entry(e.id())
+            .or_default()
+            .push(name.clone());
+        let psvi = self.attributes.entry((e.id(), name)).or_default();
+        psvi.schema_specified = SchemaSpecified::Schema;
+        psvi
+    }
+
+    /// Determines the [validation attempted] of element `e` from that of its [children] and
+    /// [attributes], once they have been assessed (§3.3.5.1). The attributes xsi:type, xsi:nil,
+    /// xsi:schemaLocation and xsi:noNamespaceSchemaLocation are governed by the built-in
+    /// attribute declarations and are thus considered fully assessed.
+    pub(super) fn validation_attempted(
+        &self,
+        e: &Node,
+        strictly_assessed: bool,
+    ) -> ValidationAttempted {
+        let children = e
+            .children()
+            .filter(|c| c.is_element())
+            .map(|c| self.element(c.id()).map(|p| p.validation_attempted));
+        let attributes = e
+            .attributes()
+            .filter(|a| !is_builtin_xsi_attribute(a))
+            .map(|a| {
+                self.attribute(e.id(), &attribute_name(&a))
+                    .map(|p| p.validation_attempted)
+            });
+        let mut items = children
+            .chain(attributes)
+            .map(|attempted| attempted.unwrap_or_default());
+        // 1 If it was ·strictly assessed· and neither its [children] nor its [attributes]
+        //   contains an information item (element or attribute respectively) whose [validation
+        //   attempted] is not full, then full;
+        // 2 If it was not ·strictly assessed· and neither its [children] nor its [attributes]
+        //   contains an information item (element or attribute respectively) whose [validation
+        //   attempted] is not none, then none;
+        // 3 otherwise partial.
+        if strictly_assessed && items.all(|a| a == ValidationAttempted::Full) {
+            ValidationAttempted::Full
+        } else if !strictly_assessed && items.all(|a| a == ValidationAttempted::None) {
+            ValidationAttempted::None
+        } else {
+            ValidationAttempted::Partial
+        }
+    }
+}
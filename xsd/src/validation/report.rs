@@ -0,0 +1,170 @@
+//! The outcome of validating an element information item
+
+use std::fmt;
+
+use super::{
+    attribute_path,
+    clauses::ClauseViolation,
+    id_idref::IdIdrefViolation,
+    node_path,
+    psvi::{Psvi, ValidationAttempted, Validity},
+};
+use roxmltree::{Attribute, Node, NodeId};
+
+/// A violated validation rule
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// The name of the validation rule and the violated clause, if any, e.g.
+    /// `cvc-complex-type.1.4` for clause 1.4 of Element Locally Valid (Complex Type) (§3.4.4.2)
+    pub code: &'static str,
+    pub message: String,
+    /// The path of the offending node, e.g. `/order/item[2]/@id`
+    pub path: String,
+    /// The line of the offending node, starting at 1
+    pub line: u32,
+    /// The column of the offending node, starting at 1
+    pub column: u32,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}: {}",
+            self.line, self.column, self.path, self.code, self.message
+        )
+    }
+}
+
+/// All violations found while validating an element information item and its descendants
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    /// The violations, in document order
+    pub violations: Vec<Violation>,
+    /// The properties of the assessed element and attribute information items
+    pub psvi: Psvi,
+    /// The violations of Validation Root Valid (ID/IDREF) (§3.3.4.5), in document order, which
+    /// are also among `violations`
+    pub id_idref_violations: Vec<IdIdrefViolation>,
+}
+
+impl ValidationReport {
+    /// Returns whether no validation rule was violated
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Returns the [validity] of element `e`, which is `NotKnown` unless `e` was ·strictly
+    /// assessed·
+    pub fn validity(&self, e: NodeId) -> Validity {
+        self.psvi
+            .element(e)
+            .map_or(Validity::NotKnown, |psvi| psvi.validity)
+    }
+
+    /// Returns a marker for the violations reported after this call, see
+    /// [`Self::record_assessment`]
+    pub(super) fn mark(&self) -> usize {
+        self.violations.len()
+    }
+
+    /// Records the [validity] and [validation attempted] of element `e`, which has been
+    /// ·strictly assessed· since `mark` was taken: `e` is invalid if a violation was reported for
+    /// it or any of its descendants since then.
+    pub(super) fn record_assessment(&mut self, e: &Node, mark: usize) {
+        let validity = self.validity_since(mark);
+        let validation_attempted = self.psvi.validation_attempted(e, true);
+        let psvi = self.psvi.element_mut(e);
+        psvi.validity = validity;
+        psvi.validation_attempted = validation_attempted;
+    }
+
+    /// Records the [validation attempted] of element `e`, which was not ·strictly assessed·, but
+    /// whose attributes and children have been assessed laxly
+    pub(super) fn record_lax_assessment(&mut self, e: &Node) {
+        let validation_attempted = self.psvi.validation_attempted(e, false);
+        let psvi = self.psvi.element_mut(e);
+        psvi.validity = Validity::NotKnown;
+        psvi.validation_attempted = validation_attempted;
+    }
+
+    /// Records the [validity] and [validation attempted] of attribute `a` of element `e`, which
+    /// has been ·strictly assessed· since `mark` was taken
+    pub(super) fn record_attribute_assessment(&mut self, e: &Node, a: &Attribute, mark: usize) {
+        let validity = self.validity_since(mark);
+        let psvi = self.psvi.attribute_mut(e, a);
+        psvi.validity = validity;
+        psvi.validation_attempted = ValidationAttempted::Full;
+    }
+
+    fn validity_since(&self, mark: usize) -> Validity {
+        if self.violations.len() > mark {
+            Validity::Invalid
+        } else {
+            Validity::Valid
+        }
+    }
+
+    /// Records a violation by element `e`
+    pub(super) fn element(&mut self, e: &Node, code: &'static str, message: impl Into<String>) {
+        self.push(e, e.range().start, node_path(*e), code, message.into());
+    }
+
+    /// Records the violation of a clause by element `e`
+    pub(super) fn element_violation(&mut self, e: &Node, violation: ClauseViolation) {
+        self.element(e, violation.code, violation.message);
+    }
+
+    /// Records the violation of a clause by attribute `a` of element `e`
+    pub(super) fn attribute_violation(
+        &mut self,
+        e: &Node,
+        a: &Attribute,
+        violation: ClauseViolation,
+    ) {
+        self.attribute(e, a, violation.code, violation.message);
+    }
+
+    /// Records a violation by attribute `a` of element `e`
+    pub(super) fn attribute(
+        &mut self,
+        e: &Node,
+        a: &Attribute,
+        code: &'static str,
+        message: impl Into<String>,
+    ) {
+        self.push(
+            e,
+            a.range().start,
+            attribute_path(e, a),
+            code,
+            message.into(),
+        );
+    }
+
+    /// Records a violation by the node with the given path, starting at byte `position` of the
+    /// document of `e`
+    pub(super) fn push(
+        &mut self,
+        e: &Node,
+        position: usize,
+        path: String,
+        code: &'static str,
+        message: String,
+    ) {
+        let position = e.document().text_pos_at(position);
+        self.violations.push(Violation {
+            code,
+            message,
+            path,
+            line: position.row,
+            column: position.col,
+        });
+    }
+
+    /// Orders the violations by their position in the document, as nodes are not assessed in
+    /// document order
+    pub(super) fn sort(&mut self) {
+        self.violations.sort_by_key(|v| (v.line, v.column));
+    }
+}
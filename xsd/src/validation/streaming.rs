@@ -0,0 +1,1416 @@
+//! Streaming validation of a document read as a sequence of [`XmlEvent`]s, e.g. from an
+//! [`EventReader`], for documents which are too large to be loaded into a tree.
+//!
+//! Only the open elements are kept in memory, each with the state of the automaton of its content
+//! model and, for elements with simple content, its character data. The exceptions are the ·node
+//! tables· of identity constraints and the ID values of the document, together with the IDREF
+//! values which precede their ID, which grow with the document, and the subtrees of the open
+//! elements whose type definition has assertions, which are evaluated on the whole subtree.
+//!
+//! The validation rules are those of [`element_locally_valid_element`](super::element_locally_valid_element),
+//! whose clauses are shared through the [`clauses`](super::clauses) module, except that:
+//! - no PSVI is produced;
+//! - violations are reported as soon as they are detected, which is once an element ends for the
+//!   violations concerning its content.
+
+use std::{collections::HashMap, io::BufRead};
+
+use super::{
+    Value, Violation,
+    assertion::assertions_satisfied,
+    clauses::{
+        AttributeAttribution, Attribution, Children, ClauseViolation, ContentModelMatcher,
+        WildcardAssessment, attribute_attribution, attribute_fixed_value_valid,
+        attribute_not_allowed_by_simple_type, attribute_use_fixed_value_valid,
+        declaration_not_abstract, default_valid_for_instance_type, defaulted_attribute_valid,
+        defaulted_attributes, element_only_content_valid, empty_content_valid, fixed_value_valid,
+        instance_specified_type_valid, missing_required_attributes, nilled, nilled_element_valid,
+        simple_content_children_valid, simple_type_children_valid, type_not_abstract,
+        wildcard_assessment,
+    },
+    conditionally_selected_type,
+    datatype::{MemberTypes, normalized_string_valid},
+    global_attribute_declaration, global_element_declaration,
+    id_idref::{IdIdrefKind, IdIdrefViolation, IdIdrefViolationKind, for_each_id_idref},
+    identity::KeyValue,
+    is_builtin_xsi_attribute_name, overrides,
+    report::ValidationReport,
+    type_definition_by_name,
+    value::{NamespaceContext, qname_value},
+};
+use crate::{
+    AttributeDeclaration, ComplexTypeDefinition, ElementDeclaration, SchemaComponentTable,
+    SimpleTypeDefinition, TypeDefinition,
+    assertion::NamespaceBinding,
+    builtins::XSI_NAMESPACE,
+    complex_type_def::{ContentType, ContentTypeVariety},
+    shared::ValueConstraintVariety,
+    state_machine::StateMachineCache,
+    wildcard::ProcessContents,
+    xstypes::QName,
+};
+use identity::IdentityTables;
+use roxmltree::TextPos;
+use subtree::Subtrees;
+
+mod identity;
+mod reader;
+mod subtree;
+
+pub use reader::{EventReader, ReadError};
+
+/// An event of a document, see [`EventReader`]
+#[derive(Clone, Debug)]
+pub enum XmlEvent {
+    StartElement(StartElement),
+    /// Character data of the current element, which may be split into several events
+    Text(String),
+    /// The end of the current element
+    EndElement,
+}
+
+/// The start tag of an element
+#[derive(Clone, Debug)]
+pub struct StartElement {
+    pub name: QName,
+    /// The attributes, without the namespace declarations
+    pub attributes: Vec<EventAttribute>,
+    /// The namespace declarations of the element. The default namespace has an empty prefix, and
+    /// is undeclared by binding it to the empty string.
+    pub namespaces: Vec<NamespaceBinding>,
+    /// The position of the `<` of the start tag
+    pub position: TextPos,
+}
+
+/// An attribute of a [`StartElement`]
+#[derive(Clone, Debug)]
+pub struct EventAttribute {
+    pub name: QName,
+    pub value: String,
+    pub position: TextPos,
+}
+
+/// The violations found so far, of which `pending` have not been taken yet
+#[derive(Default)]
+struct Violations {
+    pending: Vec<Violation>,
+    count: usize,
+}
+
+impl Violations {
+    fn push(&mut self, position: TextPos, path: String, code: &'static str, message: String) {
+        self.count += 1;
+        self.pending.push(Violation {
+            code,
+            message,
+            path,
+            line: position.row,
+            column: position.col,
+        });
+    }
+
+    fn clause(&mut self, position: TextPos, path: String, violation: ClauseViolation) {
+        self.push(position, path, violation.code, violation.message);
+    }
+}
+
+/// The namespace bindings in scope, in the order of their declaration
+struct InScopeNamespaces<'a>(&'a [NamespaceBinding]);
+
+impl NamespaceContext for InScopeNamespaces<'_> {
+    fn namespace_uri(&self, prefix: Option<&str>) -> Option<&str> {
+        // The innermost declaration applies, and an empty namespace name undeclares the default
+        // namespace
+        let prefix = prefix.unwrap_or_default();
+        self.0
+            .iter()
+            .rev()
+            .find(|binding| binding.prefix == prefix)
+            .map(|binding| binding.namespace.as_str())
+            .filter(|namespace| !namespace.is_empty())
+    }
+}
+
+/// An open element
+struct Frame<'c> {
+    path: String,
+    position: TextPos,
+    /// The number of child elements with each expanded name so far, for the paths of the children
+    child_counts: HashMap<QName, usize>,
+    /// The number of namespace bindings declared by the element
+    namespaces: usize,
+    /// The number of violations found before the element started
+    violations: usize,
+    children: Children,
+    /// The ·initial value· of the element, if it is needed once the element ends
+    initial_value: Option<String>,
+    /// The offset of the subtree of the element, if its assertions are evaluated once it ends
+    subtree: Option<usize>,
+    assessment: Assessment<'c>,
+}
+
+enum Assessment<'c> {
+    /// Neither the element nor its descendants are assessed
+    Skip,
+    /// The element is not ·strictly assessed·, but its attributes and children are assessed
+    /// laxly, i.e. with respect to their global declarations, if any
+    Lax,
+    Strict(Box<Strict<'c>>),
+}
+
+struct Strict<'c> {
+    /// The ·governing element declaration·, which is absent for an element matched by a wildcard
+    /// and governed by its ·instance-specified type definition·
+    declaration: Option<&'c ElementDeclaration>,
+    /// The ·governing type definition·
+    type_definition: TypeDefinition,
+    /// Whether the element has an ·instance-specified type definition· which is its ·governing
+    /// type definition·
+    instance_specified: bool,
+    nilled: bool,
+    content: Content<'c>,
+}
+
+/// The content of an element, as allowed by its ·governing type definition·
+enum Content<'c> {
+    /// The content is not assessed, as the type definition is abstract or the element is
+    /// ·nilled·
+    Unassessed,
+    Empty,
+    /// `simple_type` is the ·governing type definition· itself if `is_simple_type`, and the
+    /// simple type definition of its simple {content type} otherwise
+    Simple {
+        simple_type: &'c SimpleTypeDefinition,
+        is_simple_type: bool,
+    },
+    Complex(ComplexContent<'c>),
+}
+
+/// The content of an element with element-only or mixed content
+struct ComplexContent<'c> {
+    matcher: ContentModelMatcher<'c>,
+    /// Set after an element which cannot be attributed to the content model, as the remaining
+    /// elements cannot be attributed reliably
+    stopped: bool,
+    element_only: bool,
+}
+
+/// Validates a document from its [`XmlEvent`]s, with the global element declaration of its root
+/// element as the ·governing element declaration· of the ·validation root·
+pub struct StreamingValidator<'c> {
+    components: &'c SchemaComponentTable,
+    elements: Vec<Frame<'c>>,
+    /// The expanded names of the open elements
+    names: Vec<QName>,
+    /// The namespace bindings in scope, in the order of their declaration
+    namespaces: Vec<NamespaceBinding>,
+    /// The [schema actual values] of the attributes of the element which is starting, for the
+    /// identity constraints
+    attribute_values: Vec<(QName, KeyValue)>,
+    state_machines: StateMachineCache,
+    /// The path of the item with each ID value
+    ids: HashMap<String, String>,
+    /// The IDREF values which do not refer to an ID yet, with the paths and positions of their
+    /// items
+    idrefs: HashMap<String, Vec<(String, TextPos)>>,
+    identity: IdentityTables<'c>,
+    subtrees: Subtrees,
+    violations: Violations,
+}
+
+impl<'c> StreamingValidator<'c> {
+    pub fn new(components: &'c SchemaComponentTable) -> Self {
+        Self {
+            components,
+            elements: Vec::new(),
+            names: Vec::new(),
+            namespaces: Vec::new(),
+            attribute_values: Vec::new(),
+            state_machines: StateMachineCache::default(),
+            ids: HashMap::new(),
+            idrefs: HashMap::new(),
+            identity: IdentityTables::new(components),
+            subtrees: Subtrees::default(),
+            violations: Violations::default(),
+        }
+    }
+
+    pub fn event(&mut self, event: XmlEvent) {
+        match event {
+            XmlEvent::StartElement(start) => self.start_element(start),
+            XmlEvent::Text(text) => self.text(&text),
+            XmlEvent::EndElement => self.end_element(),
+        }
+    }
+
+    /// Returns the violations found since the last call
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        std::mem::take(&mut self.violations.pending)
+    }
+
+    /// Returns whether no violation has been found so far
+    pub fn is_valid(&self) -> bool {
+        self.violations.count == 0
+    }
+
+    /// Completes the validation once the document has ended, returning the violations which have
+    /// not been taken yet. These include the IDREF values which do not refer to any ID.
+    pub fn finish(&mut self) -> Vec<Violation> {
+        debug_assert!(self.elements.is_empty(), "the document has ended");
+        // 1 There is no ·ID/IDREF binding· in E.[ID/IDREF table] whose [binding] is the empty
+        //   set. (Validation Root Valid (ID/IDREF) (§3.3.4.5))
+        let mut unresolved = self
+            .idrefs
+            .drain()
+            .flat_map(|(value, occurrences)| {
+                occurrences
+                    .into_iter()
+                    .map(move |(path, position)| (value.clone(), path, position))
+            })
+            .collect::<Vec<_>>();
+        unresolved.sort_by_key(|(_, _, position)| (position.row, position.col));
+        for (value, path, position) in unresolved {
+            let violation = IdIdrefViolation {
+                value,
+                kind: IdIdrefViolationKind::UnresolvedIdref,
+                path,
+            };
+            let message = violation.message();
+            self.violations
+                .push(position, violation.path, "cvc-id.1", message);
+        }
+        self.take_violations()
+    }
+
+    pub fn start_element(&mut self, start: StartElement) {
+        let StartElement {
+            name,
+            attributes,
+            namespaces,
+            position,
+        } = start;
+        let declared_namespaces = namespaces.len();
+        self.namespaces.extend(namespaces);
+        let violations = self.violations.count;
+
+        let path = match self.elements.last_mut() {
+            Some(parent) => {
+                parent.children.elements = true;
+                // The same path as node_path() gives for the element in a tree
+                let count = parent.child_counts.entry(name.clone()).or_default();
+                *count += 1;
+                format!("{}/{}[{count}]", parent.path, name.local_name())
+            }
+            None => format!("/{}", name.local_name()),
+        };
+        let attribution = self.attribution(&name, &path, position);
+        self.attribute_values.clear();
+        let assessment = self.assessment(attribution, &name, &attributes, &path, position);
+        let initial_value = match &assessment {
+            Assessment::Strict(strict) => match &strict.content {
+                Content::Simple { .. } if !strict.nilled => Some(String::new()),
+                // Needed for clause 5.2.2.2.1 of Element Locally Valid (Element)
+                Content::Complex(content)
+                    if !content.element_only
+                        && strict.declaration.is_some_and(|d| {
+                            d.value_constraint
+                                .as_ref()
+                                .is_some_and(|vc| vc.variety == ValueConstraintVariety::Fixed)
+                        }) =>
+                {
+                    Some(String::new())
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let has_assertions = match &assessment {
+            Assessment::Strict(strict) => strict.type_definition.complex().is_some_and(|t| {
+                let t = t.get(self.components);
+                !t.abstract_ && !t.assertions.is_empty()
+            }),
+            _ => false,
+        };
+        let subtree = if has_assertions || self.subtrees.is_open() {
+            let offset = self.subtrees.start_element(
+                &name,
+                &attributes,
+                &self.namespaces,
+                declared_namespaces,
+                has_assertions,
+            );
+            has_assertions.then_some(offset)
+        } else {
+            None
+        };
+
+        // 6 E is ·valid· with respect to each of the {identity-constraint definitions} as per
+        //   Identity-constraint Satisfied (§3.11.4).
+        let declaration = match &assessment {
+            Assessment::Strict(strict) => strict.declaration,
+            _ => None,
+        };
+        let attribute_values = attributes
+            .iter()
+            .map(|a| {
+                let value = self
+                    .attribute_values
+                    .iter()
+                    .find(|(name, _)| *name == a.name)
+                    .map(|(_, value)| value.clone());
+                (&a.name, value)
+            })
+            .collect::<Vec<_>>();
+        self.names.push(name);
+        self.identity
+            .start_element(&self.names, &attribute_values, &path, position, declaration);
+
+        self.elements.push(Frame {
+            path,
+            position,
+            child_counts: HashMap::new(),
+            namespaces: declared_namespaces,
+            violations,
+            children: Children::default(),
+            initial_value,
+            subtree,
+            assessment,
+        });
+    }
+
+    pub fn text(&mut self, text: &str) {
+        let Some(frame) = self.elements.last_mut() else {
+            return;
+        };
+        if self.subtrees.is_open() {
+            self.subtrees.text(text);
+        }
+        frame.children.add_characters(text);
+        if let Some(initial_value) = &mut frame.initial_value {
+            initial_value.push_str(text);
+        }
+    }
+
+    pub fn end_element(&mut self) {
+        let depth = self.elements.len() - 1;
+        let mut frame = self.elements.pop().unwrap();
+        if self.subtrees.is_open() {
+            self.subtrees
+                .end_element(self.names.last().unwrap(), &self.namespaces);
+        }
+        let (value, nillable) = match std::mem::replace(&mut frame.assessment, Assessment::Skip) {
+            Assessment::Strict(strict) => {
+                let nillable = strict.declaration.is_some_and(|d| d.nillable);
+                let value = self.strict_assessment_end(*strict, &frame);
+                // The element is ·valid· if neither it nor its descendants violate a constraint
+                (value, nillable && self.violations.count == frame.violations)
+            }
+            _ => (None, false),
+        };
+        if frame.subtree == Some(0) {
+            self.subtrees.clear();
+        }
+        self.identity
+            .end_element(depth, value, nillable, &mut self.violations);
+        self.names.pop();
+        self.namespaces
+            .truncate(self.namespaces.len() - frame.namespaces);
+    }
+
+    /// Attributes the element named `name`, which just started, to the content model of its
+    /// parent, see Element Sequence Locally Valid (Particle) (§3.9.4.2). Returns `None` if the
+    /// element is not assessed.
+    fn attribution(&mut self, name: &QName, path: &str, position: TextPos) -> Option<Attribution> {
+        let components = self.components;
+        let Some(parent) = self.elements.last_mut() else {
+            // The ·validation root· is governed by the global declaration of its name
+            return match global_element_declaration(name, components) {
+                Some(d) => Some(Attribution::Declaration(d)),
+                None => {
+                    self.violations.push(
+                        position,
+                        path.to_string(),
+                        "cvc-elt.1",
+                        format!("no declaration found for element {name}"),
+                    );
+                    None
+                }
+            };
+        };
+        let content = match &mut parent.assessment {
+            Assessment::Skip => return None,
+            Assessment::Lax => return Some(Attribution::Wildcard(ProcessContents::Lax)),
+            Assessment::Strict(strict) => match &mut strict.content {
+                Content::Complex(content) if !content.stopped => content,
+                // Element children which are not allowed are reported once the parent ends
+                _ => return None,
+            },
+        };
+        match content.matcher.next(name) {
+            Ok(attribution) => Some(attribution),
+            Err(violation) => {
+                content.stopped = true;
+                self.violations
+                    .clause(position, path.to_string(), violation);
+                None
+            }
+        }
+    }
+
+    /// Starts assessing the element named `name` as determined by its `attribution`, checking
+    /// the clauses which only concern its attributes
+    fn assessment(
+        &mut self,
+        attribution: Option<Attribution>,
+        name: &QName,
+        attributes: &[EventAttribute],
+        path: &str,
+        position: TextPos,
+    ) -> Assessment<'c> {
+        let components = self.components;
+        let declaration = match attribution {
+            None => return Assessment::Skip,
+            Some(Attribution::Declaration(d)) => d,
+            Some(Attribution::Wildcard(process_contents)) => {
+                match wildcard_assessment(
+                    name,
+                    &process_contents,
+                    self.instance_specified_type_definition(attributes),
+                    components,
+                ) {
+                    Ok(WildcardAssessment::Skip) => return Assessment::Skip,
+                    Ok(WildcardAssessment::Declaration(d)) => d,
+                    Ok(WildcardAssessment::Type(t)) => {
+                        let content =
+                            self.type_assessment(t, false, name, attributes, path, position);
+                        return Assessment::Strict(Box::new(Strict {
+                            declaration: None,
+                            type_definition: t,
+                            instance_specified: true,
+                            nilled: false,
+                            content,
+                        }));
+                    }
+                    Ok(WildcardAssessment::Lax) => {
+                        for a in attributes.iter().filter(|a| !is_builtin_xsi(a)) {
+                            if let Some(d) = global_attribute_declaration(&a.name, components) {
+                                self.attribute_locally_valid(a, path, d.get(components));
+                            }
+                        }
+                        return Assessment::Lax;
+                    }
+                    Err(violation) => {
+                        self.violations
+                            .clause(position, path.to_string(), violation);
+                        return Assessment::Skip;
+                    }
+                }
+            }
+        };
+
+        let d = declaration.get(components);
+        let attribute_path = |a: &EventAttribute| format!("{path}/@{}", a.name.local_name());
+
+        if let Err(violation) = declaration_not_abstract(d, name) {
+            self.violations
+                .clause(position, path.to_string(), violation);
+        }
+
+        let xsi_nil = xsi_attribute(attributes, "nil");
+        let is_nilled = match nilled(d, name, xsi_nil.map(|a| a.value.as_str())) {
+            Ok(is_nilled) => is_nilled,
+            Err(violation) => {
+                let xsi_nil = xsi_nil.unwrap();
+                self.violations
+                    .clause(xsi_nil.position, attribute_path(xsi_nil), violation);
+                false
+            }
+        };
+
+        let selected_type_definition = match d.type_table.as_ref() {
+            Some(type_table) => {
+                let attributes = attributes
+                    .iter()
+                    .map(|a| (a.name.clone(), a.value.clone()))
+                    .collect();
+                conditionally_selected_type(&attributes, type_table, components)
+            }
+            None => d.type_definition,
+        };
+        let instance_specified_type_definition =
+            self.instance_specified_type_definition(attributes);
+        if let Some(xsi_type) = xsi_attribute(attributes, "type")
+            && let Err(violation) = instance_specified_type_valid(
+                &xsi_type.value,
+                instance_specified_type_definition,
+                selected_type_definition,
+                d,
+                name,
+                components,
+            )
+        {
+            self.violations
+                .clause(xsi_type.position, attribute_path(xsi_type), violation);
+        }
+        let type_definition = match instance_specified_type_definition {
+            Some(t) if overrides(t, selected_type_definition, d, components) => t,
+            _ => selected_type_definition,
+        };
+
+        let content =
+            self.type_assessment(type_definition, is_nilled, name, attributes, path, position);
+        Assessment::Strict(Box::new(Strict {
+            declaration: Some(d),
+            type_definition,
+            instance_specified: instance_specified_type_definition == Some(type_definition),
+            nilled: is_nilled,
+            content,
+        }))
+    }
+
+    /// Starts assessing the element named `name` with respect to its ·governing type definition·
+    /// `t` as per Element Locally Valid (Type) (§3.3.4.4), checking its attributes
+    fn type_assessment(
+        &mut self,
+        t: TypeDefinition,
+        nilled: bool,
+        name: &QName,
+        attributes: &[EventAttribute],
+        path: &str,
+        position: TextPos,
+    ) -> Content<'c> {
+        let components = self.components;
+
+        if let Err(violation) = type_not_abstract(t, components) {
+            self.violations
+                .clause(position, path.to_string(), violation);
+            return Content::Unassessed;
+        }
+
+        match t {
+            TypeDefinition::Simple(t) => {
+                for a in attributes.iter().filter(|a| !is_builtin_xsi(a)) {
+                    self.violations.clause(
+                        a.position,
+                        format!("{path}/@{}", a.name.local_name()),
+                        attribute_not_allowed_by_simple_type(&a.name, name),
+                    );
+                }
+                // 3.1.2 and 3.1.3 are checked once the element ends
+                Content::Simple {
+                    simple_type: t.get(components),
+                    is_simple_type: true,
+                }
+            }
+            TypeDefinition::Complex(t) => {
+                let t = t.get(components);
+                self.complex_type_attributes(t, attributes, path, position);
+                // 1 If E is not ·nilled·, then all of the following are true: (checked once the
+                //   element ends)
+                match &t.content_type {
+                    _ if nilled => Content::Unassessed,
+                    ContentType::Empty => Content::Empty,
+                    ContentType::Simple {
+                        simple_type_definition,
+                    } => Content::Simple {
+                        simple_type: simple_type_definition.get(components),
+                        is_simple_type: false,
+                    },
+                    ContentType::ElementOnly {
+                        particle,
+                        open_content,
+                    }
+                    | ContentType::Mixed {
+                        particle,
+                        open_content,
+                    } => match ContentModelMatcher::new(
+                        *particle,
+                        open_content,
+                        &mut self.state_machines,
+                        components,
+                    ) {
+                        Ok(matcher) => Content::Complex(ComplexContent {
+                            matcher,
+                            stopped: false,
+                            element_only: t.content_type.variety()
+                                == ContentTypeVariety::ElementOnly,
+                        }),
+                        Err(violation) => {
+                            self.violations
+                                .clause(position, path.to_string(), violation);
+                            Content::Unassessed
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Clauses 2 to 4 of Element Locally Valid (Complex Type) (§3.4.4.2), which concern the
+    /// attributes of the element at `path`
+    fn complex_type_attributes(
+        &mut self,
+        t: &'c ComplexTypeDefinition,
+        attributes: &[EventAttribute],
+        path: &str,
+        position: TextPos,
+    ) {
+        let components = self.components;
+        let attribute_path = |a: &EventAttribute| format!("{path}/@{}", a.name.local_name());
+
+        for a in attributes.iter().filter(|a| !is_builtin_xsi(a)) {
+            match attribute_attribution(t, &a.name, components) {
+                Ok(AttributeAttribution::Use(u)) => {
+                    let d = u.attribute_declaration.get(components);
+                    if let Some(value) = self.attribute_locally_valid(a, path, d)
+                        && let Err(violation) =
+                            attribute_use_fixed_value_valid(u, &a.value, &value, components)
+                    {
+                        self.violations
+                            .clause(a.position, attribute_path(a), violation);
+                    }
+                }
+                Ok(AttributeAttribution::Wildcard(d)) => {
+                    if let Some(d) = d {
+                        self.attribute_locally_valid(a, path, d.get(components));
+                    }
+                }
+                Err(violation) => self
+                    .violations
+                    .clause(a.position, attribute_path(a), violation),
+            }
+        }
+
+        let has_attribute = |name: &QName| attributes.iter().any(|a| a.name == *name);
+        for violation in missing_required_attributes(t, has_attribute, components) {
+            self.violations
+                .clause(position, path.to_string(), violation);
+        }
+        for (u, value_constraint) in defaulted_attributes(t, has_attribute, components) {
+            if let Err(violation) = defaulted_attribute_valid(
+                u,
+                value_constraint,
+                &InScopeNamespaces(&self.namespaces),
+                components,
+            ) {
+                self.violations
+                    .clause(position, path.to_string(), violation);
+            }
+        }
+    }
+
+    /// 3.2.4.1 Attribute Locally Valid, for attribute `a` of the element at `element_path`
+    ///
+    /// Returns the ·actual value· of A if it is locally ·valid· with respect to D.
+    fn attribute_locally_valid(
+        &mut self,
+        a: &EventAttribute,
+        element_path: &str,
+        d: &AttributeDeclaration,
+    ) -> Option<Value> {
+        let components = self.components;
+        let path = format!("{element_path}/@{}", a.name.local_name());
+
+        // 3 A's ·initial value· is locally ·valid· with respect to D.{type definition} as per
+        //   String Valid (§3.16.4).
+        let t = d.type_definition.get(components);
+        let value = match normalized_string_valid(
+            &a.value,
+            t,
+            &InScopeNamespaces(&self.namespaces),
+            components,
+        ) {
+            Ok((normalized, value, member_types)) => {
+                self.record_id_idrefs(t, &value, &member_types, &path, a.position);
+                self.attribute_values.push((
+                    a.name.clone(),
+                    KeyValue {
+                        actual: value.clone(),
+                        normalized,
+                    },
+                ));
+                value
+            }
+            Err(error) => {
+                self.violations
+                    .push(a.position, path, error.code(), error.to_string());
+                return None;
+            }
+        };
+
+        if let Err(violation) = attribute_fixed_value_valid(d, &a.value, &value) {
+            self.violations.clause(a.position, path, violation);
+            return None;
+        }
+
+        Some(value)
+    }
+
+    /// Completes the assessment of the element which just ended, checking the clauses which
+    /// concern its content. Returns its [schema actual value], if any.
+    fn strict_assessment_end(&mut self, strict: Strict<'c>, frame: &Frame) -> Option<KeyValue> {
+        let components = self.components;
+        let name = self.names.last().unwrap().clone();
+        let Strict {
+            declaration,
+            type_definition: t,
+            instance_specified,
+            nilled,
+            content,
+        } = strict;
+        let children = frame.children;
+
+        if nilled && let Some(d) = declaration {
+            for violation in nilled_element_valid(d, &name, children) {
+                self.element_violation(frame, violation);
+            }
+        }
+
+        // 5.1 If D has a {value constraint}, and E has neither element nor character [children],
+        //   and E is not ·nilled· with respect to D , then all of the following are true:
+        let value_constraint = declaration.and_then(|d| d.value_constraint.as_ref());
+        let default =
+            value_constraint.filter(|_| !children.elements && !children.characters && !nilled);
+        if let Some(value_constraint) = default
+            && instance_specified
+            && let Err(violation) = default_valid_for_instance_type(
+                value_constraint,
+                t,
+                &name,
+                &InScopeNamespaces(&self.namespaces),
+                components,
+            )
+        {
+            self.element_violation(frame, violation);
+        }
+        //   5.1.2 The element information item with D.{value constraint}.{lexical form} used as
+        //     its ·normalized value· is locally ·valid· with respect to the ·governing type
+        //     definition· as defined by Element Locally Valid (Type) (§3.3.4.4).
+        let initial_value = match default {
+            Some(value_constraint) => value_constraint.lexical_form.as_str(),
+            None => frame.initial_value.as_deref().unwrap_or_default(),
+        };
+
+        let value = match content {
+            Content::Unassessed => None,
+            Content::Empty => {
+                if let Err(violation) = empty_content_valid(&name, children) {
+                    self.element_violation(frame, violation);
+                }
+                None
+            }
+            Content::Simple {
+                simple_type,
+                is_simple_type,
+            } => {
+                let children_valid = if is_simple_type {
+                    simple_type_children_valid(&name, children)
+                } else {
+                    simple_content_children_valid(&name, children)
+                };
+                match children_valid {
+                    Err(violation) => {
+                        self.element_violation(frame, violation);
+                        None
+                    }
+                    // 3.1.3 If E is not ·nilled·, then the ·initial value· is ·valid· with
+                    //   respect to T as defined by String Valid (§3.16.4).
+                    Ok(()) if nilled => None,
+                    Ok(()) => match normalized_string_valid(
+                        initial_value,
+                        simple_type,
+                        &InScopeNamespaces(&self.namespaces),
+                        components,
+                    ) {
+                        Ok((normalized, value, member_types)) => {
+                            self.record_id_idrefs(
+                                simple_type,
+                                &value,
+                                &member_types,
+                                &frame.path,
+                                frame.position,
+                            );
+                            Some(KeyValue {
+                                actual: value,
+                                normalized,
+                            })
+                        }
+                        Err(error) => {
+                            self.violations.push(
+                                frame.position,
+                                frame.path.clone(),
+                                error.code(),
+                                error.to_string(),
+                            );
+                            None
+                        }
+                    },
+                }
+            }
+            Content::Complex(content) => {
+                if content.element_only
+                    && let Err(violation) = element_only_content_valid(&name, children)
+                {
+                    self.element_violation(frame, violation);
+                }
+                if !content.stopped
+                    && let Err(violation) = content.matcher.end(&name)
+                {
+                    self.element_violation(frame, violation);
+                }
+                None
+            }
+        };
+
+        if let Some(d) = declaration
+            && let Some(value_constraint) = value_constraint
+                .filter(|vc| vc.variety == ValueConstraintVariety::Fixed)
+                .filter(|_| default.is_none() && !nilled)
+            && let Err(violation) = fixed_value_valid(
+                d,
+                value_constraint,
+                t,
+                children,
+                initial_value,
+                value.as_ref().map(|value| &value.actual),
+                components,
+            )
+        {
+            self.element_violation(frame, violation);
+        }
+
+        // 6 E is ·valid· with respect to each of the assertions in T.{assertions} as per
+        //   Assertion Satisfied (§3.13.4.1).
+        if let Some(offset) = frame.subtree
+            && let Some(t) = t.complex()
+        {
+            self.assertions_satisfied(t.get(components), offset, frame);
+        }
+        value
+    }
+
+    /// Evaluates the assertions of `t` on the subtree of the element which just ended, which
+    /// starts at `offset`
+    fn assertions_satisfied(&mut self, t: &ComplexTypeDefinition, offset: usize, frame: &Frame) {
+        // The subtree is serialized from the events of a well-formed document
+        let document = roxmltree::Document::parse(self.subtrees.subtree(offset)).unwrap();
+        let mut report = ValidationReport::default();
+        assertions_satisfied(&document.root_element(), t, &mut report, self.components);
+        for violation in report.violations {
+            self.violations.push(
+                frame.position,
+                frame.path.clone(),
+                violation.code,
+                violation.message,
+            );
+        }
+    }
+
+    fn element_violation(&mut self, frame: &Frame, violation: ClauseViolation) {
+        self.violations
+            .clause(frame.position, frame.path.clone(), violation);
+    }
+
+    /// Records the ID and IDREF values of the item at `path`, whose ·actual value· with respect
+    /// to `t` is `value`, validated by `member_types`, as needed for Validation Root Valid
+    /// (ID/IDREF) (§3.3.4.5)
+    fn record_id_idrefs(
+        &mut self,
+        t: &SimpleTypeDefinition,
+        value: &Value,
+        member_types: &MemberTypes,
+        path: &str,
+        position: TextPos,
+    ) {
+        let Self {
+            components,
+            ids,
+            idrefs,
+            violations,
+            ..
+        } = self;
+        for_each_id_idref(
+            t,
+            value,
+            member_types,
+            components,
+            &mut |kind, value| match kind {
+                IdIdrefKind::Id => {
+                    // 2 There is no ·ID/IDREF binding· in E.[ID/IDREF table] whose [binding] has
+                    //   more than one member.
+                    if let Some(other) = ids.get(value) {
+                        let violation = IdIdrefViolation {
+                            value: value.to_string(),
+                            kind: IdIdrefViolationKind::DuplicateId {
+                                other: other.clone(),
+                            },
+                            path: path.to_string(),
+                        };
+                        violations.push(
+                            position,
+                            path.to_string(),
+                            "cvc-id.2",
+                            violation.message(),
+                        );
+                    } else {
+                        idrefs.remove(value);
+                        ids.insert(value.to_string(), path.to_string());
+                    }
+                }
+                IdIdrefKind::Idref => {
+                    if !ids.contains_key(value) {
+                        idrefs
+                            .entry(value.to_string())
+                            .or_default()
+                            .push((path.to_string(), position));
+                    }
+                }
+            },
+        );
+    }
+
+    /// Returns the ·instance-specified type definition· of the element with the given
+    /// `attributes`, i.e. the type definition its xsi:type attribute ·resolves· to, if any
+    fn instance_specified_type_definition(
+        &self,
+        attributes: &[EventAttribute],
+    ) -> Option<TypeDefinition> {
+        let xsi_type = xsi_attribute(attributes, "type")?;
+        let name = qname_value(xsi_type.value.trim(), &InScopeNamespaces(&self.namespaces))?;
+        type_definition_by_name(&name, self.components)
+    }
+}
+
+/// Returns the attribute in the XSI namespace with the given local name, if any
+fn xsi_attribute<'a>(
+    attributes: &'a [EventAttribute],
+    local_name: &str,
+) -> Option<&'a EventAttribute> {
+    attributes.iter().find(|a| {
+        a.name.namespace_name() == Some(XSI_NAMESPACE) && a.name.local_name() == local_name
+    })
+}
+
+/// Returns whether `a` is one of the attributes which are not governed by the type of their
+/// element, see [`is_builtin_xsi_attribute_name`]
+fn is_builtin_xsi(a: &EventAttribute) -> bool {
+    is_builtin_xsi_attribute_name(a.name.namespace_name(), a.name.local_name())
+}
+
+/// Validates the document read by `reader`, calling `on_violation` with each violation as soon
+/// as it is found. Returns whether the document is valid, or the error which stopped reading it.
+pub fn validate_stream<R: BufRead>(
+    reader: &mut EventReader<R>,
+    components: &SchemaComponentTable,
+    mut on_violation: impl FnMut(Violation),
+) -> Result<bool, ReadError> {
+    let mut validator = StreamingValidator::new(components);
+    while let Some(event) = reader.next_event()? {
+        validator.event(event);
+        validator
+            .take_violations()
+            .into_iter()
+            .for_each(&mut on_violation);
+    }
+    validator.finish().into_iter().for_each(&mut on_violation);
+    Ok(validator.is_valid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventReader, validate_stream};
+    use crate::validation::Violation;
+    use roxmltree::Document;
+
+    /// Validates `instance` against `schema` both as a tree and as a stream, returning the codes
+    /// of the violations in document order after checking that both validations find the same
+    /// violations at the same positions and paths
+    fn violations(schema: &str, instance: &str) -> Vec<&'static str> {
+        violations_with_paths(schema, instance)
+            .into_iter()
+            .map(|(code, _)| code)
+            .collect()
+    }
+
+    /// Like [`violations`], but also returns the paths of the violations
+    fn violations_with_paths(schema: &str, instance: &str) -> Vec<(&'static str, String)> {
+        let (schema, components) = crate::testing::read_schema(schema).unwrap();
+        assert!(crate::constraints::check_schema(&schema, &components).is_empty());
+
+        let document = Document::parse(instance).unwrap();
+        let root = document.root_element();
+        let d = schema
+            .find_element_by_name(
+                super::super::element_namespace(&root),
+                root.tag_name().name(),
+                &components,
+            )
+            .unwrap();
+        let report = super::super::element_locally_valid_element(&root, Some(d), &components);
+        let mut streamed = Vec::new();
+        let valid = validate_stream(
+            &mut EventReader::new(instance.as_bytes()),
+            &components,
+            |v| streamed.push(v),
+        )
+        .unwrap();
+        assert_eq!(valid, report.is_valid());
+        let key = |v: &Violation| (v.line, v.column, v.code, v.path.clone());
+        let mut streamed = streamed.iter().map(key).collect::<Vec<_>>();
+        streamed.sort();
+        let mut expected = report.violations.iter().map(key).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(streamed, expected);
+        streamed
+            .into_iter()
+            .map(|(_, _, code, path)| (code, path))
+            .collect()
+    }
+
+    #[test]
+    fn streaming_matches_tree_validation() {
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns="urn:test"
+                xmlns:t="urn:test" targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="order">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:simpleContent>
+                          <xs:extension base="xs:positiveInteger">
+                            <xs:attribute name="sku" type="xs:ID" use="required"/>
+                          </xs:extension>
+                        </xs:simpleContent>
+                      </xs:complexType>
+                    </xs:element>
+                    <xs:element name="ref" type="xs:IDREF" minOccurs="0" maxOccurs="unbounded"/>
+                    <xs:element name="total" type="xs:decimal" fixed="3"/>
+                    <xs:any namespace="urn:other" processContents="lax" minOccurs="0"/>
+                  </xs:sequence>
+                  <xs:attribute name="date" type="xs:date"/>
+                </xs:complexType>
+                <xs:key name="skus">
+                  <xs:selector xpath="t:item"/>
+                  <xs:field xpath="@sku"/>
+                </xs:key>
+                <xs:keyref name="refs" refer="skus">
+                  <xs:selector xpath="t:ref"/>
+                  <xs:field xpath="."/>
+                </xs:keyref>
+              </xs:element>
+            </xs:schema>
+            "#;
+
+        assert!(
+            violations(
+                schema,
+                r#"<order xmlns="urn:test" date="2024-01-01">
+                  <item sku="a">1</item>
+                  <item sku="b">2</item>
+                  <ref>b</ref>
+                  <total>3.0</total>
+                  <note xmlns="urn:other" lang="en"><text>lax</text></note>
+                </order>"#
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            violations(
+                schema,
+                r#"<order xmlns="urn:test" date="today" extra="1">
+                  <item sku="a">0</item>
+                  <item sku="a">2</item>
+                  <ref>c</ref>
+                  <total>4</total>
+                  <total/>
+                </order>"#
+            ),
+            [
+                "cvc-datatype-valid.1",
+                "cvc-complex-type.2.2.1",
+                "cvc-minInclusive-valid",
+                "cvc-identity-constraint.4.2.2",
+                "cvc-id.2",
+                "cvc-id.1",
+                "cvc-identity-constraint.4.3",
+                "cvc-elt.5.2.2.2.2",
+                "cvc-complex-type.1.4",
+            ]
+        );
+        assert_eq!(
+            violations(
+                schema,
+                r#"<order xmlns="urn:test"><item sku="a">1<b/></item></order>"#
+            ),
+            ["cvc-complex-type.1.4", "cvc-complex-type.1.2"]
+        );
+    }
+
+    #[test]
+    fn same_paths_in_both_modes() {
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="root">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:sequence>
+                          <xs:element name="n" type="xs:int" maxOccurs="unbounded"/>
+                        </xs:sequence>
+                        <xs:attribute name="id" type="xs:ID"/>
+                      </xs:complexType>
+                    </xs:element>
+                    <xs:any namespace="urn:other" processContents="skip"/>
+                    <xs:element name="last" type="xs:int"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#;
+        // Elements are numbered among the siblings with the same expanded name, even if they
+        // are the only one
+        assert_eq!(
+            violations_with_paths(
+                schema,
+                r#"<root>
+                  <item id="a"><n>x</n></item>
+                  <item id="a"><n>1</n><n>y</n></item>
+                  <item xmlns="urn:other"/>
+                  <last>z</last>
+                </root>"#
+            ),
+            [
+                ("cvc-datatype-valid.1", "/root/item[1]/n[1]".into()),
+                ("cvc-id.2", "/root/item[2]/@id".into()),
+                ("cvc-datatype-valid.1", "/root/item[2]/n[2]".into()),
+                ("cvc-datatype-valid.1", "/root/last[1]".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn large_documents() {
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="root">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:attribute name="id" type="xs:ID"/>
+                        <xs:attribute name="ref" type="xs:IDREF"/>
+                        <xs:attribute name="code" type="xs:int"/>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+                <xs:unique name="codes">
+                  <xs:selector xpath="item"/>
+                  <xs:field xpath="@code"/>
+                </xs:unique>
+              </xs:element>
+            </xs:schema>
+            "#;
+        // IDs, IDREFs and key-sequences are looked up in hash tables, so that this does not take
+        // quadratic time
+        const ITEMS: usize = 10_000;
+        let mut instance = String::from("<root>");
+        for i in 1..=ITEMS {
+            instance.push_str(&format!(
+                r#"<item id="i{i}" ref="i{}" code="{i}"/>"#,
+                ITEMS - i + 1
+            ));
+        }
+        instance.push_str(r#"<item id="i1" ref="missing" code="1"/></root>"#);
+        let last = format!("/root/item[{}]", ITEMS + 1);
+        assert_eq!(
+            violations_with_paths(schema, &instance),
+            [
+                ("cvc-identity-constraint.4.1", last.clone()),
+                ("cvc-id.2", format!("{last}/@id")),
+                ("cvc-id.1", format!("{last}/@ref")),
+            ]
+        );
+    }
+
+    #[test]
+    fn undeclared_default_namespace() {
+        // roxmltree reports elements in the scope of xmlns="" as being in the namespace ""
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+              <xs:element name="root">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="a" type="xs:int"/>
+                  </xs:sequence>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#;
+        assert!(violations(schema, r#"<root xmlns=""><a>1</a></root>"#).is_empty());
+        assert_eq!(
+            violations(schema, r#"<root xmlns=""><a>x</a></root>"#),
+            ["cvc-datatype-valid.1"]
+        );
+
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns:t="urn:t"
+                targetNamespace="urn:t" elementFormDefault="qualified">
+              <xs:element name="root">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="t">
+                      <xs:complexType>
+                        <xs:sequence>
+                          <xs:element name="a" form="unqualified" maxOccurs="unbounded">
+                            <xs:complexType>
+                              <xs:attribute name="k" type="xs:string"/>
+                            </xs:complexType>
+                          </xs:element>
+                        </xs:sequence>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+                <xs:unique name="k">
+                  <xs:selector xpath="t:t/a"/>
+                  <xs:field xpath="@k"/>
+                </xs:unique>
+              </xs:element>
+            </xs:schema>
+            "#;
+        assert!(
+            violations(
+                schema,
+                r#"<root xmlns="urn:t"><t><a xmlns="" k="1"/><a xmlns="" k="2"/></t></root>"#
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            violations(
+                schema,
+                r#"<root xmlns="urn:t"><t><a xmlns="" k="1"/><a xmlns="" k="1"/></t></root>"#
+            ),
+            ["cvc-identity-constraint.4.1"]
+        );
+    }
+
+    #[test]
+    fn typed_key_sequences() {
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns:t="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="people">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="person" maxOccurs="unbounded">
+                      <xs:complexType>
+                        <xs:sequence>
+                          <xs:element name="id" type="xs:decimal"/>
+                          <xs:element name="name" type="xs:string" nillable="true"/>
+                        </xs:sequence>
+                        <xs:attribute name="code" type="xs:double"/>
+                      </xs:complexType>
+                    </xs:element>
+                  </xs:sequence>
+                </xs:complexType>
+                <xs:key name="ids">
+                  <xs:selector xpath="t:person"/>
+                  <xs:field xpath="t:id"/>
+                </xs:key>
+                <xs:unique name="codes">
+                  <xs:selector xpath="t:person"/>
+                  <xs:field xpath="@code"/>
+                </xs:unique>
+                <xs:key name="names">
+                  <xs:selector xpath="t:person"/>
+                  <xs:field xpath="t:name"/>
+                </xs:key>
+              </xs:element>
+            </xs:schema>
+            "#;
+
+        // Key-sequences are compared by their actual values, and a key field must not be
+        // governed by a nillable declaration
+        assert_eq!(
+            violations(
+                schema,
+                r#"<people xmlns="urn:test">
+                  <person code="1e0"><id>1</id><name>a</name></person>
+                  <person code=" 1"><id>01.0</id><name xsi:nil="true"
+                    xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"/></person>
+                </people>"#
+            ),
+            [
+                "cvc-identity-constraint.4.2.3",
+                "cvc-identity-constraint.4.1",
+                "cvc-identity-constraint.4.2.2",
+                "cvc-identity-constraint.4.2.3",
+            ]
+        );
+    }
+
+    #[test]
+    fn assertions() {
+        let schema = r#"
+            <xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns:t="urn:test"
+                targetNamespace="urn:test" elementFormDefault="qualified">
+              <xs:element name="ranges">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element ref="t:range" maxOccurs="unbounded"/>
+                    <xs:element name="note" type="xs:string" minOccurs="0"/>
+                  </xs:sequence>
+                  <xs:assert test="count(t:range) le 2"/>
+                  <xs:assert test="not(t:note) or t:note = 'a &lt; b'"/>
+                </xs:complexType>
+              </xs:element>
+              <xs:element name="range">
+                <xs:complexType>
+                  <xs:sequence>
+                    <xs:element name="item" type="xs:integer" maxOccurs="unbounded"/>
+                  </xs:sequence>
+                  <xs:attribute name="min" type="xs:integer"/>
+                  <xs:attribute name="max" type="xs:integer"/>
+                  <xs:assert test="@min le @max"/>
+                  <xs:assert test="every $i in t:item satisfies $i ge @min and $i le @max"/>
+                </xs:complexType>
+              </xs:element>
+            </xs:schema>
+            "#;
+
+        // The assertions of nested elements are evaluated on their own subtrees, with the
+        // namespaces in scope
+        assert!(
+            violations(
+                schema,
+                r#"<p:ranges xmlns:p="urn:test">
+                  <p:range min="1" max="3"><p:item>2</p:item></p:range>
+                  <range xmlns="urn:test" min=" 1" max="3"><item>3</item></range>
+                  <p:note>a &lt; b</p:note>
+                </p:ranges>"#
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            violations(
+                schema,
+                r#"<ranges xmlns="urn:test">
+                  <range min="3" max="1"><item>2</item></range>
+                  <range min="1" max="3"><item>2</item></range>
+                  <range min="1" max="3"><item>4</item></range>
+                  <note>a <![CDATA[<]]> c</note>
+                </ranges>"#
+            ),
+            [
+                "cvc-assertion",
+                "cvc-assertion",
+                "cvc-assertion",
+                "cvc-assertion",
+                "cvc-assertion",
+            ]
+        );
+    }
+}
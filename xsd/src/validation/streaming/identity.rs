@@ -0,0 +1,510 @@
+//! Identity-constraint Satisfied (§3.11.4) while streaming: the {selector} and {fields} are
+//! matched against the names of the open elements as each element starts, and the
+//! ·key-sequences· are completed as the selected elements end.
+//!
+//! Unlike the rest of the streaming validation, the memory used grows with the size of the
+//! ·node tables·, which are kept until the element the constraint is declared on ends.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    super::identity::{IdentityConstraintViolationKind, KeyValue},
+    Violations,
+};
+use crate::{
+    ElementDeclaration, IdentityConstraintDefinition, Ref, SchemaComponentTable,
+    components::Named,
+    identity_constraint_def::IdentityConstraintCategory,
+    xpath::{IdentityPath, Path, Step},
+    xstypes::QName,
+};
+use roxmltree::TextPos;
+
+/// The identity constraints being evaluated for the open elements
+pub(super) struct IdentityTables<'c> {
+    components: &'c SchemaComponentTable,
+    scopes: Vec<Scope<'c>>,
+    /// The ·node tables· propagated from the descendants of the open elements, by the depth of
+    /// the element (§3.11.5)
+    propagated: Vec<(
+        usize,
+        HashMap<Ref<IdentityConstraintDefinition>, PropagatedTable>,
+    )>,
+    /// The keys referenced by a keyref, whose node tables need to be propagated
+    referenced_keys: HashSet<Ref<IdentityConstraintDefinition>>,
+    /// A number identifying the next element, to tell nodes with equal ·key-sequences· apart
+    next_node: usize,
+}
+
+/// An identity-constraint definition of the element E at `depth`
+struct Scope<'c> {
+    depth: usize,
+    icd_ref: Ref<IdentityConstraintDefinition>,
+    icd: &'c IdentityConstraintDefinition,
+    name: QName,
+    /// The open members of the ·target node set·
+    targets: Vec<Target>,
+    /// The ·node table· of the ·qualified node set·, with the paths of the nodes
+    table: HashMap<Vec<KeyValue>, (usize, String)>,
+    /// The ·key-sequences· which occur more than once in `table`
+    duplicates: HashSet<Vec<KeyValue>>,
+    /// The members of the ·qualified node set· of a keyref, which are resolved once E ends
+    keyrefs: Vec<(Vec<KeyValue>, String, TextPos)>,
+}
+
+/// A member of the ·target node set· whose {fields} are being evaluated
+struct Target {
+    depth: usize,
+    node: usize,
+    path: String,
+    position: TextPos,
+    fields: Vec<FieldValue>,
+    /// The elements selected by the fields, whose [schema actual value] is known once they end
+    captures: Vec<Capture>,
+}
+
+enum FieldValue {
+    /// The field selects no node
+    Absent,
+    /// The field selects an element which has not ended yet
+    Pending,
+    /// The field selects a node with the given [schema actual value], if any
+    Node(Option<KeyValue>),
+    /// The field selects an element which was assessed as ·valid· by reference to an element
+    /// declaration whose {nillable} is true, with the given [schema actual value], if any
+    Nillable(Option<KeyValue>, String, TextPos),
+    /// The field selects an element with element children
+    Complex(String, TextPos),
+    /// The field selects more than one node
+    Multiple,
+}
+
+struct Capture {
+    field: usize,
+    depth: usize,
+    path: String,
+    position: TextPos,
+    complex: bool,
+}
+
+#[derive(Default)]
+struct PropagatedTable {
+    entries: HashMap<Vec<KeyValue>, usize>,
+    /// The ·key-sequences· of different nodes in the tables of different descendants
+    conflicting: HashSet<Vec<KeyValue>>,
+}
+
+impl PropagatedTable {
+    fn insert(&mut self, key_sequence: &[KeyValue], node: usize) {
+        match self.entries.get(key_sequence) {
+            Some(&other) if other != node => {
+                self.conflicting.insert(key_sequence.to_vec());
+            }
+            Some(_) => {}
+            None => {
+                self.entries.insert(key_sequence.to_vec(), node);
+            }
+        }
+    }
+
+    fn merge(&mut self, other: PropagatedTable) {
+        for (key_sequence, node) in other.entries {
+            self.insert(&key_sequence, node);
+        }
+        self.conflicting.extend(other.conflicting);
+    }
+}
+
+/// Returns whether `path` selects the last element of `names`, which are the names of the
+/// elements from a child of the context node down to the candidate; an empty `names` stands for
+/// the context node itself
+fn selects(path: &Path, names: &[QName]) -> bool {
+    let steps = path
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::SelfNode => None,
+            Step::Child(name_test) => Some(name_test),
+        })
+        .collect::<Vec<_>>();
+    let depth_matches = if path.descendants {
+        names.len() >= steps.len()
+    } else {
+        names.len() == steps.len()
+    };
+    depth_matches
+        && steps
+            .iter()
+            .zip(&names[names.len() - steps.len()..])
+            .all(|(name_test, name)| name_test.matches(name.namespace_name(), name.local_name()))
+}
+
+impl<'c> IdentityTables<'c> {
+    pub(super) fn new(components: &'c SchemaComponentTable) -> Self {
+        let referenced_keys = components
+            .refs::<IdentityConstraintDefinition>()
+            .filter_map(|icd| icd.get(components).referenced_key)
+            .collect();
+        Self {
+            components,
+            scopes: Vec::new(),
+            propagated: Vec::new(),
+            referenced_keys,
+            next_node: 0,
+        }
+    }
+
+    /// Evaluates the selectors and fields for the element which just started, whose ancestors
+    /// and itself have the given `names`. `attributes` are the names of its attributes, with
+    /// their [schema actual values] if they have been assessed. `declaration` is its ·governing
+    /// element declaration·, if any.
+    pub(super) fn start_element(
+        &mut self,
+        names: &[QName],
+        attributes: &[(&QName, Option<KeyValue>)],
+        path: &str,
+        position: TextPos,
+        declaration: Option<&ElementDeclaration>,
+    ) {
+        let depth = names.len() - 1;
+        let node = self.next_node;
+        self.next_node += 1;
+
+        // The element is a child of the elements selected by the fields, which thus have element
+        // children
+        for target in self.scopes.iter_mut().flat_map(|s| &mut s.targets) {
+            for capture in &mut target.captures {
+                if capture.depth + 1 == depth {
+                    capture.complex = true;
+                }
+            }
+        }
+
+        // Evaluate the fields of the open targets, and the selectors of the open scopes
+        for scope in &mut self.scopes {
+            for target in &mut scope.targets {
+                select_fields(
+                    scope.icd,
+                    target,
+                    &names[target.depth + 1..],
+                    attributes,
+                    path,
+                    position,
+                );
+            }
+        }
+        let new_scopes = declaration
+            .into_iter()
+            .flat_map(|d| &d.identity_constraint_definitions)
+            .map(|&icd_ref| {
+                let icd = icd_ref.get(self.components);
+                Scope {
+                    depth,
+                    icd_ref,
+                    icd,
+                    name: icd.name().unwrap(),
+                    targets: Vec::new(),
+                    table: HashMap::new(),
+                    duplicates: HashSet::new(),
+                    keyrefs: Vec::new(),
+                }
+            })
+            .collect::<Vec<_>>();
+        self.scopes.extend(new_scopes);
+        for scope in &mut self.scopes {
+            // 1 The {selector}, with the element information item E as the context node,
+            //   evaluates to a sequence of element nodes. [Definition:] Call this sequence the
+            //   target node set.
+            let relative_names = &names[scope.depth + 1..];
+            if scope
+                .icd
+                .selector_path
+                .paths
+                .iter()
+                .any(|p| selects(p, relative_names))
+            {
+                let mut target = Target {
+                    depth,
+                    node,
+                    path: path.to_string(),
+                    position,
+                    fields: scope
+                        .icd
+                        .field_paths
+                        .iter()
+                        .map(|_| FieldValue::Absent)
+                        .collect(),
+                    captures: Vec::new(),
+                };
+                select_fields(scope.icd, &mut target, &[], attributes, path, position);
+                scope.targets.push(target);
+            }
+        }
+    }
+
+    /// Completes the fields, targets and scopes of the element at `depth`, which just ended.
+    /// `value` is its [schema actual value], if any, and `nillable` whether it was assessed as
+    /// ·valid· by reference to an element declaration whose {nillable} is true.
+    pub(super) fn end_element(
+        &mut self,
+        depth: usize,
+        value: Option<KeyValue>,
+        nillable: bool,
+        violations: &mut Violations,
+    ) {
+        if self.scopes.is_empty() && self.propagated.is_empty() {
+            return;
+        }
+
+        for scope in &mut self.scopes {
+            for target in &mut scope.targets {
+                let (ended, open): (Vec<_>, Vec<_>) = std::mem::take(&mut target.captures)
+                    .into_iter()
+                    .partition(|capture| capture.depth == depth);
+                target.captures = open;
+                for capture in ended {
+                    let field = &mut target.fields[capture.field];
+                    if let FieldValue::Pending = field {
+                        *field = if capture.complex {
+                            FieldValue::Complex(capture.path, capture.position)
+                        } else if nillable {
+                            FieldValue::Nillable(value.clone(), capture.path, capture.position)
+                        } else {
+                            FieldValue::Node(value.clone())
+                        };
+                    }
+                }
+            }
+            while scope.targets.last().is_some_and(|t| t.depth == depth) {
+                let target = scope.targets.pop().unwrap();
+                scope.complete_target(target, violations);
+            }
+        }
+
+        // The scopes declared on the element end together, after all of their targets
+        let first_ended = self
+            .scopes
+            .iter()
+            .position(|scope| scope.depth == depth)
+            .unwrap_or(self.scopes.len());
+        let ended = self.scopes.split_off(first_ended);
+        let propagated = match self.propagated.last() {
+            Some((d, _)) if *d == depth => self.propagated.pop().unwrap().1,
+            _ => HashMap::new(),
+        };
+        for scope in ended.iter().filter(|scope| {
+            matches!(
+                scope.icd.identity_constraint_category,
+                IdentityConstraintCategory::KeyRef
+            )
+        }) {
+            let Some(key) = scope.icd.referenced_key else {
+                continue;
+            };
+            let own_table = ended.iter().find(|scope| scope.icd_ref == key);
+            let propagated_table = propagated.get(&key);
+            for (key_sequence, path, position) in &scope.keyrefs {
+                // 4.3 If the {identity-constraint category} is keyref, then for each member of
+                //   the ·qualified node set· (call this the keyref member), there is a ·node
+                //   table· associated with the {referenced key} in the [identity-constraint
+                //   table] of E which contains an entry whose ·key-sequence· is equal to the
+                //   keyref member's ·key-sequence· member for member.
+                let resolved = own_table.is_some_and(|t| t.table.contains_key(key_sequence))
+                    || propagated_table.is_some_and(|t| {
+                        t.entries.contains_key(key_sequence)
+                            && !t.conflicting.contains(key_sequence)
+                    });
+                if !resolved {
+                    let kind = IdentityConstraintViolationKind::UnresolvedKeyref {
+                        key_sequence: key_sequence
+                            .iter()
+                            .map(|value| value.normalized.clone())
+                            .collect(),
+                    };
+                    violations.push(
+                        *position,
+                        path.clone(),
+                        kind.code(&scope.icd.identity_constraint_category),
+                        kind.message(&scope.name),
+                    );
+                }
+            }
+        }
+
+        // The node tables of the keys are propagated to the ancestors of E
+        if depth == 0 {
+            return;
+        }
+        let mut to_parent = propagated;
+        for scope in ended
+            .into_iter()
+            .filter(|scope| self.referenced_keys.contains(&scope.icd_ref))
+        {
+            let table = to_parent.entry(scope.icd_ref).or_default();
+            for (key_sequence, (node, _)) in &scope.table {
+                table.insert(key_sequence, *node);
+            }
+            table.conflicting.extend(scope.duplicates);
+        }
+        if to_parent.is_empty() {
+            return;
+        }
+        let parent = match self.propagated.last_mut() {
+            Some((d, parent)) if *d == depth - 1 => parent,
+            _ => {
+                self.propagated.push((depth - 1, HashMap::new()));
+                &mut self.propagated.last_mut().unwrap().1
+            }
+        };
+        for (key, table) in to_parent {
+            parent.entry(key).or_default().merge(table);
+        }
+    }
+}
+
+impl Scope<'_> {
+    /// Adds the ·key-sequence· of `target`, whose fields have been evaluated, to the ·node
+    /// table·
+    fn complete_target(&mut self, target: Target, violations: &mut Violations) {
+        let category = &self.icd.identity_constraint_category;
+        let mut violation = |kind: IdentityConstraintViolationKind, path, position| {
+            violations.push(
+                position,
+                path,
+                kind.code(category),
+                kind.message(&self.name),
+            );
+        };
+        let is_key = matches!(category, IdentityConstraintCategory::Key);
+        let mut key_sequence = Vec::with_capacity(target.fields.len());
+        for field in target.fields {
+            // 3 For each node in the ·target node set· all of the {fields}, with that node as
+            //   the context node, evaluate to either an empty sequence or a sequence with
+            //   exactly one member, which is either an element or attribute node with a simple
+            //   type.
+            let value = match field {
+                FieldValue::Multiple => {
+                    violation(
+                        IdentityConstraintViolationKind::MultipleFieldNodes,
+                        target.path,
+                        target.position,
+                    );
+                    return;
+                }
+                FieldValue::Complex(path, position) => {
+                    violation(
+                        IdentityConstraintViolationKind::ComplexFieldNode,
+                        path,
+                        position,
+                    );
+                    return;
+                }
+                // 4.2.3 No element member of the ·key-sequence· of any member of the ·qualified
+                //   node set· was assessed as ·valid· by reference to an element declaration
+                //   whose {nillable} is true.
+                FieldValue::Nillable(_, path, position) if is_key => {
+                    violation(
+                        IdentityConstraintViolationKind::NillableKeyField,
+                        path,
+                        position,
+                    );
+                    return;
+                }
+                FieldValue::Nillable(value, ..) | FieldValue::Node(value) => value,
+                FieldValue::Absent | FieldValue::Pending => None,
+            };
+            // Nodes whose fields evaluate to an ·absent· [schema actual value] are not in the
+            // ·qualified node set·
+            let Some(value) = value else {
+                // 4.2.1 The ·target node set· and the ·qualified node set· are equal
+                if is_key {
+                    violation(
+                        IdentityConstraintViolationKind::MissingKeyField,
+                        target.path,
+                        target.position,
+                    );
+                }
+                return;
+            };
+            key_sequence.push(value);
+        }
+
+        match category {
+            // 4.1 / 4.2.2 No two members of the ·qualified node set· have ·key-sequences· whose
+            //   members are pairwise equal, as defined by Equality in XML Schema: Datatypes.
+            IdentityConstraintCategory::Unique | IdentityConstraintCategory::Key => {
+                if let Some((_, other)) = self.table.get(&key_sequence) {
+                    let other = other.clone();
+                    self.duplicates.insert(key_sequence);
+                    violation(
+                        IdentityConstraintViolationKind::Duplicate { other },
+                        target.path,
+                        target.position,
+                    );
+                } else {
+                    self.table.insert(key_sequence, (target.node, target.path));
+                }
+            }
+            IdentityConstraintCategory::KeyRef => {
+                self.keyrefs
+                    .push((key_sequence, target.path, target.position));
+            }
+        }
+    }
+}
+
+/// Evaluates the {fields} of `icd` for `target` on the element which just started, whose name is
+/// the last of `names`, the names of the elements from a child of the target down to the element
+fn select_fields(
+    icd: &IdentityConstraintDefinition,
+    target: &mut Target,
+    names: &[QName],
+    attributes: &[(&QName, Option<KeyValue>)],
+    path: &str,
+    position: TextPos,
+) {
+    let depth = target.depth + names.len();
+    for (i, field) in icd.field_paths.iter().enumerate() {
+        let mut selected_element = false;
+        let mut selected_attributes = Vec::new();
+        for field_path in field_paths(field).filter(|p| selects(p, names)) {
+            match &field_path.attribute {
+                None => selected_element = true,
+                Some(name_test) => selected_attributes.extend(
+                    attributes.iter().enumerate().filter(|(_, (name, _))| {
+                        name_test.matches(name.namespace_name(), name.local_name())
+                    }),
+                ),
+            }
+        }
+        selected_attributes.sort_by_key(|(i, _)| *i);
+        selected_attributes.dedup_by_key(|(i, _)| *i);
+
+        let slot = &mut target.fields[i];
+        for (_, (_, value)) in selected_attributes {
+            *slot = match slot {
+                FieldValue::Absent => FieldValue::Node(value.clone()),
+                _ => FieldValue::Multiple,
+            };
+        }
+        if selected_element {
+            *slot = match slot {
+                FieldValue::Absent => {
+                    target.captures.push(Capture {
+                        field: i,
+                        depth,
+                        path: path.to_string(),
+                        position,
+                        complex: false,
+                    });
+                    FieldValue::Pending
+                }
+                _ => FieldValue::Multiple,
+            };
+        }
+    }
+}
+
+fn field_paths(field: &IdentityPath) -> impl Iterator<Item = &Path> {
+    field.paths.iter()
+}
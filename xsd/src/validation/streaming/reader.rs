@@ -0,0 +1,589 @@
+//! A pull parser producing the [`XmlEvent`]s of a document read from a [`BufRead`]
+
+use std::io::BufRead;
+
+use super::{super::value::is_ncname, EventAttribute, StartElement, XmlEvent};
+use crate::{assertion::NamespaceBinding, xstypes::QName};
+use roxmltree::TextPos;
+use thiserror::Error;
+
+pub(super) const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+
+/// The size in bytes from which character data is reported in several [`XmlEvent::Text`] events
+const TEXT_CHUNK_SIZE: usize = 8192;
+
+#[derive(Debug, Error)]
+pub enum ReadError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The document is not a well-formed, namespace-well-formed XML document
+    #[error("{}:{}: {message}", position.row, position.col)]
+    Syntax { message: String, position: TextPos },
+}
+
+/// Reads a document as a sequence of [`XmlEvent`]s, without keeping more than the open elements
+/// and their namespace bindings in memory.
+///
+/// The document must be encoded in UTF-8. Comments, processing instructions and the XML
+/// declaration are skipped. A document type declaration is skipped as well if allowed, but the
+/// entities it declares are not supported. Character data is reported in chunks of bounded size,
+/// so that large text nodes are not loaded into memory either.
+pub struct EventReader<R> {
+    input: R,
+    allow_dtd: bool,
+    /// The position of the next byte of `input`
+    position: TextPos,
+    /// The qualified names of the open elements, for matching their end tags
+    open_elements: Vec<String>,
+    /// The namespace bindings in scope, in the order of their declaration, and the number of
+    /// bindings declared by each open element. The default namespace has an empty prefix, and is
+    /// undeclared by binding it to the empty string.
+    namespaces: Vec<NamespaceBinding>,
+    declared_namespaces: Vec<usize>,
+    /// Set after an empty-element tag, whose end is reported by the next event
+    pending_end: bool,
+    /// Set while the content of a CDATA section is being reported
+    in_cdata: bool,
+    seen_root: bool,
+}
+
+impl<R: BufRead> EventReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            allow_dtd: false,
+            position: TextPos::new(1, 1),
+            open_elements: Vec::new(),
+            namespaces: Vec::new(),
+            declared_namespaces: Vec::new(),
+            pending_end: false,
+            in_cdata: false,
+            seen_root: false,
+        }
+    }
+
+    /// Allows a document type declaration to occur
+    pub fn allow_dtd(mut self, allow_dtd: bool) -> Self {
+        self.allow_dtd = allow_dtd;
+        self
+    }
+
+    /// Returns the next event, or `None` at the end of the document
+    pub fn next_event(&mut self) -> Result<Option<XmlEvent>, ReadError> {
+        if self.position == TextPos::new(1, 1)
+            && self.input.fill_buf()?.starts_with(b"\xEF\xBB\xBF")
+        {
+            // The byte order mark is not part of the document
+            self.input.consume(3);
+        }
+        loop {
+            if self.pending_end {
+                self.pending_end = false;
+                self.close_element();
+                return Ok(Some(XmlEvent::EndElement));
+            }
+            if self.in_cdata {
+                return Ok(Some(XmlEvent::Text(self.cdata()?)));
+            }
+            let position = self.position;
+            match self.peek()? {
+                None => {
+                    if !self.open_elements.is_empty() {
+                        return self.error("unexpected end of the document");
+                    }
+                    if !self.seen_root {
+                        return self.error("the document has no root element");
+                    }
+                    return Ok(None);
+                }
+                Some(b'<') => {
+                    self.bump()?;
+                    match self.peek()? {
+                        Some(b'?') => self.skip_until(b"?>")?,
+                        Some(b'!') => {
+                            self.bump()?;
+                            match self.bump()? {
+                                Some(b'-') => {
+                                    self.expect(b"-")?;
+                                    self.skip_until(b"-->")?;
+                                }
+                                Some(b'[') if !self.open_elements.is_empty() => {
+                                    self.expect(b"CDATA[")?;
+                                    self.in_cdata = true;
+                                    return Ok(Some(XmlEvent::Text(self.cdata()?)));
+                                }
+                                Some(b'D') if !self.seen_root => {
+                                    self.expect(b"OCTYPE")?;
+                                    if !self.allow_dtd {
+                                        return self.error_at(
+                                            "a document type declaration is not allowed",
+                                            position,
+                                        );
+                                    }
+                                    self.skip_doctype()?;
+                                }
+                                _ => return self.error_at("unexpected markup", position),
+                            }
+                        }
+                        Some(b'/') => {
+                            self.bump()?;
+                            let name = self.name()?;
+                            self.skip_whitespace()?;
+                            self.expect(b">")?;
+                            if self.open_elements.last() != Some(&name) {
+                                return self
+                                    .error_at(&format!("unexpected end tag </{name}>"), position);
+                            }
+                            self.close_element();
+                            return Ok(Some(XmlEvent::EndElement));
+                        }
+                        _ => {
+                            if self.seen_root && self.open_elements.is_empty() {
+                                return self.error_at("multiple root elements", position);
+                            }
+                            self.seen_root = true;
+                            let start = self.start_tag(position)?;
+                            return Ok(Some(XmlEvent::StartElement(start)));
+                        }
+                    }
+                }
+                Some(_) => {
+                    let text = self.text()?;
+                    if !self.open_elements.is_empty() {
+                        return Ok(Some(XmlEvent::Text(text)));
+                    }
+                    if !text.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+                        return self
+                            .error_at("character data outside of the root element", position);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the remainder of a start tag beginning at `position`, after its `<`
+    fn start_tag(&mut self, position: TextPos) -> Result<StartElement, ReadError> {
+        let qualified_name = self.name()?;
+        let mut attributes = Vec::<(String, String, TextPos)>::new();
+        loop {
+            let had_whitespace = self.skip_whitespace()?;
+            match self.peek()? {
+                Some(b'/') => {
+                    self.bump()?;
+                    self.expect(b">")?;
+                    self.pending_end = true;
+                    break;
+                }
+                Some(b'>') => {
+                    self.bump()?;
+                    break;
+                }
+                Some(_) if had_whitespace => {
+                    let attribute_position = self.position;
+                    let name = self.name()?;
+                    self.skip_whitespace()?;
+                    self.expect(b"=")?;
+                    self.skip_whitespace()?;
+                    let value = self.attribute_value()?;
+                    if attributes.iter().any(|(other, _, _)| *other == name) {
+                        return self
+                            .error_at(&format!("duplicate attribute {name}"), attribute_position);
+                    }
+                    attributes.push((name, value, attribute_position));
+                }
+                _ => return self.error("expected an attribute, '>' or '/>'"),
+            }
+        }
+
+        // Namespace declarations are in scope for the element itself and its attributes
+        let mut namespaces = Vec::new();
+        attributes.retain(|(name, value, _)| {
+            let prefix = match name.split_once(':') {
+                None if name == "xmlns" => "",
+                Some(("xmlns", prefix)) => prefix,
+                _ => return true,
+            };
+            namespaces.push(NamespaceBinding {
+                prefix: prefix.to_string(),
+                namespace: value.clone(),
+            });
+            false
+        });
+        self.declared_namespaces.push(namespaces.len());
+        self.namespaces.extend(namespaces.iter().cloned());
+        self.open_elements.push(qualified_name.clone());
+
+        let name = self.resolve(&qualified_name, true, position)?;
+        let mut resolved_attributes: Vec<EventAttribute> = Vec::with_capacity(attributes.len());
+        for (qualified_name, value, position) in attributes {
+            let name = self.resolve(&qualified_name, false, position)?;
+            if resolved_attributes.iter().any(|a| a.name == name) {
+                return self.error_at(&format!("duplicate attribute {name}"), position);
+            }
+            resolved_attributes.push(EventAttribute {
+                name,
+                value,
+                position,
+            });
+        }
+        Ok(StartElement {
+            name,
+            attributes: resolved_attributes,
+            namespaces,
+            position,
+        })
+    }
+
+    fn close_element(&mut self) {
+        self.open_elements.pop();
+        let declared = self.declared_namespaces.pop().unwrap_or_default();
+        self.namespaces.truncate(self.namespaces.len() - declared);
+    }
+
+    /// Resolves the prefix of a qualified name using the namespace bindings in scope. The default
+    /// namespace only applies to element names.
+    fn resolve(
+        &self,
+        qualified_name: &str,
+        is_element: bool,
+        position: TextPos,
+    ) -> Result<QName, ReadError> {
+        let (prefix, local_name) = match qualified_name.split_once(':') {
+            Some((prefix, local_name)) => (prefix, local_name),
+            None if is_element => ("", qualified_name),
+            None => return Ok(QName::without_namespace(qualified_name)),
+        };
+        let namespace = match prefix {
+            "xml" => Some(XML_NAMESPACE),
+            "xmlns" => Some(XMLNS_NAMESPACE),
+            _ => self
+                .namespaces
+                .iter()
+                .rev()
+                .find(|binding| binding.prefix == prefix)
+                .map(|binding| binding.namespace.as_str()),
+        };
+        match namespace {
+            Some("") | None if prefix.is_empty() => Ok(QName::without_namespace(local_name)),
+            Some("") | None => self.error_at(&format!("unbound prefix {prefix:?}"), position),
+            Some(namespace) => Ok(QName::with_namespace(namespace, local_name)),
+        }
+    }
+
+    /// Reads character data up to the next `<`, or a chunk of it, replacing references and
+    /// normalizing line ends
+    fn text(&mut self) -> Result<String, ReadError> {
+        let mut text = Vec::new();
+        // The number of `]` read last, which must not be followed by `>` (XML 1.0 §2.4)
+        let mut brackets = 0;
+        while let Some(b) = self.peek()? {
+            if self.at_chunk_end(&text, b) {
+                break;
+            }
+            match b {
+                b'<' => break,
+                b'&' => {
+                    self.reference(&mut text)?;
+                    brackets = 0;
+                    continue;
+                }
+                b'>' if brackets >= 2 => {
+                    return self.error("']]>' is not allowed in character data");
+                }
+                b'\r' => {
+                    self.bump()?;
+                    if self.peek()? != Some(b'\n') {
+                        text.push(b'\n');
+                    }
+                }
+                _ => {
+                    self.bump()?;
+                    text.push(b);
+                }
+            }
+            brackets = if b == b']' { brackets + 1 } else { 0 };
+        }
+        self.string(text)
+    }
+
+    /// Reads the content of a CDATA section up to its `]]>`, or a chunk of it, normalizing line
+    /// ends
+    fn cdata(&mut self) -> Result<String, ReadError> {
+        let mut text = Vec::new();
+        loop {
+            match self.peek()? {
+                None => return self.error("unexpected end of the document"),
+                Some(b) if self.at_chunk_end(&text, b) => break,
+                Some(b'>') if text.ends_with(b"]]") => {
+                    self.bump()?;
+                    text.truncate(text.len() - 2);
+                    self.in_cdata = false;
+                    break;
+                }
+                Some(b'\r') => {
+                    self.bump()?;
+                    if self.peek()? != Some(b'\n') {
+                        text.push(b'\n');
+                    }
+                }
+                Some(b) => {
+                    self.bump()?;
+                    text.push(b);
+                }
+            }
+        }
+        self.string(text)
+    }
+
+    /// Returns whether a chunk of character data ends with `text`, before the byte `next`. A
+    /// chunk neither ends within a character nor after a `]`, which may start a `]]>`.
+    fn at_chunk_end(&self, text: &[u8], next: u8) -> bool {
+        text.len() >= TEXT_CHUNK_SIZE && next & 0xC0 != 0x80 && text.last() != Some(&b']')
+    }
+
+    /// Reads a quoted attribute value, normalized as in Attribute-Value Normalization (XML 1.0
+    /// §3.3.3)
+    fn attribute_value(&mut self) -> Result<String, ReadError> {
+        let quote = match self.bump()? {
+            Some(quote @ (b'"' | b'\'')) => quote,
+            _ => return self.error("expected a quoted attribute value"),
+        };
+        let mut value = Vec::new();
+        loop {
+            match self.peek()? {
+                None => return self.error("unexpected end of the document"),
+                Some(b) if b == quote => {
+                    self.bump()?;
+                    break;
+                }
+                Some(b'<') => return self.error("'<' is not allowed in attribute values"),
+                Some(b'&') => self.reference(&mut value)?,
+                Some(b'\r') => {
+                    self.bump()?;
+                    if self.peek()? != Some(b'\n') {
+                        value.push(b' ');
+                    }
+                }
+                Some(b'\t' | b'\n') => {
+                    self.bump()?;
+                    value.push(b' ');
+                }
+                Some(b) => {
+                    self.bump()?;
+                    value.push(b);
+                }
+            }
+        }
+        self.string(value)
+    }
+
+    /// Reads an entity or character reference, appending its replacement text to `output`
+    fn reference(&mut self, output: &mut Vec<u8>) -> Result<(), ReadError> {
+        let position = self.position;
+        self.bump()?;
+        let mut name = Vec::new();
+        loop {
+            match self.bump()? {
+                Some(b';') => break,
+                Some(b) if name.len() < 32 && !b.is_ascii_whitespace() && b != b'<' => name.push(b),
+                _ => return self.error_at("malformed reference", position),
+            }
+        }
+        let c = match name.as_slice() {
+            b"lt" => '<',
+            b"gt" => '>',
+            b"amp" => '&',
+            b"apos" => '\'',
+            b"quot" => '"',
+            [b'#', b'x', digits @ ..] => self.character(digits, 16, position)?,
+            [b'#', digits @ ..] => self.character(digits, 10, position)?,
+            _ => {
+                return self.error_at(
+                    &format!(
+                        "unknown entity {:?}",
+                        String::from_utf8_lossy(&name).into_owned()
+                    ),
+                    position,
+                );
+            }
+        };
+        output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        Ok(())
+    }
+
+    fn character(&self, digits: &[u8], radix: u32, position: TextPos) -> Result<char, ReadError> {
+        std::str::from_utf8(digits)
+            .ok()
+            .filter(|digits| !digits.is_empty() && !digits.starts_with('+'))
+            .and_then(|digits| u32::from_str_radix(digits, radix).ok())
+            .and_then(char::from_u32)
+            .filter(|&c| c != '\0')
+            .map_or_else(
+                || self.error_at("invalid character reference", position),
+                Ok,
+            )
+    }
+
+    /// Reads a qualified name, up to the next whitespace, `/`, `>` or `=`
+    fn name(&mut self) -> Result<String, ReadError> {
+        let position = self.position;
+        let mut name = Vec::new();
+        while let Some(b) = self.peek()? {
+            if b.is_ascii_whitespace() || matches!(b, b'/' | b'>' | b'=') {
+                break;
+            }
+            if matches!(b, b'<' | b'&' | b'"' | b'\'') {
+                return self.error("invalid character in name");
+            }
+            self.bump()?;
+            name.push(b);
+        }
+        if name.is_empty() {
+            return self.error("expected a name");
+        }
+        let name = self.string(name)?;
+        // The QName production of Namespaces in XML 1.0, §4
+        let is_qname = match name.split_once(':') {
+            Some((prefix, local_name)) => is_ncname(prefix) && is_ncname(local_name),
+            None => is_ncname(&name),
+        };
+        if !is_qname {
+            return self.error_at(&format!("invalid name {name:?}"), position);
+        }
+        Ok(name)
+    }
+
+    /// Skips a document type declaration, after `<!DOCTYPE`, including its internal subset
+    fn skip_doctype(&mut self) -> Result<(), ReadError> {
+        let mut depth = 0;
+        let mut quote = None;
+        loop {
+            match (self.bump()?, quote) {
+                (None, _) => return self.error("unexpected end of the document"),
+                (Some(b), Some(q)) if b == q => quote = None,
+                (Some(_), Some(_)) => {}
+                (Some(q @ (b'"' | b'\'')), None) => quote = Some(q),
+                (Some(b'['), None) => depth += 1,
+                (Some(b']'), None) => depth -= 1,
+                (Some(b'>'), None) if depth == 0 => return Ok(()),
+                (Some(_), None) => {}
+            }
+        }
+    }
+
+    /// Skips input up to and including `delimiter`, only keeping as many bytes as it has
+    fn skip_until(&mut self, delimiter: &[u8]) -> Result<(), ReadError> {
+        let mut last = Vec::with_capacity(delimiter.len());
+        while last != delimiter {
+            let Some(b) = self.bump()? else {
+                return self.error("unexpected end of the document");
+            };
+            if last.len() == delimiter.len() {
+                last.remove(0);
+            }
+            last.push(b);
+        }
+        Ok(())
+    }
+
+    /// Skips whitespace, returning whether there was any
+    fn skip_whitespace(&mut self) -> Result<bool, ReadError> {
+        let mut skipped = false;
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.bump()?;
+            skipped = true;
+        }
+        Ok(skipped)
+    }
+
+    fn expect(&mut self, expected: &[u8]) -> Result<(), ReadError> {
+        for &b in expected {
+            if self.bump()? != Some(b) {
+                return self.error(&format!(
+                    "expected {:?}",
+                    String::from_utf8_lossy(expected).into_owned()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, ReadError> {
+        Ok(self.input.fill_buf()?.first().copied())
+    }
+
+    fn bump(&mut self) -> Result<Option<u8>, ReadError> {
+        let b = self.peek()?;
+        if let Some(b) = b {
+            self.input.consume(1);
+            if b == b'\n' {
+                self.position.row += 1;
+                self.position.col = 1;
+            } else if b & 0xC0 != 0x80 {
+                // Columns count characters, i.e. all bytes but UTF-8 continuation bytes
+                self.position.col += 1;
+            }
+        }
+        Ok(b)
+    }
+
+    fn string(&self, bytes: Vec<u8>) -> Result<String, ReadError> {
+        String::from_utf8(bytes).or_else(|_| self.error("invalid UTF-8"))
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ReadError> {
+        self.error_at(message, self.position)
+    }
+
+    fn error_at<T>(&self, message: &str, position: TextPos) -> Result<T, ReadError> {
+        Err(ReadError::Syntax {
+            message: message.to_string(),
+            position,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventReader, ReadError, TEXT_CHUNK_SIZE, XmlEvent};
+
+    /// Returns the character data of the events of `document`, one string per event
+    fn texts(document: &str) -> Result<Vec<String>, ReadError> {
+        let mut reader = EventReader::new(document.as_bytes());
+        let mut texts = Vec::new();
+        while let Some(event) = reader.next_event()? {
+            if let XmlEvent::Text(text) = event {
+                texts.push(text);
+            }
+        }
+        Ok(texts)
+    }
+
+    #[test]
+    fn well_formedness_and_chunks() {
+        // Names must match the QName production
+        assert!(texts("<a:b-c.d xmlns:a='urn:a'/>").is_ok());
+        for document in ["<1a/>", "<a b:c:d='1'/>", "<a -b='1'/>", "<a>text</a!>"] {
+            assert!(
+                matches!(texts(document), Err(ReadError::Syntax { .. })),
+                "{document}"
+            );
+        }
+
+        // ']]>' may only end a CDATA section
+        assert_eq!(texts("<a>]]&gt;]]</a>").unwrap(), ["]]>]]"]);
+        assert!(texts("<a>x]]>y</a>").is_err());
+        assert!(texts("<a>x]]]>y</a>").is_err());
+
+        // Large character data, CDATA sections and comments are read in chunks
+        let large = "é]".repeat(TEXT_CHUNK_SIZE);
+        let texts = texts(&format!(
+            "<a>{large}<!--{large}--><![CDATA[{large}]]]]><![CDATA[>\r\n]]></a>"
+        ))
+        .unwrap();
+        assert!(texts.len() > 4);
+        assert!(texts.iter().all(|text| text.len() <= TEXT_CHUNK_SIZE + 4));
+        assert_eq!(texts.concat(), format!("{large}{large}]]>\n"));
+    }
+}
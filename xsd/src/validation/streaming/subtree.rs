@@ -0,0 +1,146 @@
+//! The subtrees of the open elements whose ·governing type definition· has assertions, which are
+//! evaluated on the whole subtree of an element (Assertion Satisfied (§3.13.4.1)).
+//!
+//! The events of such an element and its descendants are serialized as a document of its own,
+//! which is parsed once the element ends. Only these subtrees are kept in memory.
+
+use std::fmt::Write;
+
+use super::{
+    super::value::NamespaceContext, EventAttribute, InScopeNamespaces, reader::XML_NAMESPACE,
+};
+use crate::{assertion::NamespaceBinding, xstypes::QName};
+
+/// The serialization of the subtree of the outermost open element with assertions, in which the
+/// subtrees of the elements with assertions among its descendants are nested
+#[derive(Default)]
+pub(super) struct Subtrees {
+    buffer: String,
+}
+
+impl Subtrees {
+    /// Returns whether an element with assertions is open, i.e. whether the events belong to its
+    /// subtree
+    pub(super) fn is_open(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Adds the start tag of an element, whose namespace bindings are the last `declared` of the
+    /// bindings in scope `namespaces`. If `root`, the element starts a subtree of its own, whose
+    /// offset is returned.
+    pub(super) fn start_element(
+        &mut self,
+        name: &QName,
+        attributes: &[EventAttribute],
+        namespaces: &[NamespaceBinding],
+        declared: usize,
+        root: bool,
+    ) -> usize {
+        let offset = self.buffer.len();
+        let context = InScopeNamespaces(namespaces);
+        self.buffer.push('<');
+        write_name(&mut self.buffer, name, &context, false);
+        // The root of a subtree declares all the namespaces in scope, which its attributes and
+        // character data may refer to
+        let bindings = if root {
+            namespaces
+                .iter()
+                .enumerate()
+                .filter(|(i, binding)| {
+                    !binding.namespace.is_empty()
+                        && !namespaces[i + 1..]
+                            .iter()
+                            .any(|inner| inner.prefix == binding.prefix)
+                })
+                .map(|(_, binding)| binding)
+                .collect::<Vec<_>>()
+        } else {
+            namespaces[namespaces.len() - declared..].iter().collect()
+        };
+        for binding in bindings {
+            match binding.prefix.as_str() {
+                "" => self.buffer.push_str(" xmlns=\""),
+                prefix => {
+                    let _ = write!(self.buffer, " xmlns:{prefix}=\"");
+                }
+            }
+            escape(&mut self.buffer, &binding.namespace, true);
+            self.buffer.push('"');
+        }
+        for a in attributes {
+            self.buffer.push(' ');
+            write_name(&mut self.buffer, &a.name, &context, true);
+            self.buffer.push_str("=\"");
+            escape(&mut self.buffer, &a.value, true);
+            self.buffer.push('"');
+        }
+        self.buffer.push('>');
+        offset
+    }
+
+    pub(super) fn text(&mut self, text: &str) {
+        escape(&mut self.buffer, text, false);
+    }
+
+    /// Adds the end tag of the element named `name`, with the bindings in scope `namespaces`
+    pub(super) fn end_element(&mut self, name: &QName, namespaces: &[NamespaceBinding]) {
+        self.buffer.push_str("</");
+        write_name(
+            &mut self.buffer,
+            name,
+            &InScopeNamespaces(namespaces),
+            false,
+        );
+        self.buffer.push('>');
+    }
+
+    /// Returns the subtree of the element at `offset`, which has ended
+    pub(super) fn subtree(&self, offset: usize) -> &str {
+        &self.buffer[offset..]
+    }
+
+    /// Discards the subtrees once the outermost element with assertions has ended
+    pub(super) fn clear(&mut self) {
+        self.buffer.clear();
+    }
+}
+
+/// Writes `name` with a prefix bound to its namespace in `context`. Such a prefix exists, as the
+/// name was resolved against the same bindings; unprefixed attribute names have no namespace.
+fn write_name(buffer: &mut String, name: &QName, context: &InScopeNamespaces, attribute: bool) {
+    if let Some(namespace) = name.namespace_name() {
+        let prefix = if namespace == XML_NAMESPACE {
+            Some("xml")
+        } else {
+            context
+                .0
+                .iter()
+                .rev()
+                .map(|binding| binding.prefix.as_str())
+                .filter(|prefix| !(attribute && prefix.is_empty()))
+                .find(|prefix| context.namespace_uri(Some(prefix)) == Some(namespace))
+        };
+        if let Some(prefix) = prefix.filter(|prefix| !prefix.is_empty()) {
+            buffer.push_str(prefix);
+            buffer.push(':');
+        }
+    }
+    buffer.push_str(name.local_name());
+}
+
+/// Escapes the markup characters of `text`, and the characters which a parser would normalize
+fn escape(buffer: &mut String, text: &str, attribute: bool) {
+    for c in text.chars() {
+        match c {
+            '&' => buffer.push_str("&amp;"),
+            '<' => buffer.push_str("&lt;"),
+            '>' => buffer.push_str("&gt;"),
+            '"' if attribute => buffer.push_str("&quot;"),
+            '\r' => buffer.push_str("&#13;"),
+            '\t' | '\n' if attribute => {
+                let _ = write!(buffer, "&#{};", c as u32);
+            }
+            c => buffer.push(c),
+        }
+    }
+}
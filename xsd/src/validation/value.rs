@@ -0,0 +1,1267 @@
+//! Value spaces and lexical mappings of the primitive datatypes (pt. 2, §3.3)
+
+use crate::{assertion::NamespaceBinding, xstypes::QName};
+use roxmltree::Node;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Add, Mul, Neg, Sub},
+};
+
+/// The primitive datatypes (pt. 2, §3.3)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    String,
+    Boolean,
+    Decimal,
+    Float,
+    Double,
+    Duration,
+    DateTime,
+    Time,
+    Date,
+    GYearMonth,
+    GYear,
+    GMonthDay,
+    GDay,
+    GMonth,
+    HexBinary,
+    Base64Binary,
+    AnyURI,
+    QName,
+    Notation,
+}
+
+impl Primitive {
+    const ALL: [Self; 19] = [
+        Self::String,
+        Self::Boolean,
+        Self::Decimal,
+        Self::Float,
+        Self::Double,
+        Self::Duration,
+        Self::DateTime,
+        Self::Time,
+        Self::Date,
+        Self::GYearMonth,
+        Self::GYear,
+        Self::GMonthDay,
+        Self::GDay,
+        Self::GMonth,
+        Self::HexBinary,
+        Self::Base64Binary,
+        Self::AnyURI,
+        Self::QName,
+        Self::Notation,
+    ];
+
+    /// Returns the primitive datatype with the given local name in the XSD namespace
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Boolean => "boolean",
+            Self::Decimal => "decimal",
+            Self::Float => "float",
+            Self::Double => "double",
+            Self::Duration => "duration",
+            Self::DateTime => "dateTime",
+            Self::Time => "time",
+            Self::Date => "date",
+            Self::GYearMonth => "gYearMonth",
+            Self::GYear => "gYear",
+            Self::GMonthDay => "gMonthDay",
+            Self::GDay => "gDay",
+            Self::GMonth => "gMonth",
+            Self::HexBinary => "hexBinary",
+            Self::Base64Binary => "base64Binary",
+            Self::AnyURI => "anyURI",
+            Self::QName => "QName",
+            Self::Notation => "NOTATION",
+        }
+    }
+}
+
+/// The namespace bindings against which the prefixes of QName and NOTATION literals are resolved:
+/// either the [in-scope namespaces] of an element information item, or a set of Namespace Binding
+/// property records captured from a schema document.
+pub trait NamespaceContext {
+    /// Returns the namespace name bound to `prefix`, or the default namespace if `prefix` is
+    /// `None`. Returns `None` if there is no such binding.
+    fn namespace_uri(&self, prefix: Option<&str>) -> Option<&str>;
+}
+
+impl NamespaceContext for Node<'_, '_> {
+    fn namespace_uri(&self, prefix: Option<&str>) -> Option<&str> {
+        self.lookup_namespace_uri(prefix)
+    }
+}
+
+impl NamespaceContext for Vec<NamespaceBinding> {
+    fn namespace_uri(&self, prefix: Option<&str>) -> Option<&str> {
+        // The default namespace is recorded with an empty prefix
+        let prefix = prefix.unwrap_or_default();
+        self.iter()
+            .find(|binding| binding.prefix == prefix)
+            .map(|binding| binding.namespace.as_str())
+    }
+}
+
+/// An actual value of a simple type
+#[derive(Clone, Debug)]
+pub enum Value {
+    /// A value of `xs:string`, or of `xs:anySimpleType` or `xs:anyAtomicType`
+    String(String),
+    Boolean(bool),
+    Decimal(Decimal),
+    Float(f32),
+    Double(f64),
+    Duration(Duration),
+    /// A value of one of the date/time datatypes, `dateTime` through `gMonth`
+    DateTime(Primitive, DateTime),
+    HexBinary(Vec<u8>),
+    Base64Binary(Vec<u8>),
+    AnyURI(String),
+    QName(QName),
+    Notation(QName),
+    /// A value of a list datatype
+    List(Vec<Value>),
+}
+
+impl Value {
+    /// Maps a whitespace-normalized literal to a value of the primitive datatype, or returns
+    /// `None` if the literal is not in its lexical space. QName and NOTATION literals are resolved
+    /// against `context`.
+    pub fn parse(
+        primitive: Primitive,
+        literal: &str,
+        context: &dyn NamespaceContext,
+    ) -> Option<Self> {
+        match primitive {
+            Primitive::String => Some(Self::String(literal.to_string())),
+            Primitive::Boolean => match literal {
+                "true" | "1" => Some(Self::Boolean(true)),
+                "false" | "0" => Some(Self::Boolean(false)),
+                _ => None,
+            },
+            Primitive::Decimal => Decimal::parse(literal).map(Self::Decimal),
+            Primitive::Float => {
+                float_literal_value::<f32>(literal, f32::INFINITY, f32::NAN).map(Self::Float)
+            }
+            Primitive::Double => {
+                float_literal_value::<f64>(literal, f64::INFINITY, f64::NAN).map(Self::Double)
+            }
+            Primitive::Duration => Duration::parse(literal).map(Self::Duration),
+            Primitive::DateTime
+            | Primitive::Time
+            | Primitive::Date
+            | Primitive::GYearMonth
+            | Primitive::GYear
+            | Primitive::GMonthDay
+            | Primitive::GDay
+            | Primitive::GMonth => {
+                DateTime::parse(primitive, literal).map(|d| Self::DateTime(primitive, d))
+            }
+            Primitive::HexBinary => hex_binary_value(literal).map(Self::HexBinary),
+            Primitive::Base64Binary => base64_binary_value(literal).map(Self::Base64Binary),
+            // Any string is accepted; checking the URI syntax is not required (pt. 2, §3.3.17.1)
+            Primitive::AnyURI => Some(Self::AnyURI(literal.to_string())),
+            Primitive::QName => qname_value(literal, context).map(Self::QName),
+            Primitive::Notation => qname_value(literal, context).map(Self::Notation),
+        }
+    }
+
+    /// Returns the primitive datatype of the value, or `None` for list values
+    pub fn primitive(&self) -> Option<Primitive> {
+        Some(match self {
+            Self::String(_) => Primitive::String,
+            Self::Boolean(_) => Primitive::Boolean,
+            Self::Decimal(_) => Primitive::Decimal,
+            Self::Float(_) => Primitive::Float,
+            Self::Double(_) => Primitive::Double,
+            Self::Duration(_) => Primitive::Duration,
+            Self::DateTime(primitive, _) => *primitive,
+            Self::HexBinary(_) => Primitive::HexBinary,
+            Self::Base64Binary(_) => Primitive::Base64Binary,
+            Self::AnyURI(_) => Primitive::AnyURI,
+            Self::QName(_) => Primitive::QName,
+            Self::Notation(_) => Primitive::Notation,
+            Self::List(_) => return None,
+        })
+    }
+
+    /// Compares two values in the order of their datatype. Returns `None` if the values are
+    /// incomparable, which includes values of unordered datatypes and of different primitive
+    /// datatypes.
+    pub fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Decimal(a), Self::Decimal(b)) => Some(a.cmp(b)),
+            (Self::Float(a), Self::Float(b)) => a.partial_cmp(b),
+            (Self::Double(a), Self::Double(b)) => a.partial_cmp(b),
+            (Self::Duration(a), Self::Duration(b)) => a.partial_cmp(b),
+            (Self::DateTime(p, a), Self::DateTime(q, b)) if p == q => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+
+    /// The length of the value as measured by the length, minLength and maxLength facets, or
+    /// `None` if these facets do not apply (pt. 2, §4.3.1)
+    pub fn length(&self) -> Option<u64> {
+        match self {
+            Self::String(s) | Self::AnyURI(s) => Some(s.chars().count() as u64),
+            Self::HexBinary(b) | Self::Base64Binary(b) => Some(b.len() as u64),
+            Self::List(items) => Some(items.len() as u64),
+            // Length facets are deprecated for QName and NOTATION and always satisfied
+            _ => None,
+        }
+    }
+}
+
+/// Two values are equal if they are equal or identical in the sense of pt. 2, §2.2.2, i.e. NaN is
+/// identical to itself.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Boolean(a), Self::Boolean(b)) => a == b,
+            (Self::Decimal(a), Self::Decimal(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (Self::Double(a), Self::Double(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (Self::Duration(a), Self::Duration(b)) => a == b,
+            (Self::DateTime(p, a), Self::DateTime(q, b)) => p == q && a == b,
+            (Self::HexBinary(a), Self::HexBinary(b)) => a == b,
+            (Self::Base64Binary(a), Self::Base64Binary(b)) => a == b,
+            (Self::AnyURI(a), Self::AnyURI(b)) => a == b,
+            (Self::QName(a), Self::QName(b)) => a == b,
+            (Self::Notation(a), Self::Notation(b)) => a == b,
+            (Self::List(a), Self::List(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+/// Values which are equal hash alike, so that values can be looked up in hash tables, e.g. the
+/// ·node tables· of identity constraints
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::String(s) | Self::AnyURI(s) => s.hash(state),
+            Self::Boolean(b) => b.hash(state),
+            Self::Decimal(d) => d.hash(state),
+            // Positive and negative zero are equal, and NaN is identical to itself
+            Self::Float(f) if *f == 0.0 || f.is_nan() => (*f == 0.0).hash(state),
+            Self::Float(f) => f.to_bits().hash(state),
+            Self::Double(f) if *f == 0.0 || f.is_nan() => (*f == 0.0).hash(state),
+            Self::Double(f) => f.to_bits().hash(state),
+            Self::Duration(d) => d.hash(state),
+            // Equal date/time values may have different properties, e.g. in different timezones
+            Self::DateTime(primitive, _) => primitive.hash(state),
+            Self::HexBinary(b) | Self::Base64Binary(b) => b.hash(state),
+            Self::QName(name) | Self::Notation(name) => name.hash(state),
+            Self::List(items) => items.hash(state),
+        }
+    }
+}
+
+/// A value of `xs:decimal` (pt. 2, §3.3.3), of arbitrary precision
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    negative: bool,
+    /// Digits before the decimal point, without leading zeros
+    integer: String,
+    /// Digits after the decimal point, without trailing zeros
+    fraction: String,
+}
+
+impl Decimal {
+    /// The lexical mapping of `xs:decimal`:
+    /// `(\+|-)?([0-9]+(\.[0-9]*)?|\.[0-9]+)`
+    pub fn parse(literal: &str) -> Option<Self> {
+        let (negative, unsigned) = match literal.as_bytes().first()? {
+            b'-' => (true, &literal[1..]),
+            b'+' => (false, &literal[1..]),
+            _ => (false, literal),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let integer = integer.trim_start_matches('0').to_string();
+        let fraction = fraction.trim_end_matches('0').to_string();
+        Some(Self {
+            negative: negative && !(integer.is_empty() && fraction.is_empty()),
+            integer,
+            fraction,
+        })
+    }
+
+    /// The number of significant digits, as constrained by the totalDigits facet
+    pub fn total_digits(&self) -> u64 {
+        (self.integer.len() + self.fraction.len()) as u64
+    }
+
+    /// The number of digits after the decimal point, as constrained by the fractionDigits facet
+    pub fn fraction_digits(&self) -> u64 {
+        self.fraction.len() as u64
+    }
+
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        self.integer
+            .len()
+            .cmp(&other.integer.len())
+            .then_with(|| self.integer.cmp(&other.integer))
+            .then_with(|| self.fraction.cmp(&other.fraction))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.integer.is_empty() && self.fraction.is_empty()
+    }
+
+    /// Converts a finite `f64` to the decimal its shortest representation denotes
+    pub fn from_f64(n: f64) -> Option<Self> {
+        // The Display implementation of f64 never uses an exponent
+        n.is_finite().then(|| Self::parse(&n.to_string())).flatten()
+    }
+
+    /// The `f64` closest to the value
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// The value if it is an integer in the range of `i64`
+    pub fn to_i64(&self) -> Option<i64> {
+        if !self.fraction.is_empty() {
+            return None;
+        }
+        self.to_string().parse().ok()
+    }
+
+    /// The digits of the value without the decimal point, and the number of fraction digits
+    fn unscaled(&self) -> (String, usize) {
+        (
+            format!("{}{}", self.integer, self.fraction),
+            self.fraction.len(),
+        )
+    }
+
+    /// The value `digits` * 10^-`scale`, negated if `negative`
+    fn from_unscaled(negative: bool, digits: &str, scale: usize) -> Self {
+        let padding = (scale + 1).saturating_sub(digits.len());
+        let digits = format!("{}{digits}", "0".repeat(padding));
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        let integer = integer.trim_start_matches('0').to_string();
+        let fraction = fraction.trim_end_matches('0').to_string();
+        Self {
+            negative: negative && !(integer.is_empty() && fraction.is_empty()),
+            integer,
+            fraction,
+        }
+    }
+
+    /// The unscaled digits of `self` and `other` with the same number of fraction digits, and
+    /// that number
+    fn aligned(&self, other: &Self) -> (String, String, usize) {
+        let scale = self.fraction.len().max(other.fraction.len());
+        let pad = |d: &Self| {
+            let (digits, d_scale) = d.unscaled();
+            format!("{digits}{}", "0".repeat(scale - d_scale))
+        };
+        (pad(self), pad(other), scale)
+    }
+
+    /// Division, truncated to [`DIVISION_FRACTION_DIGITS`] fraction digits (or as many as the
+    /// dividend has). Returns `None` if `other` is zero.
+    pub fn checked_div(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        // self / other = a / b * 10^(b_scale - a_scale)
+        let (a, a_scale) = self.unscaled();
+        let (b, b_scale) = other.unscaled();
+        let scale = DIVISION_FRACTION_DIGITS.max(a_scale);
+        let shifted = format!("{a}{}", "0".repeat(scale + b_scale - a_scale));
+        let (quotient, _) = divide_digits(&shifted, &b);
+        Some(Self::from_unscaled(
+            self.negative != other.negative,
+            &quotient,
+            scale,
+        ))
+    }
+
+    /// Division truncated towards zero, as by `idiv`. Returns `None` if `other` is zero.
+    pub fn checked_integer_div(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        let (a, b, _) = self.aligned(other);
+        let (quotient, _) = divide_digits(&a, &b);
+        Some(Self::from_unscaled(
+            self.negative != other.negative,
+            &quotient,
+            0,
+        ))
+    }
+
+    /// The remainder of [`Self::checked_integer_div`], which has the sign of `self`, as by
+    /// `mod`. Returns `None` if `other` is zero.
+    pub fn checked_rem(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        let (a, b, scale) = self.aligned(other);
+        let (_, remainder) = divide_digits(&a, &b);
+        Some(Self::from_unscaled(self.negative, &remainder, scale))
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            negative: false,
+            ..self.clone()
+        }
+    }
+
+    /// The value without its fraction digits
+    pub fn trunc(&self) -> Self {
+        Self::from_unscaled(self.negative, &self.integer, 0)
+    }
+
+    /// The largest integer not greater than the value
+    pub fn floor(&self) -> Self {
+        if self.negative && !self.fraction.is_empty() {
+            self.trunc() - Self::from(1)
+        } else {
+            self.trunc()
+        }
+    }
+
+    /// The smallest integer not less than the value
+    pub fn ceiling(&self) -> Self {
+        if !self.negative && !self.fraction.is_empty() {
+            self.trunc() + Self::from(1)
+        } else {
+            self.trunc()
+        }
+    }
+
+    /// The nearest integer, rounding halves towards positive infinity
+    pub fn round(&self) -> Self {
+        (self.clone() + Self::from_unscaled(false, "5", 1)).floor()
+    }
+}
+
+/// The number of fraction digits of a quotient of decimals. The precision of xs:decimal division
+/// is implementation-defined (XPath and XQuery Functions and Operators, §4.2).
+const DIVISION_FRACTION_DIGITS: usize = 18;
+
+/// Compares two strings of decimal digits as unsigned integers
+fn cmp_digits(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+fn add_digits(a: &str, b: &str) -> String {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let (mut a, mut b) = (a.bytes().rev(), b.bytes().rev());
+    let mut carry = 0;
+    loop {
+        let (x, y) = (a.next(), b.next());
+        if x.is_none() && y.is_none() {
+            break;
+        }
+        let sum = x.map_or(0, |x| x - b'0') + y.map_or(0, |y| y - b'0') + carry;
+        result.push(b'0' + sum % 10);
+        carry = sum / 10;
+    }
+    if carry > 0 {
+        result.push(b'0' + carry);
+    }
+    result.reverse();
+    String::from_utf8(result).unwrap()
+}
+
+/// Subtracts `b` from `a`, which must not be less than `b`
+fn subtract_digits(a: &str, b: &str) -> String {
+    let mut result = Vec::with_capacity(a.len());
+    let mut b = b.bytes().rev();
+    let mut borrow = 0;
+    for x in a.bytes().rev() {
+        let y = b.next().map_or(0, |y| y - b'0') + borrow;
+        let x = x - b'0';
+        if x < y {
+            result.push(b'0' + x + 10 - y);
+            borrow = 1;
+        } else {
+            result.push(b'0' + x - y);
+            borrow = 0;
+        }
+    }
+    result.reverse();
+    String::from_utf8(result).unwrap()
+}
+
+fn multiply_digits(a: &str, b: &str) -> String {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.bytes().rev().enumerate() {
+        for (j, y) in b.bytes().rev().enumerate() {
+            result[i + j] += u32::from(x - b'0') * u32::from(y - b'0');
+        }
+        // Propagate the carries, so that no digit exceeds 9 * 9 + 9 * 10
+        for k in 0..result.len() - 1 {
+            result[k + 1] += result[k] / 10;
+            result[k] %= 10;
+        }
+    }
+    result
+        .iter()
+        .rev()
+        .map(|d| char::from(b'0' + *d as u8))
+        .collect()
+}
+
+/// Long division of two strings of decimal digits, returning the quotient and the remainder.
+/// `b` must not be zero.
+fn divide_digits(a: &str, b: &str) -> (String, String) {
+    let mut quotient = String::with_capacity(a.len());
+    let mut remainder = String::new();
+    for digit in a.chars() {
+        remainder.push(digit);
+        let mut q = b'0';
+        while cmp_digits(&remainder, b) != Ordering::Less {
+            remainder = subtract_digits(&remainder, b);
+            q += 1;
+        }
+        quotient.push(char::from(q));
+    }
+    (quotient, remainder)
+}
+
+impl From<i64> for Decimal {
+    fn from(n: i64) -> Self {
+        Self::from_unscaled(n < 0, &n.unsigned_abs().to_string(), 0)
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            negative: !self.negative && !self.is_zero(),
+            ..self
+        }
+    }
+}
+
+impl Add for Decimal {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (a, b, scale) = self.aligned(&other);
+        if self.negative == other.negative {
+            Self::from_unscaled(self.negative, &add_digits(&a, &b), scale)
+        } else if cmp_digits(&a, &b) != Ordering::Less {
+            Self::from_unscaled(self.negative, &subtract_digits(&a, &b), scale)
+        } else {
+            Self::from_unscaled(other.negative, &subtract_digits(&b, &a), scale)
+        }
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let (a, a_scale) = self.unscaled();
+        let (b, b_scale) = other.unscaled();
+        Self::from_unscaled(
+            self.negative != other.negative,
+            &multiply_digits(&a, &b),
+            a_scale + b_scale,
+        )
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        if self.integer.is_empty() {
+            write!(f, "0")?;
+        } else {
+            write!(f, "{}", self.integer)?;
+        }
+        if !self.fraction.is_empty() {
+            write!(f, ".{}", self.fraction)?;
+        }
+        Ok(())
+    }
+}
+
+/// The lexical mapping of `xs:float` and `xs:double`:
+/// `(\+|-)?([0-9]+(\.[0-9]*)?|\.[0-9]+)([Ee](\+|-)?[0-9]+)?|(\+|-)?INF|NaN`
+fn float_literal_value<F: std::str::FromStr + std::ops::Neg<Output = F>>(
+    literal: &str,
+    infinity: F,
+    nan: F,
+) -> Option<F> {
+    match literal {
+        "INF" | "+INF" => return Some(infinity),
+        "-INF" => return Some(-infinity),
+        "NaN" => return Some(nan),
+        _ => {}
+    }
+    let (mantissa, exponent) = match literal.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (literal, None),
+    };
+    Decimal::parse(mantissa)?;
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+    }
+    literal.parse().ok()
+}
+
+/// Fractional seconds are kept to this many digits; further digits are truncated.
+const SECOND_FRACTION_DIGITS: u32 = 18;
+/// One second, in the units used for seconds in [`Duration`] and [`DateTime`]
+const SECOND: i128 = 10i128.pow(SECOND_FRACTION_DIGITS);
+const MINUTE: i128 = 60 * SECOND;
+const HOUR: i128 = 60 * MINUTE;
+const DAY: i128 = 24 * HOUR;
+
+/// Parses `[0-9]+(\.[0-9]+)?` as seconds, in units of 10^-18 seconds
+fn parse_seconds(literal: &str) -> Option<i128> {
+    let (whole, fraction) = match literal.split_once('.') {
+        Some((whole, fraction)) if !fraction.is_empty() => (whole, fraction),
+        Some(_) => return None,
+        None => (literal, ""),
+    };
+    if whole.is_empty()
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let mut value = whole.parse::<i128>().ok()?.checked_mul(SECOND)?;
+    let mut unit = SECOND;
+    for digit in fraction.bytes().take(SECOND_FRACTION_DIGITS as usize) {
+        unit /= 10;
+        value += i128::from(digit - b'0') * unit;
+    }
+    Some(value)
+}
+
+/// A value of `xs:duration` (pt. 2, §3.3.6)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Duration {
+    months: i64,
+    /// In units of 10^-18 seconds
+    seconds: i128,
+}
+
+impl Duration {
+    /// The lexical mapping of `xs:duration`:
+    /// `-?P((([0-9]+Y)?([0-9]+M)?([0-9]+D)?(T(([0-9]+H)?([0-9]+M)?([0-9]+(\.[0-9]+)?S)?))?)`,
+    /// with at least one field and at least one field after `T`
+    pub fn parse(literal: &str) -> Option<Self> {
+        let (negative, rest) = match literal.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, literal),
+        };
+        let rest = rest.strip_prefix('P')?;
+        let (date, time) = match rest.split_once('T') {
+            Some((_, "")) => return None,
+            Some((date, time)) => (date, Some(time)),
+            None => (rest, None),
+        };
+        if date.is_empty() && time.is_none() {
+            return None;
+        }
+
+        /// Splits `fields` into numbers followed by the given designators, in order
+        fn fields<'a>(mut fields: &'a str, designators: &[char]) -> Option<Vec<(char, &'a str)>> {
+            let mut result = Vec::new();
+            let mut designators = designators.iter();
+            while !fields.is_empty() {
+                let end = fields.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+                let designator = fields[end..].chars().next()?;
+                designators.find(|&&d| d == designator)?;
+                result.push((designator, &fields[..end]));
+                fields = &fields[end + 1..];
+            }
+            Some(result)
+        }
+        let integer = |number: &str| -> Option<i128> {
+            if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            number.parse().ok()
+        };
+
+        let mut months = 0i128;
+        let mut seconds = 0i128;
+        for (designator, number) in fields(date, &['Y', 'M', 'D'])? {
+            match designator {
+                'Y' => months = months.checked_add(integer(number)?.checked_mul(12)?)?,
+                'M' => months = months.checked_add(integer(number)?)?,
+                _ => seconds = seconds.checked_add(integer(number)?.checked_mul(DAY)?)?,
+            }
+        }
+        for (designator, number) in fields(time.unwrap_or_default(), &['H', 'M', 'S'])? {
+            let value = match designator {
+                'H' => integer(number)?.checked_mul(HOUR)?,
+                'M' => integer(number)?.checked_mul(MINUTE)?,
+                _ => parse_seconds(number)?,
+            };
+            seconds = seconds.checked_add(value)?;
+        }
+
+        let months = i64::try_from(months).ok()?;
+        Some(if negative {
+            Self {
+                months: -months,
+                seconds: -seconds,
+            }
+        } else {
+            Self { months, seconds }
+        })
+    }
+
+    /// Adds the duration to the first instant of the given month (in UTC), returning the result
+    /// on the time line
+    fn add_to_month_start(&self, year: i64, month: u8) -> i128 {
+        let month_index = i128::from(year) * 12 + i128::from(month - 1) + i128::from(self.months);
+        let year = month_index.div_euclid(12);
+        let month = month_index.rem_euclid(12) as u8 + 1;
+        days_from_civil(year, month, 1) * DAY + self.seconds
+    }
+}
+
+/// Durations are ordered by comparing the results of adding them to four reference dateTimes,
+/// and are incomparable if the results disagree (pt. 2, §3.3.6.2)
+impl PartialOrd for Duration {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.months == other.months {
+            return Some(self.seconds.cmp(&other.seconds));
+        }
+        let [first, rest @ ..] = [(1696, 9), (1697, 2), (1903, 3), (1903, 7)].map(|(y, m)| {
+            self.add_to_month_start(y, m)
+                .cmp(&other.add_to_month_start(y, m))
+        });
+        (first != Ordering::Equal && rest.iter().all(|&o| o == first)).then_some(first)
+    }
+}
+
+/// A value of one of the date/time datatypes (pt. 2, §3.3.7 to §3.3.14), in the seven-property
+/// model of pt. 2, §D.2.1
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DateTime {
+    year: Option<i64>,
+    month: Option<u8>,
+    day: Option<u8>,
+    hour: Option<u8>,
+    minute: Option<u8>,
+    /// In units of 10^-18 seconds
+    second: Option<i128>,
+    /// Offset from UTC in minutes
+    timezone_offset: Option<i16>,
+}
+
+/// Years are limited to this magnitude, so that positions on the time line fit into an `i128`
+const MAX_YEAR: i64 = 1_000_000_000_000;
+
+fn is_leap_year(year: i64) -> bool {
+    year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0)
+}
+
+/// `daysInMonth` (pt. 2, §E.3.2); an absent year is treated as a leap year
+fn days_in_month(year: Option<i64>, month: u8) -> u8 {
+    match month {
+        2 if year.is_none_or(is_leap_year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days between 1970-01-01 and the given date of the proleptic Gregorian calendar
+fn days_from_civil(year: i128, month: u8, day: u8) -> i128 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i128::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i128::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// A cursor over a date/time literal
+struct Cursor<'a>(&'a str);
+
+impl Cursor<'_> {
+    fn eat(&mut self, prefix: &str) -> bool {
+        match self.0.strip_prefix(prefix) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Consumes exactly two digits
+    fn two_digits(&mut self) -> Option<u8> {
+        let digits = self.0.get(..2)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        self.0 = &self.0[2..];
+        digits.parse().ok()
+    }
+
+    /// `-?([1-9][0-9]{3,}|0[0-9]{3})`
+    fn year(&mut self) -> Option<i64> {
+        let negative = self.eat("-");
+        let end = self
+            .0
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.0.len());
+        let digits = &self.0[..end];
+        if digits.len() < 4 || (digits.len() > 4 && digits.starts_with('0')) {
+            return None;
+        }
+        self.0 = &self.0[end..];
+        let year = digits.parse::<i64>().ok().filter(|&y| y <= MAX_YEAR)?;
+        Some(if negative { -year } else { year })
+    }
+
+    /// `[0-9]{2}(\.[0-9]+)?`
+    fn seconds(&mut self) -> Option<i128> {
+        let end = self
+            .0
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(self.0.len());
+        let seconds = &self.0[..end];
+        if seconds.find('.').unwrap_or(seconds.len()) != 2 {
+            return None;
+        }
+        self.0 = &self.0[end..];
+        parse_seconds(seconds)
+    }
+
+    /// `(Z|(\+|-)((0[0-9]|1[0-3]):[0-5][0-9]|14:00))?`
+    fn timezone(&mut self) -> Option<Option<i16>> {
+        if self.eat("Z") {
+            return Some(Some(0));
+        }
+        let sign = if self.eat("+") {
+            1
+        } else if self.eat("-") {
+            -1
+        } else {
+            return Some(None);
+        };
+        let hours = self.two_digits()?;
+        if !self.eat(":") {
+            return None;
+        }
+        let minutes = self.two_digits()?;
+        if hours > 14 || minutes > 59 || (hours == 14 && minutes != 0) {
+            return None;
+        }
+        Some(Some(sign * (i16::from(hours) * 60 + i16::from(minutes))))
+    }
+}
+
+impl DateTime {
+    /// The lexical mappings of the date/time datatypes
+    pub fn parse(primitive: Primitive, literal: &str) -> Option<Self> {
+        let mut cursor = Cursor(literal);
+        let mut value = Self::default();
+        let c = &mut cursor;
+        match primitive {
+            Primitive::DateTime => {
+                value.year = Some(c.year()?);
+                c.eat("-").then_some(())?;
+                value.month = Some(c.two_digits()?);
+                c.eat("-").then_some(())?;
+                value.day = Some(c.two_digits()?);
+                c.eat("T").then_some(())?;
+                value.parse_time(c)?;
+            }
+            Primitive::Time => value.parse_time(c)?,
+            Primitive::Date => {
+                value.year = Some(c.year()?);
+                c.eat("-").then_some(())?;
+                value.month = Some(c.two_digits()?);
+                c.eat("-").then_some(())?;
+                value.day = Some(c.two_digits()?);
+            }
+            Primitive::GYearMonth => {
+                value.year = Some(c.year()?);
+                c.eat("-").then_some(())?;
+                value.month = Some(c.two_digits()?);
+            }
+            Primitive::GYear => value.year = Some(c.year()?),
+            Primitive::GMonthDay => {
+                c.eat("--").then_some(())?;
+                value.month = Some(c.two_digits()?);
+                c.eat("-").then_some(())?;
+                value.day = Some(c.two_digits()?);
+            }
+            Primitive::GDay => {
+                c.eat("---").then_some(())?;
+                value.day = Some(c.two_digits()?);
+            }
+            Primitive::GMonth => {
+                c.eat("--").then_some(())?;
+                value.month = Some(c.two_digits()?);
+            }
+            _ => return None,
+        }
+        value.timezone_offset = c.timezone()?;
+        if !cursor.0.is_empty() {
+            return None;
+        }
+
+        if value.month.is_some_and(|m| !(1..=12).contains(&m)) {
+            return None;
+        }
+        if let Some(day) = value.day {
+            let max = value.month.map_or(31, |m| days_in_month(value.year, m));
+            if !(1..=max).contains(&day) {
+                return None;
+            }
+        }
+        if value.hour == Some(24) {
+            // 24:00:00 is the first instant of the following day
+            if value.minute != Some(0) || value.second != Some(0) {
+                return None;
+            }
+            value.hour = Some(0);
+            if let (Some(year), Some(month), Some(day)) = (value.year, value.month, value.day) {
+                if day < days_in_month(Some(year), month) {
+                    value.day = Some(day + 1);
+                } else if month < 12 {
+                    (value.month, value.day) = (Some(month + 1), Some(1));
+                } else {
+                    (value.year, value.month, value.day) = (Some(year + 1), Some(1), Some(1));
+                }
+            }
+        }
+        Some(value)
+    }
+
+    /// `hh:mm:ss(.s+)?`
+    fn parse_time(&mut self, c: &mut Cursor) -> Option<()> {
+        let hour = c.two_digits()?;
+        c.eat(":").then_some(())?;
+        let minute = c.two_digits()?;
+        c.eat(":").then_some(())?;
+        let second = c.seconds()?;
+        if hour > 24 || minute > 59 || second >= 60 * SECOND {
+            return None;
+        }
+        (self.hour, self.minute, self.second) = (Some(hour), Some(minute), Some(second));
+        Some(())
+    }
+
+    /// The timezone offset in minutes, if the value has a timezone
+    pub fn timezone_offset(&self) -> Option<i16> {
+        self.timezone_offset
+    }
+
+    /// `timeOnTimeline` (pt. 2, §E.3.4), with absent properties replaced by those of
+    /// 1972-12-31T00:00:00 and an absent timezone treated as UTC
+    fn time_on_timeline(&self) -> i128 {
+        let year = self.year.unwrap_or(1972);
+        let month = self.month.unwrap_or(12);
+        let day = self.day.unwrap_or_else(|| days_in_month(Some(year), month));
+        days_from_civil(i128::from(year), month, day) * DAY
+            + i128::from(self.hour.unwrap_or(0)) * HOUR
+            + (i128::from(self.minute.unwrap_or(0)) - i128::from(self.timezone_offset.unwrap_or(0)))
+                * MINUTE
+            + self.second.unwrap_or(0)
+    }
+}
+
+/// Values with and without timezone are only comparable if they are more than 14 hours apart
+/// (pt. 2, §3.3.7.3, order relation of the date/time datatypes)
+impl PartialOrd for DateTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let (a, b) = (self.time_on_timeline(), other.time_on_timeline());
+        let compare_to_local = |known: i128, local: i128| {
+            if known < local - 14 * HOUR {
+                Some(Ordering::Less)
+            } else if known > local + 14 * HOUR {
+                Some(Ordering::Greater)
+            } else {
+                None
+            }
+        };
+        match (self.timezone_offset, other.timezone_offset) {
+            (Some(_), None) => compare_to_local(a, b),
+            (None, Some(_)) => compare_to_local(b, a).map(Ordering::reverse),
+            _ => Some(a.cmp(&b)),
+        }
+    }
+}
+
+impl PartialEq for DateTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+/// The lexical mapping of `xs:hexBinary`: `([0-9a-fA-F]{2})*`
+fn hex_binary_value(literal: &str) -> Option<Vec<u8>> {
+    if !literal.len().is_multiple_of(2) {
+        return None;
+    }
+    literal
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |b: u8| (b as char).to_digit(16);
+            Some((digit(pair[0])? * 16 + digit(pair[1])?) as u8)
+        })
+        .collect()
+}
+
+/// The lexical mapping of `xs:base64Binary` (pt. 2, §3.3.16): groups of four characters of the
+/// base64 alphabet, optionally separated by single spaces, where the last group can be padded
+/// with `=` if its unused bits are zero
+fn base64_binary_value(literal: &str) -> Option<Vec<u8>> {
+    if literal.starts_with(' ') || literal.ends_with(' ') || literal.contains("  ") {
+        return None;
+    }
+    let characters = literal.bytes().filter(|&b| b != b' ').collect::<Vec<_>>();
+    if !characters.len().is_multiple_of(4) {
+        return None;
+    }
+    let padding = characters.iter().rev().take_while(|&&b| b == b'=').count();
+    if padding > 2 {
+        return None;
+    }
+    let sextet = |b: u8| match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    let mut octets = Vec::with_capacity(characters.len() / 4 * 3);
+    for &c in &characters[..characters.len() - padding] {
+        bits = (bits << 6) | u32::from(sextet(c)?);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            octets.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    // The bits left over by the padded group must be zero
+    (bits == 0).then_some(octets)
+}
+
+/// Returns whether `s` matches the NCName production of Namespaces in XML
+pub(super) fn is_ncname(s: &str) -> bool {
+    let is_name_start_char = |c: char| {
+        c.is_ascii_alphabetic()
+            || c == '_'
+            || ('\u{C0}'..='\u{D6}').contains(&c)
+            || ('\u{D8}'..='\u{F6}').contains(&c)
+            || ('\u{F8}'..='\u{2FF}').contains(&c)
+            || ('\u{370}'..='\u{37D}').contains(&c)
+            || ('\u{37F}'..='\u{1FFF}').contains(&c)
+            || ('\u{200C}'..='\u{200D}').contains(&c)
+            || ('\u{2070}'..='\u{218F}').contains(&c)
+            || ('\u{2C00}'..='\u{2FEF}').contains(&c)
+            || ('\u{3001}'..='\u{D7FF}').contains(&c)
+            || ('\u{F900}'..='\u{FDCF}').contains(&c)
+            || ('\u{FDF0}'..='\u{FFFD}').contains(&c)
+            || ('\u{10000}'..='\u{EFFFF}').contains(&c)
+    };
+    let is_name_char = |c: char| {
+        is_name_start_char(c)
+            || c.is_ascii_digit()
+            || c == '-'
+            || c == '.'
+            || c == '\u{B7}'
+            || ('\u{0300}'..='\u{036F}').contains(&c)
+            || ('\u{203F}'..='\u{2040}').contains(&c)
+    };
+    let mut chars = s.chars();
+    chars.next().is_some_and(is_name_start_char) && chars.all(is_name_char)
+}
+
+/// The lexical mapping of `xs:QName`: a QName whose prefix, if any, is bound in `context`
+pub(super) fn qname_value(literal: &str, context: &dyn NamespaceContext) -> Option<QName> {
+    match literal.split_once(':') {
+        Some((prefix, local_name)) => {
+            if !is_ncname(prefix) || !is_ncname(local_name) {
+                return None;
+            }
+            // The prefix xml is by definition bound to the namespace name
+            // http://www.w3.org/XML/1998/namespace.
+            // (Namespaces in XML 1.0, §3, Reserved Prefixes and Namespace Names)
+            let namespace_name = if prefix == "xml" {
+                "http://www.w3.org/XML/1998/namespace"
+            } else {
+                context.namespace_uri(Some(prefix))?
+            };
+            Some(QName::with_namespace(namespace_name, local_name))
+        }
+        None if is_ncname(literal) => Some(QName::with_optional_namespace(
+            context.namespace_uri(None),
+            literal,
+        )),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decimal, Duration, Primitive, Value};
+    use std::cmp::Ordering;
+
+    #[test]
+    fn value_comparisons() {
+        let doc = roxmltree::Document::parse("<root/>").unwrap();
+        let value = |primitive, literal| Value::parse(primitive, literal, &doc.root_element());
+        let compare = |primitive, a, b| {
+            value(primitive, a)
+                .unwrap()
+                .compare(&value(primitive, b).unwrap())
+        };
+
+        assert_eq!(Decimal::parse("-0.0"), Decimal::parse("0"));
+        assert_eq!(
+            compare(
+                Primitive::Decimal,
+                "18446744073709551615",
+                "18446744073709551616"
+            ),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(Primitive::Decimal, "-1.5", "-1.25"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(Decimal::parse("001.2300").unwrap().total_digits(), 3);
+        assert!(Decimal::parse(".").is_none());
+
+        assert_eq!(
+            value(Primitive::DateTime, "2000-01-01T12:00:00Z"),
+            value(Primitive::DateTime, "2000-01-01T13:00:00+01:00")
+        );
+        assert_eq!(
+            value(Primitive::DateTime, "1999-12-31T24:00:00"),
+            value(Primitive::DateTime, "2000-01-01T00:00:00")
+        );
+        assert_eq!(
+            compare(Primitive::Date, "2000-01-01Z", "2000-01-01"),
+            None,
+            "a value without timezone is incomparable within 14 hours"
+        );
+        assert_eq!(
+            compare(Primitive::Date, "2000-01-01Z", "2000-01-03"),
+            Some(Ordering::Less)
+        );
+        assert!(value(Primitive::Date, "2001-02-29").is_none());
+        assert!(value(Primitive::GMonthDay, "--02-29").is_some());
+        assert!(value(Primitive::Time, "12:00:00+14:30").is_none());
+
+        assert_eq!(
+            Duration::parse("P1M").partial_cmp(&Duration::parse("P30D")),
+            None
+        );
+        assert_eq!(
+            compare(Primitive::Duration, "P1Y", "P364D"),
+            Some(Ordering::Greater)
+        );
+        assert!(Duration::parse("P").is_none());
+        assert!(Duration::parse("P1YT").is_none());
+        assert!(Duration::parse("PT1.5S").is_some());
+        assert!(Duration::parse("P1D2Y").is_none());
+
+        assert!(value(Primitive::Base64Binary, "Zm9v YmE=").is_some());
+        assert!(value(Primitive::Base64Binary, "Zm9vYmF=").is_none());
+        assert_eq!(
+            value(Primitive::HexBinary, "0fB1").unwrap().length(),
+            Some(2)
+        );
+        assert!(value(Primitive::Float, "1e").is_none());
+        assert!(value(Primitive::Float, "inf").is_none());
+        assert_eq!(
+            value(Primitive::Double, "NaN"),
+            value(Primitive::Double, "NaN")
+        );
+    }
+
+    #[test]
+    fn decimal_arithmetic() {
+        let d = |literal: &str| Decimal::parse(literal).unwrap();
+        assert_eq!(d("0.1") + d("0.2"), d("0.3"));
+        assert_eq!(d("1.5") + d("-2.25"), d("-0.75"));
+        assert_eq!(d("12345678901234567.1") - d("12345678901234567"), d("0.1"));
+        assert_eq!(d("-1.5") * d("2.5"), d("-3.75"));
+        assert_eq!(d("1").checked_div(&d("3")), Some(d("0.333333333333333333")));
+        assert_eq!(d("7").checked_div(&d("0.5")), Some(d("14")));
+        assert_eq!(d("-7.5").checked_integer_div(&d("2")), Some(d("-3")));
+        assert_eq!(d("-7.5").checked_rem(&d("2")), Some(d("-1.5")));
+        assert_eq!(d("1").checked_rem(&d("0")), None);
+        assert_eq!(d("-2.5").floor(), d("-3"));
+        assert_eq!(d("-2.5").ceiling(), d("-2"));
+        assert_eq!(d("-2.5").round(), d("-2"));
+        assert_eq!(d("2.5").round(), d("3"));
+    }
+}
@@ -1,30 +1,30 @@
-use crate::xstypes::QName;
+use crate::{error::XsdError, xstypes::QName};
 use roxmltree::Node;
 
-pub trait ActualValue<'a> {
-    fn convert(src: &'a str, parent: Node) -> Self;
+pub trait ActualValue<'a>: Sized {
+    fn convert(src: &'a str, parent: Node) -> Result<Self, XsdError>;
 }
 
 impl<'a> ActualValue<'a> for &'a str {
-    fn convert(src: &'a str, _parent: Node) -> Self {
-        src
+    fn convert(src: &'a str, _parent: Node) -> Result<Self, XsdError> {
+        Ok(src)
     }
 }
 
 impl ActualValue<'_> for String {
-    fn convert(src: &'_ str, _parent: Node) -> Self {
-        src.to_string()
+    fn convert(src: &'_ str, _parent: Node) -> Result<Self, XsdError> {
+        Ok(src.to_string())
     }
 }
 
 impl ActualValue<'_> for QName {
-    fn convert(src: &'_ str, parent: Node) -> Self {
-        QName::parse(src, parent).unwrap()
+    fn convert(src: &'_ str, parent: Node) -> Result<Self, XsdError> {
+        QName::parse(src, parent)
     }
 }
 
 impl<'a, T: ActualValue<'a>> ActualValue<'a> for Vec<T> {
-    fn convert(src: &'a str, _parent: Node) -> Self {
+    fn convert(src: &'a str, _parent: Node) -> Result<Self, XsdError> {
         // NOTE: This assumes a list with whiteSpace="collapse"
         // TODO: split_ascii_whitespace includes U+000C FORM FEED, which is not considered
         // whitespace by the spec.
@@ -35,22 +35,28 @@ impl<'a, T: ActualValue<'a>> ActualValue<'a> for Vec<T> {
 }
 
 impl ActualValue<'_> for bool {
-    fn convert(src: &str, _parent: Node) -> Self {
+    fn convert(src: &str, _parent: Node) -> Result<Self, XsdError> {
         match src {
-            "true" | "1" => true,
-            "false" | "0" => false,
-            _ => panic!("Invalid value for boolean: {src}"),
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(XsdError::InvalidAttributeValue {
+                value: src.to_string(),
+                expected: "boolean",
+            }),
         }
     }
 }
 
 impl ActualValue<'_> for u64 {
-    fn convert(src: &str, _parent: Node) -> Self {
-        src.parse().unwrap()
+    fn convert(src: &str, _parent: Node) -> Result<Self, XsdError> {
+        src.parse().map_err(|_| XsdError::InvalidAttributeValue {
+            value: src.to_string(),
+            expected: "nonNegativeInteger",
+        })
     }
 }
 
-pub fn actual_value<'a, T: ActualValue<'a>>(x: &'a str, parent: Node) -> T {
+pub fn actual_value<'a, T: ActualValue<'a>>(x: &'a str, parent: Node) -> Result<T, XsdError> {
     T::convert(x, parent)
 }
 
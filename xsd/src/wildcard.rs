@@ -5,6 +5,7 @@ use super::{
     Ref,
     annotation::Annotation,
     components::Component,
+    error::XsdError,
     mapping_context::MappingContext,
     values::{ActualValue, actual_value},
     xstypes::{AnyURI, QName, Sequence, Set},
@@ -91,12 +92,133 @@ impl fmt::Debug for DisallowedNameSet {
     }
 }
 
+impl NamespaceConstraint {
+    /// 3.10.4.3 Wildcard allows Namespace Name
+    pub fn allows_namespace(&self, namespace: Option<&str>) -> bool {
+        // For a value V which is either a namespace name or ·absent· to be ·valid· with respect to
+        // a wildcard constraint (the value of a {namespace constraint}) C the appropriate case
+        // among the following is true:
+        let is_member = || self.namespaces.iter().any(|n| n.as_deref() == namespace);
+        match self.variety {
+            // 1 If C.{variety} = any, then true.
+            NamespaceConstraintVariety::Any => true,
+            // 2 If C.{variety} = not, then V is not identical to any of the members of
+            //   C.{namespaces}.
+            NamespaceConstraintVariety::Not => !is_member(),
+            // 3 If C.{variety} = enumeration, then V is identical to one of the members of
+            //   C.{namespaces}.
+            NamespaceConstraintVariety::Enumeration => is_member(),
+        }
+    }
+
+    /// Returns whether there is a namespace name (or ·absent·) allowed by both `self` and
+    /// `other`, i.e. whether the ranges of two wildcards with these constraints overlap. Names
+    /// disallowed by {disallowed names} are not considered.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        use NamespaceConstraintVariety::*;
+        match (&self.variety, &other.variety) {
+            (Any, _) | (_, Any) => true,
+            // There are infinitely many namespace names that are in neither set
+            (Not, Not) => true,
+            (Enumeration, _) => self
+                .namespaces
+                .iter()
+                .any(|n| other.allows_namespace(n.as_deref())),
+            (Not, Enumeration) => other.overlaps(self),
+        }
+    }
+
+    /// 3.10.6.3 Attribute Wildcard Union
+    pub(super) fn union(&self, other: &Self) -> Self {
+        use NamespaceConstraintVariety::*;
+
+        // For two Namespace Constraints O1 and O2, the wildcard union O is a Namespace
+        // Constraint whose {variety}, {namespaces} and {disallowed names} are determined as
+        // follows.
+        let (o1, o2) = (self, other);
+        let subtract = |a: &Set<Option<AnyURI>>, b: &Set<Option<AnyURI>>| {
+            a.iter().filter(|n| !b.contains(n)).cloned().collect()
+        };
+        let (variety, namespaces) = match (&o1.variety, &o2.variety) {
+            // 2 If either O1.{variety} or O2.{variety} is any, then O.{variety} is any and
+            //   O.{namespaces} is the empty set.
+            (Any, _) | (_, Any) => (Any, Set::new()),
+            // 3 If both O1.{variety} and O2.{variety} are enumeration, then O.{variety} is
+            //   enumeration and O.{namespaces} is the union of O1.{namespaces} and
+            //   O2.{namespaces}.
+            (Enumeration, Enumeration) => {
+                let mut namespaces = o1.namespaces.clone();
+                namespaces.extend(subtract(&o2.namespaces, &o1.namespaces));
+                (Enumeration, namespaces)
+            }
+            // 4 If both O1.{variety} and O2.{variety} are not, then O.{variety} is not and
+            //   O.{namespaces} is the intersection of O1.{namespaces} and O2.{namespaces}.
+            (Not, Not) => (
+                Not,
+                o1.namespaces
+                    .iter()
+                    .filter(|n| o2.namespaces.contains(n))
+                    .cloned()
+                    .collect(),
+            ),
+            // 5 If either O1.{variety} or O2.{variety} is not, and the other is enumeration, then
+            //   O.{variety} is not and O.{namespaces} is the set difference of the {namespaces}
+            //   of the one whose {variety} is not minus the {namespaces} of the other.
+            (Not, Enumeration) => (Not, subtract(&o1.namespaces, &o2.namespaces)),
+            (Enumeration, Not) => (Not, subtract(&o2.namespaces, &o1.namespaces)),
+        };
+
+        // O.{disallowed names} is the set of QName members of O1.{disallowed names} that are not
+        // allowed by O2 and those of O2.{disallowed names} that are not allowed by O1, plus the
+        // keyword defined if it is included in both O1.{disallowed names} and O2.{disallowed
+        // names}.
+        let allows = |o: &Self, name: &QName| {
+            o.allows_namespace(name.namespace_name()) && !o.disallowed_names.contains_name(name)
+        };
+        let mut disallowed_names = DisallowedNameSet::default();
+        for name in o1.disallowed_names.names.iter().filter(|n| !allows(o2, n)) {
+            disallowed_names.insert_name(name.clone());
+        }
+        for name in o2.disallowed_names.names.iter().filter(|n| !allows(o1, n)) {
+            disallowed_names.insert_name(name.clone());
+        }
+        if o1.disallowed_names.contains_defined() && o2.disallowed_names.contains_defined() {
+            disallowed_names.insert_defined();
+        }
+
+        // 1 If O1 and O2 are the same, then O is the same as O1 (which follows from the clauses
+        //   above).
+        NamespaceConstraint {
+            variety,
+            namespaces,
+            disallowed_names,
+        }
+    }
+}
+
 impl Wildcard {
+    /// 3.10.4.2 Wildcard allows Expanded Name, except for the keywords `defined` and `sibling`
+    /// in {disallowed names}, which depend on the declarations in the schema and in the content
+    /// model and must be checked by the caller
+    pub fn allows_name(&self, name: &QName) -> bool {
+        // For an expanded name E, i.e. a (namespace name, local name) pair, to be ·valid· with
+        // respect to a namespace constraint C all of the following must be true:
+        // 1 The namespace name is ·valid· with respect to C, as defined in Wildcard allows
+        //   Namespace Name (§3.10.4.3);
+        // 2 C.{disallowed names} does not contain E.
+        self.namespace_constraint
+            .allows_namespace(name.namespace_name())
+            && !self
+                .namespace_constraint
+                .disallowed_names
+                .contains_name(name)
+    }
+
     pub(super) fn map_from_xml_any(
         context: &mut MappingContext,
         any: Node,
         schema: Node,
-    ) -> Ref<Self> {
+    ) -> Result<Ref<Self>, XsdError> {
         // A Namespace Constraint with the following properties:
         let namespace_constraint = {
             // {variety} the appropriate case among the following:
@@ -132,8 +254,8 @@ impl Wildcard {
                 //   targetNamespace [attribute] of the <schema> ancestor element information item
                 //   is present, its ·actual value·;
                 let mut namespaces = vec![None];
-                if let Some(target_namespace) = schema.attribute("targetNamespace") {
-                    namespaces.push(Some(actual_value::<AnyURI>(target_namespace, schema)));
+                if let Some(target_namespace) = context.target_namespace(schema)? {
+                    namespaces.push(Some(target_namespace));
                 }
                 namespaces
             } else {
@@ -144,39 +266,43 @@ impl Wildcard {
                 //     the ·actual value· of the targetNamespace [attribute] of the <schema>
                 //     ancestor element information item if present, otherwise ·absent·;
                 //   4.2 if one such substring is ##local, the corresponding member is ·absent·.
+                // One of these is present here (ensured by 1)
                 let namespaces = any
                     .attribute("namespace")
                     .or_else(|| any.attribute("notNamespace"))
-                    .unwrap(); // One of these must be present here (ensured by 1)
-                let namespaces = actual_value::<Vec<String>>(namespaces, any);
+                    .ok_or_else(|| {
+                        let error = XsdError::missing_attribute(any, "namespace");
+                        context.locate(error, any, Self::DISPLAY_NAME)
+                    })?;
+                let namespaces = actual_value::<Vec<String>>(namespaces, any)?;
                 namespaces
                     .into_iter()
                     .map(|ns| match ns.as_str() {
-                        "##targetNamespace" => schema
-                            .attribute("namespace")
-                            .map(|v| actual_value::<AnyURI>(v, schema)),
-                        "##local" => None,
-                        _ => Some(ns),
+                        "##targetNamespace" => context.target_namespace(schema),
+                        "##local" => Ok(None),
+                        _ => Ok(Some(ns)),
                     })
-                    .collect()
+                    .collect::<Result<_, _>>()?
             };
 
             let disallowed_names = if let Some(not_qname) = any.attribute("notQName") {
                 // If the notQName [attribute] is present, then a set whose members correspond to
                 // the items in the ·actual value· of the notQName [attribute], as follows.
-                let not_qname = actual_value::<Vec<String>>(not_qname, any);
+                let not_qname = actual_value::<Vec<String>>(not_qname, any)?;
                 let mut disallowed_names = DisallowedNameSet::default();
-                not_qname.into_iter().for_each(|n| match n.as_str() {
-                    // If the item is the token "##defined", then the keyword defined is a
-                    // member of the set.
-                    "##defined" => disallowed_names.insert_defined(),
-                    // If the item is the token "##definedSibling", then the keyword sibling is
-                    // a member of the set.
-                    "##definedSibling" => disallowed_names.insert_sibling(),
-                    // If the item is a QName value (i.e. an expanded name), then that QName
-                    // value is a member of the set.
-                    _ => disallowed_names.insert_name(QName::parse(&n, any).unwrap()),
-                });
+                for n in not_qname {
+                    match n.as_str() {
+                        // If the item is the token "##defined", then the keyword defined is a
+                        // member of the set.
+                        "##defined" => disallowed_names.insert_defined(),
+                        // If the item is the token "##definedSibling", then the keyword sibling
+                        // is a member of the set.
+                        "##definedSibling" => disallowed_names.insert_sibling(),
+                        // If the item is a QName value (i.e. an expanded name), then that QName
+                        // value is a member of the set.
+                        _ => disallowed_names.insert_name(context.qname(&n, any)?),
+                    }
+                }
                 disallowed_names
             } else {
                 // If the notQName [attribute] is not present, then the empty set.
@@ -194,27 +320,31 @@ impl Wildcard {
         let process_contents = any
             .attribute("processContents")
             .map(|v| actual_value::<ProcessContents>(v, any))
+            .transpose()?
             .unwrap_or(ProcessContents::Strict);
 
         // The ·annotation mapping· of the <any> element, as defined in XML Representation of
         // Annotation Schema Components (§3.15.2).
         let annotations = Annotation::xml_element_annotation_mapping(context, any);
 
-        context.create(Wildcard {
+        Ok(context.create(Wildcard {
             namespace_constraint,
             process_contents,
             annotations,
-        })
+        }))
     }
 }
 
 impl ActualValue<'_> for ProcessContents {
-    fn convert(src: &'_ str, _parent: Node) -> Self {
+    fn convert(src: &'_ str, _parent: Node) -> Result<Self, XsdError> {
         match src {
-            "lax" => ProcessContents::Lax,
-            "skip" => ProcessContents::Skip,
-            "strict" => ProcessContents::Strict,
-            _ => panic!("Invalid value for processContents"),
+            "lax" => Ok(ProcessContents::Lax),
+            "skip" => Ok(ProcessContents::Skip),
+            "strict" => Ok(ProcessContents::Strict),
+            _ => Err(XsdError::InvalidAttributeValue {
+                value: src.to_string(),
+                expected: "processContents value",
+            }),
         }
     }
 }
@@ -0,0 +1,2024 @@
+//! The XPath 2.0 subset for assertions: the {assertions} of complex type definitions (pt. 1,
+//! §3.13.4.1) and the assertions constraining facet (pt. 2, §4.3.13).
+//!
+//! The subset covers the expressions typically found in assertions:
+//!
+//! ```text
+//! Expr             ::= ExprSingle ( ',' ExprSingle )*
+//! ExprSingle       ::= ForExpr | QuantifiedExpr | IfExpr | OrExpr
+//! ForExpr          ::= 'for' VarBindings 'return' ExprSingle
+//! QuantifiedExpr   ::= ( 'some' | 'every' ) VarBindings 'satisfies' ExprSingle
+//! VarBindings      ::= '$' QName 'in' ExprSingle ( ',' '$' QName 'in' ExprSingle )*
+//! IfExpr           ::= 'if' '(' Expr ')' 'then' ExprSingle 'else' ExprSingle
+//! OrExpr           ::= AndExpr ( 'or' AndExpr )*
+//! AndExpr          ::= ComparisonExpr ( 'and' ComparisonExpr )*
+//! ComparisonExpr   ::= RangeExpr ( Comparator RangeExpr )?
+//! RangeExpr        ::= AdditiveExpr ( 'to' AdditiveExpr )?
+//! AdditiveExpr     ::= MultiplicativeExpr ( ( '+' | '-' ) MultiplicativeExpr )*
+//! MultiplicativeExpr ::= UnionExpr ( ( '*' | 'div' | 'idiv' | 'mod' ) UnionExpr )*
+//! UnionExpr        ::= CastableExpr ( ( 'union' | '|' ) CastableExpr )*
+//! CastableExpr     ::= CastExpr ( 'castable' 'as' QName '?'? )?
+//! CastExpr         ::= UnaryExpr ( 'cast' 'as' QName '?'? )?
+//! UnaryExpr        ::= ( '-' | '+' )* PathExpr
+//! PathExpr         ::= '/' RelativePathExpr? | '//' RelativePathExpr | RelativePathExpr
+//! RelativePathExpr ::= StepExpr ( ( '/' | '//' ) StepExpr )*
+//! StepExpr         ::= ( AxisStep | PrimaryExpr ) Predicate*
+//! AxisStep         ::= ( Axis '::' | '@' )? NodeTest | '..'
+//! PrimaryExpr      ::= Literal | '$' QName | '(' Expr? ')' | '.' | FunctionCall
+//! FunctionCall     ::= QName '(' ( ExprSingle ( ',' ExprSingle )* )? ')'
+//! ```
+//!
+//! Values of `xs:decimal` and `xs:integer` are computed with arbitrary precision, values of
+//! `xs:float` are approximated by `xs:double`.
+
+use super::{
+    NameTest, XPathError,
+    cta::{self, AtomicType, Comparator, EvaluationError, FN_NAMESPACE, Value},
+};
+use crate::{
+    assertion::XPathExpression,
+    builtins::XS_NAMESPACE,
+    validation::{self, Decimal},
+};
+use roxmltree::{Attribute, Node};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A QName or NCName, which might also be a keyword like `and` or `every`
+    Name(String),
+    /// `prefix:*`
+    PrefixWildcard(String),
+    /// `$name`
+    Variable(String),
+    String(String),
+    Number(String),
+    At,
+    Star,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Slash,
+    DoubleSlash,
+    Dot,
+    DoubleDot,
+    Plus,
+    Minus,
+    Pipe,
+    Question,
+    /// `::`, which separates an axis from the node test
+    Axis,
+    Comparator(Comparator),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, XPathError> {
+    let is_name_start = |c: char| c.is_alphabetic() || c == '_';
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '·');
+
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '@' => Token::At,
+            '*' => Token::Star,
+            '(' if chars.peek().is_some_and(|&(_, c)| c == ':') => {
+                // XPath comment, which may be nested
+                let mut depth = 0;
+                let mut previous = '(';
+                for (_, c) in chars.by_ref() {
+                    match (previous, c) {
+                        ('(', ':') => depth += 1,
+                        (':', ')') => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    previous = c;
+                }
+                if depth != 0 {
+                    return Err(XPathError {
+                        position,
+                        reason: "unterminated comment".into(),
+                    });
+                }
+                continue;
+            }
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            ',' => Token::Comma,
+            '/' if chars.next_if(|&(_, c)| c == '/').is_some() => Token::DoubleSlash,
+            '/' => Token::Slash,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '|' => Token::Pipe,
+            '?' => Token::Question,
+            '=' => Token::Comparator(Comparator::Eq),
+            '!' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Comparator(Comparator::Ne),
+            '<' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Comparator(Comparator::Le),
+            '<' => Token::Comparator(Comparator::Lt),
+            '>' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Comparator(Comparator::Ge),
+            '>' => Token::Comparator(Comparator::Gt),
+            '\'' | '"' => {
+                // A quote is escaped by doubling it
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => {
+                            if chars.next_if(|&(_, q)| q == c).is_some() {
+                                literal.push(c);
+                            } else {
+                                break;
+                            }
+                        }
+                        Some((_, other)) => literal.push(other),
+                        None => {
+                            return Err(XPathError {
+                                position,
+                                reason: "unterminated string literal".into(),
+                            });
+                        }
+                    }
+                }
+                Token::String(literal)
+            }
+            '.' if chars.next_if(|&(_, c)| c == '.').is_some() => Token::DoubleDot,
+            '.' if !chars.peek().is_some_and(|&(_, c)| c.is_ascii_digit()) => Token::Dot,
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::from(c);
+                while let Some((_, c)) =
+                    chars.next_if(|&(_, c)| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E'))
+                {
+                    number.push(c);
+                    if matches!(c, 'e' | 'E')
+                        && let Some((_, sign)) = chars.next_if(|&(_, c)| matches!(c, '+' | '-'))
+                    {
+                        number.push(sign);
+                    }
+                }
+                Token::Number(number)
+            }
+            '$' => {
+                let mut name = String::new();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| is_name_char(c) || c == ':') {
+                    name.push(c);
+                }
+                if !name.starts_with(is_name_start) {
+                    return Err(XPathError {
+                        position,
+                        reason: "expected a variable name".into(),
+                    });
+                }
+                Token::Variable(name)
+            }
+            c if is_name_start(c) => {
+                let mut name = String::from(c);
+                while let Some((_, c)) = chars.next_if(|&(_, c)| is_name_char(c)) {
+                    name.push(c);
+                }
+                if chars.peek().is_some_and(|&(_, c)| c == ':') {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    match lookahead.peek() {
+                        // A prefixed QName
+                        Some(&(_, c)) if is_name_start(c) => {
+                            chars.next();
+                            name.push(':');
+                            while let Some((_, c)) = chars.next_if(|&(_, c)| is_name_char(c)) {
+                                name.push(c);
+                            }
+                        }
+                        Some((_, '*')) => {
+                            chars.next();
+                            chars.next();
+                            tokens.push((position, Token::PrefixWildcard(name)));
+                            continue;
+                        }
+                        Some(&(axis_position, ':')) => {
+                            chars.next();
+                            chars.next();
+                            tokens.push((position, Token::Name(name)));
+                            tokens.push((axis_position - 1, Token::Axis));
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+                Token::Name(name)
+            }
+            _ => {
+                return Err(XPathError {
+                    position,
+                    reason: format!("unexpected character {c:?}"),
+                });
+            }
+        };
+        tokens.push((position, token));
+    }
+    Ok(tokens)
+}
+
+/// A parsed {test} of an [`Assertion`](crate::Assertion)
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssertionTest(Expr);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    /// `a, b, ...`
+    Sequence(Vec<Expr>),
+    /// `for $variable in sequence return body`
+    For {
+        variable: String,
+        sequence: Box<Expr>,
+        body: Box<Expr>,
+    },
+    /// `some` or `every $variable in sequence satisfies condition`
+    Quantified {
+        every: bool,
+        variable: String,
+        sequence: Box<Expr>,
+        condition: Box<Expr>,
+    },
+    If {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    /// A general (`=`) or value (`eq`) comparison
+    Comparison {
+        left: Box<Expr>,
+        comparator: Comparator,
+        general: bool,
+        right: Box<Expr>,
+    },
+    /// `from to to`
+    Range(Box<Expr>, Box<Expr>),
+    Arithmetic(Box<Expr>, ArithmeticOperator, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Cast {
+        operand: Box<Expr>,
+        to: AtomicType,
+        /// Whether the empty sequence is allowed, i.e. the type is followed by `?`
+        optional: bool,
+        /// Whether this is a `castable as` expression
+        castable: bool,
+    },
+    /// `/`, the root of the tree containing the context node
+    Root,
+    /// `left/right`, evaluating `right` for each node selected by `left`
+    Path(Box<Expr>, Box<Expr>),
+    Step {
+        axis: Axis,
+        test: NodeTest,
+        predicates: Vec<Expr>,
+    },
+    Filter(Box<Expr>, Vec<Expr>),
+    Literal(Value),
+    Variable(String),
+    ContextItem,
+    FunctionCall(Function, Vec<Expr>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntegerDivide,
+    Modulo,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Axis {
+    Child,
+    Attribute,
+    SelfNode,
+    Descendant,
+    DescendantOrSelf,
+    Parent,
+    Ancestor,
+    AncestorOrSelf,
+    FollowingSibling,
+    PrecedingSibling,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "child" => Self::Child,
+            "attribute" => Self::Attribute,
+            "self" => Self::SelfNode,
+            "descendant" => Self::Descendant,
+            "descendant-or-self" => Self::DescendantOrSelf,
+            "parent" => Self::Parent,
+            "ancestor" => Self::Ancestor,
+            "ancestor-or-self" => Self::AncestorOrSelf,
+            "following-sibling" => Self::FollowingSibling,
+            "preceding-sibling" => Self::PrecedingSibling,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum NodeTest {
+    /// A name test, which matches nodes of the principal node kind of the axis
+    Name(NameTest),
+    /// `node()`
+    AnyNode,
+    /// `text()`
+    Text,
+    /// `comment()`
+    Comment,
+    /// `element()` or `element(*)`
+    Element,
+    /// `attribute()` or `attribute(*)`
+    Attribute,
+}
+
+impl NodeTest {
+    fn from_kind_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "node" => Self::AnyNode,
+            "text" => Self::Text,
+            "comment" => Self::Comment,
+            "element" => Self::Element,
+            "attribute" => Self::Attribute,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Function {
+    Count,
+    Exists,
+    Empty,
+    Not,
+    True,
+    False,
+    Boolean,
+    String,
+    StringLength,
+    NormalizeSpace,
+    Concat,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Substring,
+    SubstringBefore,
+    SubstringAfter,
+    UpperCase,
+    LowerCase,
+    StringJoin,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Number,
+    Abs,
+    Floor,
+    Ceiling,
+    Round,
+    Data,
+    DistinctValues,
+    Position,
+    Last,
+    LocalName,
+    /// A constructor function like `xs:integer()`
+    Constructor(AtomicType),
+}
+
+impl Function {
+    /// Returns the function in the function namespace with the given local name, and its minimum
+    /// and maximum arity
+    fn from_name(name: &str) -> Option<(Self, usize, usize)> {
+        Some(match name {
+            "count" => (Self::Count, 1, 1),
+            "exists" => (Self::Exists, 1, 1),
+            "empty" => (Self::Empty, 1, 1),
+            "not" => (Self::Not, 1, 1),
+            "true" => (Self::True, 0, 0),
+            "false" => (Self::False, 0, 0),
+            "boolean" => (Self::Boolean, 1, 1),
+            "string" => (Self::String, 0, 1),
+            "string-length" => (Self::StringLength, 0, 1),
+            "normalize-space" => (Self::NormalizeSpace, 0, 1),
+            "concat" => (Self::Concat, 2, usize::MAX),
+            "contains" => (Self::Contains, 2, 2),
+            "starts-with" => (Self::StartsWith, 2, 2),
+            "ends-with" => (Self::EndsWith, 2, 2),
+            "substring" => (Self::Substring, 2, 3),
+            "substring-before" => (Self::SubstringBefore, 2, 2),
+            "substring-after" => (Self::SubstringAfter, 2, 2),
+            "upper-case" => (Self::UpperCase, 1, 1),
+            "lower-case" => (Self::LowerCase, 1, 1),
+            "string-join" => (Self::StringJoin, 2, 2),
+            "sum" => (Self::Sum, 1, 1),
+            "avg" => (Self::Avg, 1, 1),
+            "min" => (Self::Min, 1, 1),
+            "max" => (Self::Max, 1, 1),
+            "number" => (Self::Number, 0, 1),
+            "abs" => (Self::Abs, 1, 1),
+            "floor" => (Self::Floor, 1, 1),
+            "ceiling" => (Self::Ceiling, 1, 1),
+            "round" => (Self::Round, 1, 1),
+            "data" => (Self::Data, 1, 1),
+            "distinct-values" => (Self::DistinctValues, 1, 1),
+            "position" => (Self::Position, 0, 0),
+            "last" => (Self::Last, 0, 0),
+            "local-name" => (Self::LocalName, 0, 1),
+            _ => return None,
+        })
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+    xpath: &'a XPathExpression,
+    /// The variables in scope, starting with `$value`
+    variables: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, t)| t)
+    }
+
+    fn peek_second(&self) -> Option<&Token> {
+        self.tokens.get(self.position + 1).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(offset, _)| *offset)
+    }
+
+    fn error<T>(&self, reason: impl Into<String>) -> Result<T, XPathError> {
+        Err(XPathError {
+            position: self.offset(),
+            reason: reason.into(),
+        })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|(_, t)| t.clone());
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        if self.peek() == Some(&token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), XPathError> {
+        if self.eat(expected.clone()) {
+            Ok(())
+        } else {
+            self.error(format!("expected {expected:?}"))
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(name)) if name == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), XPathError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{keyword}'"))
+        }
+    }
+
+    fn resolve(
+        &self,
+        name: &str,
+        default_namespace: Option<&str>,
+    ) -> Result<crate::xstypes::QName, XPathError> {
+        cta::resolve(self.xpath, name, default_namespace).or_else(|reason| self.error(reason))
+    }
+
+    fn expr(&mut self) -> Result<Expr, XPathError> {
+        let mut operands = vec![self.expr_single()?];
+        while self.eat(Token::Comma) {
+            operands.push(self.expr_single()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expr::Sequence(operands)
+        })
+    }
+
+    fn expr_single(&mut self) -> Result<Expr, XPathError> {
+        match (self.peek(), self.peek_second()) {
+            (Some(Token::Name(keyword)), Some(Token::Variable(_)))
+                if matches!(keyword.as_str(), "for" | "some" | "every") =>
+            {
+                let keyword = keyword.clone();
+                self.next();
+                self.variable_bindings(&keyword)
+            }
+            (Some(Token::Name(keyword)), Some(Token::LeftParen)) if keyword == "if" => {
+                self.position += 2;
+                let condition = self.expr()?;
+                self.expect(Token::RightParen)?;
+                self.expect_keyword("then")?;
+                let then = self.expr_single()?;
+                self.expect_keyword("else")?;
+                let otherwise = self.expr_single()?;
+                Ok(Expr::If {
+                    condition: Box::new(condition),
+                    then: Box::new(then),
+                    otherwise: Box::new(otherwise),
+                })
+            }
+            _ => self.or_expr(),
+        }
+    }
+
+    /// Parses the variable bindings and the body of a `for`, `some` or `every` expression. Each
+    /// binding is in scope in the following bindings, so they map to nested expressions.
+    fn variable_bindings(&mut self, keyword: &str) -> Result<Expr, XPathError> {
+        let Some(Token::Variable(variable)) = self.next() else {
+            self.position -= 1;
+            return self.error("expected a variable");
+        };
+        self.expect_keyword("in")?;
+        let sequence = Box::new(self.expr_single()?);
+
+        self.variables.push(variable.clone());
+        let body = if self.eat(Token::Comma) {
+            if !matches!(self.peek(), Some(Token::Variable(_))) {
+                return self.error("expected a variable");
+            }
+            self.variable_bindings(keyword)
+        } else {
+            self.expect_keyword(if keyword == "for" {
+                "return"
+            } else {
+                "satisfies"
+            })?;
+            self.expr_single()
+        };
+        self.variables.pop();
+        let body = Box::new(body?);
+
+        Ok(match keyword {
+            "for" => Expr::For {
+                variable,
+                sequence,
+                body,
+            },
+            _ => Expr::Quantified {
+                every: keyword == "every",
+                variable,
+                sequence,
+                condition: body,
+            },
+        })
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut operands = vec![self.and_expr()?];
+        while self.eat_keyword("or") {
+            operands.push(self.and_expr()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expr::Or(operands)
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut operands = vec![self.comparison_expr()?];
+        while self.eat_keyword("and") {
+            operands.push(self.comparison_expr()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expr::And(operands)
+        })
+    }
+
+    fn comparison_expr(&mut self) -> Result<Expr, XPathError> {
+        let left = self.range_expr()?;
+        let comparator = match self.peek() {
+            Some(Token::Comparator(comparator)) => Some((*comparator, true)),
+            Some(Token::Name(name)) => match name.as_str() {
+                "eq" => Some((Comparator::Eq, false)),
+                "ne" => Some((Comparator::Ne, false)),
+                "lt" => Some((Comparator::Lt, false)),
+                "le" => Some((Comparator::Le, false)),
+                "gt" => Some((Comparator::Gt, false)),
+                "ge" => Some((Comparator::Ge, false)),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some((comparator, general)) = comparator else {
+            return Ok(left);
+        };
+        self.next();
+        let right = self.range_expr()?;
+        Ok(Expr::Comparison {
+            left: Box::new(left),
+            comparator,
+            general,
+            right: Box::new(right),
+        })
+    }
+
+    fn range_expr(&mut self) -> Result<Expr, XPathError> {
+        let from = self.additive_expr()?;
+        if !self.eat_keyword("to") {
+            return Ok(from);
+        }
+        let to = self.additive_expr()?;
+        Ok(Expr::Range(Box::new(from), Box::new(to)))
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.multiplicative_expr()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Plus) => ArithmeticOperator::Add,
+                Some(Token::Minus) => ArithmeticOperator::Subtract,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.multiplicative_expr()?;
+            left = Expr::Arithmetic(Box::new(left), operator, Box::new(right));
+        }
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.union_expr()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Star) => ArithmeticOperator::Multiply,
+                Some(Token::Name(name)) => match name.as_str() {
+                    "div" => ArithmeticOperator::Divide,
+                    "idiv" => ArithmeticOperator::IntegerDivide,
+                    "mod" => ArithmeticOperator::Modulo,
+                    _ => return Ok(left),
+                },
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.union_expr()?;
+            left = Expr::Arithmetic(Box::new(left), operator, Box::new(right));
+        }
+    }
+
+    fn union_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.castable_expr()?;
+        while self.eat(Token::Pipe) || self.eat_keyword("union") {
+            let right = self.castable_expr()?;
+            left = Expr::Union(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn castable_expr(&mut self) -> Result<Expr, XPathError> {
+        let operand = self.cast_expr()?;
+        if !self.eat_keyword("castable") {
+            return Ok(operand);
+        }
+        self.single_type(operand, true)
+    }
+
+    fn cast_expr(&mut self) -> Result<Expr, XPathError> {
+        let operand = self.unary_expr()?;
+        if !self.eat_keyword("cast") {
+            return Ok(operand);
+        }
+        self.single_type(operand, false)
+    }
+
+    /// Parses the `as` QName `?`? following `cast` or `castable`
+    fn single_type(&mut self, operand: Expr, castable: bool) -> Result<Expr, XPathError> {
+        self.expect_keyword("as")?;
+        let Some(Token::Name(name)) = self.peek().cloned() else {
+            return self.error("expected a type name");
+        };
+        let to = self.atomic_type(&name)?;
+        self.next();
+        let optional = self.eat(Token::Question);
+        Ok(Expr::Cast {
+            operand: Box::new(operand),
+            to,
+            optional,
+            castable,
+        })
+    }
+
+    fn atomic_type(&self, name: &str) -> Result<AtomicType, XPathError> {
+        let type_name = self.resolve(name, self.xpath.default_namespace.as_deref())?;
+        match AtomicType::from_name(&type_name) {
+            Some(atomic_type) => Ok(atomic_type),
+            None => self.error(format!("unsupported type {type_name}")),
+        }
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut negate = false;
+        loop {
+            if self.eat(Token::Minus) {
+                negate = !negate;
+            } else if !self.eat(Token::Plus) {
+                break;
+            }
+        }
+        let operand = self.path_expr()?;
+        Ok(if negate {
+            Expr::Negate(Box::new(operand))
+        } else {
+            operand
+        })
+    }
+
+    fn path_expr(&mut self) -> Result<Expr, XPathError> {
+        if self.eat(Token::Slash) {
+            // A lone `/` selects the root
+            let starts_step = matches!(
+                self.peek(),
+                Some(
+                    Token::Name(_)
+                        | Token::PrefixWildcard(_)
+                        | Token::Star
+                        | Token::At
+                        | Token::Dot
+                        | Token::DoubleDot
+                )
+            );
+            if !starts_step {
+                return Ok(Expr::Root);
+            }
+            self.relative_path_expr(Expr::Root)
+        } else if self.eat(Token::DoubleSlash) {
+            self.relative_path_expr(descendant_or_self(Expr::Root))
+        } else {
+            let first = self.step_expr()?;
+            self.continue_path(first)
+        }
+    }
+
+    /// Parses a relative path, whose steps are applied to the nodes selected by `left`
+    fn relative_path_expr(&mut self, left: Expr) -> Result<Expr, XPathError> {
+        let step = self.step_expr()?;
+        self.continue_path(Expr::Path(Box::new(left), Box::new(step)))
+    }
+
+    fn continue_path(&mut self, mut left: Expr) -> Result<Expr, XPathError> {
+        loop {
+            if self.eat(Token::Slash) {
+                let step = self.step_expr()?;
+                left = Expr::Path(Box::new(left), Box::new(step));
+            } else if self.eat(Token::DoubleSlash) {
+                let step = self.step_expr()?;
+                left = Expr::Path(Box::new(descendant_or_self(left)), Box::new(step));
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn step_expr(&mut self) -> Result<Expr, XPathError> {
+        let axis = match (self.peek(), self.peek_second()) {
+            (Some(Token::DoubleDot), _) => {
+                self.next();
+                return Ok(Expr::Step {
+                    axis: Axis::Parent,
+                    test: NodeTest::AnyNode,
+                    predicates: self.predicates()?,
+                });
+            }
+            (Some(Token::At), _) => {
+                self.next();
+                Some(Axis::Attribute)
+            }
+            (Some(Token::Name(name)), Some(Token::Axis)) => match Axis::from_name(name) {
+                Some(axis) => {
+                    self.position += 2;
+                    Some(axis)
+                }
+                None => return self.error(format!("unsupported axis {name}")),
+            },
+            (Some(Token::Name(name)), Some(Token::LeftParen))
+                if NodeTest::from_kind_name(name).is_some() =>
+            {
+                Some(Axis::Child)
+            }
+            // Otherwise, this is a function call
+            (Some(Token::Name(_)), Some(Token::LeftParen)) => None,
+            (Some(Token::Name(_) | Token::PrefixWildcard(_) | Token::Star), _) => Some(Axis::Child),
+            _ => None,
+        };
+
+        if let Some(axis) = axis {
+            let test = self.node_test(axis)?;
+            return Ok(Expr::Step {
+                axis,
+                test,
+                predicates: self.predicates()?,
+            });
+        }
+
+        let primary = self.primary_expr()?;
+        let predicates = self.predicates()?;
+        Ok(if predicates.is_empty() {
+            primary
+        } else {
+            Expr::Filter(Box::new(primary), predicates)
+        })
+    }
+
+    fn node_test(&mut self, axis: Axis) -> Result<NodeTest, XPathError> {
+        match self.next() {
+            Some(Token::Star) => Ok(NodeTest::Name(NameTest::Any)),
+            Some(Token::PrefixWildcard(prefix)) => {
+                // Resolve a dummy name to look up the prefix
+                let name = self.resolve(&format!("{prefix}:_"), None)?;
+                Ok(NodeTest::Name(NameTest::AnyInNamespace(
+                    name.namespace_name().map(String::from),
+                )))
+            }
+            Some(Token::Name(name)) if self.peek() == Some(&Token::LeftParen) => {
+                let Some(test) = NodeTest::from_kind_name(&name) else {
+                    self.position -= 1;
+                    return self.error(format!("unsupported kind test {name}()"));
+                };
+                self.next();
+                if matches!(test, NodeTest::Element | NodeTest::Attribute) {
+                    self.eat(Token::Star);
+                }
+                self.expect(Token::RightParen)?;
+                Ok(test)
+            }
+            Some(Token::Name(name)) => {
+                // Unprefixed attribute names are not in any namespace, while unprefixed element
+                // names are in the {default namespace}
+                let default_namespace = match axis {
+                    Axis::Attribute => None,
+                    _ => self.xpath.default_namespace.as_deref(),
+                };
+                Ok(NodeTest::Name(NameTest::Name(
+                    self.resolve(&name, default_namespace)?,
+                )))
+            }
+            _ => {
+                self.position -= 1;
+                self.error("expected a node test")
+            }
+        }
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>, XPathError> {
+        let mut predicates = Vec::new();
+        while self.eat(Token::LeftBracket) {
+            predicates.push(self.expr()?);
+            self.expect(Token::RightBracket)?;
+        }
+        Ok(predicates)
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr, XPathError> {
+        match self.next() {
+            Some(Token::String(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Number(n)) => match cta::numeric_literal(&n) {
+                Some(value) => Ok(Expr::Literal(value)),
+                None => {
+                    self.position -= 1;
+                    self.error(format!("invalid numeric literal {n:?}"))
+                }
+            },
+            Some(Token::Variable(name)) => {
+                if !self.variables.contains(&name) {
+                    self.position -= 1;
+                    return self.error(format!("undeclared variable ${name}"));
+                }
+                Ok(Expr::Variable(name))
+            }
+            Some(Token::Dot) => Ok(Expr::ContextItem),
+            Some(Token::LeftParen) => {
+                if self.eat(Token::RightParen) {
+                    return Ok(Expr::Sequence(Vec::new()));
+                }
+                let expr = self.expr()?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) if self.peek() == Some(&Token::LeftParen) => {
+                self.position -= 1;
+                self.function_call(&name)
+            }
+            _ => {
+                self.position -= 1;
+                self.error("expected an expression")
+            }
+        }
+    }
+
+    fn function_call(&mut self, name: &str) -> Result<Expr, XPathError> {
+        let function_name = self.resolve(name, Some(FN_NAMESPACE))?;
+        let (function, min_arity, max_arity) = match function_name.namespace_name() {
+            Some(FN_NAMESPACE) => match Function::from_name(function_name.local_name()) {
+                Some(function) => function,
+                None => return self.error(format!("unsupported function {name}()")),
+            },
+            Some(XS_NAMESPACE) => (Function::Constructor(self.atomic_type(name)?), 1, 1),
+            _ => return self.error(format!("unsupported function {name}()")),
+        };
+        self.position += 2;
+
+        let mut arguments = Vec::new();
+        if !self.eat(Token::RightParen) {
+            loop {
+                arguments.push(self.expr_single()?);
+                if !self.eat(Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(Token::RightParen)?;
+        }
+        if !(min_arity..=max_arity).contains(&arguments.len()) {
+            return self.error(format!(
+                "wrong number of arguments for {name}(): {}",
+                arguments.len()
+            ));
+        }
+        Ok(Expr::FunctionCall(function, arguments))
+    }
+}
+
+/// `left//`, i.e. `left/descendant-or-self::node()/`
+fn descendant_or_self(left: Expr) -> Expr {
+    Expr::Path(
+        Box::new(left),
+        Box::new(Expr::Step {
+            axis: Axis::DescendantOrSelf,
+            test: NodeTest::AnyNode,
+            predicates: Vec::new(),
+        }),
+    )
+}
+
+/// The type annotations of the nodes of the tree an assertion is evaluated on, which determine
+/// their typed values
+pub trait TypeAnnotations {
+    /// Returns the typed value of element `e`, or of its `attribute`, or `None` if the node is
+    /// untyped, in which case its typed value is its string value as `xs:untypedAtomic`
+    fn typed_value(&self, e: Node, attribute: Option<&Attribute>) -> Option<validation::Value>;
+}
+
+/// All nodes are untyped
+impl TypeAnnotations for () {
+    fn typed_value(&self, _e: Node, _attribute: Option<&Attribute>) -> Option<validation::Value> {
+        None
+    }
+}
+
+/// A node of the tree. roxmltree does not represent attributes as nodes, so they are identified
+/// by their element and their index.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum TreeNode<'a, 'input> {
+    Node(Node<'a, 'input>),
+    Attribute(Node<'a, 'input>, usize),
+}
+
+impl<'a, 'input> TreeNode<'a, 'input> {
+    /// The position of the node in document order, in which attributes follow their element and
+    /// precede its children
+    fn document_order(self) -> (usize, usize) {
+        match self {
+            Self::Node(node) => (node.id().get_usize(), 0),
+            Self::Attribute(e, index) => (e.id().get_usize(), index + 1),
+        }
+    }
+
+    fn attribute(e: Node<'a, 'input>, index: usize) -> Attribute<'a, 'input> {
+        e.attributes().nth(index).expect("attribute index is valid")
+    }
+
+    fn string_value(self) -> String {
+        match self {
+            Self::Node(node) if node.is_element() || node.is_root() => node
+                .descendants()
+                .filter(|n| n.is_text())
+                .filter_map(|n| n.text())
+                .collect(),
+            Self::Node(node) => node.text().unwrap_or_default().to_string(),
+            Self::Attribute(e, index) => Self::attribute(e, index).value().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Item<'a, 'input> {
+    Node(TreeNode<'a, 'input>),
+    Atomic(Value),
+}
+
+/// The context item, its position and the size of the sequence it was taken from
+#[derive(Clone)]
+struct Focus<'a, 'input> {
+    item: Item<'a, 'input>,
+    position: usize,
+    size: usize,
+}
+
+type Sequence<'a, 'input> = Vec<Item<'a, 'input>>;
+
+/// Maps a value of a simple type to atomic values, splitting list values into their items
+fn atomic_values(value: &validation::Value, lexical: &str) -> Vec<Value> {
+    match value {
+        validation::Value::List(items) => items
+            .iter()
+            .zip(lexical.split_whitespace())
+            .flat_map(|(item, lexical)| atomic_values(item, lexical))
+            .collect(),
+        validation::Value::String(s) | validation::Value::AnyURI(s) => {
+            vec![Value::String(s.clone())]
+        }
+        validation::Value::Boolean(b) => vec![Value::Boolean(*b)],
+        validation::Value::Float(f) => vec![Value::Double(f64::from(*f))],
+        validation::Value::Double(d) => vec![Value::Double(*d)],
+        validation::Value::Decimal(d) => vec![Value::Decimal(d.clone())],
+        other => vec![Value::Typed(other.clone(), lexical.trim().to_string())],
+    }
+}
+
+/// The dynamic context of an evaluation
+struct Evaluator<'e, 'a, 'input> {
+    /// The root of the tree, whose parent and siblings are not accessible
+    root: Option<Node<'a, 'input>>,
+    annotations: &'e dyn TypeAnnotations,
+    /// The in-scope variables, innermost last
+    variables: Vec<(&'e str, Sequence<'a, 'input>)>,
+}
+
+impl<'e, 'a, 'input> Evaluator<'e, 'a, 'input> {
+    fn atomize(&self, item: &Item<'a, 'input>) -> Vec<Value> {
+        match *item {
+            Item::Atomic(ref value) => vec![value.clone()],
+            Item::Node(node) => {
+                let typed_value = match node {
+                    TreeNode::Node(e) if e.is_element() => self.annotations.typed_value(e, None),
+                    TreeNode::Attribute(e, index) => self
+                        .annotations
+                        .typed_value(e, Some(&TreeNode::attribute(e, index))),
+                    TreeNode::Node(_) => None,
+                };
+                let string_value = node.string_value();
+                match (typed_value, node) {
+                    (Some(value), _) => atomic_values(&value, &string_value),
+                    // Comments are typed as xs:string
+                    (None, TreeNode::Node(n)) if n.is_comment() => {
+                        vec![Value::String(string_value)]
+                    }
+                    (None, _) => vec![Value::UntypedAtomic(string_value)],
+                }
+            }
+        }
+    }
+
+    fn atomize_all(&self, items: &[Item<'a, 'input>]) -> Vec<Value> {
+        items.iter().flat_map(|item| self.atomize(item)).collect()
+    }
+
+    /// Atomizes a sequence that may hold at most one item
+    fn optional_atomic(
+        &self,
+        items: &[Item<'a, 'input>],
+    ) -> Result<Option<Value>, EvaluationError> {
+        let mut values = self.atomize_all(items);
+        match values.len() {
+            0 => Ok(None),
+            1 => Ok(values.pop()),
+            _ => Err(EvaluationError::Unspecified),
+        }
+    }
+
+    /// The string value of an optional item, or the empty string
+    fn string_argument(&self, items: &[Item<'a, 'input>]) -> Result<String, EvaluationError> {
+        match self.optional_atomic(items)? {
+            None => Ok(String::new()),
+            Some(value) => string(&value),
+        }
+    }
+
+    /// The numeric value of an optional item, with untyped values cast to `xs:double`
+    fn numeric_argument(
+        &self,
+        items: &[Item<'a, 'input>],
+    ) -> Result<Option<Value>, EvaluationError> {
+        self.optional_atomic(items)?
+            .map(|value| numeric(&value))
+            .transpose()
+    }
+
+    /// The effective boolean value of a sequence (XPath 2.0, §2.4.3)
+    fn effective_boolean_value(&self, items: &[Item<'a, 'input>]) -> Result<bool, EvaluationError> {
+        match items {
+            [Item::Node(_), ..] => Ok(true),
+            _ => cta::effective_boolean_value(
+                &items
+                    .iter()
+                    .map(|item| match item {
+                        Item::Atomic(value) => Ok(value.clone()),
+                        Item::Node(_) => Err(EvaluationError::Unspecified),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        }
+    }
+
+    fn context_item(
+        &self,
+        focus: Option<&Focus<'a, 'input>>,
+    ) -> Result<Item<'a, 'input>, EvaluationError> {
+        focus
+            .map(|focus| focus.item.clone())
+            .ok_or(EvaluationError::Unspecified)
+    }
+
+    fn evaluate(
+        &mut self,
+        expr: &'e Expr,
+        focus: Option<&Focus<'a, 'input>>,
+    ) -> Result<Sequence<'a, 'input>, EvaluationError> {
+        let boolean = |b: bool| Ok(vec![Item::Atomic(Value::Boolean(b))]);
+        match expr {
+            Expr::Sequence(operands) => {
+                let mut items = Vec::new();
+                for operand in operands {
+                    items.extend(self.evaluate(operand, focus)?);
+                }
+                Ok(items)
+            }
+            Expr::For {
+                variable,
+                sequence,
+                body,
+            } => {
+                let mut items = Vec::new();
+                for item in self.evaluate(sequence, focus)? {
+                    self.variables.push((variable.as_str(), vec![item]));
+                    let result = self.evaluate(body, focus);
+                    self.variables.pop();
+                    items.extend(result?);
+                }
+                Ok(items)
+            }
+            Expr::Quantified {
+                every,
+                variable,
+                sequence,
+                condition,
+            } => {
+                for item in self.evaluate(sequence, focus)? {
+                    self.variables.push((variable.as_str(), vec![item]));
+                    let result = self
+                        .evaluate(condition, focus)
+                        .and_then(|items| self.effective_boolean_value(&items));
+                    self.variables.pop();
+                    if result? != *every {
+                        return boolean(!every);
+                    }
+                }
+                boolean(*every)
+            }
+            Expr::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition = self.evaluate(condition, focus)?;
+                if self.effective_boolean_value(&condition)? {
+                    self.evaluate(then, focus)
+                } else {
+                    self.evaluate(otherwise, focus)
+                }
+            }
+            Expr::Or(operands) => {
+                for operand in operands {
+                    let items = self.evaluate(operand, focus)?;
+                    if self.effective_boolean_value(&items)? {
+                        return boolean(true);
+                    }
+                }
+                boolean(false)
+            }
+            Expr::And(operands) => {
+                for operand in operands {
+                    let items = self.evaluate(operand, focus)?;
+                    if !self.effective_boolean_value(&items)? {
+                        return boolean(false);
+                    }
+                }
+                boolean(true)
+            }
+            Expr::Comparison {
+                left,
+                comparator,
+                general,
+                right,
+            } => {
+                let left = self.evaluate(left, focus)?;
+                let right = self.evaluate(right, focus)?;
+                if !general {
+                    // Value comparisons require at most one item on each side, and are empty if
+                    // either operand is empty
+                    return match (self.optional_atomic(&left)?, self.optional_atomic(&right)?) {
+                        (Some(l), Some(r)) => boolean(
+                            Value::compare(&l, &r, false)?.is_some_and(|o| comparator.holds(o)),
+                        ),
+                        _ => Ok(Vec::new()),
+                    };
+                }
+                // A general comparison is true if any pair of values satisfies it
+                let right = self.atomize_all(&right);
+                for l in self.atomize_all(&left) {
+                    for r in &right {
+                        if Value::compare(&l, r, true)?.is_some_and(|o| comparator.holds(o)) {
+                            return boolean(true);
+                        }
+                    }
+                }
+                boolean(false)
+            }
+            Expr::Range(from, to) => {
+                let from = self.evaluate(from, focus)?;
+                let to = self.evaluate(to, focus)?;
+                let integer = |value: Option<Value>| match value {
+                    None => Ok(None),
+                    Some(value) => match value.cast(AtomicType::Integer)? {
+                        Value::Decimal(n) => n.to_i64().map(Some).ok_or(EvaluationError::Overflow),
+                        _ => Err(EvaluationError::Unspecified),
+                    },
+                };
+                match (
+                    integer(self.optional_atomic(&from)?)?,
+                    integer(self.optional_atomic(&to)?)?,
+                ) {
+                    (Some(from), Some(to)) => Ok((from..=to)
+                        .map(|n| Item::Atomic(Value::Decimal(Decimal::from(n))))
+                        .collect()),
+                    _ => Ok(Vec::new()),
+                }
+            }
+            Expr::Arithmetic(left, operator, right) => {
+                let left = self.evaluate(left, focus)?;
+                let right = self.evaluate(right, focus)?;
+                let (Some(l), Some(r)) = (
+                    self.numeric_argument(&left)?,
+                    self.numeric_argument(&right)?,
+                ) else {
+                    // An empty operand yields the empty sequence
+                    return Ok(Vec::new());
+                };
+                Ok(vec![Item::Atomic(arithmetic(l, *operator, r)?)])
+            }
+            Expr::Negate(operand) => {
+                let operand = self.evaluate(operand, focus)?;
+                Ok(self
+                    .numeric_argument(&operand)?
+                    .map(|n| {
+                        Item::Atomic(match n {
+                            Value::Decimal(d) => Value::Decimal(-d),
+                            n => Value::Double(-n.as_double().unwrap_or(f64::NAN)),
+                        })
+                    })
+                    .into_iter()
+                    .collect())
+            }
+            Expr::Union(left, right) => {
+                let mut nodes = self.evaluate(left, focus)?;
+                nodes.extend(self.evaluate(right, focus)?);
+                if nodes.iter().any(|item| matches!(item, Item::Atomic(_))) {
+                    return Err(EvaluationError::Unspecified);
+                }
+                Ok(in_document_order(nodes))
+            }
+            Expr::Cast {
+                operand,
+                to,
+                optional,
+                castable,
+            } => {
+                let operand = self.evaluate(operand, focus)?;
+                let result = match self.optional_atomic(&operand) {
+                    Ok(Some(value)) => value.cast(*to).map(Some),
+                    Ok(None) if *optional => Ok(None),
+                    Ok(None) | Err(_) => Err(EvaluationError::Unspecified),
+                };
+                if *castable {
+                    return boolean(result.is_ok());
+                }
+                Ok(result?.map(Item::Atomic).into_iter().collect())
+            }
+            Expr::Root => {
+                // The root of the tree is the element the assertion is evaluated on, which is not
+                // a document node
+                Err(EvaluationError::Unspecified)
+            }
+            Expr::Path(left, right) => {
+                let left = self.evaluate(left, focus)?;
+                let size = left.len();
+                let mut items = Vec::new();
+                for (i, item) in left.into_iter().enumerate() {
+                    if !matches!(item, Item::Node(_)) {
+                        return Err(EvaluationError::Unspecified);
+                    }
+                    let focus = Focus {
+                        item,
+                        position: i + 1,
+                        size,
+                    };
+                    items.extend(self.evaluate(right, Some(&focus))?);
+                }
+                // The result is either a sequence of nodes in document order, or of atomic values
+                if items.iter().all(|item| matches!(item, Item::Node(_))) {
+                    Ok(in_document_order(items))
+                } else if items.iter().all(|item| matches!(item, Item::Atomic(_))) {
+                    Ok(items)
+                } else {
+                    Err(EvaluationError::Unspecified)
+                }
+            }
+            Expr::Step {
+                axis,
+                test,
+                predicates,
+            } => {
+                let Item::Node(node) = self.context_item(focus)? else {
+                    return Err(EvaluationError::Unspecified);
+                };
+                let items = self
+                    .axis(node, *axis)
+                    .into_iter()
+                    .filter(|&node| matches_node_test(node, *axis, test))
+                    .map(Item::Node)
+                    .collect();
+                self.filter(items, predicates)
+            }
+            Expr::Filter(primary, predicates) => {
+                let items = self.evaluate(primary, focus)?;
+                self.filter(items, predicates)
+            }
+            Expr::Literal(value) => Ok(vec![Item::Atomic(value.clone())]),
+            Expr::Variable(name) => Ok(self
+                .variables
+                .iter()
+                .rev()
+                .find(|(n, _)| *n == name.as_str())
+                .map(|(_, value)| value.clone())
+                .expect("variables are checked when parsing")),
+            Expr::ContextItem => Ok(vec![self.context_item(focus)?]),
+            Expr::FunctionCall(function, arguments) => {
+                self.function_call(*function, arguments, focus)
+            }
+        }
+    }
+
+    /// Filters a sequence by the predicates: numeric predicates select the item at that position,
+    /// others the items for which their effective boolean value is true
+    fn filter(
+        &mut self,
+        mut items: Sequence<'a, 'input>,
+        predicates: &'e [Expr],
+    ) -> Result<Sequence<'a, 'input>, EvaluationError> {
+        for predicate in predicates {
+            let size = items.len();
+            let mut filtered = Vec::new();
+            for (i, item) in items.into_iter().enumerate() {
+                let focus = Focus {
+                    item,
+                    position: i + 1,
+                    size,
+                };
+                let result = self.evaluate(predicate, Some(&focus))?;
+                let keep = match result.as_slice() {
+                    [Item::Atomic(Value::Decimal(n))] => *n == Decimal::from((i + 1) as i64),
+                    [Item::Atomic(Value::Double(n))] => *n == (i + 1) as f64,
+                    _ => self.effective_boolean_value(&result)?,
+                };
+                if keep {
+                    filtered.push(focus.item);
+                }
+            }
+            items = filtered;
+        }
+        Ok(items)
+    }
+
+    /// The parent of a node, unless the node is the root of the tree
+    fn parent(&self, node: TreeNode<'a, 'input>) -> Option<TreeNode<'a, 'input>> {
+        match node {
+            TreeNode::Node(node) if Some(node) == self.root => None,
+            TreeNode::Node(node) => node.parent().map(TreeNode::Node),
+            TreeNode::Attribute(e, _) => Some(TreeNode::Node(e)),
+        }
+    }
+
+    /// Returns the nodes on the axis, in the order of the axis (reverse document order for the
+    /// reverse axes)
+    fn axis(&self, node: TreeNode<'a, 'input>, axis: Axis) -> Vec<TreeNode<'a, 'input>> {
+        let is_root = matches!(node, TreeNode::Node(n) if Some(n) == self.root);
+        match (axis, node) {
+            (Axis::SelfNode, _) => vec![node],
+            (Axis::Child, TreeNode::Node(n)) => n.children().map(TreeNode::Node).collect(),
+            (Axis::Attribute, TreeNode::Node(n)) => (0..n.attributes().len())
+                .map(|index| TreeNode::Attribute(n, index))
+                .collect(),
+            (Axis::Descendant, TreeNode::Node(n)) => {
+                n.descendants().skip(1).map(TreeNode::Node).collect()
+            }
+            (Axis::DescendantOrSelf, TreeNode::Node(n)) => {
+                n.descendants().map(TreeNode::Node).collect()
+            }
+            (Axis::DescendantOrSelf, TreeNode::Attribute(..)) => vec![node],
+            (Axis::Parent, _) => self.parent(node).into_iter().collect(),
+            (Axis::Ancestor | Axis::AncestorOrSelf, _) => {
+                let start = if axis == Axis::Ancestor {
+                    self.parent(node)
+                } else {
+                    Some(node)
+                };
+                std::iter::successors(start, |&n| self.parent(n)).collect()
+            }
+            (Axis::FollowingSibling, TreeNode::Node(n)) if !is_root => {
+                n.next_siblings().skip(1).map(TreeNode::Node).collect()
+            }
+            (Axis::PrecedingSibling, TreeNode::Node(n)) if !is_root => {
+                n.prev_siblings().skip(1).map(TreeNode::Node).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn function_call(
+        &mut self,
+        function: Function,
+        arguments: &'e [Expr],
+        focus: Option<&Focus<'a, 'input>>,
+    ) -> Result<Sequence<'a, 'input>, EvaluationError> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            values.push(self.evaluate(argument, focus)?);
+        }
+        // Functions with an optional argument apply to the context item if it is omitted
+        let argument = |index: usize| -> Result<Sequence<'a, 'input>, EvaluationError> {
+            match values.get(index) {
+                Some(value) => Ok(value.clone()),
+                None => Ok(vec![self.context_item(focus)?]),
+            }
+        };
+        let atomic = |value: Value| Ok(vec![Item::Atomic(value)]);
+        let optional = |value: Option<Value>| Ok(value.map(Item::Atomic).into_iter().collect());
+        let string_function = |f: &dyn Fn(&str, &str) -> Value| {
+            let left = self.string_argument(&values[0])?;
+            let right = self.string_argument(&values[1])?;
+            atomic(f(&left, &right))
+        };
+        let integer = |n: usize| atomic(Value::Decimal(Decimal::from(n as i64)));
+        let numeric_function = |decimal: fn(&Decimal) -> Decimal, double: fn(f64) -> f64| {
+            optional(self.numeric_argument(&values[0])?.map(|n| match n {
+                Value::Decimal(d) => Value::Decimal(decimal(&d)),
+                n => Value::Double(double(n.as_double().unwrap_or(f64::NAN))),
+            }))
+        };
+
+        match function {
+            Function::Count => integer(values[0].len()),
+            Function::Exists => atomic(Value::Boolean(!values[0].is_empty())),
+            Function::Empty => atomic(Value::Boolean(values[0].is_empty())),
+            Function::Not => atomic(Value::Boolean(!self.effective_boolean_value(&values[0])?)),
+            Function::True => atomic(Value::Boolean(true)),
+            Function::False => atomic(Value::Boolean(false)),
+            Function::Boolean => atomic(Value::Boolean(self.effective_boolean_value(&values[0])?)),
+            Function::String => atomic(Value::String(self.string_argument(&argument(0)?)?)),
+            Function::StringLength => integer(self.string_argument(&argument(0)?)?.chars().count()),
+            Function::NormalizeSpace => atomic(Value::String(
+                self.string_argument(&argument(0)?)?
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )),
+            Function::Concat => atomic(Value::String(
+                values
+                    .iter()
+                    .map(|value| self.string_argument(value))
+                    .collect::<Result<String, _>>()?,
+            )),
+            Function::Contains => string_function(&|s, t| Value::Boolean(s.contains(t))),
+            Function::StartsWith => string_function(&|s, t| Value::Boolean(s.starts_with(t))),
+            Function::EndsWith => string_function(&|s, t| Value::Boolean(s.ends_with(t))),
+            Function::SubstringBefore => string_function(&|s, t| {
+                Value::String(s.split_once(t).map_or("", |(before, _)| before).into())
+            }),
+            Function::SubstringAfter => string_function(&|s, t| {
+                Value::String(s.split_once(t).map_or("", |(_, after)| after).into())
+            }),
+            Function::Substring => {
+                // The characters at the positions p with round(start) <= p < round(start) +
+                // round(length), counting from 1
+                let s = self.string_argument(&values[0])?;
+                let double_argument = |items| {
+                    self.numeric_argument(items)?
+                        .and_then(|n| n.as_double())
+                        .ok_or(EvaluationError::Unspecified)
+                };
+                let start = round(double_argument(&values[1])?);
+                let end = match values.get(2) {
+                    Some(length) => start + round(double_argument(length)?),
+                    None => f64::INFINITY,
+                };
+                atomic(Value::String(
+                    s.chars()
+                        .enumerate()
+                        .filter(|&(i, _)| {
+                            let p = (i + 1) as f64;
+                            start <= p && p < end
+                        })
+                        .map(|(_, c)| c)
+                        .collect(),
+                ))
+            }
+            Function::UpperCase => atomic(Value::String(
+                self.string_argument(&values[0])?.to_uppercase(),
+            )),
+            Function::LowerCase => atomic(Value::String(
+                self.string_argument(&values[0])?.to_lowercase(),
+            )),
+            Function::StringJoin => {
+                let separator = self.string_argument(&values[1])?;
+                atomic(Value::String(
+                    self.atomize_all(&values[0])
+                        .iter()
+                        .map(string)
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join(&separator),
+                ))
+            }
+            Function::Sum | Function::Avg => {
+                let numbers = self
+                    .atomize_all(&values[0])
+                    .iter()
+                    .map(numeric)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let count = numbers.len();
+                let sum = numbers
+                    .into_iter()
+                    .try_fold(Value::Decimal(Decimal::from(0)), |sum, n| {
+                        arithmetic(sum, ArithmeticOperator::Add, n)
+                    })?;
+                match function {
+                    Function::Sum => atomic(sum),
+                    _ if count == 0 => Ok(Vec::new()),
+                    _ => atomic(arithmetic(
+                        sum,
+                        ArithmeticOperator::Divide,
+                        Value::Decimal(Decimal::from(count as i64)),
+                    )?),
+                }
+            }
+            Function::Min | Function::Max => {
+                let wanted = if function == Function::Min {
+                    Comparator::Lt
+                } else {
+                    Comparator::Gt
+                };
+                let mut result: Option<Value> = None;
+                for value in self.atomize_all(&values[0]) {
+                    // Untyped values are compared as xs:double
+                    let value = match value {
+                        Value::UntypedAtomic(_) => value.cast(AtomicType::Double)?,
+                        value => value,
+                    };
+                    if matches!(value, Value::Double(n) if n.is_nan()) {
+                        return atomic(value);
+                    }
+                    let replace = match &result {
+                        None => true,
+                        Some(current) => Value::compare(&value, current, false)?
+                            .ok_or(EvaluationError::Unspecified)
+                            .map(|o| wanted.holds(o))?,
+                    };
+                    if replace {
+                        result = Some(value);
+                    }
+                }
+                optional(result)
+            }
+            Function::Number => {
+                let value = self.optional_atomic(&argument(0)?)?;
+                atomic(Value::Double(
+                    match value.map(|value| value.cast(AtomicType::Double)) {
+                        Some(Ok(Value::Double(n))) => n,
+                        _ => f64::NAN,
+                    },
+                ))
+            }
+            Function::Abs => numeric_function(Decimal::abs, f64::abs),
+            Function::Floor => numeric_function(Decimal::floor, f64::floor),
+            Function::Ceiling => numeric_function(Decimal::ceiling, f64::ceil),
+            Function::Round => numeric_function(Decimal::round, round),
+            Function::Data => Ok(self
+                .atomize_all(&values[0])
+                .into_iter()
+                .map(Item::Atomic)
+                .collect()),
+            Function::DistinctValues => {
+                let mut distinct: Vec<Value> = Vec::new();
+                for value in self.atomize_all(&values[0]) {
+                    // Untyped values are compared as strings
+                    let value = match value {
+                        Value::UntypedAtomic(s) => Value::String(s),
+                        value => value,
+                    };
+                    let is_duplicate = distinct.iter().any(|other| {
+                        Value::compare(&value, other, false)
+                            .is_ok_and(|o| o == Some(std::cmp::Ordering::Equal))
+                    });
+                    if !is_duplicate {
+                        distinct.push(value);
+                    }
+                }
+                Ok(distinct.into_iter().map(Item::Atomic).collect())
+            }
+            Function::Position => integer(focus.ok_or(EvaluationError::Unspecified)?.position),
+            Function::Last => integer(focus.ok_or(EvaluationError::Unspecified)?.size),
+            Function::LocalName => {
+                let local_name = match argument(0)?.as_slice() {
+                    [] => "",
+                    [Item::Node(TreeNode::Node(n))] => n.tag_name().name(),
+                    [Item::Node(TreeNode::Attribute(e, index))] => {
+                        TreeNode::attribute(*e, *index).name()
+                    }
+                    _ => return Err(EvaluationError::Unspecified),
+                };
+                atomic(Value::String(local_name.into()))
+            }
+            Function::Constructor(to) => optional(
+                self.optional_atomic(&values[0])?
+                    .map(|v| v.cast(to))
+                    .transpose()?,
+            ),
+        }
+    }
+}
+
+/// `fn:round`, which rounds halves towards positive infinity
+fn round(n: f64) -> f64 {
+    (n + 0.5).floor()
+}
+
+fn string(value: &Value) -> Result<String, EvaluationError> {
+    match value.cast(AtomicType::String)? {
+        Value::String(s) => Ok(s),
+        _ => unreachable!("cast to xs:string yields a string"),
+    }
+}
+
+/// The numeric value of an atomic value, with untyped values cast to `xs:double`
+fn numeric(value: &Value) -> Result<Value, EvaluationError> {
+    match value {
+        Value::Decimal(_) | Value::Double(_) => Ok(value.clone()),
+        Value::UntypedAtomic(_) => value.cast(AtomicType::Double),
+        _ => Err(EvaluationError::Unspecified),
+    }
+}
+
+/// Applies an arithmetic operator to two numeric values (XPath 2.0, §3.4; XPath and XQuery
+/// Functions and Operators, §6.2). If both are `xs:decimal`s, the result is exact, otherwise
+/// both are promoted to `xs:double`.
+fn arithmetic(
+    left: Value,
+    operator: ArithmeticOperator,
+    right: Value,
+) -> Result<Value, EvaluationError> {
+    if let (Value::Decimal(l), Value::Decimal(r)) = (&left, &right) {
+        let (l, r) = (l.clone(), r.clone());
+        return Ok(Value::Decimal(
+            match operator {
+                ArithmeticOperator::Add => Some(l + r),
+                ArithmeticOperator::Subtract => Some(l - r),
+                ArithmeticOperator::Multiply => Some(l * r),
+                ArithmeticOperator::Divide => l.checked_div(&r),
+                ArithmeticOperator::IntegerDivide => l.checked_integer_div(&r),
+                ArithmeticOperator::Modulo => l.checked_rem(&r),
+            }
+            .ok_or(EvaluationError::DivisionByZero)?,
+        ));
+    }
+    let l = left.as_double().ok_or(EvaluationError::Unspecified)?;
+    let r = right.as_double().ok_or(EvaluationError::Unspecified)?;
+    Ok(Value::Double(match operator {
+        ArithmeticOperator::Add => l + r,
+        ArithmeticOperator::Subtract => l - r,
+        ArithmeticOperator::Multiply => l * r,
+        // Division of doubles by zero yields an infinity or NaN, not an error
+        ArithmeticOperator::Divide => l / r,
+        ArithmeticOperator::IntegerDivide => {
+            if r == 0.0 {
+                return Err(EvaluationError::DivisionByZero);
+            }
+            if l.is_nan() || r.is_nan() || l.is_infinite() {
+                return Err(EvaluationError::Overflow);
+            }
+            // The result is an xs:integer
+            return Decimal::from_f64((l / r).trunc())
+                .map(Value::Decimal)
+                .ok_or(EvaluationError::Overflow);
+        }
+        // The remainder of doubles has the sign of the dividend, and is NaN for a zero divisor
+        ArithmeticOperator::Modulo => l % r,
+    }))
+}
+
+fn matches_node_test(node: TreeNode, axis: Axis, test: &NodeTest) -> bool {
+    match (test, node) {
+        (NodeTest::AnyNode, _) => true,
+        (NodeTest::Text, TreeNode::Node(n)) => n.is_text(),
+        (NodeTest::Comment, TreeNode::Node(n)) => n.is_comment(),
+        (NodeTest::Element, TreeNode::Node(n)) => n.is_element(),
+        (NodeTest::Attribute, TreeNode::Attribute(..)) => true,
+        // Name tests match nodes of the principal node kind of the axis
+        (NodeTest::Name(name), TreeNode::Node(n)) if axis != Axis::Attribute && n.is_element() => {
+            name.matches(n.tag_name().namespace(), n.tag_name().name())
+        }
+        (NodeTest::Name(name), TreeNode::Attribute(e, index)) if axis == Axis::Attribute => {
+            let attribute = TreeNode::attribute(e, index);
+            name.matches(attribute.namespace(), attribute.name())
+        }
+        _ => false,
+    }
+}
+
+/// Sorts a sequence of nodes into document order, removing duplicates
+fn in_document_order<'a, 'input>(mut items: Sequence<'a, 'input>) -> Sequence<'a, 'input> {
+    let key = |item: &Item| match item {
+        Item::Node(node) => node.document_order(),
+        Item::Atomic(_) => unreachable!("only nodes are sorted"),
+    };
+    items.sort_by_key(key);
+    items.dedup_by_key(|item| key(item));
+    items
+}
+
+impl AssertionTest {
+    /// Parses the {expression} of `xpath`, resolving prefixes using its {namespace bindings}
+    pub fn parse(xpath: &XPathExpression) -> Result<Self, XPathError> {
+        let mut parser = Parser {
+            tokens: tokenize(&xpath.expression)?,
+            position: 0,
+            end: xpath.expression.len(),
+            xpath,
+            variables: vec!["value".into()],
+        };
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return parser.error("unexpected trailing tokens");
+        }
+        Ok(Self(expr))
+    }
+
+    /// Evaluates the test and returns its effective boolean value.
+    ///
+    /// For Assertion Satisfied (§3.13.4.1), `context` is the element the assertion is evaluated
+    /// on, which is both the context item and the root of the tree, so its parent and siblings
+    /// are not accessible. For Assertions Valid (pt. 2, §4.3.13.4) there is no context item.
+    ///
+    /// `$value` is bound to the given value, with its normalized lexical form, or to the empty
+    /// sequence. Returns the dynamic or type error raised during evaluation, if any.
+    pub fn evaluate(
+        &self,
+        context: Option<Node>,
+        value: Option<(&str, &validation::Value)>,
+        annotations: &dyn TypeAnnotations,
+    ) -> Result<bool, EvaluationError> {
+        let value = value
+            .map(|(lexical, value)| atomic_values(value, lexical))
+            .unwrap_or_default()
+            .into_iter()
+            .map(Item::Atomic)
+            .collect();
+        let mut evaluator = Evaluator {
+            root: context,
+            annotations,
+            variables: vec![("value", value)],
+        };
+        let focus = context.map(|e| Focus {
+            item: Item::Node(TreeNode::Node(e)),
+            position: 1,
+            size: 1,
+        });
+        evaluator
+            .evaluate(&self.0, focus.as_ref())
+            .and_then(|items| evaluator.effective_boolean_value(&items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssertionTest, EvaluationError};
+    use crate::{
+        assertion::{NamespaceBinding, XPathExpression},
+        validation::{Primitive, Value},
+    };
+    use roxmltree::Document;
+
+    fn xpath(expression: &str) -> XPathExpression {
+        XPathExpression {
+            namespace_bindings: vec![NamespaceBinding {
+                prefix: "xs".into(),
+                namespace: "http://www.w3.org/2001/XMLSchema".into(),
+            }],
+            default_namespace: None,
+            base_uri: None,
+            expression: expression.into(),
+        }
+    }
+
+    fn evaluate(expression: &str, document: &str) -> Result<bool, EvaluationError> {
+        let document = Document::parse(document).unwrap();
+        AssertionTest::parse(&xpath(expression)).unwrap().evaluate(
+            Some(document.root_element()),
+            None,
+            &(),
+        )
+    }
+
+    fn test(expression: &str, document: &str) -> bool {
+        evaluate(expression, document).unwrap_or(false)
+    }
+
+    #[test]
+    fn paths_and_comparisons() {
+        let order = r#"<order min="2" max="10">
+            <item qty="3">a</item><item qty="4">b</item><note/>
+        </order>"#;
+        // Untyped values are compared as strings, unless the other operand is numeric
+        assert!(test("number(@min) <= @max and @min > @max", order));
+        assert!(test("@min < 3 and @max - @min = 8", order));
+        assert!(test("count(item) = 2", order));
+        assert!(test("count(*) eq 3 and count(.//item) = 2", order));
+        assert!(test("item[2] = 'b' and item[@qty = 3] = 'a'", order));
+        assert!(test("item[last()]/@qty = 4", order));
+        assert!(test("sum(item/@qty) = 7 and max(item/@qty) = 4", order));
+        assert!(test(
+            "exists(note) and empty(note/*) and not(comment)",
+            order
+        ));
+        assert!(test("string(.) = 'ab'", order));
+        assert!(test("item[1]/following-sibling::item = 'b'", order));
+        assert!(test("empty(..) and empty(following-sibling::*)", order));
+        assert!(!test("count(item) > 2", order));
+        assert!(!test("@min > 3", order));
+        assert!(!test("/order", order));
+        assert!(!test("item eq 'a'", order));
+    }
+
+    #[test]
+    fn expressions() {
+        let e = r#"<e start="2024-01-01" end="2024-02-01" code="AB-12"/>"#;
+        assert!(test("xs:date(@start) lt xs:date(@end)", e));
+        assert!(test("@end cast as xs:date gt xs:date('2024-01-15')", e));
+        assert!(test(
+            "@start castable as xs:date and not(@code castable as xs:integer)",
+            e
+        ));
+        assert!(test(
+            "starts-with(@code, 'AB') and string-length(@code) = 5",
+            e
+        ));
+        assert!(test(
+            "substring(@code, 4) = '12' and substring-before(@code, '-') = 'AB'",
+            e
+        ));
+        assert!(test(
+            "upper-case('ab') = 'AB' and concat('a', 1, true()) = 'a1true'",
+            e
+        ));
+        assert!(test("every $i in (1, 2, 3) satisfies $i > 0", e));
+        assert!(test("some $i in 1 to 10 satisfies $i * $i = 49", e));
+        assert!(test("if (@code) then true() else false()", e));
+        assert!(test("sum(for $i in 1 to 4 return $i) = 10", e));
+        assert!(test(
+            "7 idiv 2 = 3 and 7 mod 2 = 1 and 7 div 2 = 3.5 and -(1) = -1",
+            e
+        ));
+        assert!(test("count(distinct-values(('a', 'b', 'a'))) = 2", e));
+        assert!(test("(1, 2) = 2 and not((1, 2) != 1 and false())", e));
+        assert!(!test("1 div 0 = 1 and 1 idiv 0 = 1", e));
+    }
+
+    #[test]
+    fn decimal_arithmetic() {
+        let e = r#"<e price="0.1" total="12345678901234567.1"/>"#;
+        assert!(test("0.1 + 0.2 eq 0.3 and 1.1 * 3 eq 3.3", e));
+        assert!(test("xs:decimal(@price) * 3 eq 0.3", e));
+        assert!(test("1 div 3 * 3 lt 1 and 10 div 4 eq 2.5", e));
+        assert!(test("-7.5 idiv 2 eq -3 and -7.5 mod 2 eq -1.5", e));
+        assert!(test(
+            "xs:decimal(@total) ne 12345678901234567.0 and \
+             12345678901234567.1 - 12345678901234567 eq 0.1",
+            e
+        ));
+        assert!(test("sum((0.1, 0.2)) eq 0.3 and avg((1, 2)) eq 1.5", e));
+        assert!(test("round(-2.5) eq -2 and floor(-0.5) eq -1", e));
+        // Untyped operands are promoted to xs:double
+        assert!(test("@price + 0.2 ne 0.3", e));
+        assert_eq!(
+            evaluate("1 idiv 0 = 0", e),
+            Err(EvaluationError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("1.5 mod 0 = 0", e),
+            Err(EvaluationError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("1 div 0.0 = 0", e),
+            Err(EvaluationError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("xs:double(1) idiv 0 = 0", e),
+            Err(EvaluationError::DivisionByZero)
+        );
+        assert_eq!(evaluate("1 div 0e0 gt 1", e), Ok(true));
+    }
+
+    #[test]
+    fn value_variable() {
+        let evaluate = |expression: &str, lexical: &str, value: &Value| {
+            AssertionTest::parse(&xpath(expression))
+                .unwrap()
+                .evaluate(None, Some((lexical, value)), &())
+                .unwrap_or(false)
+        };
+        let even = Value::Double(4.0);
+        assert!(evaluate("$value mod 2 = 0", "4", &even));
+        assert!(!evaluate("$value mod 2 = 1", "4", &even));
+        assert!(!evaluate(". = 4", "4", &even));
+
+        let list = Value::List(vec![Value::Boolean(true), Value::Boolean(false)]);
+        assert!(evaluate(
+            "count($value) = 2 and $value[2] = false()",
+            "true 0",
+            &list
+        ));
+
+        let date = Value::parse(Primitive::Date, "2024-03-01", &Vec::new()).unwrap();
+        assert!(evaluate(
+            "$value ge xs:date('2024-01-01')",
+            "2024-03-01",
+            &date
+        ));
+        assert!(evaluate(
+            "string($value) = '2024-03-01'",
+            "2024-03-01",
+            &date
+        ));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(AssertionTest::parse(&xpath("count(")).is_err());
+        assert!(AssertionTest::parse(&xpath("$undeclared = 1")).is_err());
+        assert!(AssertionTest::parse(&xpath("matches(., 'a')")).is_err());
+        assert!(AssertionTest::parse(&xpath("count(a, b)")).is_err());
+        assert!(AssertionTest::parse(&xpath("namespace::x")).is_err());
+        assert!(AssertionTest::parse(&xpath("p:a = 1")).is_err());
+        assert!(AssertionTest::parse(&xpath("every $a in b")).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! The XPath subset for identity-constraint selectors and fields (pt. 1, §3.11.6.2):
+//!
+//! ```text
+//! Selector ::= Path ( '|' Path )*
+//! Path     ::= ('.//')? Step ( '/' Step )*
+//! Field    ::= Path ( '|' Path )*
+//! Path     ::= ('.//')? ( Step '/' )* ( Step | '@' NameTest )
+//! Step     ::= '.' | NameTest
+//! NameTest ::= QName | '*' | NCName ':*'
+//! ```
+//!
+//! The abbreviated steps can also be written with the explicit `child::` and `attribute::` axes.
+
+use super::XPathError;
+use crate::{assertion::XPathExpression, xstypes::QName};
+use roxmltree::{Attribute, Node};
+
+/// A parsed identity-constraint {selector} or {field}: a union of [`Path`]s
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentityPath {
+    pub paths: Vec<Path>,
+}
+
+/// A path of an [`IdentityPath`], selecting elements or (only in fields) attributes relative to
+/// the context element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    /// Whether the path starts with `.//`, i.e. the steps are applied to the context element and
+    /// all of its descendants
+    pub descendants: bool,
+    pub steps: Vec<Step>,
+    /// The final `@` step, if the path selects attributes of the elements selected by `steps`
+    pub attribute: Option<NameTest>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// `.`, which selects the current element
+    SelfNode,
+    /// A child step selecting the child elements matching the name test
+    Child(NameTest),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NameTest {
+    /// An expanded name
+    Name(QName),
+    /// `*`, matching any name
+    Any,
+    /// `prefix:*`, matching any name in the namespace bound to the prefix
+    AnyInNamespace(Option<String>),
+}
+
+impl NameTest {
+    /// Returns whether the name test matches the expanded name `(namespace, local_name)`. An
+    /// empty `namespace`, as roxmltree reports for elements in the scope of `xmlns=""`, is no
+    /// namespace.
+    pub fn matches(&self, namespace: Option<&str>, local_name: &str) -> bool {
+        let namespace = namespace.filter(|namespace| !namespace.is_empty());
+        match self {
+            Self::Name(name) => {
+                name.namespace_name() == namespace && name.local_name() == local_name
+            }
+            Self::Any => true,
+            Self::AnyInNamespace(n) => n.as_deref() == namespace,
+        }
+    }
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    position: usize,
+    xpath: &'a XPathExpression,
+    /// Whether attribute steps are allowed, i.e. a field is parsed
+    is_field: bool,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.expression[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&self, reason: impl Into<String>) -> Result<T, XPathError> {
+        Err(XPathError {
+            position: self.position,
+            reason: reason.into(),
+        })
+    }
+
+    fn ncname(&mut self) -> Option<&str> {
+        let rest = self.rest();
+        let mut chars = rest.char_indices();
+        if !chars
+            .next()
+            .is_some_and(|(_, c)| c.is_alphabetic() || c == '_')
+        {
+            return None;
+        }
+        let end = chars
+            .find(|&(_, c)| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '·')))
+            .map_or(rest.len(), |(i, _)| i);
+        let start = self.position;
+        self.position += end;
+        Some(&self.expression[start..start + end])
+    }
+
+    fn union(&mut self) -> Result<IdentityPath, XPathError> {
+        let mut paths = vec![self.path()?];
+        while self.eat("|") {
+            paths.push(self.path()?);
+        }
+        self.skip_whitespace();
+        if !self.rest().is_empty() {
+            return self.error("unexpected trailing characters");
+        }
+        Ok(IdentityPath { paths })
+    }
+
+    fn path(&mut self) -> Result<Path, XPathError> {
+        let descendants = self.eat(".//");
+        let mut steps = Vec::new();
+        loop {
+            if self.eat("@") || self.eat("attribute::") {
+                if !self.is_field {
+                    return self.error("a selector cannot select attributes");
+                }
+                let attribute = self.name_test(false)?;
+                return Ok(Path {
+                    descendants,
+                    steps,
+                    attribute: Some(attribute),
+                });
+            }
+            let step = if self.eat("child::") {
+                Step::Child(self.name_test(true)?)
+            } else if self.eat(".") {
+                Step::SelfNode
+            } else {
+                Step::Child(self.name_test(true)?)
+            };
+            steps.push(step);
+            if self.eat("//") {
+                return self.error("'//' is only allowed at the start of a path, as './/'");
+            }
+            if !self.eat("/") {
+                break;
+            }
+        }
+        Ok(Path {
+            descendants,
+            steps,
+            attribute: None,
+        })
+    }
+
+    /// Parses a name test. Unprefixed element names are in the {default namespace}, unprefixed
+    /// attribute names are not in any namespace.
+    fn name_test(&mut self, is_element: bool) -> Result<NameTest, XPathError> {
+        self.skip_whitespace();
+        if self.eat("*") {
+            return Ok(NameTest::Any);
+        }
+        let start = self.position;
+        let Some(prefix_or_name) = self.ncname().map(String::from) else {
+            return self.error("expected a name test");
+        };
+        // No whitespace is allowed within a QName
+        if !self.rest().starts_with(':') {
+            let namespace = if is_element {
+                self.xpath.default_namespace.clone()
+            } else {
+                None
+            };
+            return Ok(NameTest::Name(QName::with_optional_namespace(
+                namespace,
+                prefix_or_name,
+            )));
+        }
+        self.position += 1;
+        let Some(binding) = self
+            .xpath
+            .namespace_bindings
+            .iter()
+            .find(|b| b.prefix == prefix_or_name)
+        else {
+            self.position = start;
+            return self.error(format!("unbound namespace prefix {prefix_or_name:?}"));
+        };
+        let namespace = binding.namespace.clone();
+        if self.rest().starts_with('*') {
+            self.position += 1;
+            return Ok(NameTest::AnyInNamespace(Some(namespace)));
+        }
+        match self.ncname() {
+            Some(local_name) => Ok(NameTest::Name(QName::with_namespace(namespace, local_name))),
+            None => self.error("expected a local name"),
+        }
+    }
+}
+
+impl IdentityPath {
+    /// Parses the {expression} of an identity-constraint {selector}
+    pub fn parse_selector(xpath: &XPathExpression) -> Result<Self, XPathError> {
+        Self::parse(xpath, false)
+    }
+
+    /// Parses the {expression} of one of the {fields} of an identity-constraint
+    pub fn parse_field(xpath: &XPathExpression) -> Result<Self, XPathError> {
+        Self::parse(xpath, true)
+    }
+
+    fn parse(xpath: &XPathExpression, is_field: bool) -> Result<Self, XPathError> {
+        Parser {
+            expression: &xpath.expression,
+            position: 0,
+            xpath,
+            is_field,
+        }
+        .union()
+    }
+}
+
+/// A node selected by a field: an element, or an attribute of an element
+#[derive(Clone, Copy, Debug)]
+pub enum FieldNode<'a, 'input> {
+    Element(Node<'a, 'input>),
+    Attribute(Node<'a, 'input>, Attribute<'a, 'input>),
+}
+
+impl Path {
+    /// Appends the elements selected by the steps of the path, relative to `context`, to
+    /// `selected`
+    fn select_elements<'a, 'input>(
+        &self,
+        context: Node<'a, 'input>,
+        selected: &mut Vec<Node<'a, 'input>>,
+    ) {
+        let mut current = if self.descendants {
+            context.descendants().filter(Node::is_element).collect()
+        } else {
+            vec![context]
+        };
+        for step in &self.steps {
+            current = match step {
+                Step::SelfNode => current,
+                Step::Child(name_test) => current
+                    .iter()
+                    .flat_map(|node| node.children())
+                    .filter(|child| {
+                        child.is_element()
+                            && name_test
+                                .matches(child.tag_name().namespace(), child.tag_name().name())
+                    })
+                    .collect(),
+            };
+        }
+        selected.extend(current);
+    }
+}
+
+impl IdentityPath {
+    /// Evaluates a selector with `context` as the context node, returning the selected elements
+    /// in document order
+    pub fn select<'a, 'input>(&self, context: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+        let mut selected = Vec::new();
+        for path in &self.paths {
+            path.select_elements(context, &mut selected);
+        }
+        selected.sort_by_key(|node| node.range().start);
+        selected.dedup();
+        selected
+    }
+
+    /// Evaluates a field with `context` as the context node, returning the selected elements and
+    /// attributes in document order
+    pub fn select_fields<'a, 'input>(
+        &self,
+        context: Node<'a, 'input>,
+    ) -> Vec<FieldNode<'a, 'input>> {
+        let mut selected = Vec::new();
+        for path in &self.paths {
+            let mut elements = Vec::new();
+            path.select_elements(context, &mut elements);
+            match &path.attribute {
+                None => selected.extend(elements.into_iter().map(FieldNode::Element)),
+                Some(name_test) => selected.extend(elements.into_iter().flat_map(|element| {
+                    element
+                        .attributes()
+                        .filter(|a| name_test.matches(a.namespace(), a.name()))
+                        .map(move |a| FieldNode::Attribute(element, a))
+                })),
+            }
+        }
+        // Attributes follow their element, in attribute order
+        let position = |node: &FieldNode| match node {
+            FieldNode::Element(e) => e.range().start,
+            FieldNode::Attribute(_, a) => a.range().start,
+        };
+        selected.sort_by_key(position);
+        selected.dedup_by_key(|node| position(node));
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdentityPath, NameTest, Path, Step};
+    use crate::{
+        assertion::{NamespaceBinding, XPathExpression},
+        xstypes::QName,
+    };
+
+    fn xpath(expression: &str) -> XPathExpression {
+        XPathExpression {
+            namespace_bindings: vec![NamespaceBinding {
+                prefix: "t".into(),
+                namespace: "urn:test".into(),
+            }],
+            default_namespace: None,
+            base_uri: None,
+            expression: expression.into(),
+        }
+    }
+
+    #[test]
+    fn selectors() {
+        let selector = IdentityPath::parse_selector(&xpath(".//t:item | child::*/ t:* ")).unwrap();
+        assert_eq!(
+            selector.paths,
+            [
+                Path {
+                    descendants: true,
+                    steps: vec![Step::Child(NameTest::Name(QName::with_namespace(
+                        "urn:test", "item"
+                    )))],
+                    attribute: None,
+                },
+                Path {
+                    descendants: false,
+                    steps: vec![
+                        Step::Child(NameTest::Any),
+                        Step::Child(NameTest::AnyInNamespace(Some("urn:test".into()))),
+                    ],
+                    attribute: None,
+                },
+            ]
+        );
+
+        assert!(IdentityPath::parse_selector(&xpath("@id")).is_err());
+        assert!(IdentityPath::parse_selector(&xpath("a//b")).is_err());
+        assert!(IdentityPath::parse_selector(&xpath("../a")).is_err());
+        assert!(IdentityPath::parse_selector(&xpath("u:a")).is_err());
+        assert!(IdentityPath::parse_selector(&xpath("a[1]")).is_err());
+    }
+
+    #[test]
+    fn fields() {
+        let field = IdentityPath::parse_field(&xpath("./name/@id")).unwrap();
+        assert_eq!(
+            field.paths,
+            [Path {
+                descendants: false,
+                steps: vec![
+                    Step::SelfNode,
+                    Step::Child(NameTest::Name(QName::without_namespace("name"))),
+                ],
+                attribute: Some(NameTest::Name(QName::without_namespace("id"))),
+            }]
+        );
+
+        assert!(IdentityPath::parse_field(&xpath("@id/name")).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+//! Parsing and evaluation of the XPath 2.0 subsets used by schema components.
+
+use std::fmt;
+
+mod assertion;
+mod cta;
+mod identity;
+
+pub use assertion::{AssertionTest, TypeAnnotations};
+pub use cta::{EvaluationError, TypeAlternativeTest};
+pub use identity::{FieldNode, IdentityPath, NameTest, Path, Step};
+
+/// An error while parsing an XPath expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XPathError {
+    /// The byte offset in the expression at which the error occurred
+    pub position: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for XPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at offset {})", self.reason, self.position)
+    }
+}
@@ -84,7 +84,11 @@ impl QName {
         // an unprefixed element name has the URI of the default namespace as its namespace name.
         // If there is no default namespace declaration in scope, the namespace name has no value.
         // (Namespaces in XML 1.0, §6.2)
-        let namespace_name = context.lookup_namespace_uri(None);
+        // An empty default namespace declaration "has the same effect [...] of there being no
+        // default namespace" (Namespaces in XML 1.0, §3).
+        let namespace_name = context
+            .lookup_namespace_uri(None)
+            .filter(|namespace_name| !namespace_name.is_empty());
         QName::with_optional_namespace(namespace_name, local_name)
     }
 